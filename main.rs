@@ -1,603 +1,10850 @@
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::collections::{HashMap, VecDeque};
 use std::f32::consts::PI;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+#[cfg(not(target_arch = "wasm32"))]
+use std::future::Future;
+use std::io::{BufWriter, Write};
 use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
-use std::path::Path;
-use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::rc::Rc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, SystemTime};
+// `std::time::Instant` panics in the browser, which has no monotonic clock for it.
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
 
-use minifb::{Key, KeyRepeat, Window, WindowOptions};
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
 
 const WIDTH: usize = 960;
 const HEIGHT: usize = 540;
-const STAR_COUNT: usize = 420;
-const ORBIT_SEGMENTS: usize = 120;
+// Frame pacing for the window backends, about 60 Hz.
+const FRAME_INTERVAL: Duration = Duration::from_micros(16_600);
+const DEFAULT_STAR_COUNT: usize = 420;
+// How far each background layer turns with the camera, far to near. Values below 1
+// make the layers slide at different rates, which reads as depth.
+const SKY_LAYER_PARALLAX: [f32; 3] = [0.25, 0.45, 0.7];
+const SKY_LAYER_BRIGHTNESS: [f32; 3] = [0.6, 0.8, 1.0];
+// Texels around the equator of the sky snapshot used for reflections.
+const ENVIRONMENT_WIDTH: usize = 256;
+// Orbit polylines get enough segments that the chord-to-arc gap stays under
+// ORBIT_MAX_SAG pixels where the orbit passes closest to the camera.
+const ORBIT_MIN_SEGMENTS: usize = 24;
+const ORBIT_MAX_SEGMENTS: usize = 720;
+const ORBIT_MAX_SAG: f32 = 0.5;
+const ORBIT_PROBE_POINTS: usize = 32;
 const CAMERA_SPEED: f32 = 28.0;
 const WARP_DURATION: f32 = 0.9;
+// Share of the previous frame kept at a warp's top speed, for the motion blur.
+const WARP_BLUR_PERSISTENCE: f32 = 0.65;
+// Extra vertical fov (radians) a warp widens to before snapping back on arrival, and
+// the length of the star streaks at top speed as a share of each star's distance
+// from the screen centre.
+const WARP_FOV_KICK: f32 = 0.3;
+const WARP_STREAK_LENGTH: f32 = 0.35;
+// Camera shake: trauma drained per second, the offset (world units) and turn
+// (radians) at full trauma, and how fast the noise driving it wanders.
+const SHAKE_DECAY: f32 = 1.4;
+const SHAKE_MAX_OFFSET: f32 = 0.35;
+const SHAKE_MAX_ANGLE: f32 = 0.025;
+const SHAKE_FREQUENCY: f32 = 18.0;
+// Trauma per world unit a collision pushes the camera back.
+const SHAKE_PER_PUSH: f32 = 0.3;
+// Flybys faster than this, closer to a planet's surface than `SHAKE_FLYBY_RANGE`
+// times its radius, add up to `SHAKE_FLYBY_RATE` trauma per second.
+const SHAKE_FLYBY_SPEED: f32 = CAMERA_SPEED * 0.75;
+const SHAKE_FLYBY_RANGE: f32 = 0.8;
+const SHAKE_FLYBY_RATE: f32 = 1.5;
+// Flight HUD: how fast the speed readout settles, and the artificial horizon's size.
+const HUD_SPEED_SMOOTHING: f32 = 6.0;
+const HUD_HORIZON_WIDTH: i32 = 44;
+const HUD_HORIZON_HEIGHT: i32 = 22;
+// Top-down minimap in the top-left corner: side in pixels and segments per orbit.
+const MINIMAP_SIZE: i32 = 140;
+const MINIMAP_ORBIT_SEGMENTS: usize = 48;
+// Most Loop subdivision passes a scene mesh may ask for; each one quadruples the triangles.
+const MAX_SUBDIVIDE: usize = 3;
+// Most triangles in one leaf of a mesh's picking hierarchy.
+const BVH_LEAF_TRIANGLES: usize = 4;
+// Icosphere planets: subdivision passes when the scene gives none, and the most allowed.
+const DEFAULT_ICOSPHERE_SUBDIVISIONS: usize = 3;
+const MAX_ICOSPHERE_SUBDIVISIONS: usize = 5;
+// Rocky comet nuclei: icosphere passes under the noise and how many craters dent each.
+const ASTEROID_SUBDIVISIONS: usize = 4;
+const ASTEROID_CRATERS: usize = 7;
+const DEFAULT_FOV: f32 = PI / 3.5;
+const MAX_FOV: f32 = PI * 0.6;
+// Co-pilot telescope: zoom range and the picture-in-picture inset's share of the window.
+const TELESCOPE_MIN_FOV: f32 = PI / 90.0;
+const TELESCOPE_ZOOM_RATE: f32 = 1.5;
+const PIP_SCALE: f32 = 0.3;
+// Pilot zoom: FOV factor per mouse wheel notch, rate while PageUp/PageDown are held,
+// and how quickly the FOV eases toward the requested one.
+const ZOOM_WHEEL_STEP: f32 = 0.85;
+const ZOOM_KEY_RATE: f32 = 1.5;
+const ZOOM_SMOOTHING: f32 = 10.0;
+// Close-up of the selected body: inset size, how much of it the body fills, and how
+// many framing distances away the camera must be before the inset shows.
+const CLOSEUP_SCALE: f32 = 0.25;
+const CLOSEUP_FILL: f32 = 0.6;
+const CLOSEUP_MIN_DISTANCE: f32 = 3.0;
+// Operation budget per script evaluation (see `ScriptHost`).
+#[cfg(feature = "scripting")]
+const SCRIPT_MAX_OPERATIONS: u64 = 100_000;
+// Split-screen overview: how much wider than the outermost orbit its diagram reaches.
+const OVERVIEW_MARGIN: f32 = 1.15;
+const NEAR_PLANE: f32 = 0.1;
+const FAR_PLANE: f32 = 800.0;
+/// How far behind an orthographic camera its stand-in eye sits, so lighting and
+/// culling see near-parallel view rays.
+const ORTHO_EYE_DISTANCE: f32 = 1.0e5;
+/// Silhouette shrink applied to occluders, covering the gap between the tessellated
+/// limb and the true sphere.
+const OCCLUDER_MARGIN: f32 = 0.95;
+const FRAME_FILL: f32 = 0.7;
+const LENSING_REACH: f32 = 4.0;
+const COMET_TAIL_SPEED: f32 = 14.0;
+const COMET_SPAWN_RATE: f32 = 90.0;
+const COMET_PARTICLE_LIFE: f32 = 2.6;
+// Loops erupting off a star: how many start per second on average, at most how many
+// are alive per star, the share that are short bright flares, the points each is
+// drawn through, and the hydrogen-red their light leans towards.
+const PROMINENCE_RATE: f32 = 0.35;
+const PROMINENCE_MAX: usize = 5;
+const FLARE_CHANCE: f32 = 0.25;
+const PROMINENCE_SAMPLES: usize = 40;
+const PROMINENCE_TINT: Color = Color::new(1.0, 0.4, 0.3);
+const RADIATION_PRESSURE: f32 = 2400.0;
+// Scene distance that plays the role of 1 AU for a star of light_intensity 1.0.
+const EQUILIBRIUM_AU: f32 = 45.0;
+const HEAT_GLOW_START: f32 = 450.0;
+const HEAT_GLOW_FULL: f32 = 900.0;
+const TRANSIT_DISTANCE: f32 = 600.0;
+const LIGHT_CURVE_INTERVAL: f32 = 0.05;
+const LIGHT_CURVE_SAMPLES: usize = 240;
+// Planet mass per unit radius cubed, relative to a star's (which is 1).
+const PLANET_DENSITY: f32 = 0.02;
+const WOBBLE_SCALE: f32 = 20.0;
+// Motion trails keep a sample every `TRAIL_INTERVAL` seconds, the newest
+// `TRAIL_SAMPLES` of them, and start at `TRAIL_BRIGHTNESS` behind the body.
+const TRAIL_INTERVAL: f32 = 0.1;
+const TRAIL_SAMPLES: usize = 90;
+const TRAIL_BRIGHTNESS: f32 = 0.6;
+const UNDO_LIMIT: usize = 32;
+const GIZMO_PICK_RADIUS: f32 = 8.0;
+const GIZMO_RADIUS_SNAP: f32 = 1.0;
+const GIZMO_ANGLE_SNAP: f32 = PI / 36.0;
+const RING_PARTICLE_COUNT: usize = 4000;
+const FRAME_BUDGET: f32 = 1.0 / 60.0;
+const GOVERNOR_COOLDOWN: f32 = 1.0;
+/// Render resolution scales the governor falls back to, in order, once every
+/// optional pass is already shed.
+const RESOLUTION_STEPS: [f32; 3] = [0.75, 0.6, 0.5];
+const SEARCH_MAX_RESULTS: usize = 6;
+const SEARCH_MAX_QUERY: usize = 24;
+const CONSOLE_MAX_LINE: usize = 40;
+const GLYPH_WIDTH: i32 = 5;
+const GLYPH_HEIGHT: i32 = 7;
+const GLYPH_ADVANCE: i32 = 6;
+// Built-in scenes are compiled in, but their source path is still watched so
+// editing them from a checkout reloads live.
+const BUILTIN_SCENES: [(&str, &str); 2] = [
+    ("scenes/icy.scene", include_str!("scenes/icy.scene")),
+    ("scenes/ember.scene", include_str!("scenes/ember.scene")),
+];
+// Meshes the scenes use by default, compiled in for the browser build (which has no
+// file system) and as a fallback when the file is missing next to the binary.
+const BUILTIN_MESHES: [(&str, &str); 2] = [
+    ("spaceship.obj", include_str!("spaceship.obj")),
+    ("station.obj", include_str!("station.obj")),
+];
+const RELOAD_POLL_INTERVAL: f32 = 0.5;
+const RELOAD_MESSAGE_TIME: f32 = 3.0;
+const SETTINGS_PATH: &str = "settings.ini";
+const BOOKMARKS_PATH: &str = "bookmarks.ini";
+const BINDINGS_PATH: &str = "bindings.ini";
+const AUDIO_DEFAULT_RANGE: f32 = 60.0;
+// Adaptive planet tessellation: an edge is split while its arc bulges more than
+// TESS_MAX_ERROR pixels from the chord on screen or it spans over TESS_MAX_EDGE pixels.
+// Chords are in unit-sphere units; TESS_NEAR_CHORD bounds edges crossing the near plane.
+const TESS_MAX_ERROR: f32 = 0.5;
+const TESS_MAX_EDGE: f32 = 48.0;
+const TESS_MIN_CHORD: f32 = 0.02;
+const TESS_NEAR_CHORD: f32 = 0.2;
+const HORIZON_CULL_SLACK: f32 = 0.05;
+const VERTEX_LANES: usize = 8;
+const SUBPIXEL_BITS: u32 = 4;
+const RASTER_TILE: usize = 8;
+// Wireframe edges are pulled this far toward the camera in depth-buffer units, so
+// they win against filled surfaces they lie on.
+const WIREFRAME_DEPTH_OFFSET: f32 = 1e-4;
+// Fragments per pixel at which the overdraw debug view turns fully red.
+const OVERDRAW_HOT: u16 = 8;
+// Ordered-dither thresholds (a 4x4 Bayer matrix): a pixel of a surface with
+// `opacity` below its entry is left undrawn.
+const SCREEN_DOOR: [[f32; 4]; 4] = [
+    [0.5 / 16.0, 8.5 / 16.0, 2.5 / 16.0, 10.5 / 16.0],
+    [12.5 / 16.0, 4.5 / 16.0, 14.5 / 16.0, 6.5 / 16.0],
+    [3.5 / 16.0, 11.5 / 16.0, 1.5 / 16.0, 9.5 / 16.0],
+    [15.5 / 16.0, 7.5 / 16.0, 13.5 / 16.0, 5.5 / 16.0],
+];
+// Width of the soft edge of a planet's polar caps, in unit-sphere height.
+const POLAR_CAP_BLEND: f32 = 0.04;
+// How fast the turbulence of a gas giant's cloud bands drifts, in noise cells per second.
+const CLOUD_BAND_DRIFT: f32 = 0.03;
+// Ice: strength and Blinn-Phong exponent of the highlight, the reflection it keeps
+// facing the viewer and reaches at grazing angles, and the blue light scattered
+// under the surface around the terminator, over `ICE_SUBSURFACE_WIDTH` of N·L.
+const ICE_SPECULAR: f32 = 0.9;
+const ICE_SHININESS: i32 = 40;
+const ICE_REFLECTIVITY: f32 = 0.08;
+const ICE_FRESNEL_MAX: f32 = 0.8;
+const ICE_SUBSURFACE: Color = Color::new(0.2, 0.42, 0.75);
+const ICE_SUBSURFACE_WIDTH: f32 = 0.25;
+// Stars: how many times a second the granulation cells dissolve into a new pattern,
+// how far apart in noise space successive patterns lie, and the tint a star's disk
+// fades to at its limb.
+const GRANULATION_RATE: f32 = 0.2;
+const GRANULATION_SHIFT: Vec3 = Vec3::new(17.3, 5.1, 11.7);
+const STAR_LIMB: Color = Color::new(0.55, 0.42, 0.32);
+// Auroras: angle of the oval from each pole, how far above the surface the curtain
+// starts and how tall it stands, all in planet radii, and its segments around.
+const AURORA_OVAL: f32 = 0.33;
+const AURORA_BASE: f32 = 1.02;
+const AURORA_HEIGHT: f32 = 0.25;
+const AURORA_SEGMENTS: usize = 64;
+// Glow over the nozzle at the back of spaceship.obj, in the model's own units.
+const SHIP_ENGINE_GLOW: [EmissiveSpot; 1] = [EmissiveSpot {
+    center: Vec3::new(-0.256, 0.93, -5.5),
+    radius: 1.1,
+    color: Color::new(1.0, 0.55, 0.2),
+}];
+// Under adaptive shading, instances smaller than this on screen (radius in pixels)
+// are lit per vertex.
+const GOURAUD_MAX_RADIUS: f32 = 40.0;
+// Toon style: surface points whose normal is within this |cos| of perpendicular to
+// the view are drawn as outline.
+const TOON_OUTLINE_THRESHOLD: f32 = 0.28;
+// Emitters hidden behind a star or planet keep this fraction of their gain.
+#[cfg(feature = "audio")]
+const AUDIO_OCCLUDED_GAIN: f32 = 0.3;
+// Frame time used while recording or replaying input, so replays are exact.
+const FIXED_TIMESTEP: f32 = 1.0 / 60.0;
+// Simulation tick rates offered in `settings.ini`; the first one is the default.
+const TICK_RATES: [usize; 3] = [60, 120, 240];
+const DEFAULT_MAX_CATCH_UP: usize = 8;
+const SHOT_HOLD: f32 = 5.0;
+// Fixed step used to fast-forward the simulation to a shot or turntable time.
+const SIM_FAST_FORWARD_STEP: f32 = 0.05;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut window = Window::new(
-        "Icy System",
-        WIDTH,
-        HEIGHT,
-        WindowOptions {
-            resize: false,
-            scale: minifb::Scale::X1,
-            ..WindowOptions::default()
-        },
-    )?;
-    window.limit_update_rate(Some(Duration::from_micros(16_600)));
-
-    let mut theme_index = 0usize;
-    let mut active_theme = THEMES[theme_index];
-    window.set_title(&format!("Icy System - {}", active_theme.name));
-
-    let sphere_mesh = Mesh::uv_sphere(28, 18);
-    let spaceship_mesh = Mesh::from_obj(Path::new("spaceship.obj"))?;
-
-    let mut renderer = Renderer::new(WIDTH, HEIGHT, STAR_COUNT, active_theme.palette);
-    let mut planets = build_planets(active_theme.planets);
-    let mut sun = build_sun(active_theme);
-    let mut light = Light {
-        direction: Vec3::new(-0.4, -1.0, -0.2).normalized(),
-        color: active_theme.light_color,
-        intensity: active_theme.light_intensity,
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("scene-diff") => run_scene_diff(&args[1..]),
+        Some("scene-merge") => run_scene_merge(&args[1..]),
+        _ if args.iter().any(|arg| arg == "--render-turntable") => run_turntable(&args),
+        _ => start_window(args),
     };
-    let mut ship_color = active_theme.ship_color;
-
-    let mut camera = Camera::new(Vec3::new(0.0, 8.0, -40.0));
-    camera.yaw = 0.0;
-    camera.pitch = 0.08;
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    let mut last_frame = Instant::now();
-    let mut warp: Option<Warp> = None;
+/// Failures callers may want to tell apart, such as a broken OBJ that a built-in
+/// mesh can stand in for. Everything else still travels as a boxed message.
+#[derive(Debug)]
+enum AppError {
+    /// `line` is 1-based.
+    ObjParse {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+    MissingFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// PLY data has no useful line numbers once it is binary.
+    PlyParse {
+        path: PathBuf,
+        reason: String,
+    },
+    WindowInit(String),
+    /// `reason` carries the line and section from the scene parser.
+    SceneParse {
+        path: PathBuf,
+        reason: String,
+    },
+}
 
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        let now = Instant::now();
-        let mut dt = (now - last_frame).as_secs_f32();
-        if dt > 0.1 {
-            dt = 0.1;
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AppError::ObjParse { path, line, reason } => {
+                write!(f, "{}: line {}: {}", path.display(), line, reason)
+            }
+            AppError::MissingFile { path, source } => write!(f, "{}: {}", path.display(), source),
+            AppError::PlyParse { path, reason } => write!(f, "{}: {}", path.display(), reason),
+            AppError::WindowInit(reason) => write!(f, "could not open the window: {}", reason),
+            AppError::SceneParse { path, reason } => write!(f, "{}: {}", path.display(), reason),
         }
-        last_frame = now;
-
-        update_planets(&mut planets, dt);
-        update_sun(&mut sun, dt);
+    }
+}
 
-        if window.is_key_pressed(Key::T, KeyRepeat::No) {
-            theme_index = (theme_index + 1) % THEMES.len();
-            active_theme = THEMES[theme_index];
-            planets = build_planets(active_theme.planets);
-            sun = build_sun(active_theme);
-            light.color = active_theme.light_color;
-            light.intensity = active_theme.light_intensity;
-            ship_color = active_theme.ship_color;
-            renderer.set_palette(active_theme.palette);
-            window.set_title(&format!("Icy System - {}", active_theme.name));
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::MissingFile { source, .. } => Some(source),
+            _ => None,
         }
+    }
+}
 
-        let warp_targets = collect_warp_targets(&sun, &planets);
+/// Runs the window loop to the end.
+#[cfg(not(target_arch = "wasm32"))]
+fn start_window(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    block_on(run_window(args))
+}
 
-        if warp.is_none() {
-            handle_input(&window, &mut camera, dt);
+/// In the browser the page has to get control back between frames, so the window
+/// loop runs as a future that each animation frame resumes, after `main` returns.
+#[cfg(target_arch = "wasm32")]
+fn start_window(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    wasm_bindgen_futures::spawn_local(async {
+        if let Err(err) = run_window(args).await {
+            web_sys::console::error_1(&err.to_string().into());
         }
+    });
+    Ok(())
+}
 
-        if let Some(active_warp) = warp.as_mut() {
-            active_warp.progress += dt;
-            let t = (active_warp.progress / active_warp.duration).min(1.0);
-            let eased = smoothstep(t);
-            camera.position = Vec3::lerp(active_warp.start, active_warp.target, eased);
-            if t >= 1.0 {
-                warp = None;
-            }
-        } else if let Some(requested) = detect_warp_request(&window, &warp_targets) {
-            warp = Some(Warp {
-                start: camera.position,
-                target: requested,
-                progress: 0.0,
-                duration: WARP_DURATION,
-            });
+/// Drives a future that never waits, as the window loop is on the desktop.
+#[cfg(not(target_arch = "wasm32"))]
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut context = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
         }
+    }
+}
 
-        apply_collisions(&mut camera.position, &sun, &planets);
-
-        renderer.begin_frame();
-        renderer.draw_ecliptic_band();
-        let view = camera.view_matrix();
-        let projection = Mat4::perspective(
-            camera.fov,
-            WIDTH as f32 / HEIGHT as f32,
-            0.1,
-            800.0,
-        );
-        let view_projection = projection * view;
-
-        draw_orbits(&mut renderer, &planets, &view_projection);
-
-        let mut instances = Vec::with_capacity(planets.len() + 2);
-        instances.push(RenderInstance {
-            mesh: &sphere_mesh,
-            transform: sun.transform,
-            material: Material {
-                color: sun.color,
-                emissive: 0.85,
-            },
+/// Resolves on the next animation frame in the browser and right away elsewhere,
+/// where `Platform::present` already paces the loop.
+async fn next_frame() {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let promise = js_sys::Promise::new(&mut |resolve, _| {
+            web_sys::window()
+                .and_then(|window| window.request_animation_frame(&resolve).ok())
+                .expect("requestAnimationFrame is unavailable");
         });
+        let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+    }
+}
 
-        for planet in &planets {
-            instances.push(RenderInstance {
-                mesh: &sphere_mesh,
-                transform: planet.transform,
-                material: Material {
-                    color: planet.color,
-                    emissive: 0.05,
-                },
-            });
-            if let Some(ring) = &planet.ring {
-                instances.push(RenderInstance {
-                    mesh: &ring.mesh,
-                    transform: ring.transform,
-                    material: Material {
-                        color: ring.color,
-                        emissive: 0.1,
-                    },
-                });
-            }
+/// Installs the log subscriber, writing to stderr or to the file after `--log`, at
+/// the level after `--log-level` (info when absent). Debug adds a span per frame
+/// stage (update, render, present) and the scene's renderer counters; trace adds the
+/// vertex and raster stages of every mesh.
+#[cfg(feature = "tracing")]
+fn init_tracing(args: &mut Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut take = |flag: &str| -> Result<Option<String>, String> {
+        let Some(idx) = args.iter().position(|arg| arg == flag) else {
+            return Ok(None);
+        };
+        args.remove(idx);
+        if idx >= args.len() {
+            return Err(format!("{} expects a value", flag));
+        }
+        Ok(Some(args.remove(idx)))
+    };
+    let target = take("--log")?;
+    let level = match take("--log-level")? {
+        None => tracing::Level::INFO,
+        Some(name) => name.parse().map_err(|_| {
+            format!(
+                "--log-level expects error, warn, info, debug or trace, got {}",
+                name
+            )
+        })?,
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE);
+    match target.as_deref() {
+        None | Some("stderr") => builder.with_writer(std::io::stderr).init(),
+        Some(path) => {
+            let file = std::fs::File::create(path).map_err(|err| format!("{}: {}", path, err))?;
+            builder
+                .with_ansi(false)
+                .with_writer(std::sync::Mutex::new(file))
+                .init()
         }
+    }
+    Ok(())
+}
 
-        let spaceship_transform = spaceship_transform_for_camera(&camera);
-        instances.push(RenderInstance {
-            mesh: &spaceship_mesh,
-            transform: spaceship_transform,
-            material: Material {
-                color: ship_color,
-                emissive: 0.2,
-            },
-        });
+/// Which windowing library the window loop runs on, picked with `--platform`.
+#[derive(Clone, Copy)]
+enum PlatformBackend {
+    Minifb,
+    #[cfg(all(feature = "winit", not(target_arch = "wasm32")))]
+    Winit,
+}
 
-        renderer.render(&instances, &view_projection, &camera, &light);
+impl PlatformBackend {
+    /// Takes `--platform <name>` out of `args`; minifb when absent.
+    fn from_args(args: &mut Vec<String>) -> Result<Self, Box<dyn std::error::Error>> {
+        let Some(idx) = args.iter().position(|arg| arg == "--platform") else {
+            return Ok(PlatformBackend::Minifb);
+        };
+        args.remove(idx);
+        if idx >= args.len() {
+            return Err("--platform expects minifb or winit".into());
+        }
+        match args.remove(idx).as_str() {
+            "minifb" => Ok(PlatformBackend::Minifb),
+            #[cfg(all(feature = "winit", not(target_arch = "wasm32")))]
+            "winit" => Ok(PlatformBackend::Winit),
+            #[cfg(not(all(feature = "winit", not(target_arch = "wasm32"))))]
+            "winit" => Err("this build has no winit backend, rebuild with --features winit".into()),
+            other => Err(format!("unknown platform `{}`, expected minifb or winit", other).into()),
+        }
+    }
 
-        window.update_with_buffer(renderer.color_buffer(), WIDTH, HEIGHT)?;
+    fn open(self, title: &str) -> Result<Box<dyn Platform>, AppError> {
+        let failed = |err: &dyn std::fmt::Display| AppError::WindowInit(err.to_string());
+        Ok(match self {
+            PlatformBackend::Minifb => {
+                Box::new(MinifbPlatform::open(title).map_err(|e| failed(&e))?)
+            }
+            #[cfg(all(feature = "winit", not(target_arch = "wasm32")))]
+            PlatformBackend::Winit => Box::new(WinitPlatform::open(title).map_err(|e| failed(&e))?),
+        })
     }
+}
 
-    Ok(())
+/// The window the loop draws into: where input comes from and frames go to.
+trait Platform {
+    fn is_open(&self) -> bool;
+    /// Input since the previous call. This also services the window's events, so the
+    /// loop calls it every frame, even when a replay supplies the input.
+    fn capture(&mut self) -> InputFrame;
+    fn set_title(&mut self, title: &str);
+    /// Switches between the normal window and borderless fullscreen.
+    fn toggle_fullscreen(&mut self) -> Result<(), Box<dyn std::error::Error>>;
+    /// Size to render at so the frame fills the window at the `WIDTH`:`HEIGHT` aspect.
+    fn frame_size(&self) -> (usize, usize);
+    /// Shows a finished frame of 0RGB pixels, letterboxed if the window's aspect
+    /// differs from the frame's.
+    fn present(
+        &mut self,
+        frame: &[u32],
+        width: usize,
+        height: usize,
+    ) -> Result<(), Box<dyn std::error::Error>>;
 }
 
-fn handle_input(window: &Window, camera: &mut Camera, dt: f32) {
-    let mut movement = Vec3::ZERO;
-    let forward = camera.forward();
-    let right = forward.cross(Vec3::UP).normalized();
-    if window.is_key_down(Key::W) {
-        movement += forward;
-    }
-    if window.is_key_down(Key::S) {
-        movement -= forward;
-    }
-    if window.is_key_down(Key::D) {
-        movement += right;
-    }
-    if window.is_key_down(Key::A) {
-        movement -= right;
+/// minifb can't size a window to the monitor, so fullscreen here is a borderless
+/// window at the largest whole multiple of the frame that fits the screen, and
+/// minifb scales the frame up itself.
+struct MinifbPlatform {
+    window: Window,
+    title: String,
+    fullscreen: bool,
+}
+
+impl MinifbPlatform {
+    fn open(title: &str) -> minifb::Result<Self> {
+        Ok(Self {
+            window: Self::create_window(title, false)?,
+            title: title.to_string(),
+            fullscreen: false,
+        })
     }
-    if window.is_key_down(Key::Space) {
-        movement += Vec3::UP;
+
+    fn create_window(title: &str, fullscreen: bool) -> minifb::Result<Window> {
+        let options = if fullscreen {
+            WindowOptions {
+                borderless: true,
+                topmost: true,
+                scale: minifb::Scale::FitScreen,
+                ..WindowOptions::default()
+            }
+        } else {
+            WindowOptions {
+                resize: false,
+                scale: minifb::Scale::X1,
+                ..WindowOptions::default()
+            }
+        };
+        let mut window = Window::new(title, WIDTH, HEIGHT, options)?;
+        if fullscreen {
+            window.set_position(0, 0);
+        }
+        window.limit_update_rate(Some(FRAME_INTERVAL));
+        Ok(window)
     }
-    if window.is_key_down(Key::LeftShift) {
-        movement -= Vec3::UP;
+}
+
+impl Platform for MinifbPlatform {
+    fn is_open(&self) -> bool {
+        self.window.is_open()
     }
 
-    if movement.length_squared() > 0.0 {
-        camera.position += movement.normalized() * CAMERA_SPEED * dt;
+    fn capture(&mut self) -> InputFrame {
+        let window = &self.window;
+        let keep = |keys: Vec<Key>| -> Vec<Key> {
+            keys.into_iter()
+                .filter(|key| INPUT_KEYS.contains(key))
+                .collect()
+        };
+        InputFrame {
+            down: keep(window.get_keys()),
+            pressed: keep(window.get_keys_pressed(KeyRepeat::No)),
+            repeated: keep(window.get_keys_pressed(KeyRepeat::Yes)),
+            mouse: window.get_mouse_pos(MouseMode::Discard),
+            mouse_down: window.get_mouse_down(MouseButton::Left),
+            scroll: window.get_scroll_wheel().map_or(0.0, |(_, y)| y),
+        }
     }
 
-    if window.is_key_down(Key::Left) {
-        camera.yaw -= 0.9 * dt;
+    fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
+        self.window.set_title(title);
     }
-    if window.is_key_down(Key::Right) {
-        camera.yaw += 0.9 * dt;
+
+    /// minifb has no way to change a window's style, so this opens a new window.
+    fn toggle_fullscreen(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if cfg!(target_arch = "wasm32") {
+            return Err("not available in the browser".into());
+        }
+        self.window = Self::create_window(&self.title, !self.fullscreen)?;
+        self.fullscreen = !self.fullscreen;
+        Ok(())
     }
-    if window.is_key_down(Key::Up) {
-        camera.pitch += 0.6 * dt;
+
+    fn frame_size(&self) -> (usize, usize) {
+        (WIDTH, HEIGHT)
     }
-    if window.is_key_down(Key::Down) {
-        camera.pitch -= 0.6 * dt;
+
+    /// The web canvas reads the buffer's bytes as RGBA, so the browser build swaps
+    /// red and blue and makes every pixel opaque first.
+    fn present(
+        &mut self,
+        frame: &[u32],
+        width: usize,
+        height: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        #[cfg(target_arch = "wasm32")]
+        let converted: Vec<u32> = frame
+            .iter()
+            .map(|&pixel| {
+                0xff00_0000 | (pixel & 0xff) << 16 | (pixel & 0xff00) | (pixel >> 16) & 0xff
+            })
+            .collect();
+        #[cfg(target_arch = "wasm32")]
+        let frame = &converted;
+        Ok(self.window.update_with_buffer(frame, width, height)?)
     }
-    camera.pitch = camera.pitch.clamp(-1.1, 1.1);
 }
 
-fn detect_warp_request(window: &Window, targets: &[WarpTarget]) -> Option<Vec3> {
-    let mut selected: Option<Vec3> = None;
-    for (idx, warp_key) in [Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5]
-        .iter()
-        .enumerate()
-    {
-        if window.is_key_pressed(*warp_key, KeyRepeat::No) {
-            if let Some(target) = targets.get(idx) {
-                selected = Some(target.anchor);
-            }
-        }
-    }
-    selected
+/// winit window presented through softbuffer. The loop keeps control and pumps the
+/// event loop itself once per frame, so both backends fit the same `while`.
+#[cfg(all(feature = "winit", not(target_arch = "wasm32")))]
+struct WinitPlatform {
+    event_loop: winit::event_loop::EventLoop<()>,
+    state: WinitState,
+    last_present: Instant,
 }
 
-fn smoothstep(t: f32) -> f32 {
-    t * t * (3.0 - 2.0 * t)
+#[cfg(all(feature = "winit", not(target_arch = "wasm32")))]
+struct WinitState {
+    title: String,
+    window: Option<std::rc::Rc<winit::window::Window>>,
+    surface: Option<
+        softbuffer::Surface<
+            winit::event_loop::OwnedDisplayHandle,
+            std::rc::Rc<winit::window::Window>,
+        >,
+    >,
+    error: Option<String>,
+    open: bool,
+    input: InputFrame,
+    /// Cursor in window pixels; `input.mouse` is only filled in per frame, in frame pixels.
+    cursor: Option<(f32, f32)>,
 }
 
-fn update_planets(planets: &mut [Planet], dt: f32) {
-    for planet in planets.iter_mut() {
-        planet.orbit_angle += planet.orbit_speed * dt;
-        if planet.orbit_angle > TAU {
-            planet.orbit_angle -= TAU;
-        }
-        planet.rotation += planet.rotation_speed * dt;
-        if planet.rotation > TAU {
-            planet.rotation -= TAU;
-        }
-        let pos = Vec3::new(
-            planet.orbit_angle.cos() * planet.orbit_radius,
-            0.0,
-            planet.orbit_angle.sin() * planet.orbit_radius,
-        );
-        planet.position = pos;
-        planet.transform = Mat4::translation(pos)
-            * Mat4::rotation_y(planet.rotation)
-            * Mat4::rotation_x(planet.axial_tilt)
-            * Mat4::scale(Vec3::splat(planet.radius));
-        if let Some(ring) = planet.ring.as_mut() {
-            ring.transform = Mat4::translation(pos)
-                * Mat4::rotation_y(planet.rotation)
-                * Mat4::rotation_x(planet.axial_tilt);
+#[cfg(all(feature = "winit", not(target_arch = "wasm32")))]
+impl WinitPlatform {
+    fn open(title: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let event_loop = winit::event_loop::EventLoop::new()?;
+        let mut platform = Self {
+            event_loop,
+            state: WinitState {
+                title: title.to_string(),
+                window: None,
+                surface: None,
+                error: None,
+                open: true,
+                input: InputFrame::default(),
+                cursor: None,
+            },
+            last_present: Instant::now(),
+        };
+        // The window only exists once the event loop has resumed.
+        while platform.state.surface.is_none() {
+            platform.pump();
+            if let Some(err) = platform.state.error.take() {
+                return Err(err.into());
+            }
+            if !platform.state.open {
+                return Err("window closed while opening".into());
+            }
         }
+        Ok(platform)
     }
-}
-
-fn update_sun(sun: &mut Star, dt: f32) {
-    sun.rotation += dt * 0.1;
-    sun.transform = Mat4::rotation_y(sun.rotation)
-        * Mat4::scale(Vec3::splat(sun.radius));
-}
 
-fn apply_collisions(position: &mut Vec3, sun: &Star, planets: &[Planet]) {
-    let mut constraints = Vec::with_capacity(planets.len() + 1);
-    constraints.push((sun.position, sun.radius + 6.0));
-    for planet in planets {
-        constraints.push((planet.position, planet.radius + 3.0));
+    fn window_size(&self) -> (usize, usize) {
+        self.state
+            .window
+            .as_ref()
+            .map_or((WIDTH, HEIGHT), |window| {
+                let size = window.inner_size();
+                (size.width as usize, size.height as usize)
+            })
     }
-    for (center, radius) in constraints {
-        let to_camera = *position - center;
-        let dist = to_camera.length();
-        if dist < radius {
-            let push_dir = if dist < 0.001 {
-                Vec3::new(0.0, 1.0, 0.0)
-            } else {
-                to_camera / dist
-            };
-            *position = center + push_dir * radius;
+
+    fn pump(&mut self) {
+        use winit::platform::pump_events::{EventLoopExtPumpEvents, PumpStatus};
+
+        if let PumpStatus::Exit(_) = self
+            .event_loop
+            .pump_app_events(Some(Duration::ZERO), &mut self.state)
+        {
+            self.state.open = false;
         }
     }
 }
 
-fn draw_orbits(renderer: &mut Renderer, planets: &[Planet], view_projection: &Mat4) {
-    for planet in planets {
-        let mut last: Option<Vec2> = None;
-        for segment in 0..ORBIT_SEGMENTS {
-            let angle = (segment as f32 / ORBIT_SEGMENTS as f32) * TAU;
-            let world = Vec3::new(angle.cos() * planet.orbit_radius, 0.0, angle.sin() * planet.orbit_radius);
-            if let Some(screen) = renderer.project_point(world, view_projection) {
-                if let Some(prev) = last {
-                    renderer.draw_line(prev, screen, planet.orbit_color);
-                }
-                last = Some(screen);
-            } else {
-                last = None;
+#[cfg(all(feature = "winit", not(target_arch = "wasm32")))]
+impl winit::application::ApplicationHandler for WinitState {
+    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+        let attributes = winit::window::Window::default_attributes()
+            .with_title(self.title.clone())
+            .with_inner_size(winit::dpi::PhysicalSize::new(WIDTH as u32, HEIGHT as u32));
+        let opened = event_loop
+            .create_window(attributes)
+            .map_err(|err| err.to_string())
+            .and_then(|window| {
+                let window = std::rc::Rc::new(window);
+                let context = softbuffer::Context::new(event_loop.owned_display_handle())
+                    .map_err(|err| err.to_string())?;
+                let surface = softbuffer::Surface::new(&context, std::rc::Rc::clone(&window))
+                    .map_err(|err| err.to_string())?;
+                Ok((window, surface))
+            });
+        match opened {
+            Ok((window, surface)) => {
+                self.window = Some(window);
+                self.surface = Some(surface);
+            }
+            Err(err) => {
+                self.error = Some(err);
+                event_loop.exit();
             }
         }
     }
-}
 
-fn spaceship_transform_for_camera(camera: &Camera) -> Mat4 {
-    let forward = camera.forward();
-    // Push the ship further in front of the camera so it always sits fully visible on screen.
-    let offset = forward * 14.0 + Vec3::new(0.0, -2.5, 0.0);
-    let position = camera.position + offset;
-    let up_reference = Vec3::UP;
-    let right = forward.cross(up_reference).normalized();
-    let corrected_up = right.cross(forward).normalized();
-    Mat4::from_basis(right, corrected_up, forward, position) * Mat4::scale(Vec3::splat(0.8))
-}
+    fn window_event(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        _: winit::window::WindowId,
+        event: winit::event::WindowEvent,
+    ) {
+        use winit::event::{ElementState, MouseScrollDelta, WindowEvent};
 
-fn build_planets(descriptors: &[PlanetDescriptor]) -> Vec<Planet> {
-    descriptors.iter().map(Planet::from_descriptor).collect()
+        let input = &mut self.input;
+        match event {
+            WindowEvent::CloseRequested => {
+                self.open = false;
+                event_loop.exit();
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                let winit::keyboard::PhysicalKey::Code(code) = event.physical_key else {
+                    return;
+                };
+                let Some(key) = minifb_key(code) else {
+                    return;
+                };
+                match event.state {
+                    ElementState::Pressed => {
+                        if !event.repeat {
+                            input.pressed.push(key);
+                        }
+                        input.repeated.push(key);
+                        if !input.down.contains(&key) {
+                            input.down.push(key);
+                        }
+                    }
+                    ElementState::Released => input.down.retain(|&down| down != key),
+                }
+            }
+            WindowEvent::Focused(false) => input.down.clear(),
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Some((position.x as f32, position.y as f32));
+            }
+            WindowEvent::CursorLeft { .. } => self.cursor = None,
+            WindowEvent::MouseInput {
+                state,
+                button: winit::event::MouseButton::Left,
+                ..
+            } => input.mouse_down = state == ElementState::Pressed,
+            // Only the sign is used, so lines and pixels can share the field.
+            WindowEvent::MouseWheel { delta, .. } => {
+                input.scroll += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32,
+                };
+            }
+            _ => {}
+        }
+    }
 }
 
-fn build_sun(theme: Theme) -> Star {
-    Star {
-        position: Vec3::ZERO,
-        radius: 14.0,
-        rotation: 0.0,
-        transform: Mat4::scale(Vec3::splat(14.0)),
-        color: theme.sun_color,
+#[cfg(all(feature = "winit", not(target_arch = "wasm32")))]
+impl Platform for WinitPlatform {
+    fn is_open(&self) -> bool {
+        self.state.open
     }
-}
 
-fn collect_warp_targets(sun: &Star, planets: &[Planet]) -> Vec<WarpTarget> {
-    let mut targets = Vec::with_capacity(planets.len() + 1);
-    targets.push(WarpTarget {
-        name: "Axiom Star",
-        anchor: sun.position + Vec3::new(0.0, sun.radius * 0.4, sun.radius + 8.0),
-    });
-    for planet in planets {
-        targets.push(WarpTarget {
-            name: planet.name,
-            anchor: planet.position + Vec3::new(0.0, planet.radius * 0.5, planet.radius + 6.0),
+    fn capture(&mut self) -> InputFrame {
+        self.pump();
+        let (x, y, width, height) = letterbox(self.window_size().0, self.window_size().1);
+        let mouse = self.state.cursor.and_then(|(cx, cy)| {
+            let (mx, my) = (cx - x as f32, cy - y as f32);
+            (mx >= 0.0 && my >= 0.0 && mx < width as f32 && my < height as f32).then_some((mx, my))
         });
+        let input = &mut self.state.input;
+        InputFrame {
+            down: input.down.clone(),
+            pressed: std::mem::take(&mut input.pressed),
+            repeated: std::mem::take(&mut input.repeated),
+            mouse,
+            mouse_down: input.mouse_down,
+            scroll: std::mem::take(&mut input.scroll),
+        }
     }
-    targets
-}
 
-struct Warp {
-    start: Vec3,
-    target: Vec3,
-    progress: f32,
-    duration: f32,
-}
+    fn set_title(&mut self, title: &str) {
+        if let Some(window) = &self.state.window {
+            window.set_title(title);
+        }
+    }
 
-struct WarpTarget {
-    #[allow(dead_code)]
-    name: &'static str,
-    anchor: Vec3,
-}
+    fn toggle_fullscreen(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let window = self.state.window.as_ref().ok_or("no window")?;
+        window.set_fullscreen(match window.fullscreen() {
+            Some(_) => None,
+            None => Some(winit::window::Fullscreen::Borderless(None)),
+        });
+        Ok(())
+    }
 
-#[derive(Clone, Copy)]
-struct Palette {
-    sky_top: Color,
-    sky_bottom: Color,
-    star_color: Color,
-    ecliptic: Color,
-}
+    fn frame_size(&self) -> (usize, usize) {
+        let (_, _, width, height) = letterbox(self.window_size().0, self.window_size().1);
+        (width, height)
+    }
 
-#[derive(Clone, Copy)]
-struct Theme {
-    name: &'static str,
-    palette: Palette,
-    sun_color: Color,
-    light_color: Color,
-    light_intensity: f32,
-    ship_color: Color,
-    planets: &'static [PlanetDescriptor],
+    /// Waits out the rest of the frame interval afterwards, which minifb does for
+    /// us through `limit_update_rate`.
+    fn present(
+        &mut self,
+        frame: &[u32],
+        width: usize,
+        height: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (window_width, window_height) = self.window_size();
+        if let (Some(surface), Some(surface_width), Some(surface_height)) = (
+            &mut self.state.surface,
+            std::num::NonZeroU32::new(window_width as u32),
+            std::num::NonZeroU32::new(window_height as u32),
+        ) {
+            surface.resize(surface_width, surface_height)?;
+            let mut buffer = surface.buffer_mut()?;
+            buffer.fill(0);
+            // The frame can lag a resize by one frame; copy whatever overlaps.
+            let (x, y, _, _) = letterbox(window_width, window_height);
+            let columns = width.min(window_width - x);
+            for row in 0..height.min(window_height - y) {
+                let start = (y + row) * window_width + x;
+                buffer[start..start + columns]
+                    .copy_from_slice(&frame[row * width..row * width + columns]);
+            }
+            buffer.present()?;
+        }
+        let elapsed = self.last_present.elapsed();
+        if elapsed < FRAME_INTERVAL {
+            std::thread::sleep(FRAME_INTERVAL - elapsed);
+        }
+        self.last_present = Instant::now();
+        Ok(())
+    }
 }
 
-#[derive(Clone, Copy)]
-struct PlanetDescriptor {
-    name: &'static str,
-    radius: f32,
-    orbit_radius: f32,
-    orbit_speed: f32,
-    rotation_speed: f32,
-    axial_tilt: f32,
-    color: Color,
-    orbit_color: Color,
-    ring: Option<RingDescriptor>,
+/// Largest `WIDTH`:`HEIGHT` rectangle centered in a `width` x `height` area, as
+/// (x, y, width, height). The rest of the area is the black bars.
+#[cfg(all(feature = "winit", not(target_arch = "wasm32")))]
+fn letterbox(width: usize, height: usize) -> (usize, usize, usize, usize) {
+    let fitted_width = (height * WIDTH / HEIGHT).clamp(1, width.max(1));
+    let fitted_height = (fitted_width * HEIGHT / WIDTH).clamp(1, height.max(1));
+    (
+        width.saturating_sub(fitted_width) / 2,
+        height.saturating_sub(fitted_height) / 2,
+        fitted_width,
+        fitted_height,
+    )
 }
 
-#[derive(Clone, Copy)]
-struct RingDescriptor {
-    inner_radius: f32,
-    outer_radius: f32,
-    color: Color,
+/// The key `code` sits on, in the minifb names the rest of the program uses.
+/// Only the keys in `INPUT_KEYS` are mapped.
+#[cfg(all(feature = "winit", not(target_arch = "wasm32")))]
+fn minifb_key(code: winit::keyboard::KeyCode) -> Option<Key> {
+    use winit::keyboard::KeyCode;
+
+    Some(match code {
+        KeyCode::KeyA => Key::A,
+        KeyCode::KeyB => Key::B,
+        KeyCode::KeyC => Key::C,
+        KeyCode::KeyD => Key::D,
+        KeyCode::KeyE => Key::E,
+        KeyCode::KeyF => Key::F,
+        KeyCode::KeyG => Key::G,
+        KeyCode::KeyH => Key::H,
+        KeyCode::KeyI => Key::I,
+        KeyCode::KeyJ => Key::J,
+        KeyCode::KeyK => Key::K,
+        KeyCode::KeyL => Key::L,
+        KeyCode::KeyM => Key::M,
+        KeyCode::KeyN => Key::N,
+        KeyCode::KeyO => Key::O,
+        KeyCode::KeyP => Key::P,
+        KeyCode::KeyQ => Key::Q,
+        KeyCode::KeyR => Key::R,
+        KeyCode::KeyS => Key::S,
+        KeyCode::KeyT => Key::T,
+        KeyCode::KeyU => Key::U,
+        KeyCode::KeyV => Key::V,
+        KeyCode::KeyW => Key::W,
+        KeyCode::KeyX => Key::X,
+        KeyCode::KeyY => Key::Y,
+        KeyCode::KeyZ => Key::Z,
+        KeyCode::Digit0 => Key::Key0,
+        KeyCode::Digit1 => Key::Key1,
+        KeyCode::Digit2 => Key::Key2,
+        KeyCode::Digit3 => Key::Key3,
+        KeyCode::Digit4 => Key::Key4,
+        KeyCode::Digit5 => Key::Key5,
+        KeyCode::Digit6 => Key::Key6,
+        KeyCode::Digit7 => Key::Key7,
+        KeyCode::Digit8 => Key::Key8,
+        KeyCode::Digit9 => Key::Key9,
+        KeyCode::Space => Key::Space,
+        KeyCode::ShiftLeft => Key::LeftShift,
+        KeyCode::ControlLeft => Key::LeftCtrl,
+        KeyCode::ControlRight => Key::RightCtrl,
+        KeyCode::ArrowLeft => Key::Left,
+        KeyCode::ArrowRight => Key::Right,
+        KeyCode::ArrowUp => Key::Up,
+        KeyCode::ArrowDown => Key::Down,
+        KeyCode::Escape => Key::Escape,
+        KeyCode::Tab => Key::Tab,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::NumpadEnter => Key::NumPadEnter,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Slash => Key::Slash,
+        KeyCode::Backquote => Key::Backquote,
+        KeyCode::Minus => Key::Minus,
+        KeyCode::Equal => Key::Equal,
+        KeyCode::Period => Key::Period,
+        KeyCode::Comma => Key::Comma,
+        KeyCode::BracketLeft => Key::LeftBracket,
+        KeyCode::BracketRight => Key::RightBracket,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::F11 => Key::F11,
+        _ => return None,
+    })
 }
 
-const ICE_PLANETS: [PlanetDescriptor; 4] = [
-    PlanetDescriptor {
-        name: "Naiad",
-        radius: 3.6,
-        orbit_radius: 16.0,
-        orbit_speed: 0.42,
-        rotation_speed: 1.7,
-        axial_tilt: 0.18,
-        color: Color::new(0.25, 0.55, 0.95),
-        orbit_color: Color::new(0.45, 0.75, 1.0),
-        ring: None,
-    },
-    PlanetDescriptor {
-        name: "Pyra",
-        radius: 5.8,
-        orbit_radius: 28.0,
-        orbit_speed: 0.3,
-        rotation_speed: 1.2,
-        axial_tilt: 0.35,
-        color: Color::new(0.92, 0.4, 0.18),
-        orbit_color: Color::new(1.0, 0.58, 0.3),
-        ring: None,
-    },
-    PlanetDescriptor {
-        name: "Terranox",
-        radius: 8.6,
-        orbit_radius: 44.0,
-        orbit_speed: 0.2,
-        rotation_speed: 0.95,
-        axial_tilt: 0.24,
-        color: Color::new(0.32, 0.65, 0.38),
-        orbit_color: Color::new(0.52, 0.85, 0.5),
-        ring: None,
-    },
-    PlanetDescriptor {
-        name: "Obsidian",
-        radius: 11.5,
-        orbit_radius: 64.0,
-        orbit_speed: 0.12,
-        rotation_speed: 0.7,
-        axial_tilt: 0.15,
-        color: Color::new(0.45, 0.46, 0.55),
-        orbit_color: Color::new(0.73, 0.74, 0.82),
-        ring: Some(RingDescriptor {
-            inner_radius: 15.0,
-            outer_radius: 20.0,
-            color: Color::new(0.65, 0.8, 0.95),
-        }),
-    },
-];
+async fn run_window(mut args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut session = InputSession::from_args(&mut args)?;
+    let backend = PlatformBackend::from_args(&mut args)?;
+    #[cfg(feature = "tracing")]
+    init_tracing(&mut args)?;
+    let mut themes = load_themes(&args)?;
 
-const EMBER_PLANETS: [PlanetDescriptor; 4] = [
-    PlanetDescriptor {
-        name: "Cinder",
-        radius: 4.2,
-        orbit_radius: 20.0,
-        orbit_speed: 0.38,
-        rotation_speed: 1.4,
-        axial_tilt: 0.1,
-        color: Color::new(0.95, 0.5, 0.15),
-        orbit_color: Color::new(1.0, 0.65, 0.25),
-        ring: None,
-    },
-    PlanetDescriptor {
-        name: "Boreal",
-        radius: 7.5,
-        orbit_radius: 36.0,
-        orbit_speed: 0.26,
-        rotation_speed: 1.1,
-        axial_tilt: 0.32,
-        color: Color::new(0.26, 0.8, 0.72),
-        orbit_color: Color::new(0.35, 0.95, 0.85),
-        ring: None,
-    },
-    PlanetDescriptor {
-        name: "Oasis",
-        radius: 5.1,
-        orbit_radius: 48.0,
-        orbit_speed: 0.18,
-        rotation_speed: 1.0,
-        axial_tilt: 0.28,
-        color: Color::new(0.3, 0.5, 0.95),
-        orbit_color: Color::new(0.45, 0.65, 1.0),
-        ring: None,
-    },
-    PlanetDescriptor {
-        name: "Titanforge",
-        radius: 13.0,
-        orbit_radius: 74.0,
-        orbit_speed: 0.1,
-        rotation_speed: 0.6,
-        axial_tilt: 0.12,
-        color: Color::new(0.55, 0.4, 0.35),
-        orbit_color: Color::new(0.75, 0.55, 0.4),
-        ring: Some(RingDescriptor {
-            inner_radius: 18.0,
-            outer_radius: 26.0,
-            color: Color::new(0.98, 0.86, 0.62),
-        }),
-    },
-];
+    let mut platform = backend.open("Icy System")?;
 
-const THEMES: [Theme; 2] = [
-    Theme {
-        name: "Icy System",
-        palette: Palette {
-            sky_top: Color::new(0.08, 0.12, 0.22),
-            sky_bottom: Color::new(0.01, 0.03, 0.08),
-            star_color: Color::new(0.82, 0.93, 1.0),
-            ecliptic: Color::new(0.2, 0.35, 0.45),
-        },
-        sun_color: Color::new(0.65, 0.9, 1.0),
-        light_color: Color::new(0.85, 0.95, 1.0),
-        light_intensity: 1.4,
-        ship_color: Color::new(0.7, 0.92, 1.0),
-        planets: &ICE_PLANETS,
-    },
-    Theme {
-        name: "Ember ",
-        palette: Palette {
-            sky_top: Color::new(0.18, 0.07, 0.02),
-            sky_bottom: Color::new(0.05, 0.02, 0.12),
-            star_color: Color::new(1.0, 0.85, 0.7),
-            ecliptic: Color::new(0.4, 0.2, 0.15),
+    let mut theme_index = 0usize;
+    let mut active_theme = themes[theme_index].clone();
+    let mut tag_filter = TagFilter::All;
+    let mut ring_style = RingStyle::Mesh;
+    let mut wireframe = false;
+    let mut orthographic = false;
+    // Split screen: the pilot's view on the left, a locked top-down one on the right.
+    let mut split_view = false;
+    let mut overview_renderer: Option<Renderer> = None;
+    let mut split_renderer: Option<Renderer> = None;
+    let mut zoom = FovZoom::default();
+    let mut bookmarks = Bookmarks::load(Path::new(BOOKMARKS_PATH));
+    let mut bookmark_mode = false;
+    let bindings = KeyBindings::load(Path::new(BINDINGS_PATH));
+    let mut telescope: Option<Telescope> = None;
+    let mut pip_renderer: Option<Renderer> = None;
+    let mut closeup_renderer: Option<Renderer> = None;
+    let mut physics_mode = false;
+    let mut transit: Option<TransitView> = None;
+    let mut wobble: Option<WobbleView> = None;
+    let mut trails: Option<Trails> = Some(Trails::default());
+    platform.set_title(&window_title(&active_theme, tag_filter));
+
+    let sphere_mesh = Mesh::uv_sphere(28, 18);
+    let ship_path = Path::new("spaceship.obj");
+    let spaceship_mesh = match Mesh::from_obj(ship_path) {
+        Err(err @ AppError::ObjParse { .. }) => {
+            eprintln!("{}; using the built-in ship", err);
+            Mesh::builtin(ship_path).expect("the ship mesh is compiled in")?
+        }
+        loaded => loaded?,
+    };
+
+    let mut settings = Settings::load(Path::new(SETTINGS_PATH));
+    let mut sim_clock = SimClock::new(&settings);
+    let mut renderer = Renderer::new(WIDTH, HEIGHT, active_theme.palette);
+    renderer.accessibility = settings.accessibility;
+    renderer.shading = settings.shading;
+    let mut planets = build_planets(&active_theme.planets);
+    let mut stars = build_stars(&active_theme.stars);
+    let mut black_holes = build_black_holes(&active_theme.black_holes);
+    let mut stations = build_stations(&active_theme.stations, &planets)?;
+    let mut asteroids = AsteroidCache::default();
+    let mut comets = build_comets(&active_theme.comets, &mut asteroids);
+    // Bodies of kinds the scene files don't describe, added in code. Everything that
+    // runs on them goes through `CelestialBody`.
+    let mut custom_bodies: Vec<Box<dyn CelestialBody>> = Vec::new();
+    let mut props = build_props(&active_theme.props)?;
+    let mut events = EventBus::default();
+    events.subscribe(report_event);
+    events.subscribe(mark_visited);
+    events.subscribe(shake_on_pushback);
+    #[cfg(feature = "audio")]
+    let audio = AudioOutput::start()
+        .map_err(|err| eprintln!("audio disabled: {}", err))
+        .ok();
+    #[cfg(feature = "audio")]
+    if let Some(output) = &audio {
+        events.subscribe(output.cue_player());
+    }
+    let (mut calendar, failed) = EventCalendar::start(
+        &active_theme,
+        0.0,
+        CalendarBodies {
+            planets: &planets,
+            stars: &mut stars,
+            stations: &mut stations,
+            comets: &mut comets,
+            asteroids: &mut asteroids,
         },
-        sun_color: Color::new(1.0, 0.75, 0.45),
-        light_color: Color::new(1.0, 0.75, 0.55),
-        light_intensity: 1.2,
-        ship_color: Color::new(0.95, 0.8, 0.65),
-        planets: &EMBER_PLANETS,
-    },
-];
+    );
+    emit_calendar(&mut events, failed);
+    let mut ship_color = active_theme.ship_color;
+
+    let mut camera = Camera::new(Vec3::new(0.0, 8.0, -40.0));
+    camera.yaw = 0.0;
+    camera.pitch = 0.08;
+
+    let mut last_frame = Instant::now();
+    let mut clock = 0.0;
+    let mut warp: Option<Warp> = None;
+    // Last blended frame of the active warp's motion blur; empty otherwise.
+    let mut warp_trail: Vec<u32> = Vec::new();
+    let mut shake = CameraShake::new(settings.shake);
+    let mut last_camera_position = camera.position;
+    let mut flight_hud = FlightHud::new();
+    let mut search: Option<SearchBox> = None;
+    let mut console: Option<Console> = None;
+    let mut selected_target: Option<usize> = None;
+    // Whether the left button was down last frame, so a click selects only once.
+    let mut mouse_was_down = false;
+    let mut governor = FrameGovernor::new(FRAME_BUDGET);
+    let mut scaled_renderer: Option<Renderer> = None;
+    let mut editor = PlanetEditor::new();
+    let mut theme_editor = ThemeEditor::new();
+    let mut prop_editor = PropEditor::new();
+    let mut watcher = SceneWatcher::new(active_theme.source.as_deref());
+    let mut shot_menu: Option<ShotMenu> = None;
+    let mut tour: Option<Tour> = None;
+    let mut sim_time = 0.0;
+    let mut paused = false;
+    let mut step_pending = false;
+    // Poses before and after the latest tick, kept for interpolated drawing.
+    let mut previous_poses: Option<BodyPoses> = None;
+    let mut ticked_poses: Option<BodyPoses> = None;
+    #[cfg(feature = "scripting")]
+    let mut scripts = ScriptHost::new();
+
+    while platform.is_open() {
+        #[cfg(feature = "tracing")]
+        let _frame_span = tracing::debug_span!("frame").entered();
+        let now = Instant::now();
+        let mut dt = (now - last_frame).as_secs_f32();
+        if dt > 0.1 {
+            dt = 0.1;
+        }
+        last_frame = now;
+        let live = platform.capture();
+        if session.fixed_step() {
+            dt = FIXED_TIMESTEP;
+            // Let the viewer stop a replay even though its keys come from the file.
+            if live.is_key_pressed(Key::Escape, KeyRepeat::No) {
+                break;
+            }
+        }
+        let Some(input) = session.next_input(live) else {
+            break;
+        };
+        let (mut frame_width, frame_height) = platform.frame_size();
+        if split_view {
+            frame_width /= 2;
+        }
+        if (renderer.width, renderer.height) != (frame_width, frame_height) {
+            renderer.resize(frame_width, frame_height);
+        }
+        let screen = (frame_width as f32, frame_height as f32);
+        clock += dt;
+
+        // Paused: only a requested single step moves the bodies, by one tick.
+        let ticks = match (paused, step_pending) {
+            (false, _) => sim_clock.ticks(dt),
+            (true, true) => 1,
+            (true, false) => 0,
+        };
+        step_pending = false;
+        #[cfg(feature = "tracing")]
+        let update_span = tracing::debug_span!("update", ticks).entered();
+        for tick in 0..ticks {
+            if tick + 1 == ticks && settings.interpolate {
+                previous_poses = Some(BodyPoses::capture(
+                    &mut planets,
+                    &mut stars,
+                    &mut black_holes,
+                    &mut stations,
+                    &mut comets,
+                ));
+            }
+            advance_simulation(
+                &mut SimBodies {
+                    stars: &mut stars,
+                    planets: &mut planets,
+                    black_holes: &mut black_holes,
+                    stations: &mut stations,
+                    comets: &mut comets,
+                    custom: &mut custom_bodies,
+                },
+                sim_clock.tick,
+                physics_mode,
+            );
+            #[cfg(feature = "scripting")]
+            for message in scripts.run(
+                &mut planets,
+                &mut stations,
+                camera.position,
+                sim_time + (tick + 1) as f32 * sim_clock.tick,
+                sim_clock.tick,
+            ) {
+                watcher.report(message);
+            }
+        }
+        if ticks > 0 && settings.interpolate {
+            ticked_poses = Some(BodyPoses::capture(
+                &mut planets,
+                &mut stars,
+                &mut black_holes,
+                &mut stations,
+                &mut comets,
+            ));
+        }
+        #[cfg(feature = "tracing")]
+        drop(update_span);
+        let sim_dt = ticks as f32 * sim_clock.tick;
+        sim_time += sim_dt;
+        let fired = calendar.update(
+            &active_theme,
+            sim_time,
+            &mut CalendarBodies {
+                planets: &planets,
+                stars: &mut stars,
+                stations: &mut stations,
+                comets: &mut comets,
+                asteroids: &mut asteroids,
+            },
+        );
+        // Warp target indices shift when bodies come and go.
+        if fired
+            .iter()
+            .any(|(event, _)| matches!(event.action, EventAction::Arrival | EventAction::Departure))
+        {
+            selected_target = None;
+        }
+        emit_calendar(&mut events, fired);
+
+        let mut requested_warp: Option<usize> = None;
+        let mut requested_shot: Option<usize> = None;
+        let search_was_open = search.is_some();
+        let console_was_open = console.is_some();
+        let menu_was_open = shot_menu.is_some();
+        if !search_was_open && !console_was_open && !menu_was_open {
+            if bindings.pressed(&input, Action::Quit, KeyRepeat::No) {
+                break;
+            }
+
+            if bindings.pressed(&input, Action::Fullscreen, KeyRepeat::No) {
+                if let Err(err) = platform.toggle_fullscreen() {
+                    watcher.report(format!("fullscreen: {}", err));
+                }
+            }
+
+            if bindings.pressed(&input, Action::CycleTheme, KeyRepeat::No) {
+                let next = (theme_index + 1) % themes.len();
+                // Like a failed reload, a theme whose meshes don't load leaves the
+                // current one in place.
+                let new_planets = build_planets(&themes[next].planets);
+                let built: Result<_, Box<dyn std::error::Error>> =
+                    build_stations(&themes[next].stations, &new_planets)
+                        .and_then(|stations| Ok((stations, build_props(&themes[next].props)?)));
+                match built {
+                    Ok((new_stations, new_props)) => {
+                        theme_index = next;
+                        active_theme = themes[theme_index].clone();
+                        planets = new_planets;
+                        stars = build_stars(&active_theme.stars);
+                        black_holes = build_black_holes(&active_theme.black_holes);
+                        stations = new_stations;
+                        comets = build_comets(&active_theme.comets, &mut asteroids);
+                        props = new_props;
+                        ship_color = active_theme.ship_color;
+                        if let Some(recorded) = trails.as_mut() {
+                            *recorded = Trails::default();
+                        }
+                        selected_target = None;
+                        editor = PlanetEditor::new();
+                        prop_editor = PropEditor::new();
+                        tour = None;
+                        sim_time = 0.0;
+                        let (started, failed) = EventCalendar::start(
+                            &active_theme,
+                            sim_time,
+                            CalendarBodies {
+                                planets: &planets,
+                                stars: &mut stars,
+                                stations: &mut stations,
+                                comets: &mut comets,
+                                asteroids: &mut asteroids,
+                            },
+                        );
+                        calendar = started;
+                        emit_calendar(&mut events, failed);
+                        watcher = SceneWatcher::new(active_theme.source.as_deref());
+                        renderer.set_palette(active_theme.palette);
+                        platform.set_title(&window_title(&active_theme, tag_filter));
+                        events.emit(AppEvent::ThemeChanged {
+                            name: active_theme.name.clone(),
+                        });
+                    }
+                    Err(err) => {
+                        eprintln!("theme switch failed: {}", err);
+                        watcher.report(format!("theme switch failed: {}", err));
+                    }
+                }
+            }
+
+            if bindings.pressed(&input, Action::PhysicsMode, KeyRepeat::No) {
+                physics_mode = !physics_mode;
+            }
+
+            // With the co-pilot seat on, Pause and Transit only answer to keys the
+            // telescope isn't aiming with.
+            let telescope_keys: &[Key] = match telescope {
+                Some(_) => &Telescope::KEYS,
+                None => &[],
+            };
+            if bindings.pressed_except(&input, Action::Pause, KeyRepeat::No, telescope_keys) {
+                paused = !paused;
+            }
+            if paused && bindings.pressed(&input, Action::Step, KeyRepeat::Yes) {
+                step_pending = true;
+            }
+
+            if bindings.pressed_except(&input, Action::Transit, KeyRepeat::No, telescope_keys) {
+                transit = match transit {
+                    Some(_) => None,
+                    None => Some(TransitView::new()),
+                };
+            }
+
+            if bindings.pressed(&input, Action::Wobble, KeyRepeat::No) {
+                wobble = match wobble {
+                    Some(_) => None,
+                    None => Some(WobbleView::new()),
+                };
+            }
+
+            if bindings.pressed(&input, Action::RingStyle, KeyRepeat::No) {
+                ring_style = ring_style.next();
+            }
+
+            if bindings.pressed(&input, Action::DebugView, KeyRepeat::No) {
+                renderer.debug_view = renderer.debug_view.next();
+                watcher.report(format!("view: {}", renderer.debug_view.label()));
+            }
+
+            if bindings.pressed(&input, Action::Telescope, KeyRepeat::No) {
+                telescope = match telescope {
+                    Some(_) => None,
+                    None => Some(Telescope::new(&camera)),
+                };
+            }
+
+            if bindings.pressed(&input, Action::Wireframe, KeyRepeat::No) {
+                wireframe = !wireframe;
+                watcher.report(format!("wireframe: {}", wireframe.scene_text()));
+            }
+
+            if bindings.pressed(&input, Action::ColorMode, KeyRepeat::No) {
+                settings.accessibility = settings.accessibility.next();
+                renderer.accessibility = settings.accessibility;
+                if let Err(err) = settings.save(Path::new(SETTINGS_PATH)) {
+                    eprintln!("could not save settings: {}", err);
+                }
+                watcher.report(format!("colors: {}", settings.accessibility.label()));
+            }
+
+            if bindings.pressed(&input, Action::TagFilter, KeyRepeat::No) {
+                tag_filter = tag_filter.next();
+                platform.set_title(&window_title(&active_theme, tag_filter));
+            }
+
+            if bindings.pressed(&input, Action::Search, KeyRepeat::No) {
+                search = Some(SearchBox::new());
+            }
+
+            if bindings.pressed(&input, Action::Console, KeyRepeat::No) {
+                console = Some(Console::new());
+            }
+
+            if bindings.pressed(&input, Action::ShotMenu, KeyRepeat::No) {
+                shot_menu = Some(ShotMenu::new());
+                tour = None;
+            }
+
+            if bindings.pressed(&input, Action::PlanetEditor, KeyRepeat::No) {
+                editor.active = !editor.active;
+                theme_editor.active = false;
+                prop_editor.active = false;
+            }
+            if editor.active {
+                editor.handle_input(&input, &mut planets, dt);
+                editor.handle_mouse(&input, &camera, screen, &mut planets);
+            }
+
+            if bindings.pressed(&input, Action::ThemeEditor, KeyRepeat::No) {
+                theme_editor.active = !theme_editor.active;
+                editor.active = false;
+                prop_editor.active = false;
+            }
+            if theme_editor.active {
+                match theme_editor.handle_input(&input, &mut active_theme, &mut stars, dt) {
+                    ThemeEditAction::None => {}
+                    ThemeEditAction::Changed => renderer.set_palette(active_theme.palette),
+                    ThemeEditAction::Save => {
+                        save_session(&mut active_theme, &planets, &props, &mut watcher)
+                    }
+                }
+            }
+
+            if bindings.pressed(&input, Action::PropEditor, KeyRepeat::No) {
+                prop_editor.active = !prop_editor.active;
+                editor.active = false;
+                theme_editor.active = false;
+            }
+            if prop_editor.active && prop_editor.handle_input(&input, &camera, screen, &mut props) {
+                save_session(&mut active_theme, &planets, &props, &mut watcher);
+            }
+        }
+
+        if !session.fixed_step() && watcher.poll(dt) {
+            let reloaded: Result<_, Box<dyn std::error::Error>> = active_theme
+                .source
+                .as_deref()
+                .ok_or_else(|| "theme has no source file".into())
+                .and_then(|path| Ok(load_scene_theme(path)?))
+                .and_then(|theme| {
+                    let new_planets = build_planets(&theme.planets);
+                    let new_stations = build_stations(&theme.stations, &new_planets)?;
+                    let new_props = build_props(&theme.props)?;
+                    Ok((theme, new_planets, new_stations, new_props))
+                });
+            match reloaded {
+                Ok((theme, new_planets, new_stations, new_props)) => {
+                    let mut new_planets = new_planets;
+                    let mut new_stars = build_stars(&theme.stars);
+                    let mut new_holes = build_black_holes(&theme.black_holes);
+                    let mut new_stations = new_stations;
+                    let mut new_comets = build_comets(&theme.comets, &mut asteroids);
+                    carry_over_phases(
+                        (&planets, &mut new_planets),
+                        (&stars, &mut new_stars),
+                        (&black_holes, &mut new_holes),
+                        (&stations, &mut new_stations),
+                        (&mut comets, &mut new_comets),
+                    );
+                    planets = new_planets;
+                    stars = new_stars;
+                    black_holes = new_holes;
+                    stations = new_stations;
+                    comets = new_comets;
+                    props = new_props;
+                    ship_color = theme.ship_color;
+                    selected_target = None;
+                    editor = PlanetEditor::new();
+                    prop_editor = PropEditor::new();
+                    tour = None;
+                    renderer.set_palette(theme.palette);
+                    themes[theme_index] = theme.clone();
+                    active_theme = theme;
+                    let (started, failed) = EventCalendar::start(
+                        &active_theme,
+                        sim_time,
+                        CalendarBodies {
+                            planets: &planets,
+                            stars: &mut stars,
+                            stations: &mut stations,
+                            comets: &mut comets,
+                            asteroids: &mut asteroids,
+                        },
+                    );
+                    calendar = started;
+                    emit_calendar(&mut events, failed);
+                    platform.set_title(&window_title(&active_theme, tag_filter));
+                    watcher.report(format!("reloaded {}", watcher.file_name()));
+                }
+                Err(err) => {
+                    eprintln!("scene reload failed: {}", err);
+                    watcher.report(format!("reload failed: {}", err));
+                }
+            }
+        }
+
+        let warp_targets = collect_warp_targets(
+            celestial_bodies(
+                &stars,
+                &planets,
+                &black_holes,
+                &stations,
+                &comets,
+                &custom_bodies,
+            ),
+            &planets,
+        );
+
+        let clicked = input.mouse_down() && !mouse_was_down;
+        mouse_was_down = input.mouse_down();
+        let editing = editor.active || theme_editor.active || prop_editor.active;
+        if let Some((mx, my)) = input
+            .mouse_pos()
+            .filter(|_| clicked && !editing && search.is_none() && console.is_none())
+        {
+            let (origin, direction) = camera.ray(mx, my, screen.0, screen.1);
+            let ship_transform = spaceship_transform_for_camera(&camera);
+            let ship =
+                (transit.is_none() && !orthographic).then_some((&spaceship_mesh, &ship_transform));
+            match pick_body(origin, direction, &warp_targets, &stations, ship) {
+                Some(Pick::Target(idx)) => {
+                    selected_target = Some(idx);
+                    events.emit(AppEvent::BodySelected {
+                        name: warp_targets[idx].name.clone(),
+                    });
+                }
+                Some(Pick::Ship) => {
+                    selected_target = None;
+                    events.emit(AppEvent::BodySelected {
+                        name: "ship".to_string(),
+                    });
+                }
+                None => {}
+            }
+        }
+
+        if let Some(search_box) = search.as_mut().filter(|_| search_was_open) {
+            match search_box.handle_keys(&input, &warp_targets) {
+                SearchAction::Typing => {}
+                SearchAction::Close => search = None,
+                SearchAction::Select(idx) => {
+                    selected_target = Some(idx);
+                    events.emit(AppEvent::BodySelected {
+                        name: warp_targets[idx].name.clone(),
+                    });
+                    search = None;
+                }
+                SearchAction::Warp(idx) => {
+                    selected_target = Some(idx);
+                    requested_warp = Some(idx);
+                    search = None;
+                }
+            }
+        } else if let Some(open_console) = console.as_mut().filter(|_| console_was_open) {
+            match open_console.handle_keys(&input) {
+                ConsoleAction::Typing => {}
+                ConsoleAction::Close => console = None,
+                ConsoleAction::Submit(line) => match parse_console_command(&line) {
+                    Ok(ConsoleCommand::Goto(coords)) => {
+                        let target = stars[0].position + coords.to_offset();
+                        warp = Some(Warp::to_position(camera.position, target));
+                        console = None;
+                    }
+                    Ok(ConsoleCommand::FrameSelected) => {
+                        match selected_target.and_then(|idx| warp_targets.get(idx)) {
+                            Some(target) => {
+                                warp = Some(Warp {
+                                    destination: Some(target.name.clone()),
+                                    ..frame_body(&camera, target.center, target.radius)
+                                });
+                                console = None;
+                            }
+                            None => {
+                                open_console.error = Some("nothing selected, use / first".into())
+                            }
+                        }
+                    }
+                    Ok(ConsoleCommand::FrameAll) => {
+                        let extent =
+                            system_extent(&stars, &planets, &black_holes, &stations, &comets);
+                        warp = Some(frame_system(&camera, extent));
+                        console = None;
+                    }
+                    Ok(ConsoleCommand::ToggleOrthographic) => {
+                        orthographic = !orthographic;
+                        console = None;
+                    }
+                    Ok(ConsoleCommand::ToggleSplitView) => {
+                        split_view = !split_view;
+                        console = None;
+                    }
+                    Ok(ConsoleCommand::ToggleTrails) => {
+                        trails = match trails {
+                            Some(_) => None,
+                            None => Some(Trails::default()),
+                        };
+                        console = None;
+                    }
+                    Ok(ConsoleCommand::Export(path)) => {
+                        let world = WorldView {
+                            trails: None,
+                            sphere: &sphere_mesh,
+                            stars: &stars,
+                            planets: &planets,
+                            black_holes: &black_holes,
+                            stations: &stations,
+                            comets: &comets,
+                            custom_bodies: &custom_bodies,
+                            props: &props,
+                            tag_filter,
+                            ring_style,
+                            wireframe,
+                        };
+                        match export_scene_obj(&path, &world) {
+                            Ok(objects) => {
+                                watcher.report(format!(
+                                    "exported {} objects to {}",
+                                    objects,
+                                    path.display()
+                                ));
+                                console = None;
+                            }
+                            Err(err) => open_console.error = Some(err.to_string()),
+                        }
+                    }
+                    Err(message) => open_console.error = Some(message),
+                },
+            }
+        } else if let Some(menu) = shot_menu.as_mut().filter(|_| menu_was_open) {
+            match menu.handle_keys(&input, active_theme.shots.len()) {
+                ShotMenuAction::Browsing => {}
+                ShotMenuAction::Close => shot_menu = None,
+                ShotMenuAction::Jump(idx) => {
+                    requested_shot = Some(idx);
+                    shot_menu = None;
+                }
+                ShotMenuAction::Tour(idx) => {
+                    tour = Some(Tour::new(idx));
+                    requested_shot = Some(idx);
+                    shot_menu = None;
+                }
+            }
+        } else if search.is_none() && console.is_none() && shot_menu.is_none() {
+            if warp.is_none() && transit.is_none() {
+                handle_input(&input, &bindings, &mut camera, dt);
+                zoom.update(&input, &bindings, &mut camera, dt);
+            }
+            if let Some(scope) = telescope.as_mut() {
+                scope.handle_input(&input, dt);
+            }
+            if bindings.pressed(&input, Action::BookmarkMode, KeyRepeat::No) {
+                bookmark_mode = !bookmark_mode;
+                watcher.report(format!("bookmarks: {}", bookmark_mode.scene_text()));
+            }
+            let ctrl = input.is_key_down(Key::LeftCtrl) || input.is_key_down(Key::RightCtrl);
+            let slot = bindings.pressed_slot(&input);
+            if ctrl {
+                if let Some(slot) = slot {
+                    bookmarks.store(slot, &camera);
+                    match bookmarks.save(Path::new(BOOKMARKS_PATH)) {
+                        Ok(()) => watcher.report(format!("bookmark {} saved", slot + 1)),
+                        Err(err) => watcher.report(format!("could not save bookmarks: {}", err)),
+                    }
+                }
+            } else if bookmark_mode {
+                match slot.map(|slot| (slot, bookmarks.get(slot))) {
+                    Some((_, Some(shot))) if warp.is_none() => {
+                        warp = Some(Warp::to_shot(&camera, shot));
+                    }
+                    Some((slot, None)) => {
+                        watcher.report(format!("bookmark {} is empty, CTRL+{0} saves it", slot + 1))
+                    }
+                    _ => {}
+                }
+            } else {
+                requested_warp = detect_warp_request(&input, &bindings, &warp_targets);
+            }
+        }
+
+        if let Some(active_warp) = warp.as_mut() {
+            zoom.target = None;
+            if active_warp.progress == 0.0 {
+                events.emit(AppEvent::WarpStarted {
+                    destination: active_warp.destination.clone(),
+                });
+            }
+            active_warp.progress += dt;
+            let t = (active_warp.progress / active_warp.duration).min(1.0);
+            let eased = smoothstep(t);
+            camera.position = Vec3::lerp(active_warp.start, active_warp.target, eased);
+            if let Some((from, to)) = active_warp.view {
+                let yaw_delta = (to.yaw - from.yaw + PI).rem_euclid(TAU) - PI;
+                camera.yaw = from.yaw + yaw_delta * eased;
+                camera.pitch = from.pitch + (to.pitch - from.pitch) * eased;
+                camera.fov = from.fov + (to.fov - from.fov) * eased;
+            }
+            if t >= 1.0 {
+                events.emit(AppEvent::WarpCompleted {
+                    planet: active_warp.planet,
+                });
+                warp = None;
+            }
+        } else if let Some(requested) = requested_warp.and_then(|idx| warp_targets.get(idx)) {
+            warp = Some(Warp::new(camera.position, requested));
+        }
+
+        if let Some(active_tour) = tour.as_mut().filter(|_| requested_shot.is_none()) {
+            if warp.is_none() {
+                active_tour.timer += dt;
+            }
+            let hold = active_theme
+                .shots
+                .get(active_tour.shot)
+                .map_or(0.0, |shot| shot.hold);
+            if active_tour.timer >= hold {
+                active_tour.shot += 1;
+                active_tour.timer = 0.0;
+                if active_tour.shot < active_theme.shots.len() {
+                    requested_shot = Some(active_tour.shot);
+                } else {
+                    tour = None;
+                }
+            }
+        }
+
+        if let Some(shot) = requested_shot.and_then(|idx| active_theme.shots.get(idx)) {
+            let rebuilt = shot.time.and_then(|time| {
+                let new_planets = build_planets(&active_theme.planets);
+                match build_stations(&active_theme.stations, &new_planets) {
+                    Ok(new_stations) => Some((time, new_planets, new_stations)),
+                    Err(err) => {
+                        eprintln!("shot {} failed: {}", shot.name, err);
+                        watcher.report(format!("shot {} failed: {}", shot.name, err));
+                        None
+                    }
+                }
+            });
+            if let Some((time, new_planets, new_stations)) = rebuilt {
+                planets = new_planets;
+                stars = build_stars(&active_theme.stars);
+                black_holes = build_black_holes(&active_theme.black_holes);
+                stations = new_stations;
+                comets = build_comets(&active_theme.comets, &mut asteroids);
+                fast_forward(
+                    &mut SimBodies {
+                        stars: &mut stars,
+                        planets: &mut planets,
+                        black_holes: &mut black_holes,
+                        stations: &mut stations,
+                        comets: &mut comets,
+                        custom: &mut custom_bodies,
+                    },
+                    time,
+                    physics_mode,
+                );
+                sim_time = time;
+                let (started, failed) = EventCalendar::start(
+                    &active_theme,
+                    sim_time,
+                    CalendarBodies {
+                        planets: &planets,
+                        stars: &mut stars,
+                        stations: &mut stations,
+                        comets: &mut comets,
+                        asteroids: &mut asteroids,
+                    },
+                );
+                calendar = started;
+                emit_calendar(&mut events, failed);
+            }
+            warp = Some(Warp::to_shot(&camera, shot));
+        }
+
+        let pushed = apply_collisions(
+            celestial_bodies(
+                &stars,
+                &planets,
+                &black_holes,
+                &stations,
+                &comets,
+                &custom_bodies,
+            ),
+            &mut camera.position,
+        );
+        if pushed > 0.0 {
+            events.emit(AppEvent::CollisionPushback {
+                depth: pushed,
+                warping: warp.is_some(),
+            });
+        }
+        events.dispatch(&mut EventTargets {
+            planets: &mut planets,
+            shake: &mut shake,
+            watcher: &mut watcher,
+        });
+        let speed = (camera.position - last_camera_position).length() / dt.max(1e-4);
+        // Warps bring their own effects, and their speed is no flyby.
+        if warp.is_none() {
+            shake.add(flyby_trauma(camera.position, speed, &planets) * dt);
+        }
+        last_camera_position = camera.position;
+        shake.update(dt);
+        // Scale the diagram so the primary star's depth keeps the framing the
+        // perspective view had there; flying closer to it zooms in.
+        camera.orthographic = orthographic
+            .then(|| (camera.position - stars[0].position).length() * (camera.fov * 0.5).tan());
+        flight_hud.update(
+            &camera,
+            speed,
+            dt,
+            &warp_targets,
+            warp.as_ref(),
+            stars[0].position,
+        );
+
+        if let Some(view) = transit.as_mut() {
+            view.record(
+                transit_brightness(&stars[0], &planets, view.observer(&stars[0])),
+                sim_dt,
+            );
+        }
+        if let Some(view) = wobble.as_mut() {
+            view.record(stellar_wobble(&stars[0], &planets), sim_dt);
+        }
+        if let Some(recorded) = trails.as_mut() {
+            recorded.record(&planets, spaceship_position_for_camera(&camera), sim_dt, dt);
+        }
+        #[cfg(feature = "audio")]
+        if let Some(output) = &audio {
+            output.update(spatialize_emitters(
+                &camera,
+                celestial_bodies(
+                    &stars,
+                    &planets,
+                    &black_holes,
+                    &stations,
+                    &comets,
+                    &custom_bodies,
+                ),
+                &planets,
+                &stations,
+            ));
+        }
+        let mut view_camera = match &transit {
+            Some(view) => view.camera(
+                &stars[0],
+                system_extent(&stars, &planets, &black_holes, &stations, &comets),
+            ),
+            None => shake.apply(camera),
+        };
+        if let Some(active_warp) = &warp {
+            view_camera.fov = (view_camera.fov + active_warp.fov_kick()).min(MAX_FOV);
+        }
+        renderer.star_streak = warp.as_ref().map_or(0.0, Warp::speed);
+        // Draw between the last two ticks, unless the bodies were rebuilt or moved
+        // since (theme switch, reload, jump to a shot) or the view is paused.
+        let mut current_poses = None;
+        if let (Some(previous), Some(ticked)) = (&previous_poses, &ticked_poses) {
+            let current = BodyPoses::capture(
+                &mut planets,
+                &mut stars,
+                &mut black_holes,
+                &mut stations,
+                &mut comets,
+            );
+            if !paused && current == *ticked {
+                previous.blend(ticked, sim_clock.alpha()).apply(
+                    &mut planets,
+                    &mut stars,
+                    &mut black_holes,
+                    &mut stations,
+                    &mut comets,
+                );
+                current_poses = Some(current);
+            }
+        }
+        let world = WorldView {
+            sphere: &sphere_mesh,
+            stars: &stars,
+            planets: &planets,
+            black_holes: &black_holes,
+            stations: &stations,
+            comets: &comets,
+            custom_bodies: &custom_bodies,
+            props: &props,
+            tag_filter,
+            ring_style,
+            wireframe,
+            trails: trails.as_ref(),
+        };
+        let ship = (transit.is_none() && !orthographic).then(|| RenderInstance {
+            mesh: &spaceship_mesh,
+            transform: spaceship_transform_for_camera(&view_camera),
+            material: Material {
+                color: ship_color,
+                emissive: 0.2,
+                emissive_map: Some(EmissiveMap::Spots(&SHIP_ENGINE_GLOW)),
+                reflectivity: 0.3,
+                ..Default::default()
+            },
+            wireframe,
+        });
+        #[cfg(feature = "tracing")]
+        let render_span = tracing::debug_span!("render").entered();
+        let scale = governor.resolution_scale();
+        let view_projection = if scale < 1.0 {
+            let scene = renderer.downscaled(scale, &mut scaled_renderer);
+            let view_projection = draw_world(scene, &world, &view_camera, clock, &governor, ship);
+            renderer.upscale_from(scene);
+            view_projection
+        } else {
+            draw_world(&mut renderer, &world, &view_camera, clock, &governor, ship)
+        };
+        match &warp {
+            Some(active_warp) => renderer.accumulate(&mut warp_trail, active_warp.motion_blur()),
+            None => warp_trail.clear(),
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            triangles_submitted = renderer.stats.triangles_submitted,
+            triangles_culled = renderer.stats.triangles_culled,
+            triangles_rasterized = renderer.stats.triangles_rasterized,
+            pixels_shaded = renderer.stats.pixels_shaded,
+            "scene drawn"
+        );
+        if let Some(scope) = &telescope {
+            let pip = renderer.downscaled(PIP_SCALE, &mut pip_renderer);
+            draw_world(pip, &world, &scope.camera(&camera), clock, &governor, None);
+        }
+        let closeup = selected_target
+            .and_then(|idx| warp_targets.get(idx))
+            .and_then(|target| {
+                closeup_camera(target, &camera, stars[0].position).map(|view| (target, view))
+            });
+        if let Some((_, closeup_view)) = &closeup {
+            let inset = renderer.downscaled(CLOSEUP_SCALE, &mut closeup_renderer);
+            draw_world(inset, &world, closeup_view, clock, &governor, None);
+        }
+        if split_view {
+            let extent = system_extent(&stars, &planets, &black_holes, &stations, &comets);
+            let overview = renderer.downscaled(1.0, &mut overview_renderer);
+            let aspect = overview.width as f32 / overview.height as f32;
+            let overview_view = overview_camera(extent, aspect);
+            let overview_projection =
+                draw_world(overview, &world, &overview_view, clock, &governor, None);
+            draw_overview_marker(overview, &camera, &overview_projection);
+        }
+        #[cfg(feature = "tracing")]
+        drop(render_span);
+        if let Some(current) = current_poses {
+            current.apply(
+                &mut planets,
+                &mut stars,
+                &mut black_holes,
+                &mut stations,
+                &mut comets,
+            );
+        }
+
+        if let Some(target) = selected_target.and_then(|idx| warp_targets.get(idx)) {
+            draw_selection_marker(
+                &mut renderer,
+                target,
+                &view_camera,
+                &view_projection,
+                &active_theme.palette,
+            );
+        }
+        let mut status_y = 6;
+        if physics_mode {
+            let label = "PHYSICS: RADIATION PRESSURE";
+            let x = renderer.width as i32 - renderer.text_width(label, 1) - 6;
+            renderer.draw_text(x, status_y, label, Color::new(1.0, 0.85, 0.4), 1);
+            status_y += GLYPH_HEIGHT + 4;
+        }
+        if paused {
+            let label = format!("PAUSED  t={:.3}s  N: step", sim_time);
+            let x = renderer.width as i32 - renderer.text_width(&label, 1) - 6;
+            renderer.draw_text(x, status_y, &label, Color::new(0.6, 0.9, 1.0), 1);
+            status_y += GLYPH_HEIGHT + 4;
+        }
+        if bookmark_mode {
+            let label = "BOOKMARKS  1-9: fly  CTRL+1-9: save";
+            let x = renderer.width as i32 - renderer.text_width(label, 1) - 6;
+            renderer.draw_text(x, status_y, label, Color::new(0.7, 1.0, 0.75), 1);
+        }
+        if transit.is_none() {
+            draw_flight_hud(&mut renderer, &flight_hud);
+        }
+        draw_coordinates(
+            &mut renderer,
+            SphericalCoords::from_offset(camera.position - stars[0].position),
+        );
+        if let (Some(scope), Some(pip)) = (&telescope, &pip_renderer) {
+            draw_telescope_inset(&mut renderer, pip, scope);
+        }
+        if let (Some((target, _)), Some(inset)) = (&closeup, &closeup_renderer) {
+            draw_closeup_inset(&mut renderer, inset, target);
+        }
+        if let Some(target) = selected_target.and_then(|idx| warp_targets.get(idx)) {
+            draw_info_panel(
+                &mut renderer,
+                target,
+                &camera,
+                equilibrium_temperature(target.center, &stars),
+            );
+        }
+        if let Some(view) = &wobble {
+            draw_wobble_trail(&mut renderer, view, &stars[0], &view_projection);
+        }
+        if let Some(view) = &transit {
+            draw_light_curve(&mut renderer, view, &active_theme.palette);
+        }
+        if let Some(view) = &wobble {
+            let stacked = if transit.is_some() { 130 } else { 0 };
+            draw_radial_velocity(&mut renderer, view, stacked);
+        }
+        if let Some(search_box) = &search {
+            draw_search(
+                &mut renderer,
+                search_box,
+                &warp_targets,
+                &active_theme.palette,
+            );
+        }
+        if let Some(open_console) = &console {
+            draw_console(&mut renderer, open_console, &active_theme.palette);
+        }
+        if let Some(menu) = &shot_menu {
+            draw_shot_menu(
+                &mut renderer,
+                menu,
+                &active_theme.shots,
+                &camera,
+                sim_time,
+                &active_theme.palette,
+            );
+        }
+        if let Some(active_tour) = &tour {
+            draw_tour_status(&mut renderer, active_tour, &active_theme.shots);
+        }
+
+        // The editor panels share the top-left corner with the minimap.
+        if settings.minimap && !editor.active && !theme_editor.active {
+            let extent = system_extent(&stars, &planets, &black_holes, &stations, &comets);
+            draw_minimap(&mut renderer, &stars, &planets, extent, &camera);
+        }
+        if editor.active {
+            if let Some(planet) = planets.get(editor.planet) {
+                draw_orbit_gizmo(&mut renderer, planet, &view_projection, editor.drag);
+            }
+            draw_editor_panel(&mut renderer, &editor, &planets);
+        }
+        if theme_editor.active {
+            draw_theme_editor_panel(&mut renderer, &theme_editor, &active_theme);
+        }
+        if prop_editor.active {
+            draw_prop_gizmo(&mut renderer, &prop_editor, &props, &view_projection);
+        }
+        draw_governor_status(&mut renderer, &governor);
+        draw_reload_status(&mut renderer, &watcher);
+
+        if !session.fixed_step() {
+            governor.record(now.elapsed().as_secs_f32(), dt);
+        }
+        session.finish_frame(clock, &input, &camera)?;
+        #[cfg(feature = "tracing")]
+        let present_span = tracing::debug_span!("present").entered();
+        match overview_renderer.as_ref().filter(|_| split_view) {
+            Some(overview) => {
+                let split = compose_split(&renderer, overview, &mut split_renderer);
+                platform.present(split.color_buffer(), split.width, split.height)?;
+            }
+            None => platform.present(renderer.color_buffer(), renderer.width, renderer.height)?,
+        }
+        #[cfg(feature = "tracing")]
+        drop(present_span);
+        next_frame().await;
+    }
+
+    session.finish()
+}
+
+/// Something that happened during a frame which other parts of the app react to.
+enum AppEvent {
+    /// Emitted on the warp's first frame; `destination` as on `Warp`.
+    WarpStarted {
+        destination: Option<String>,
+    },
+    WarpCompleted {
+        planet: Option<usize>,
+    },
+    ThemeChanged {
+        name: String,
+    },
+    BodySelected {
+        name: String,
+    },
+    /// The camera was pushed `depth` units out of a body's collider.
+    CollisionPushback {
+        depth: f32,
+        warping: bool,
+    },
+    /// A scene `[event ...]` came due; `text` as `SceneEvent::text`.
+    CalendarFired {
+        text: String,
+    },
+    /// A scene `[event ...]` that could not be applied, such as an arrival whose
+    /// mesh doesn't load.
+    CalendarFailed {
+        event: String,
+        reason: String,
+    },
+}
+
+/// State the event subscribers may change.
+struct EventTargets<'a> {
+    planets: &'a mut [Planet],
+    shake: &'a mut CameraShake,
+    watcher: &'a mut SceneWatcher,
+}
+
+type EventSubscriber = Box<dyn FnMut(&AppEvent, &mut EventTargets)>;
+
+/// Collects the frame's events as the loop runs and hands each one to every
+/// subscriber, in the order they subscribed, when the loop dispatches. Subscribers
+/// are registered once at startup, so the code raising an event never needs to
+/// know who reacts to it.
+#[derive(Default)]
+struct EventBus {
+    queue: Vec<AppEvent>,
+    subscribers: Vec<EventSubscriber>,
+}
+
+impl EventBus {
+    fn subscribe(&mut self, subscriber: impl FnMut(&AppEvent, &mut EventTargets) + 'static) {
+        self.subscribers.push(Box::new(subscriber));
+    }
+
+    fn emit(&mut self, event: AppEvent) {
+        self.queue.push(event);
+    }
+
+    fn dispatch(&mut self, targets: &mut EventTargets) {
+        for event in self.queue.drain(..) {
+            for subscriber in self.subscribers.iter_mut() {
+                subscriber(&event, targets);
+            }
+        }
+    }
+}
+
+/// Shows warps, theme switches, selections and calendar events as HUD messages.
+fn report_event(event: &AppEvent, targets: &mut EventTargets) {
+    let message = match event {
+        AppEvent::WarpStarted {
+            destination: Some(name),
+        } => format!("warping to {}", name),
+        AppEvent::ThemeChanged { name } => format!("theme: {}", name),
+        AppEvent::BodySelected { name } => format!("selected {}", name),
+        AppEvent::CalendarFired { text } => text.clone(),
+        AppEvent::CalendarFailed { event, reason } => format!("event {} failed: {}", event, reason),
+        _ => return,
+    };
+    targets.watcher.report(message);
+}
+
+/// Emits what each of the `fired` calendar events did.
+fn emit_calendar(events: &mut EventBus, fired: Vec<(&SceneEvent, EventOutcome)>) {
+    for (event, outcome) in fired {
+        events.emit(match outcome {
+            Ok(()) => AppEvent::CalendarFired { text: event.text() },
+            Err(err) => AppEvent::CalendarFailed {
+                event: event.name.clone(),
+                reason: err.to_string(),
+            },
+        });
+    }
+}
+
+/// Tags a planet as visited once a warp arrives at it.
+fn mark_visited(event: &AppEvent, targets: &mut EventTargets) {
+    if let AppEvent::WarpCompleted { planet: Some(idx) } = event {
+        if let Some(planet) = targets.planets.get_mut(*idx) {
+            planet.tags = planet.tags.with(BodyTags::VISITED);
+        }
+    }
+}
+
+/// Bumps the camera when it hits a body, except during warps, which bring their own
+/// effects.
+fn shake_on_pushback(event: &AppEvent, targets: &mut EventTargets) {
+    if let AppEvent::CollisionPushback {
+        depth,
+        warping: false,
+    } = event
+    {
+        targets.shake.add(depth * SHAKE_PER_PUSH);
+    }
+}
+
+/// Bodies of a scene as handed to `draw_world`.
+struct WorldView<'a> {
+    sphere: &'a Mesh,
+    stars: &'a [Star],
+    planets: &'a [Planet],
+    black_holes: &'a [BlackHole],
+    stations: &'a [Station],
+    comets: &'a [Comet],
+    custom_bodies: &'a [Box<dyn CelestialBody>],
+    props: &'a [Prop],
+    tag_filter: TagFilter,
+    ring_style: RingStyle,
+    /// Draws every mesh as edges only; props can also ask for it one by one.
+    wireframe: bool,
+    trails: Option<&'a Trails>,
+}
+
+impl<'a> WorldView<'a> {
+    fn bodies(&self) -> impl Iterator<Item = &'a dyn CelestialBody> {
+        celestial_bodies(
+            self.stars,
+            self.planets,
+            self.black_holes,
+            self.stations,
+            self.comets,
+            self.custom_bodies,
+        )
+    }
+}
+
+/// Writes every body and prop of `world` to an OBJ file where they are now, one
+/// object each with its color in the vertices, so the system can be opened in a 3D
+/// editor. Returns how many objects were written.
+fn export_scene_obj(path: &Path, world: &WorldView) -> Result<usize, Box<dyn std::error::Error>> {
+    let file = std::fs::File::create(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let mut out = BufWriter::new(file);
+    writeln!(
+        out,
+        "# star system export: `v x y z r g b`, one object per body"
+    )?;
+    let mut first_vertex = 0;
+    let mut objects = 0;
+    let mut write = |mesh: &Mesh, name: &str, transform: &Mat4, color: Color| {
+        objects += 1;
+        mesh.write_obj(&mut out, name, transform, color, &mut first_vertex)
+    };
+    for star in world.stars {
+        write(world.sphere, &star.name, &star.transform, star.color)?;
+    }
+    for planet in world.planets {
+        let sphere = planet.surface.as_ref().unwrap_or(world.sphere);
+        if planet.polar_cap.is_some() || planet.cloud_bands.is_some() {
+            let mut painted = sphere.clone();
+            painted.paint(|vertex| {
+                let surface = match planet.cloud_bands {
+                    Some(bands) => bands.tint(vertex, planet.color, planet.band_drift),
+                    None => planet.color,
+                };
+                match planet.polar_cap {
+                    Some(cap) => cap.tint(vertex, surface),
+                    None => surface,
+                }
+            });
+            write(
+                &painted,
+                &planet.name,
+                &planet.transform,
+                Color::new(1.0, 1.0, 1.0),
+            )?;
+        } else {
+            write(sphere, &planet.name, &planet.transform, planet.color)?;
+        }
+        if let Some(ring) = &planet.ring {
+            for (idx, (mesh, band)) in ring.meshes.iter().zip(ring.bands.iter()).enumerate() {
+                let name = match idx {
+                    0 => format!("{} ring", planet.name),
+                    idx => format!("{} ring {}", planet.name, idx + 1),
+                };
+                write(mesh, &name, &ring.transform, band.color)?;
+            }
+        }
+    }
+    for hole in world.black_holes {
+        write(
+            world.sphere,
+            &hole.name,
+            &hole.transform,
+            Color::new(0.0, 0.0, 0.0),
+        )?;
+        let name = format!("{} disk", hole.name);
+        for (mesh, band) in hole.disk.meshes.iter().zip(hole.disk.bands.iter()) {
+            write(mesh, &name, &hole.disk.transform, band.color)?;
+        }
+    }
+    for comet in world.comets {
+        write(
+            comet.mesh.as_deref().unwrap_or(world.sphere),
+            &comet.name,
+            &comet.transform,
+            comet.color,
+        )?;
+    }
+    for station in world.stations {
+        write(
+            &station.mesh,
+            &station.name,
+            &station.transform,
+            station.color,
+        )?;
+    }
+    for prop in world.props {
+        write(&prop.mesh, &prop.name, &prop.transform, prop.color)?;
+    }
+    out.flush()?;
+    Ok(objects)
+}
+
+/// A sphere as seen from the camera: the cone of directions its silhouette covers.
+struct ViewDisc {
+    direction: Vec3,
+    half_angle: f32,
+    distance: f32,
+    radius: f32,
+}
+
+impl ViewDisc {
+    /// `None` when the camera is inside the sphere.
+    fn new(eye: Vec3, center: Vec3, radius: f32) -> Option<Self> {
+        let offset = center - eye;
+        let distance = offset.length();
+        (distance > radius).then(|| Self {
+            direction: offset / distance,
+            half_angle: (radius / distance).asin(),
+            distance,
+            radius,
+        })
+    }
+
+    /// Silhouettes of the `spheres` seen from `eye`, shrunk by `OCCLUDER_MARGIN`.
+    fn occluders(eye: Vec3, spheres: impl Iterator<Item = (Vec3, f32)>) -> Vec<ViewDisc> {
+        spheres
+            .filter_map(|(center, radius)| ViewDisc::new(eye, center, radius * OCCLUDER_MARGIN))
+            .collect()
+    }
+
+    /// True when the sphere at `center` is wholly hidden from `eye` behind one of
+    /// `occluders`. Never true with the camera inside it.
+    fn sphere_hidden(eye: Vec3, center: Vec3, radius: f32, occluders: &[ViewDisc]) -> bool {
+        ViewDisc::new(eye, center, radius).is_some_and(|disc| disc.hidden_by(occluders))
+    }
+
+    /// True when some occluder's silhouette cone contains this whole cone and all
+    /// of this sphere lies beyond the occluder's center, which is further than any
+    /// point of its visible surface.
+    fn hidden_by(&self, occluders: &[ViewDisc]) -> bool {
+        occluders.iter().any(|occluder| {
+            occluder.distance < self.distance - self.radius
+                && occluder
+                    .direction
+                    .dot(self.direction)
+                    .clamp(-1.0, 1.0)
+                    .acos()
+                    + self.half_angle
+                    <= occluder.half_angle
+        })
+    }
+}
+
+/// Paints the sky and every body seen from `camera`, plus the optional passes the
+/// governor still allows. Returns the view-projection used, for overlays.
+fn draw_world(
+    renderer: &mut Renderer,
+    world: &WorldView,
+    camera: &Camera,
+    time: f32,
+    governor: &FrameGovernor,
+    ship: Option<RenderInstance>,
+) -> Mat4 {
+    renderer.begin_frame(time, camera);
+    let view = camera.view_matrix();
+    let projection = camera.projection(renderer.width as f32 / renderer.height as f32);
+    let view_projection = projection * view;
+
+    if governor.enabled(OptionalPass::Orbits) {
+        draw_orbits(
+            renderer,
+            world.planets,
+            camera,
+            &view_projection,
+            world.tag_filter,
+        );
+        draw_comet_orbits(renderer, world.comets, camera, &view_projection);
+    }
+
+    let context = BodyContext {
+        sphere: world.sphere,
+        stars: world.stars,
+        tag_filter: world.tag_filter,
+        ring_style: world.ring_style,
+        highlight: 1.0,
+        wireframe: world.wireframe,
+        aurora_color: renderer.palette.aurora,
+    };
+    let mut instances = Vec::with_capacity(world.planets.len() + world.stars.len() + 1);
+
+    // Stars and planets are opaque spheres; anything whose bounds fall entirely
+    // within one of their silhouettes from behind is never drawn. Only spheres lying
+    // wholly between the near and far planes count, since triangles crossing either
+    // plane are dropped and would leave holes. Wireframe spheres hide nothing, and
+    // orthographic views skip this since their rays don't meet at the camera.
+    let forward = camera.forward();
+    let occluders = ViewDisc::occluders(
+        camera.position,
+        world
+            .bodies()
+            .filter_map(|body| body.occluder())
+            .filter(|&(center, radius)| {
+                let depth = forward.dot(center - camera.position);
+                !world.wireframe
+                    && camera.orthographic.is_none()
+                    && depth - radius > NEAR_PLANE
+                    && depth + radius < FAR_PLANE
+            }),
+    );
+    let hidden = |center: Vec3, radius: f32| {
+        ViewDisc::sphere_hidden(camera.position, center, radius, &occluders)
+    };
+    let visible: Vec<&dyn CelestialBody> = world
+        .bodies()
+        .filter(|body| {
+            body.bounding_sphere()
+                .is_none_or(|(center, radius)| !hidden(center, radius))
+        })
+        .collect();
+    let viewport = (renderer.width as f32, renderer.height as f32);
+    let meshes: Vec<Option<Cow<Mesh>>> = visible
+        .iter()
+        .map(|body| body.sphere_mesh(&view_projection, viewport))
+        .collect();
+    for (body, mesh) in visible.iter().zip(&meshes) {
+        body.render_instances(
+            &BodyContext {
+                sphere: mesh.as_deref().unwrap_or(world.sphere),
+                ..context
+            },
+            &mut instances,
+        );
+    }
+
+    for prop in world.props {
+        instances.push(RenderInstance {
+            mesh: &prop.mesh,
+            transform: prop.transform,
+            material: Material {
+                color: prop.color,
+                emissive: 0.1,
+                ..Default::default()
+            },
+            wireframe: world.wireframe || prop.wireframe,
+        });
+    }
+
+    instances.extend(ship);
+
+    let lights: Vec<Light> = world.stars.iter().map(Star::light).collect();
+    renderer.render(&instances, &view_projection, camera, &lights);
+    if world.ring_style == RingStyle::Particles && governor.enabled(OptionalPass::Particles) {
+        for planet in world.planets {
+            if let Some(ring) = &planet.ring {
+                let highlight = world.tag_filter.highlight(planet.tags);
+                draw_ring_particles(renderer, ring, camera, &view_projection, &lights, highlight);
+            }
+        }
+    }
+    if governor.enabled(OptionalPass::Particles) {
+        for comet in world.comets {
+            draw_comet_tail(renderer, comet, &view_projection);
+        }
+        for star in world.stars {
+            draw_prominences(renderer, star, &view_projection);
+        }
+    }
+    if let Some(trails) = world
+        .trails
+        .filter(|_| governor.enabled(OptionalPass::Orbits))
+    {
+        draw_trails(renderer, trails, world, &view_projection);
+    }
+    if governor.enabled(OptionalPass::Lensing) {
+        for hole in world.black_holes {
+            apply_black_hole_lensing(renderer, hole, camera, &view_projection);
+        }
+    }
+    renderer.present_debug_view();
+    view_projection
+}
+
+struct TurntableOptions {
+    body: String,
+    theme: Option<String>,
+    frames: usize,
+    width: usize,
+    height: usize,
+    out: PathBuf,
+    time: f32,
+    elevation: f32,
+    wireframe: bool,
+    shading: ShadingQuality,
+}
+
+/// Splits the turntable flags from the remaining arguments, which go to `load_themes`.
+fn parse_turntable_args(
+    args: &[String],
+) -> Result<(TurntableOptions, Vec<String>), Box<dyn std::error::Error>> {
+    let mut options = TurntableOptions {
+        body: String::new(),
+        theme: None,
+        frames: 36,
+        width: WIDTH,
+        height: HEIGHT,
+        out: PathBuf::from("turntable"),
+        time: 0.0,
+        elevation: 0.35,
+        wireframe: false,
+        shading: ShadingQuality::PerPixel,
+    };
+    let mut rest = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .map(String::as_str)
+                .ok_or_else(|| format!("{} expects a value", arg))
+        };
+        match arg.as_str() {
+            "--render-turntable" => options.body = value()?.to_string(),
+            "--theme" => options.theme = Some(value()?.to_string()),
+            "--frames" => {
+                options.frames = value()?.parse().map_err(|_| "--frames expects a count")?
+            }
+            "--size" => {
+                let size = value()?;
+                let (w, h) = size
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                    .ok_or_else(|| format!("--size expects WIDTHxHEIGHT, got `{}`", size))?;
+                options.width = w;
+                options.height = h;
+            }
+            "--out" => options.out = PathBuf::from(value()?),
+            "--time" => options.time = value()?.parse().map_err(|_| "--time expects seconds")?,
+            "--elevation" => {
+                options.elevation = value()?
+                    .parse::<f32>()
+                    .map_err(|_| "--elevation expects radians")?
+                    .clamp(-1.5, 1.5)
+            }
+            "--wireframe" => options.wireframe = true,
+            "--shading" => {
+                let mode = value()?;
+                options.shading = ShadingQuality::parse(mode).ok_or_else(|| {
+                    format!(
+                        "--shading expects pixel, adaptive or vertex, got `{}`",
+                        mode
+                    )
+                })?
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+    if options.frames == 0 || options.width == 0 || options.height == 0 {
+        return Err("--frames and --size must be non-zero".into());
+    }
+    Ok((options, rest))
+}
+
+/// `--render-turntable <body>`: orbits the camera once around a body and writes
+/// numbered PNG stills, without opening a window. Bodies are frozen at `--time`.
+fn run_turntable(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let (options, rest) = parse_turntable_args(args)?;
+    let themes = load_themes(&rest)?;
+    // A scene given with --scene is the one being previewed, so it wins by default.
+    let theme = match &options.theme {
+        Some(name) => themes
+            .iter()
+            .find(|theme| theme.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("no theme named `{}`", name))?,
+        None if rest.iter().any(|arg| arg == "--scene") => {
+            themes.last().ok_or("no themes loaded")?
+        }
+        None => themes.first().ok_or("no themes loaded")?,
+    };
+
+    let mut planets = build_planets(&theme.planets);
+    let mut stars = build_stars(&theme.stars);
+    let mut black_holes = build_black_holes(&theme.black_holes);
+    let mut stations = build_stations(&theme.stations, &planets)?;
+    let mut asteroids = AsteroidCache::default();
+    let mut comets = build_comets(&theme.comets, &mut asteroids);
+    let props = build_props(&theme.props)?;
+    fast_forward(
+        &mut SimBodies {
+            stars: &mut stars,
+            planets: &mut planets,
+            black_holes: &mut black_holes,
+            stations: &mut stations,
+            comets: &mut comets,
+            custom: &mut [],
+        },
+        options.time,
+        false,
+    );
+    let (_, failed) = EventCalendar::start(
+        theme,
+        options.time,
+        CalendarBodies {
+            planets: &planets,
+            stars: &mut stars,
+            stations: &mut stations,
+            comets: &mut comets,
+            asteroids: &mut asteroids,
+        },
+    );
+    for (event, outcome) in failed {
+        if let Err(err) = outcome {
+            eprintln!("[event {}]: {}", event.name, err);
+        }
+    }
+
+    let targets = collect_warp_targets(
+        celestial_bodies(&stars, &planets, &black_holes, &stations, &comets, &[]),
+        &planets,
+    );
+    let target = targets
+        .iter()
+        .find(|target| target.name.eq_ignore_ascii_case(&options.body))
+        .ok_or_else(|| format!("no body named `{}` in theme {}", options.body, theme.name))?;
+
+    let sphere_mesh = Mesh::uv_sphere(28, 18);
+    let world = WorldView {
+        sphere: &sphere_mesh,
+        stars: &stars,
+        planets: &planets,
+        black_holes: &black_holes,
+        stations: &stations,
+        comets: &comets,
+        custom_bodies: &[],
+        props: &props,
+        tag_filter: TagFilter::All,
+        ring_style: RingStyle::Mesh,
+        wireframe: options.wireframe,
+        trails: None,
+    };
+    let mut renderer = Renderer::new(options.width, options.height, theme.palette);
+    renderer.shading = options.shading;
+    let governor = FrameGovernor::new(FRAME_BUDGET);
+    let distance = framing_distance(target.radius, DEFAULT_FOV, FRAME_FILL * 0.7);
+    std::fs::create_dir_all(&options.out)?;
+    for frame in 0..options.frames {
+        let angle = TAU * frame as f32 / options.frames as f32;
+        let offset = Vec3::new(
+            angle.sin() * options.elevation.cos(),
+            options.elevation.sin(),
+            -angle.cos() * options.elevation.cos(),
+        );
+        let mut camera = Camera::new(target.center + offset * distance);
+        let view = CameraView::looking(-offset, DEFAULT_FOV);
+        camera.yaw = view.yaw;
+        camera.pitch = view.pitch;
+        draw_world(
+            &mut renderer,
+            &world,
+            &camera,
+            options.time,
+            &governor,
+            None,
+        );
+        let path = options.out.join(format!("frame_{:04}.png", frame));
+        write_png(
+            &path,
+            options.width,
+            options.height,
+            renderer.color_buffer(),
+        )
+        .map_err(|err| format!("{}: {}", path.display(), err))?;
+        println!("wrote {}", path.display());
+    }
+    Ok(())
+}
+
+/// Writes a 0RGB framebuffer as an 8-bit RGB PNG. The image data goes into stored
+/// (uncompressed) deflate blocks, which keeps the encoder tiny at the cost of size.
+fn write_png(path: &Path, width: usize, height: usize, pixels: &[u32]) -> std::io::Result<()> {
+    let mut raw = Vec::with_capacity(height * (width * 3 + 1));
+    for row in pixels.chunks(width).take(height) {
+        raw.push(0); // filter type: none
+        for &pixel in row {
+            raw.extend_from_slice(&[(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]);
+        }
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(u16::MAX as usize).peekable();
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        zlib.push(u8::from(blocks.peek().is_none()));
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]); // 8-bit RGB, no interlace
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    for (kind, data) in [(b"IHDR", header), (b"IDAT", zlib), (b"IEND", Vec::new())] {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend_from_slice(kind);
+        png.extend_from_slice(&data);
+        let crc = crc32(&png[start..]);
+        png.extend_from_slice(&crc.to_be_bytes());
+    }
+    std::fs::write(path, png)
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// Built-in themes followed by any `--scene <path>` files given on the command line.
+/// A built-in theme saved from the editor loads from its user copy instead.
+fn load_themes(args: &[String]) -> Result<Vec<Theme>, Box<dyn std::error::Error>> {
+    let mut themes = Vec::new();
+    for (path, text) in BUILTIN_SCENES {
+        let user_copy = user_scene_path(Path::new(path));
+        if user_copy.exists() {
+            themes.push(load_scene_theme(&user_copy)?);
+            continue;
+        }
+        let mut theme = Theme::from_document(&parse_scene(text)?)?;
+        theme.source = Some(PathBuf::from(path));
+        themes.push(theme);
+    }
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--scene" => {
+                let path = rest.next().ok_or("--scene expects a file path")?;
+                themes.push(load_scene_theme(Path::new(path))?);
+            }
+            other => return Err(format!("unknown argument `{}`", other).into()),
+        }
+    }
+    Ok(themes)
+}
+
+fn load_scene_theme(path: &Path) -> Result<Theme, AppError> {
+    let mut theme =
+        Theme::from_document(&read_scene_file(path)?).map_err(|err| AppError::SceneParse {
+            path: path.to_path_buf(),
+            reason: err.to_string(),
+        })?;
+    theme.source = Some(path.to_path_buf());
+    Ok(theme)
+}
+
+fn read_scene_file(path: impl AsRef<Path>) -> Result<SceneDocument, AppError> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path).map_err(|source| AppError::MissingFile {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_scene(&text).map_err(|err| AppError::SceneParse {
+        path: path.to_path_buf(),
+        reason: err.to_string(),
+    })
+}
+
+/// `scene-diff <old> <new>`: lists added (+), removed (-) and changed (~) sections.
+fn run_scene_diff(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let [old_path, new_path] = args else {
+        return Err("usage: proyecto3 scene-diff <old.scene> <new.scene>".into());
+    };
+    let changes = diff_scenes(&read_scene_file(old_path)?, &read_scene_file(new_path)?);
+    if changes.is_empty() {
+        println!("scenes are identical");
+    }
+    for line in changes {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// `scene-merge <base> <ours> <theirs> [-o out]`: three-way merge of two variants of
+/// a common base. Conflicting fields keep our value and are reported on stderr.
+fn run_scene_merge(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let usage =
+        "usage: proyecto3 scene-merge <base.scene> <ours.scene> <theirs.scene> [-o <out.scene>]";
+    let (paths, output) = match args {
+        [base, ours, theirs] => ([base, ours, theirs], None),
+        [base, ours, theirs, flag, out] if flag == "-o" => ([base, ours, theirs], Some(out)),
+        _ => return Err(usage.into()),
+    };
+    let merged = merge_scenes(
+        &read_scene_file(paths[0])?,
+        &read_scene_file(paths[1])?,
+        &read_scene_file(paths[2])?,
+    );
+    let text = merged.document.to_text();
+    match output {
+        Some(path) => std::fs::write(path, text)?,
+        None => print!("{}", text),
+    }
+    for conflict in &merged.conflicts {
+        eprintln!("conflict: {}", conflict);
+    }
+    if merged.conflicts.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} conflict(s), kept our side", merged.conflicts.len()).into())
+    }
+}
+
+/// Every key the app reacts to; recordings only keep these.
+const INPUT_KEYS: [Key; 60] = [
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+    Key::Key0,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+    Key::Space,
+    Key::LeftShift,
+    Key::LeftCtrl,
+    Key::RightCtrl,
+    Key::Left,
+    Key::Right,
+    Key::Up,
+    Key::Down,
+    Key::Escape,
+    Key::Tab,
+    Key::Enter,
+    Key::NumPadEnter,
+    Key::Backspace,
+    Key::Slash,
+    Key::Backquote,
+    Key::Minus,
+    Key::Equal,
+    Key::Period,
+    Key::Comma,
+    Key::LeftBracket,
+    Key::RightBracket,
+    Key::PageUp,
+    Key::PageDown,
+    Key::F11,
+];
+
+/// Keyboard and mouse state for one frame, read from the window or from a recording.
+/// Handlers query this instead of the window so a replay drives the exact same code.
+#[derive(Default)]
+struct InputFrame {
+    down: Vec<Key>,
+    pressed: Vec<Key>,
+    repeated: Vec<Key>,
+    mouse: Option<(f32, f32)>,
+    mouse_down: bool,
+    /// Vertical mouse wheel movement this frame, positive away from the user.
+    scroll: f32,
+}
+
+impl InputFrame {
+    fn is_key_down(&self, key: Key) -> bool {
+        self.down.contains(&key)
+    }
+
+    fn is_key_pressed(&self, key: Key, repeat: KeyRepeat) -> bool {
+        self.keys_pressed(repeat).contains(&key)
+    }
+
+    fn keys_pressed(&self, repeat: KeyRepeat) -> &[Key] {
+        match repeat {
+            KeyRepeat::No => &self.pressed,
+            KeyRepeat::Yes => &self.repeated,
+        }
+    }
+
+    fn mouse_pos(&self) -> Option<(f32, f32)> {
+        self.mouse
+    }
+
+    fn mouse_down(&self) -> bool {
+        self.mouse_down
+    }
+
+    fn scroll(&self) -> f32 {
+        self.scroll
+    }
+
+    fn to_fields(&self) -> String {
+        let keys = |keys: &[Key]| {
+            keys.iter()
+                .map(|key| format!("{:?}", key))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let mouse = match self.mouse {
+            Some((x, y)) => format!("{:?},{:?}", x, y),
+            None => "-".to_string(),
+        };
+        format!(
+            "down={} pressed={} repeat={} mouse={} button={} scroll={:?}",
+            keys(&self.down),
+            keys(&self.pressed),
+            keys(&self.repeated),
+            mouse,
+            u8::from(self.mouse_down),
+            self.scroll
+        )
+    }
+}
+
+fn parse_key(name: &str) -> Result<Key, String> {
+    INPUT_KEYS
+        .iter()
+        .copied()
+        .find(|key| format!("{:?}", key) == name)
+        .ok_or_else(|| format!("unknown key `{}`", name))
+}
+
+/// Something a key can be bound to in `BINDINGS_PATH`. Keys inside the editors,
+/// menus and the telescope stay fixed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    YawLeft,
+    YawRight,
+    PitchUp,
+    PitchDown,
+    ZoomIn,
+    ZoomOut,
+    /// Warp to the body (or bookmark) in slot 0..9 of the number keys.
+    Warp(usize),
+    BookmarkMode,
+    Quit,
+    Fullscreen,
+    CycleTheme,
+    PhysicsMode,
+    Pause,
+    Step,
+    Transit,
+    Wobble,
+    RingStyle,
+    DebugView,
+    Telescope,
+    Wireframe,
+    ColorMode,
+    TagFilter,
+    Search,
+    Console,
+    ShotMenu,
+    PlanetEditor,
+    ThemeEditor,
+    PropEditor,
+}
+
+/// Every action with its name in the bindings file and its default key.
+const DEFAULT_BINDINGS: [(Action, &str, Key); 42] = [
+    (Action::MoveForward, "move_forward", Key::W),
+    (Action::MoveBack, "move_back", Key::S),
+    (Action::MoveLeft, "move_left", Key::A),
+    (Action::MoveRight, "move_right", Key::D),
+    (Action::MoveUp, "move_up", Key::Space),
+    (Action::MoveDown, "move_down", Key::LeftShift),
+    (Action::YawLeft, "yaw_left", Key::Left),
+    (Action::YawRight, "yaw_right", Key::Right),
+    (Action::PitchUp, "pitch_up", Key::Up),
+    (Action::PitchDown, "pitch_down", Key::Down),
+    (Action::ZoomIn, "zoom_in", Key::PageUp),
+    (Action::ZoomOut, "zoom_out", Key::PageDown),
+    (Action::Warp(0), "warp_1", Key::Key1),
+    (Action::Warp(1), "warp_2", Key::Key2),
+    (Action::Warp(2), "warp_3", Key::Key3),
+    (Action::Warp(3), "warp_4", Key::Key4),
+    (Action::Warp(4), "warp_5", Key::Key5),
+    (Action::Warp(5), "warp_6", Key::Key6),
+    (Action::Warp(6), "warp_7", Key::Key7),
+    (Action::Warp(7), "warp_8", Key::Key8),
+    (Action::Warp(8), "warp_9", Key::Key9),
+    (Action::BookmarkMode, "bookmark_mode", Key::Key0),
+    (Action::Quit, "quit", Key::Escape),
+    (Action::Fullscreen, "fullscreen", Key::F11),
+    (Action::CycleTheme, "cycle_theme", Key::T),
+    (Action::PhysicsMode, "physics_mode", Key::P),
+    (Action::Pause, "pause", Key::K),
+    (Action::Step, "step", Key::N),
+    (Action::Transit, "transit_view", Key::L),
+    (Action::Wobble, "wobble_view", Key::V),
+    (Action::RingStyle, "ring_style", Key::R),
+    (Action::DebugView, "debug_view", Key::B),
+    (Action::Telescope, "telescope", Key::Y),
+    (Action::Wireframe, "wireframe", Key::X),
+    (Action::ColorMode, "color_mode", Key::H),
+    (Action::TagFilter, "tag_filter", Key::F),
+    (Action::Search, "search", Key::Slash),
+    (Action::Console, "console", Key::Backquote),
+    (Action::ShotMenu, "shot_menu", Key::M),
+    (Action::PlanetEditor, "planet_editor", Key::E),
+    (Action::ThemeEditor, "theme_editor", Key::C),
+    (Action::PropEditor, "prop_editor", Key::G),
+];
+
+/// Keys for each action: the defaults, overridden per action by the `[bindings]`
+/// section of `BINDINGS_PATH` (`move_forward = W`, or several keys separated by
+/// spaces). Key names are minifb's, as in recordings.
+struct KeyBindings {
+    keys: Vec<(Action, Vec<Key>)>,
+}
+
+impl KeyBindings {
+    fn load(path: &Path) -> Self {
+        let mut bindings = KeyBindings {
+            keys: DEFAULT_BINDINGS
+                .iter()
+                .map(|&(action, _, key)| (action, vec![key]))
+                .collect(),
+        };
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return bindings;
+        };
+        let doc = match parse_scene(&text) {
+            Ok(doc) => doc,
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                return bindings;
+            }
+        };
+        let Some(section) = doc.sections.iter().find(|s| s.kind == "bindings") else {
+            return bindings;
+        };
+        for (name, value) in &section.fields {
+            let Some(slot) = DEFAULT_BINDINGS
+                .iter()
+                .position(|(_, action_name, _)| action_name == name)
+            else {
+                eprintln!("{}: unknown action `{}`", path.display(), name);
+                continue;
+            };
+            match value.split_whitespace().map(parse_key).collect() {
+                Ok(keys) => bindings.keys[slot].1 = keys,
+                Err(err) => eprintln!("{}: `{}`: {}", path.display(), name, err),
+            }
+        }
+        bindings
+    }
+
+    fn keys(&self, action: Action) -> &[Key] {
+        self.keys
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .map_or(&[], |(_, keys)| keys)
+    }
+
+    fn down(&self, input: &InputFrame, action: Action) -> bool {
+        self.keys(action).iter().any(|&key| input.is_key_down(key))
+    }
+
+    fn pressed(&self, input: &InputFrame, action: Action, repeat: KeyRepeat) -> bool {
+        self.keys(action)
+            .iter()
+            .any(|&key| input.is_key_pressed(key, repeat))
+    }
+
+    /// Like `pressed`, but ignores the keys in `taken`, which something else is
+    /// using this frame.
+    fn pressed_except(
+        &self,
+        input: &InputFrame,
+        action: Action,
+        repeat: KeyRepeat,
+        taken: &[Key],
+    ) -> bool {
+        self.keys(action)
+            .iter()
+            .any(|&key| !taken.contains(&key) && input.is_key_pressed(key, repeat))
+    }
+
+    /// Number slot (0-based) whose warp key went down this frame.
+    fn pressed_slot(&self, input: &InputFrame) -> Option<usize> {
+        (0..9).find(|&slot| self.pressed(input, Action::Warp(slot), KeyRepeat::No))
+    }
+}
+
+/// Camera state as written to recordings; floats use `{:?}` so they round-trip exactly.
+fn camera_fields(camera: &Camera) -> String {
+    let p = camera.position;
+    format!(
+        "{:?},{:?},{:?},{:?},{:?},{:?}",
+        p.x, p.y, p.z, camera.yaw, camera.pitch, camera.fov
+    )
+}
+
+struct RecordedFrame {
+    input: InputFrame,
+    camera: String,
+}
+
+/// One line of a recording:
+/// `t=<seconds> down=W,A pressed=W repeat=W mouse=x,y|- button=0|1 scroll=dy camera=x,y,z,yaw,pitch,fov`
+/// (`scroll` may be missing from older recordings).
+fn parse_recorded_frame(line: &str) -> Result<RecordedFrame, String> {
+    let mut input = InputFrame::default();
+    let mut camera = None;
+    for token in line.split_whitespace() {
+        let (key, value) = token
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=value, got `{}`", token))?;
+        let keys = || -> Result<Vec<Key>, String> {
+            value
+                .split(',')
+                .filter(|name| !name.is_empty())
+                .map(parse_key)
+                .collect()
+        };
+        match key {
+            "t" => {}
+            "down" => input.down = keys()?,
+            "pressed" => input.pressed = keys()?,
+            "repeat" => input.repeated = keys()?,
+            "mouse" if value == "-" => input.mouse = None,
+            "mouse" => {
+                input.mouse = value
+                    .split_once(',')
+                    .and_then(|(x, y)| Some((x.parse().ok()?, y.parse().ok()?)));
+                if input.mouse.is_none() {
+                    return Err(format!("bad mouse position `{}`", value));
+                }
+            }
+            "button" => input.mouse_down = value == "1",
+            "scroll" => {
+                input.scroll = value
+                    .parse()
+                    .map_err(|_| format!("bad scroll amount `{}`", value))?
+            }
+            "camera" => camera = Some(value.to_string()),
+            other => return Err(format!("unknown field `{}`", other)),
+        }
+    }
+    Ok(RecordedFrame {
+        input,
+        camera: camera.ok_or("missing camera")?,
+    })
+}
+
+/// Where each frame's input comes from. Recording and replaying both advance frames
+/// by `FIXED_TIMESTEP` with hot reload and the frame governor off, so a replay (with
+/// the same tick settings) goes through the same states as the session that was recorded.
+enum InputSession {
+    Live,
+    Record(BufWriter<std::fs::File>),
+    Replay {
+        frames: Vec<RecordedFrame>,
+        next: usize,
+        /// First frame whose camera differed from the recording.
+        desync: Option<usize>,
+    },
+}
+
+impl InputSession {
+    /// Takes `--record <file>` or `--replay <file>` out of `args`.
+    fn from_args(args: &mut Vec<String>) -> Result<Self, Box<dyn std::error::Error>> {
+        let Some(idx) = args
+            .iter()
+            .position(|arg| arg == "--record" || arg == "--replay")
+        else {
+            return Ok(InputSession::Live);
+        };
+        let flag = args.remove(idx);
+        if idx >= args.len() {
+            return Err(format!("{} expects a file path", flag).into());
+        }
+        let path = PathBuf::from(args.remove(idx));
+        let error = |err: &dyn std::fmt::Display| format!("{}: {}", path.display(), err);
+        if flag == "--record" {
+            let mut file = BufWriter::new(std::fs::File::create(&path).map_err(|e| error(&e))?);
+            writeln!(file, "# input recording, one frame per line").map_err(|e| error(&e))?;
+            return Ok(InputSession::Record(file));
+        }
+        let text = std::fs::read_to_string(&path).map_err(|e| error(&e))?;
+        let frames = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+            .map(|(number, line)| {
+                parse_recorded_frame(line)
+                    .map_err(|err| error(&format!("line {}: {}", number + 1, err)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(InputSession::Replay {
+            frames,
+            next: 0,
+            desync: None,
+        })
+    }
+
+    fn fixed_step(&self) -> bool {
+        !matches!(self, InputSession::Live)
+    }
+
+    /// Input for the coming frame, or `None` once a replay has run out. `live` is
+    /// what the window saw; a replay ignores it.
+    fn next_input(&mut self, live: InputFrame) -> Option<InputFrame> {
+        match self {
+            InputSession::Live | InputSession::Record(_) => Some(live),
+            InputSession::Replay { frames, next, .. } => {
+                let frame = frames.get_mut(*next)?;
+                *next += 1;
+                Some(std::mem::take(&mut frame.input))
+            }
+        }
+    }
+
+    fn finish_frame(
+        &mut self,
+        time: f32,
+        input: &InputFrame,
+        camera: &Camera,
+    ) -> std::io::Result<()> {
+        match self {
+            InputSession::Live => Ok(()),
+            InputSession::Record(file) => writeln!(
+                file,
+                "t={:.4} {} camera={}",
+                time,
+                input.to_fields(),
+                camera_fields(camera)
+            ),
+            InputSession::Replay {
+                frames,
+                next,
+                desync,
+            } => {
+                if desync.is_none() && frames[*next - 1].camera != camera_fields(camera) {
+                    *desync = Some(*next - 1);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(self) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            InputSession::Live => Ok(()),
+            InputSession::Record(mut file) => Ok(file.flush()?),
+            InputSession::Replay {
+                next,
+                desync: Some(frame),
+                ..
+            } => Err(format!(
+                "replay diverged at frame {} (of {} played): camera differs from the recording",
+                frame, next
+            )
+            .into()),
+            InputSession::Replay { frames, next, .. } => {
+                println!("replayed {} of {} frames", next, frames.len());
+                Ok(())
+            }
+        }
+    }
+}
+
+fn handle_input(input: &InputFrame, bindings: &KeyBindings, camera: &mut Camera, dt: f32) {
+    let mut movement = Vec3::ZERO;
+    let forward = camera.forward();
+    let right = forward.cross(Vec3::UP).normalized();
+    if bindings.down(input, Action::MoveForward) {
+        movement += forward;
+    }
+    if bindings.down(input, Action::MoveBack) {
+        movement -= forward;
+    }
+    if bindings.down(input, Action::MoveRight) {
+        movement += right;
+    }
+    if bindings.down(input, Action::MoveLeft) {
+        movement -= right;
+    }
+    if bindings.down(input, Action::MoveUp) {
+        movement += Vec3::UP;
+    }
+    if bindings.down(input, Action::MoveDown) {
+        movement -= Vec3::UP;
+    }
+
+    if movement.length_squared() > 0.0 {
+        camera.position += movement.normalized() * CAMERA_SPEED * dt;
+    }
+
+    // Zoomed in, turning slows in proportion so the view sweeps at the same speed.
+    let look = (camera.fov / DEFAULT_FOV).min(1.0) * dt;
+    if bindings.down(input, Action::YawLeft) {
+        camera.yaw -= 0.9 * look;
+    }
+    if bindings.down(input, Action::YawRight) {
+        camera.yaw += 0.9 * look;
+    }
+    if bindings.down(input, Action::PitchUp) {
+        camera.pitch += 0.6 * look;
+    }
+    if bindings.down(input, Action::PitchDown) {
+        camera.pitch -= 0.6 * look;
+    }
+    camera.pitch = camera.pitch.clamp(-1.1, 1.1);
+}
+
+/// Pilot zoom with the mouse wheel or the zoom keys: requests a FOV between
+/// `TELESCOPE_MIN_FOV` and `MAX_FOV`, which the camera eases toward.
+#[derive(Default)]
+struct FovZoom {
+    /// FOV being eased toward; `None` once reached, so warps and shots that set
+    /// the FOV themselves aren't pulled back.
+    target: Option<f32>,
+}
+
+impl FovZoom {
+    fn update(&mut self, input: &InputFrame, bindings: &KeyBindings, camera: &mut Camera, dt: f32) {
+        // Platforms report wheel notches in different units, so only the direction counts.
+        let scroll = input.scroll();
+        let mut factor = if scroll == 0.0 {
+            1.0
+        } else {
+            ZOOM_WHEEL_STEP.powf(scroll.signum())
+        };
+        if bindings.down(input, Action::ZoomIn) {
+            factor *= 1.0 - ZOOM_KEY_RATE * dt;
+        }
+        if bindings.down(input, Action::ZoomOut) {
+            factor *= 1.0 + ZOOM_KEY_RATE * dt;
+        }
+        if factor != 1.0 {
+            let from = self.target.unwrap_or(camera.fov);
+            self.target = Some((from * factor).clamp(TELESCOPE_MIN_FOV, MAX_FOV));
+        }
+        let Some(target) = self.target else {
+            return;
+        };
+        let blend = 1.0 - (-ZOOM_SMOOTHING * dt).exp();
+        camera.fov += (target - camera.fov) * blend;
+        if (target - camera.fov).abs() < 1e-4 {
+            camera.fov = target;
+            self.target = None;
+        }
+    }
+}
+
+/// Co-pilot seat: a telescope mounted on the ship that a second player aims with
+/// `I`/`J`/`K`/`L` and zooms with `U`/`O`, independently of where the pilot looks.
+/// Its view is shown in a picture-in-picture inset.
+struct Telescope {
+    yaw: f32,
+    pitch: f32,
+    fov: f32,
+}
+
+impl Telescope {
+    /// Keys the co-pilot aims and zooms with; they take priority over any action
+    /// bound to them while the seat is on.
+    const KEYS: [Key; 6] = [Key::I, Key::J, Key::K, Key::L, Key::U, Key::O];
+
+    /// Starts out looking where the pilot is looking.
+    fn new(pilot: &Camera) -> Self {
+        Self {
+            yaw: pilot.yaw,
+            pitch: pilot.pitch,
+            fov: DEFAULT_FOV,
+        }
+    }
+
+    fn handle_input(&mut self, input: &InputFrame, dt: f32) {
+        if input.is_key_down(Key::J) {
+            self.yaw -= 0.9 * dt;
+        }
+        if input.is_key_down(Key::L) {
+            self.yaw += 0.9 * dt;
+        }
+        if input.is_key_down(Key::I) {
+            self.pitch += 0.6 * dt;
+        }
+        if input.is_key_down(Key::K) {
+            self.pitch -= 0.6 * dt;
+        }
+        self.pitch = self.pitch.clamp(-1.4, 1.4);
+        // Zoom is exponential so each press feels the same at any magnification.
+        if input.is_key_down(Key::U) {
+            self.fov *= 1.0 - TELESCOPE_ZOOM_RATE * dt;
+        }
+        if input.is_key_down(Key::O) {
+            self.fov *= 1.0 + TELESCOPE_ZOOM_RATE * dt;
+        }
+        self.fov = self.fov.clamp(TELESCOPE_MIN_FOV, DEFAULT_FOV);
+    }
+
+    fn camera(&self, pilot: &Camera) -> Camera {
+        Camera {
+            position: spaceship_position_for_camera(pilot),
+            yaw: self.yaw,
+            pitch: self.pitch,
+            fov: self.fov,
+            orthographic: None,
+        }
+    }
+
+    /// Magnification relative to the pilot's default field of view.
+    fn zoom(&self) -> f32 {
+        (DEFAULT_FOV * 0.5).tan() / (self.fov * 0.5).tan()
+    }
+}
+
+fn draw_editor_panel(renderer: &mut Renderer, editor: &PlanetEditor, planets: &[Planet]) {
+    let Some(planet) = planets.get(editor.planet) else {
+        return;
+    };
+    let lines = [
+        format!("EDIT: {}  (TAB next)", planet.name),
+        format!("radius {:.1}  (- =)", planet.radius),
+        format!("orbit {:.1}  ([ ])", planet.orbit_radius),
+        format!("speed {:.2}  (, .)", planet.orbit_speed),
+        format!(
+            "incl {:.0}  node {:.0}  (drag handles)",
+            planet.inclination.to_degrees(),
+            planet.ascending_node.to_degrees()
+        ),
+        format!(
+            "undo {} / redo {}  (CTRL+Z / CTRL+Y)",
+            editor.history.undo.len(),
+            editor.history.redo.len()
+        ),
+    ];
+    let line_height = GLYPH_HEIGHT + 4;
+    renderer.fill_rect(
+        4,
+        4,
+        230,
+        line_height * lines.len() as i32 + 8,
+        Color::new(0.0, 0.0, 0.0),
+        0.6,
+    );
+    for (row, line) in lines.iter().enumerate() {
+        let color = if row == 0 {
+            Color::new(1.0, 0.85, 0.4)
+        } else {
+            Color::new(0.9, 0.93, 1.0)
+        };
+        renderer.draw_text(10, 9 + line_height * row as i32, line, color, 1);
+    }
+}
+
+fn draw_theme_editor_panel(renderer: &mut Renderer, editor: &ThemeEditor, theme: &Theme) {
+    let mut lines = vec![format!("THEME: {}  (CTRL+S save)", theme.name)];
+    for field in THEME_FIELDS {
+        let marker = if field == editor.field() { ">" } else { " " };
+        let value = match field.value(theme) {
+            ThemeValue::Color(color) => {
+                let channels = [color.r, color.g, color.b];
+                let parts: Vec<String> = channels
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, value)| {
+                        if field == editor.field() && idx == editor.channel {
+                            format!("[{:.2}]", value)
+                        } else {
+                            format!("{:.2}", value)
+                        }
+                    })
+                    .collect();
+                parts.join(" ")
+            }
+            ThemeValue::Scalar(value) => format!("[{:.2}]", value),
+        };
+        lines.push(format!("{} {} {}", marker, field.label(), value));
+    }
+    lines.push("TAB field  [ ] channel  - = adjust".to_string());
+    let line_height = GLYPH_HEIGHT + 4;
+    renderer.fill_rect(
+        4,
+        4,
+        250,
+        line_height * lines.len() as i32 + 8,
+        Color::new(0.0, 0.0, 0.0),
+        0.6,
+    );
+    for (row, line) in lines.iter().enumerate() {
+        let color = if row == 0 {
+            Color::new(1.0, 0.85, 0.4)
+        } else {
+            Color::new(0.9, 0.93, 1.0)
+        };
+        renderer.draw_text(10, 9 + line_height * row as i32, line, color, 1);
+    }
+}
+
+fn draw_prop_gizmo(
+    renderer: &mut Renderer,
+    editor: &PropEditor,
+    props: &[Prop],
+    view_projection: &Mat4,
+) {
+    let header = match props.get(editor.prop) {
+        Some(prop) => format!(
+            "PROP: {}  {} (Q)  TAB next  CTRL+S save",
+            prop.name,
+            editor.tool.label()
+        ),
+        None => "PROP: none in this scene".to_string(),
+    };
+    renderer.fill_rect(
+        4,
+        4,
+        renderer.text_width(&header, 1) + 12,
+        GLYPH_HEIGHT + 10,
+        Color::new(0.0, 0.0, 0.0),
+        0.6,
+    );
+    renderer.draw_text(10, 9, &header, Color::new(1.0, 0.85, 0.4), 1);
+    let Some(prop) = props.get(editor.prop) else {
+        return;
+    };
+    let Some(center) = renderer.project_point(prop.position, view_projection) else {
+        return;
+    };
+    let dragged = editor.drag.map(|(axis, _)| axis);
+    for (axis, direction) in GIZMO_AXES.iter().enumerate() {
+        let Some(end) = renderer.project_point(
+            prop.position + *direction * prop.gizmo_length(),
+            view_projection,
+        ) else {
+            continue;
+        };
+        let mut color =
+            Color::new(direction.x, direction.y, direction.z) * 0.8 + Color::new(0.2, 0.2, 0.2);
+        if dragged == Some(axis) {
+            color = Color::new(1.0, 1.0, 0.4);
+        }
+        renderer.draw_line(center, end, color);
+        renderer.fill_rect(end.x as i32 - 3, end.y as i32 - 3, 7, 7, color, 1.0);
+    }
+}
+
+fn draw_governor_status(renderer: &mut Renderer, governor: &FrameGovernor) {
+    let shed = governor.shed_passes();
+    if shed.is_empty() {
+        return;
+    }
+    let mut names: Vec<String> = shed.iter().map(|pass| pass.label().to_string()).collect();
+    let scale = governor.resolution_scale();
+    if scale < 1.0 {
+        names.push(format!("resolution {:.0}%", scale * 100.0));
+    }
+    let text = format!("SHED: {}", names.join(", "));
+    let y = renderer.height as i32 - GLYPH_HEIGHT - 6;
+    renderer.draw_text(6, y, &text, Color::new(1.0, 0.55, 0.35), 1);
+}
+
+fn draw_reload_status(renderer: &mut Renderer, watcher: &SceneWatcher) {
+    let Some((message, _)) = &watcher.message else {
+        return;
+    };
+    let y = renderer.height as i32 - (GLYPH_HEIGHT + 6) * 2;
+    renderer.draw_text(6, y, message, Color::new(0.6, 1.0, 0.7), 1);
+}
+
+fn window_title(theme: &Theme, filter: TagFilter) -> String {
+    match filter {
+        TagFilter::All => format!("Icy System - {}", theme.name),
+        _ => format!("Icy System - {} [{}]", theme.name, filter.label()),
+    }
+}
+
+fn detect_warp_request(
+    input: &InputFrame,
+    bindings: &KeyBindings,
+    targets: &[WarpTarget],
+) -> Option<usize> {
+    bindings
+        .pressed_slot(input)
+        .filter(|&idx| idx < targets.len())
+}
+
+fn key_to_char(key: Key) -> Option<char> {
+    let letters = [
+        Key::A,
+        Key::B,
+        Key::C,
+        Key::D,
+        Key::E,
+        Key::F,
+        Key::G,
+        Key::H,
+        Key::I,
+        Key::J,
+        Key::K,
+        Key::L,
+        Key::M,
+        Key::N,
+        Key::O,
+        Key::P,
+        Key::Q,
+        Key::R,
+        Key::S,
+        Key::T,
+        Key::U,
+        Key::V,
+        Key::W,
+        Key::X,
+        Key::Y,
+        Key::Z,
+    ];
+    let digits = [
+        Key::Key0,
+        Key::Key1,
+        Key::Key2,
+        Key::Key3,
+        Key::Key4,
+        Key::Key5,
+        Key::Key6,
+        Key::Key7,
+        Key::Key8,
+        Key::Key9,
+    ];
+    if let Some(idx) = letters.iter().position(|&k| k == key) {
+        return Some((b'a' + idx as u8) as char);
+    }
+    if let Some(idx) = digits.iter().position(|&k| k == key) {
+        return Some((b'0' + idx as u8) as char);
+    }
+    match key {
+        Key::Space => Some(' '),
+        Key::Minus => Some('-'),
+        Key::Period => Some('.'),
+        Key::Equal => Some('='),
+        _ => None,
+    }
+}
+
+/// Subsequence match of `query` inside `name`, case-insensitive. Consecutive hits,
+/// word starts and a match on the first letter score higher; gaps cost a point each.
+fn fuzzy_score(query: &str, name: &str) -> Option<i32> {
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut prev: Option<usize> = None;
+    for qc in query.to_lowercase().chars() {
+        let found = pos + name[pos..].iter().position(|&c| c == qc)?;
+        score += 1;
+        if found == 0 {
+            score += 8;
+        } else if prev == Some(found - 1) {
+            score += 5;
+        } else if name[found - 1] == ' ' {
+            score += 4;
+        }
+        score -= (found - pos) as i32;
+        prev = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+struct SearchBox {
+    query: String,
+    cursor: usize,
+}
+
+enum SearchAction {
+    Typing,
+    Close,
+    Select(usize),
+    Warp(usize),
+}
+
+impl SearchBox {
+    fn new() -> Self {
+        Self {
+            query: String::new(),
+            cursor: 0,
+        }
+    }
+
+    /// Warp target indices ordered by match quality, best first.
+    fn results(&self, targets: &[WarpTarget]) -> Vec<usize> {
+        let mut scored: Vec<(i32, usize)> = targets
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, target)| {
+                fuzzy_score(&self.query, &target.name).map(|score| (score, idx))
+            })
+            .collect();
+        scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+        scored
+            .into_iter()
+            .take(SEARCH_MAX_RESULTS)
+            .map(|(_, idx)| idx)
+            .collect()
+    }
+
+    fn handle_keys(&mut self, input: &InputFrame, targets: &[WarpTarget]) -> SearchAction {
+        for &key in input.keys_pressed(KeyRepeat::Yes) {
+            match key {
+                Key::Escape => return SearchAction::Close,
+                Key::Enter | Key::NumPadEnter => {
+                    if let Some(&idx) = self.results(targets).get(self.cursor) {
+                        return SearchAction::Warp(idx);
+                    }
+                }
+                Key::Tab => {
+                    if let Some(&idx) = self.results(targets).get(self.cursor) {
+                        return SearchAction::Select(idx);
+                    }
+                }
+                Key::Up => self.cursor = self.cursor.saturating_sub(1),
+                Key::Down => {
+                    let count = self.results(targets).len();
+                    self.cursor = (self.cursor + 1).min(count.saturating_sub(1));
+                }
+                Key::Backspace => {
+                    self.query.pop();
+                    self.cursor = 0;
+                }
+                _ => {
+                    if let Some(ch) = key_to_char(key) {
+                        if self.query.len() < SEARCH_MAX_QUERY {
+                            self.query.push(ch);
+                            self.cursor = 0;
+                        }
+                    }
+                }
+            }
+        }
+        SearchAction::Typing
+    }
+}
+
+fn draw_search(
+    renderer: &mut Renderer,
+    search: &SearchBox,
+    targets: &[WarpTarget],
+    palette: &Palette,
+) {
+    let results = search.results(targets);
+    let scale = 2;
+    let line_height = (GLYPH_HEIGHT + 4) * scale;
+    let width = 320;
+    let height = line_height * (results.len() as i32 + 1) + 12;
+    let x = (renderer.width as i32 - width) / 2;
+    let y = 40;
+    renderer.fill_rect(x, y, width, height, Color::new(0.0, 0.0, 0.0), 0.65);
+    let text_color = Color::new(0.92, 0.95, 1.0);
+    renderer.draw_text(
+        x + 8,
+        y + 6,
+        &format!("/ {}_", search.query),
+        text_color,
+        scale,
+    );
+    for (row, &idx) in results.iter().enumerate() {
+        let line_y = y + 6 + line_height * (row as i32 + 1);
+        let (prefix, color) = if row == search.cursor {
+            ("> ", palette.star_color)
+        } else {
+            ("  ", text_color * 0.7)
+        };
+        renderer.draw_text(
+            x + 8,
+            line_y,
+            &format!("{}{}", prefix, targets[idx].name),
+            color,
+            scale,
+        );
+    }
+}
+
+fn draw_selection_marker(
+    renderer: &mut Renderer,
+    target: &WarpTarget,
+    camera: &Camera,
+    view_projection: &Mat4,
+    palette: &Palette,
+) {
+    let Some(center) = renderer.project_point(target.center, view_projection) else {
+        return;
+    };
+    let right = camera.forward().cross(Vec3::UP).normalized();
+    let edge = renderer.project_point(target.center + right * target.radius, view_projection);
+    let radius = edge
+        .map_or(12.0, |e| (e.x - center.x).abs())
+        .clamp(8.0, 240.0)
+        + 4.0;
+    let arm = (radius * 0.35).max(4.0);
+    let color = palette.star_color;
+    for (sx, sy) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+        let corner = Vec2::new(center.x + sx * radius, center.y + sy * radius);
+        renderer.draw_line(corner, Vec2::new(corner.x - sx * arm, corner.y), color);
+        renderer.draw_line(corner, Vec2::new(corner.x, corner.y - sy * arm), color);
+    }
+    let label_x = center.x as i32 - renderer.text_width(&target.name, 1) / 2;
+    renderer.draw_text(
+        label_x,
+        (center.y + radius) as i32 + 4,
+        &target.name,
+        color,
+        1,
+    );
+}
+
+/// Camera position relative to the primary star: distance plus ecliptic longitude
+/// (measured from +X towards +Z) and latitude above the ecliptic, both in degrees.
+#[derive(Clone, Copy)]
+struct SphericalCoords {
+    radius: f32,
+    longitude: f32,
+    latitude: f32,
+}
+
+impl SphericalCoords {
+    fn from_offset(offset: Vec3) -> Self {
+        let radius = offset.length();
+        if radius <= f32::EPSILON {
+            return Self {
+                radius: 0.0,
+                longitude: 0.0,
+                latitude: 0.0,
+            };
+        }
+        Self {
+            radius,
+            longitude: offset.z.atan2(offset.x).to_degrees().rem_euclid(360.0),
+            latitude: (offset.y / radius).clamp(-1.0, 1.0).asin().to_degrees(),
+        }
+    }
+
+    fn to_offset(self) -> Vec3 {
+        let lon = self.longitude.to_radians();
+        let lat = self.latitude.to_radians();
+        Vec3::new(lat.cos() * lon.cos(), lat.sin(), lat.cos() * lon.sin()) * self.radius
+    }
+}
+
+/// Co-pilot view in the bottom-right corner, above the coordinate readout.
+fn draw_telescope_inset(renderer: &mut Renderer, pip: &Renderer, scope: &Telescope) {
+    let x = renderer.width as i32 - pip.width as i32 - 6;
+    let y = renderer.height as i32 - pip.height as i32 - GLYPH_HEIGHT - 14;
+    renderer.fill_rect(
+        x - 1,
+        y - 1,
+        pip.width as i32 + 2,
+        pip.height as i32 + 2,
+        Color::new(0.8, 0.9, 1.0),
+        1.0,
+    );
+    renderer.blit(pip, x, y);
+    let label = format!("TELESCOPE x{:.1}  IJKL U/O", scope.zoom());
+    renderer.draw_text(x + 4, y + 4, &label, Color::new(0.8, 0.9, 1.0), 1);
+}
+
+/// Fixed camera for the selected body's close-up inset: on the star's side of it,
+/// a little off to one side and above, framed to fill `CLOSEUP_FILL` of the inset.
+/// `None` while the pilot is already close enough to see it.
+fn closeup_camera(target: &WarpTarget, pilot: &Camera, light: Vec3) -> Option<Camera> {
+    let radius = target.radius.max(0.5);
+    let distance = framing_distance(radius, DEFAULT_FOV, CLOSEUP_FILL);
+    if (target.center - pilot.position).length() < distance * CLOSEUP_MIN_DISTANCE {
+        return None;
+    }
+    let to_light = light - target.center;
+    // A star lights itself; any side will do.
+    let to_light = if to_light.length() > radius {
+        to_light.normalized()
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let side = to_light.cross(Vec3::UP).normalized();
+    let offset = (to_light + side * 0.7 + Vec3::UP * 0.35).normalized();
+    let view = CameraView::looking(-offset, DEFAULT_FOV);
+    Some(Camera {
+        yaw: view.yaw,
+        pitch: view.pitch,
+        fov: view.fov,
+        ..Camera::new(target.center + offset * distance)
+    })
+}
+
+/// Close-up inset under the info panel on the right.
+fn draw_closeup_inset(renderer: &mut Renderer, inset: &Renderer, target: &WarpTarget) {
+    let x = renderer.width as i32 - inset.width as i32 - 6;
+    let y = 80;
+    renderer.fill_rect(
+        x - 1,
+        y - 1,
+        inset.width as i32 + 2,
+        inset.height as i32 + 2,
+        Color::new(0.8, 0.9, 1.0),
+        1.0,
+    );
+    renderer.blit(inset, x, y);
+    let label = target.name.to_uppercase();
+    renderer.draw_text(x + 4, y + 4, &label, Color::new(0.8, 0.9, 1.0), 1);
+}
+
+fn draw_coordinates(renderer: &mut Renderer, coords: SphericalCoords) {
+    let text = format!(
+        "R {:.1}  LON {:.1}  LAT {:.1}",
+        coords.radius, coords.longitude, coords.latitude
+    );
+    let x = renderer.width as i32 - renderer.text_width(&text, 1) - 6;
+    let y = renderer.height as i32 - GLYPH_HEIGHT - 6;
+    renderer.draw_text(x, y, &text, Color::new(0.8, 0.9, 1.0), 1);
+}
+
+/// Readouts for the flight HUD strip, refreshed once per frame from the camera.
+struct FlightHud {
+    /// Camera speed in units per second, smoothed so it doesn't flicker.
+    speed: f32,
+    /// Closest body and the camera's height above its surface.
+    nearest: Option<(String, f32)>,
+    /// Warp destination (if named) and how far along the warp is.
+    warp: Option<(Option<String>, f32)>,
+    pitch: f32,
+    /// Height above the ecliptic plane through the primary star.
+    elevation: f32,
+}
+
+impl FlightHud {
+    fn new() -> Self {
+        Self {
+            speed: 0.0,
+            nearest: None,
+            warp: None,
+            pitch: 0.0,
+            elevation: 0.0,
+        }
+    }
+
+    fn update(
+        &mut self,
+        camera: &Camera,
+        speed: f32,
+        dt: f32,
+        targets: &[WarpTarget],
+        warp: Option<&Warp>,
+        ecliptic: Vec3,
+    ) {
+        let blend = 1.0 - (-HUD_SPEED_SMOOTHING * dt).exp();
+        self.speed += (speed - self.speed) * blend;
+        self.nearest = targets
+            .iter()
+            .map(|target| {
+                let altitude = (target.center - camera.position).length() - target.radius;
+                (target, altitude)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(target, altitude)| (target.name.clone(), altitude.max(0.0)));
+        self.warp = warp.map(|active| (active.destination.clone(), active.fraction()));
+        self.pitch = camera.pitch;
+        self.elevation = camera.position.y - ecliptic.y;
+    }
+}
+
+/// Top-center strip: speed, nearest body, ecliptic height and warp progress, with
+/// an artificial horizon whose line drops as the nose pitches up.
+fn draw_flight_hud(renderer: &mut Renderer, hud: &FlightHud) {
+    let mut top = format!("SPD {:.1}", hud.speed);
+    if let Some((name, altitude)) = &hud.nearest {
+        top += &format!("  NEAR {} {:.1}", name.to_uppercase(), altitude);
+    }
+    let bottom = match &hud.warp {
+        Some((Some(name), fraction)) => {
+            format!("WARP > {} {:.0}%", name.to_uppercase(), fraction * 100.0)
+        }
+        Some((None, fraction)) => format!("WARP > {:.0}%", fraction * 100.0),
+        None => format!(
+            "ECL {:+.1}  PITCH {:+.0}",
+            hud.elevation,
+            hud.pitch.to_degrees()
+        ),
+    };
+    let line_height = GLYPH_HEIGHT + 4;
+    let text_width = renderer
+        .text_width(&top, 1)
+        .max(renderer.text_width(&bottom, 1));
+    let width = text_width + HUD_HORIZON_WIDTH + 20;
+    let height = line_height * 2 + 8;
+    let x = (renderer.width as i32 - width) / 2;
+    let y = 4;
+    renderer.fill_rect(x, y, width, height, Color::new(0.0, 0.0, 0.0), 0.5);
+    let text = Color::new(0.85, 0.92, 1.0);
+    renderer.draw_text(x + 6, y + 5, &top, text, 1);
+    let warping = Color::new(1.0, 0.8, 0.45);
+    let bottom_color = if hud.warp.is_some() { warping } else { text };
+    renderer.draw_text(x + 6, y + 5 + line_height, &bottom, bottom_color, 1);
+
+    let bx = x + width - HUD_HORIZON_WIDTH - 6;
+    let by = y + (height - HUD_HORIZON_HEIGHT) / 2;
+    renderer.fill_rect(
+        bx,
+        by,
+        HUD_HORIZON_WIDTH,
+        HUD_HORIZON_HEIGHT,
+        Color::new(0.1, 0.16, 0.28),
+        0.7,
+    );
+    // Full scale is +-90 degrees; beyond the box the line pins to its edge.
+    let half = HUD_HORIZON_HEIGHT as f32 * 0.5;
+    let offset = (hud.pitch / (PI * 0.5) * half).clamp(-half + 1.0, half - 1.0);
+    let horizon_y = by as f32 + half + offset;
+    renderer.draw_line(
+        Vec2::new(bx as f32 + 1.0, horizon_y),
+        Vec2::new((bx + HUD_HORIZON_WIDTH) as f32 - 2.0, horizon_y),
+        Color::new(0.4, 0.85, 1.0),
+    );
+    let (cx, cy) = (bx as f32 + HUD_HORIZON_WIDTH as f32 * 0.5, by as f32 + half);
+    let marker = Color::new(1.0, 0.85, 0.3);
+    renderer.draw_line(Vec2::new(cx - 10.0, cy), Vec2::new(cx - 4.0, cy), marker);
+    renderer.draw_line(Vec2::new(cx + 4.0, cy), Vec2::new(cx + 10.0, cy), marker);
+    renderer.draw_line(Vec2::new(cx, cy - 2.0), Vec2::new(cx, cy), marker);
+}
+
+/// Top-down plot of the ecliptic in the top-left corner: orbits, the stars, a dot
+/// per planet and the camera as an arrow along its heading. The scale fits
+/// `extent`, the `system_extent`; a camera beyond it is pinned to the map's edge and
+/// orbits are clipped to the map.
+fn draw_minimap(
+    renderer: &mut Renderer,
+    stars: &[Star],
+    planets: &[Planet],
+    extent: f32,
+    camera: &Camera,
+) {
+    let (x, y, size) = (4, 4, MINIMAP_SIZE);
+    renderer.fill_rect(x, y, size, size, Color::new(0.0, 0.0, 0.0), 0.6);
+    let map = (
+        Vec2::new(x as f32, y as f32),
+        Vec2::new((x + size - 1) as f32, (y + size - 1) as f32),
+    );
+    let half = size as f32 * 0.5;
+    let scale = (half - 6.0) / extent;
+    let center = Vec2::new(x as f32 + half, y as f32 + half);
+    // Seen from above the ecliptic, so +Z is up and +X to the left, matching the view.
+    let to_map = |point: Vec3| Vec2::new(center.x - point.x * scale, center.y - point.z * scale);
+
+    for (idx, planet) in planets.iter().enumerate() {
+        let color = renderer
+            .accessibility
+            .orbit_color(idx)
+            .unwrap_or(planet.orbit_color);
+        let mut previous = to_map(planet.orbit_point(0.0));
+        for segment in 1..=MINIMAP_ORBIT_SEGMENTS {
+            let angle = segment as f32 / MINIMAP_ORBIT_SEGMENTS as f32 * TAU;
+            let next = to_map(planet.orbit_point(angle));
+            renderer.draw_line_in(previous, next, map, color * 0.7);
+            previous = next;
+        }
+    }
+    for star in stars {
+        let dot = to_map(star.position);
+        renderer.fill_rect(dot.x as i32 - 2, dot.y as i32 - 2, 5, 5, star.color, 1.0);
+    }
+    for planet in planets {
+        let dot = to_map(planet.position);
+        let radius = (planet.radius * scale).clamp(1.0, 3.0) as i32;
+        let side = radius * 2 + 1;
+        renderer.fill_rect(
+            dot.x as i32 - radius,
+            dot.y as i32 - radius,
+            side,
+            side,
+            planet.color,
+            1.0,
+        );
+    }
+
+    let inset = 4.0;
+    let position = to_map(camera.position);
+    let position = Vec2::new(
+        position
+            .x
+            .clamp(x as f32 + inset, (x + size) as f32 - inset),
+        position
+            .y
+            .clamp(y as f32 + inset, (y + size) as f32 - inset),
+    );
+    // Arrow along the heading: `forward` pixels ahead and `across` to the right.
+    let (sin, cos) = camera.yaw.sin_cos();
+    let arrow = |forward: f32, across: f32| {
+        Vec2::new(
+            position.x - sin * forward + cos * across,
+            position.y - cos * forward - sin * across,
+        )
+    };
+    let (tip, left, right) = (arrow(6.0, 0.0), arrow(-3.0, -3.5), arrow(-3.0, 3.5));
+    let marker = Color::new(1.0, 0.85, 0.3);
+    renderer.draw_line(tip, left, marker);
+    renderer.draw_line(left, right, marker);
+    renderer.draw_line(right, tip, marker);
+}
+
+/// Locked camera for the split screen's overview: an orthographic diagram looking
+/// straight down on the whole system, +z up and mirrored like the minimap.
+fn overview_camera(extent: f32, aspect: f32) -> Camera {
+    Camera {
+        position: Vec3::new(0.0, extent, 0.0),
+        yaw: 0.0,
+        // Exactly straight down would leave the view without a right vector.
+        pitch: -(PI * 0.5 - 1e-3),
+        orthographic: Some(extent * OVERVIEW_MARGIN / aspect.min(1.0)),
+        ..Camera::new(Vec3::ZERO)
+    }
+}
+
+/// The pilot's position and heading on the overview, with the view's name.
+fn draw_overview_marker(renderer: &mut Renderer, pilot: &Camera, view_projection: &Mat4) {
+    let color = Color::new(1.0, 0.85, 0.3);
+    renderer.draw_text(8, 8, "TOP VIEW", Color::new(0.8, 0.9, 1.0), 1);
+    let (width, height) = (renderer.width as f32, renderer.height as f32);
+    let Some(position) = project_to_screen(pilot.position, view_projection, width, height) else {
+        return;
+    };
+    let (sin, cos) = pilot.yaw.sin_cos();
+    let arrow = |forward: f32, across: f32| {
+        Vec2::new(
+            position.x - sin * forward + cos * across,
+            position.y - cos * forward - sin * across,
+        )
+    };
+    let (tip, left, right) = (arrow(9.0, 0.0), arrow(-4.0, -5.0), arrow(-4.0, 5.0));
+    renderer.draw_line(tip, left, color);
+    renderer.draw_line(left, right, color);
+    renderer.draw_line(right, tip, color);
+}
+
+/// Puts two equally tall views side by side in `slot`, with a divider between them.
+fn compose_split<'a>(
+    left: &Renderer,
+    right: &Renderer,
+    slot: &'a mut Option<Renderer>,
+) -> &'a Renderer {
+    let (width, height) = (left.width + right.width, left.height);
+    if slot
+        .as_ref()
+        .is_none_or(|split| split.width != width || split.height != height)
+    {
+        *slot = Some(Renderer::new(width, height, left.palette));
+    }
+    let split = slot.as_mut().expect("slot was just filled");
+    split.blit(left, 0, 0);
+    split.blit(right, left.width as i32, 0);
+    split.fill_rect(
+        left.width as i32 - 1,
+        0,
+        2,
+        height as i32,
+        Color::new(0.8, 0.9, 1.0),
+        1.0,
+    );
+    split
+}
+
+/// Single-line command prompt opened with the backquote key.
+struct Console {
+    line: String,
+    error: Option<String>,
+}
+
+enum ConsoleAction {
+    Typing,
+    Close,
+    Submit(String),
+}
+
+enum ConsoleCommand {
+    Goto(SphericalCoords),
+    FrameSelected,
+    FrameAll,
+    ToggleOrthographic,
+    ToggleSplitView,
+    ToggleTrails,
+    Export(PathBuf),
+}
+
+impl Console {
+    fn new() -> Self {
+        Self {
+            line: String::new(),
+            error: None,
+        }
+    }
+
+    fn handle_keys(&mut self, input: &InputFrame) -> ConsoleAction {
+        for &key in input.keys_pressed(KeyRepeat::Yes) {
+            match key {
+                Key::Escape | Key::Backquote => return ConsoleAction::Close,
+                Key::Enter | Key::NumPadEnter => return ConsoleAction::Submit(self.line.clone()),
+                Key::Backspace => {
+                    self.line.pop();
+                }
+                _ => {
+                    if let Some(ch) = key_to_char(key) {
+                        if self.line.len() < CONSOLE_MAX_LINE {
+                            self.line.push(ch);
+                            self.error = None;
+                        }
+                    }
+                }
+            }
+        }
+        ConsoleAction::Typing
+    }
+}
+
+/// `goto r=<dist> lon=<deg> lat=<deg>` (`lon` and `lat` default to 0), `frame selected`,
+/// `frame all`, `ortho`, `split` or `export <file.obj>`.
+fn parse_console_command(line: &str) -> Result<ConsoleCommand, String> {
+    let mut words = line.split_whitespace();
+    match words.next() {
+        Some("goto") => {
+            let mut radius = None;
+            let mut coords = SphericalCoords {
+                radius: 0.0,
+                longitude: 0.0,
+                latitude: 0.0,
+            };
+            for word in words {
+                let (key, value) = word
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected key=value, got {}", word))?;
+                let value: f32 = value
+                    .parse()
+                    .map_err(|_| format!("{} is not a number", value))?;
+                match key {
+                    "r" => radius = Some(value.max(0.0)),
+                    "lon" => coords.longitude = value,
+                    "lat" => coords.latitude = value.clamp(-90.0, 90.0),
+                    _ => return Err(format!("unknown coordinate {}", key)),
+                }
+            }
+            coords.radius = radius.ok_or("goto needs r=<distance>")?;
+            Ok(ConsoleCommand::Goto(coords))
+        }
+        Some("frame") => match words.next() {
+            None | Some("selected") => Ok(ConsoleCommand::FrameSelected),
+            Some("all") => Ok(ConsoleCommand::FrameAll),
+            Some(other) => Err(format!("frame expects selected or all, got {}", other)),
+        },
+        Some("ortho") => Ok(ConsoleCommand::ToggleOrthographic),
+        Some("split") => Ok(ConsoleCommand::ToggleSplitView),
+        Some("trails") => Ok(ConsoleCommand::ToggleTrails),
+        Some("export") => match words.next() {
+            Some(path) => Ok(ConsoleCommand::Export(PathBuf::from(path))),
+            None => Err("export expects a file path, e.g. export system.obj".to_string()),
+        },
+        Some(other) => Err(format!("unknown command {}", other)),
+        None => Err("type a command, e.g. goto r=120 lon=45 lat=10".to_string()),
+    }
+}
+
+fn draw_console(renderer: &mut Renderer, console: &Console, palette: &Palette) {
+    let scale = 2;
+    let line_height = (GLYPH_HEIGHT + 4) * scale;
+    let rows = if console.error.is_some() { 2 } else { 1 };
+    let y = renderer.height as i32 - line_height * rows - 40;
+    let width = renderer.width as i32 - 80;
+    renderer.fill_rect(
+        40,
+        y,
+        width,
+        line_height * rows + 8,
+        Color::new(0.0, 0.0, 0.0),
+        0.65,
+    );
+    renderer.draw_text(
+        48,
+        y + 6,
+        &format!("> {}_", console.line),
+        palette.star_color,
+        scale,
+    );
+    if let Some(error) = &console.error {
+        renderer.draw_text(
+            48,
+            y + 6 + line_height,
+            error,
+            Color::new(1.0, 0.55, 0.35),
+            scale,
+        );
+    }
+}
+
+struct ShotMenu {
+    cursor: usize,
+}
+
+enum ShotMenuAction {
+    Browsing,
+    Close,
+    Jump(usize),
+    Tour(usize),
+}
+
+impl ShotMenu {
+    fn new() -> Self {
+        Self { cursor: 0 }
+    }
+
+    fn handle_keys(&mut self, input: &InputFrame, count: usize) -> ShotMenuAction {
+        for &key in input.keys_pressed(KeyRepeat::Yes) {
+            match key {
+                Key::Escape | Key::M => return ShotMenuAction::Close,
+                Key::Enter | Key::NumPadEnter if self.cursor < count => {
+                    return ShotMenuAction::Jump(self.cursor)
+                }
+                Key::Tab if self.cursor < count => return ShotMenuAction::Tour(self.cursor),
+                Key::Up => self.cursor = self.cursor.saturating_sub(1),
+                Key::Down => self.cursor = (self.cursor + 1).min(count.saturating_sub(1)),
+                _ => {}
+            }
+        }
+        ShotMenuAction::Browsing
+    }
+}
+
+/// Plays the scene's shots in order, holding each one for its `hold` time once the
+/// camera has arrived.
+struct Tour {
+    shot: usize,
+    timer: f32,
+}
+
+impl Tour {
+    fn new(shot: usize) -> Self {
+        Self { shot, timer: 0.0 }
+    }
+}
+
+fn draw_shot_menu(
+    renderer: &mut Renderer,
+    menu: &ShotMenu,
+    shots: &[CameraShot],
+    camera: &Camera,
+    sim_time: f32,
+    palette: &Palette,
+) {
+    let scale = 2;
+    let line_height = (GLYPH_HEIGHT + 4) * scale;
+    let width = 420;
+    let rows = shots.len().max(1) as i32 + 2;
+    let height = line_height * rows + 12;
+    let x = (renderer.width as i32 - width) / 2;
+    let y = 40;
+    renderer.fill_rect(x, y, width, height, Color::new(0.0, 0.0, 0.0), 0.65);
+    let text_color = Color::new(0.92, 0.95, 1.0);
+    renderer.draw_text(x + 8, y + 6, "SHOTS  ENTER go  TAB tour", text_color, scale);
+    if shots.is_empty() {
+        renderer.draw_text(
+            x + 8,
+            y + 6 + line_height,
+            "  no [shot] sections",
+            text_color * 0.7,
+            scale,
+        );
+    }
+    for (row, shot) in shots.iter().enumerate() {
+        let line_y = y + 6 + line_height * (row as i32 + 1);
+        let (prefix, color) = if row == menu.cursor {
+            ("> ", palette.star_color)
+        } else {
+            ("  ", text_color * 0.7)
+        };
+        let label = match shot.time {
+            Some(time) => format!("{}{}  t={:.0}s", prefix, shot.name, time),
+            None => format!("{}{}", prefix, shot.name),
+        };
+        renderer.draw_text(x + 8, line_y, &label, color, scale);
+    }
+    // Current camera, so authors can copy it into a new [shot] section.
+    let p = camera.position;
+    renderer.draw_text(
+        x + 8,
+        y + 6 + line_height * (rows - 1),
+        &format!(
+            "cam {:.1} {:.1} {:.1}  yaw {:.2} pitch {:.2}  t={:.1}s",
+            p.x, p.y, p.z, camera.yaw, camera.pitch, sim_time
+        ),
+        text_color * 0.6,
+        1,
+    );
+}
+
+fn draw_tour_status(renderer: &mut Renderer, tour: &Tour, shots: &[CameraShot]) {
+    let Some(shot) = shots.get(tour.shot) else {
+        return;
+    };
+    let label = format!("TOUR {}/{}  {}", tour.shot + 1, shots.len(), shot.name);
+    let width = renderer.text_width(&label, 2) + 16;
+    let x = (renderer.width as i32 - width) / 2;
+    let y = renderer.height as i32 - GLYPH_HEIGHT * 2 - 28;
+    renderer.fill_rect(
+        x,
+        y,
+        width,
+        GLYPH_HEIGHT * 2 + 12,
+        Color::new(0.0, 0.0, 0.0),
+        0.55,
+    );
+    renderer.draw_text(x + 8, y + 6, &label, Color::new(0.92, 0.95, 1.0), 2);
+}
+
+/// Blackbody equilibrium temperature in kelvin at `position`, summing the flux of
+/// every star: T = 278.6 K * (sum L / d^2)^(1/4) with d in units of `EQUILIBRIUM_AU`.
+/// A star doesn't heat itself, so stars whose surface contains the point are skipped.
+fn equilibrium_temperature(position: Vec3, stars: &[Star]) -> f32 {
+    let flux: f32 = stars
+        .iter()
+        .filter_map(|star| {
+            let distance = (position - star.position).length();
+            (distance > star.radius * 1.01)
+                .then(|| star.light_intensity * (EQUILIBRIUM_AU / distance).powi(2))
+        })
+        .sum();
+    278.6 * flux.powf(0.25)
+}
+
+/// Day-side emissive tint for a body at `temperature`: none below `HEAT_GLOW_START`,
+/// deep red rising to orange at `HEAT_GLOW_FULL`.
+fn heat_glow(temperature: f32) -> Color {
+    let t = ((temperature - HEAT_GLOW_START) / (HEAT_GLOW_FULL - HEAT_GLOW_START)).clamp(0.0, 1.0);
+    Color::lerp(Color::new(0.6, 0.08, 0.02), Color::new(1.0, 0.5, 0.15), t) * t
+}
+
+fn draw_info_panel(
+    renderer: &mut Renderer,
+    target: &WarpTarget,
+    camera: &Camera,
+    temperature: f32,
+) {
+    let mut lines = vec![
+        target.name.to_uppercase(),
+        format!("DIST {:.1}", (target.center - camera.position).length()),
+    ];
+    if temperature > 0.0 {
+        lines.push(format!("TEMP {:.0} K", temperature));
+    }
+    let line_height = GLYPH_HEIGHT + 4;
+    let width = lines
+        .iter()
+        .map(|line| renderer.text_width(line, 1))
+        .max()
+        .unwrap_or(0)
+        + 12;
+    let x = renderer.width as i32 - width - 4;
+    let y = 20;
+    renderer.fill_rect(
+        x,
+        y,
+        width,
+        line_height * lines.len() as i32 + 8,
+        Color::new(0.0, 0.0, 0.0),
+        0.6,
+    );
+    for (row, line) in lines.iter().enumerate() {
+        renderer.draw_text(
+            x + 6,
+            y + 5 + line_height * row as i32,
+            line,
+            Color::new(0.9, 0.93, 1.0),
+            1,
+        );
+    }
+}
+
+/// Transit-method view: a fixed observer far away on the ecliptic looks at the
+/// primary star while the HUD plots the star's relative flux over time.
+struct TransitView {
+    samples: VecDeque<f32>,
+    sample_timer: f32,
+}
+
+impl TransitView {
+    fn new() -> Self {
+        Self {
+            samples: VecDeque::with_capacity(LIGHT_CURVE_SAMPLES),
+            sample_timer: 0.0,
+        }
+    }
+
+    fn observer(&self, star: &Star) -> Vec3 {
+        star.position - Vec3::new(0.0, 0.0, TRANSIT_DISTANCE)
+    }
+
+    /// Camera at the observer, aimed at the star, with a FOV wide enough for every orbit.
+    fn camera(&self, star: &Star, extent: f32) -> Camera {
+        Camera {
+            yaw: 0.0,
+            pitch: 0.0,
+            fov: 2.0 * (extent * 1.1 / TRANSIT_DISTANCE).atan(),
+            ..Camera::new(self.observer(star))
+        }
+    }
+
+    fn record(&mut self, brightness: f32, dt: f32) {
+        self.sample_timer += dt;
+        while self.sample_timer >= LIGHT_CURVE_INTERVAL {
+            self.sample_timer -= LIGHT_CURVE_INTERVAL;
+            if self.samples.len() == LIGHT_CURVE_SAMPLES {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(brightness);
+        }
+    }
+}
+
+/// Area shared by two circles of radius `r1` and `r2` whose centers are `d` apart.
+fn circle_overlap(r1: f32, r2: f32, d: f32) -> f32 {
+    if d >= r1 + r2 {
+        return 0.0;
+    }
+    if d <= (r1 - r2).abs() {
+        return PI * r1.min(r2).powi(2);
+    }
+    let a1 = ((d * d + r1 * r1 - r2 * r2) / (2.0 * d * r1))
+        .clamp(-1.0, 1.0)
+        .acos();
+    let a2 = ((d * d + r2 * r2 - r1 * r1) / (2.0 * d * r2))
+        .clamp(-1.0, 1.0)
+        .acos();
+    let kite = 0.5
+        * ((-d + r1 + r2) * (d + r1 - r2) * (d - r1 + r2) * (d + r1 + r2))
+            .max(0.0)
+            .sqrt();
+    r1 * r1 * a1 + r2 * r2 * a2 - kite
+}
+
+/// Fraction of the star's disc left uncovered, as seen from `observer`. Planets are
+/// projected onto the plane through the star facing the observer, so a planet
+/// closer to the observer covers proportionally more of the disc.
+fn transit_brightness(star: &Star, planets: &[Planet], observer: Vec3) -> f32 {
+    let to_star = star.position - observer;
+    let star_distance = to_star.length();
+    let axis = to_star / star_distance;
+    let disc = PI * star.radius * star.radius;
+    let mut blocked = 0.0;
+    for planet in planets {
+        let offset = planet.position - observer;
+        let depth = offset.dot(axis);
+        if depth <= 0.0 || depth >= star_distance {
+            continue;
+        }
+        let scale = star_distance / depth;
+        let separation = (offset - axis * depth).length() * scale;
+        blocked += circle_overlap(star.radius, planet.radius * scale, separation);
+    }
+    (1.0 - blocked / disc).max(0.0)
+}
+
+fn draw_light_curve(renderer: &mut Renderer, view: &TransitView, palette: &Palette) {
+    let width = LIGHT_CURVE_SAMPLES as i32;
+    let height = 80;
+    let x = (renderer.width as i32 - width) / 2;
+    let y = renderer.height as i32 - height - 24;
+    renderer.fill_rect(
+        x - 6,
+        y - 16,
+        width + 12,
+        height + 22,
+        Color::new(0.0, 0.0, 0.0),
+        0.6,
+    );
+    let latest = view.samples.back().copied().unwrap_or(1.0);
+    renderer.draw_text(
+        x,
+        y - 12,
+        &format!("TRANSIT FLUX {:.4}", latest),
+        Color::new(0.9, 0.93, 1.0),
+        1,
+    );
+    // Keep at least a 2% span so the baseline doesn't turn into noise.
+    let lowest = view.samples.iter().copied().fold(1.0, f32::min).min(0.98);
+    let to_y = |flux: f32| y as f32 + (1.0 - flux) / (1.0 - lowest) * height as f32;
+    let mut previous: Option<Vec2> = None;
+    for (idx, &flux) in view.samples.iter().enumerate() {
+        let point = Vec2::new((x + idx as i32) as f32, to_y(flux));
+        if let Some(prev) = previous {
+            renderer.draw_line(prev, point, palette.star_color);
+        }
+        previous = Some(point);
+    }
+}
+
+/// Polls the modification time of the active scene file so edits can be picked up
+/// without restarting.
+struct SceneWatcher {
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    poll_timer: f32,
+    message: Option<(String, f32)>,
+}
+
+impl SceneWatcher {
+    fn new(path: Option<&Path>) -> Self {
+        let path = path.map(Path::to_path_buf);
+        let modified = path.as_deref().and_then(modified_time);
+        Self {
+            path,
+            modified,
+            poll_timer: 0.0,
+            message: None,
+        }
+    }
+
+    /// True once per change of the file's modification time.
+    fn poll(&mut self, dt: f32) -> bool {
+        if let Some((_, remaining)) = self.message.as_mut() {
+            *remaining -= dt;
+            if *remaining <= 0.0 {
+                self.message = None;
+            }
+        }
+        self.poll_timer += dt;
+        if self.poll_timer < RELOAD_POLL_INTERVAL {
+            return false;
+        }
+        self.poll_timer = 0.0;
+        let Some(modified) = self.path.as_deref().and_then(modified_time) else {
+            return false;
+        };
+        if self.modified == Some(modified) {
+            return false;
+        }
+        self.modified = Some(modified);
+        true
+    }
+
+    /// Accepts the file's current modification time, e.g. after saving it ourselves.
+    fn sync(&mut self) {
+        self.modified = self.path.as_deref().and_then(modified_time);
+    }
+
+    fn report(&mut self, message: String) {
+        self.message = Some((message, RELOAD_MESSAGE_TIME));
+    }
+
+    fn file_name(&self) -> String {
+        self.path
+            .as_deref()
+            .and_then(Path::file_name)
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Copies running state from `old` to the body with the same name in `new`.
+fn carry_over<T>(old: &[T], new: &mut [T], name: impl Fn(&T) -> &str, copy: impl Fn(&T, &mut T)) {
+    for body in new.iter_mut() {
+        if let Some(previous) = old.iter().find(|previous| name(previous) == name(body)) {
+            copy(previous, body);
+        }
+    }
+}
+
+/// Keeps orbit and spin phases (and comet tails) of bodies that survive a reload,
+/// so editing a scene doesn't rewind the simulation. New bodies start at their
+/// authored phase.
+fn carry_over_phases(
+    planets: (&[Planet], &mut [Planet]),
+    stars: (&[Star], &mut [Star]),
+    black_holes: (&[BlackHole], &mut [BlackHole]),
+    stations: (&[Station], &mut [Station]),
+    comets: (&mut [Comet], &mut [Comet]),
+) {
+    carry_over(
+        planets.0,
+        planets.1,
+        |p| &p.name,
+        |old, new| {
+            new.orbit_angle = old.orbit_angle;
+            new.rotation = old.rotation;
+            new.precession = old.precession;
+            new.band_drift = old.band_drift;
+        },
+    );
+    carry_over(
+        stars.0,
+        stars.1,
+        |s| &s.name,
+        |old, new| {
+            new.orbit_angle = old.orbit_angle;
+            new.rotation = old.rotation;
+            new.time = old.time;
+        },
+    );
+    carry_over(
+        black_holes.0,
+        black_holes.1,
+        |h| &h.name,
+        |old, new| {
+            new.orbit_angle = old.orbit_angle;
+            new.disk_rotation = old.disk_rotation;
+        },
+    );
+    carry_over(
+        stations.0,
+        stations.1,
+        |s| &s.name,
+        |old, new| {
+            new.orbit_angle = old.orbit_angle;
+            new.spin = old.spin;
+        },
+    );
+    for comet in comets.1.iter_mut() {
+        if let Some(previous) = comets
+            .0
+            .iter_mut()
+            .find(|previous| previous.name == comet.name)
+        {
+            comet.mean_anomaly = previous.mean_anomaly;
+            comet.tail = std::mem::take(&mut previous.tail);
+        }
+    }
+    advance_simulation(
+        &mut SimBodies {
+            stars: stars.1,
+            planets: planets.1,
+            black_holes: black_holes.1,
+            stations: stations.1,
+            comets: comets.1,
+            custom: &mut [],
+        },
+        0.0,
+        false,
+    );
+}
+
+/// Displacement of a star from the system barycenter caused by the planets pulling
+/// on it: -sum(m_i * r_i) / M, with masses taken as radius cubed and planets scaled
+/// by `PLANET_DENSITY`.
+fn stellar_wobble(star: &Star, planets: &[Planet]) -> Vec3 {
+    let star_mass = star.radius.powi(3);
+    let mut moment = Vec3::ZERO;
+    for planet in planets {
+        moment += (planet.position - star.position) * (planet.radius.powi(3) * PLANET_DENSITY);
+    }
+    -moment / star_mass
+}
+
+/// Radial-velocity view: traces the star's reflex motion (exaggerated by
+/// `WOBBLE_SCALE`) and plots its velocity along the transit observer's line of
+/// sight, positive when receding.
+struct WobbleView {
+    trail: VecDeque<Vec3>,
+    velocities: VecDeque<f32>,
+    sample_timer: f32,
+    last_offset: Option<Vec3>,
+}
+
+impl WobbleView {
+    fn new() -> Self {
+        Self {
+            trail: VecDeque::with_capacity(LIGHT_CURVE_SAMPLES),
+            velocities: VecDeque::with_capacity(LIGHT_CURVE_SAMPLES),
+            sample_timer: 0.0,
+            last_offset: None,
+        }
+    }
+
+    fn record(&mut self, offset: Vec3, dt: f32) {
+        self.sample_timer += dt;
+        if self.sample_timer < LIGHT_CURVE_INTERVAL {
+            return;
+        }
+        let elapsed = self.sample_timer;
+        self.sample_timer = 0.0;
+        if let Some(last) = self.last_offset {
+            if self.velocities.len() == LIGHT_CURVE_SAMPLES {
+                self.velocities.pop_front();
+            }
+            // The transit observer sits on -Z looking towards +Z.
+            self.velocities.push_back((offset - last).z / elapsed);
+        }
+        self.last_offset = Some(offset);
+        if self.trail.len() == LIGHT_CURVE_SAMPLES {
+            self.trail.pop_front();
+        }
+        self.trail.push_back(offset);
+    }
+}
+
+fn draw_wobble_trail(
+    renderer: &mut Renderer,
+    view: &WobbleView,
+    star: &Star,
+    view_projection: &Mat4,
+) {
+    let color = Color::new(1.0, 0.9, 0.5);
+    let mut previous: Option<Vec2> = None;
+    for &offset in &view.trail {
+        let point = renderer.project_point(star.position + offset * WOBBLE_SCALE, view_projection);
+        if let (Some(prev), Some(point)) = (previous, point) {
+            renderer.draw_line(prev, point, color);
+        }
+        previous = point;
+    }
+    if let Some(point) = previous {
+        renderer.draw_line(
+            Vec2::new(point.x - 4.0, point.y),
+            Vec2::new(point.x + 4.0, point.y),
+            color,
+        );
+        renderer.draw_line(
+            Vec2::new(point.x, point.y - 4.0),
+            Vec2::new(point.x, point.y + 4.0),
+            color,
+        );
+    }
+}
+
+/// Scrolling radial-velocity curve, tinted blue while the star approaches and red
+/// while it recedes. `stacked` lifts the panel above the light curve.
+fn draw_radial_velocity(renderer: &mut Renderer, view: &WobbleView, stacked: i32) {
+    let width = LIGHT_CURVE_SAMPLES as i32;
+    let height = 80;
+    let x = (renderer.width as i32 - width) / 2;
+    let y = renderer.height as i32 - height - 24 - stacked;
+    renderer.fill_rect(
+        x - 6,
+        y - 16,
+        width + 12,
+        height + 22,
+        Color::new(0.0, 0.0, 0.0),
+        0.6,
+    );
+    let latest = view.velocities.back().copied().unwrap_or(0.0);
+    renderer.draw_text(
+        x,
+        y - 12,
+        &format!("RADIAL VELOCITY {:+.4}", latest),
+        Color::new(0.9, 0.93, 1.0),
+        1,
+    );
+    let peak = view
+        .velocities
+        .iter()
+        .fold(1e-4_f32, |peak, v| peak.max(v.abs()));
+    let mid = y as f32 + height as f32 * 0.5;
+    renderer.draw_line(
+        Vec2::new(x as f32, mid),
+        Vec2::new((x + width) as f32, mid),
+        Color::new(0.35, 0.35, 0.4),
+    );
+    let mut previous: Option<Vec2> = None;
+    for (idx, &velocity) in view.velocities.iter().enumerate() {
+        let point = Vec2::new(
+            (x + idx as i32) as f32,
+            mid - velocity / peak * height as f32 * 0.45,
+        );
+        let color = if velocity < 0.0 {
+            Color::new(0.45, 0.65, 1.0)
+        } else {
+            Color::new(1.0, 0.45, 0.4)
+        };
+        if let Some(prev) = previous {
+            renderer.draw_line(prev, point, color);
+        }
+        previous = Some(point);
+    }
+}
+
+/// Overlay color modes. The color-blind modes move hues onto the Okabe-Ito palette,
+/// which stays distinguishable for red-green deficiencies; high contrast pushes
+/// everything to saturated, full-brightness colors. All non-standard modes draw
+/// lines two pixels wide.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AccessibilityMode {
+    Standard,
+    Deuteranopia,
+    Protanopia,
+    HighContrast,
+}
+
+impl AccessibilityMode {
+    fn next(self) -> Self {
+        match self {
+            AccessibilityMode::Standard => AccessibilityMode::Deuteranopia,
+            AccessibilityMode::Deuteranopia => AccessibilityMode::Protanopia,
+            AccessibilityMode::Protanopia => AccessibilityMode::HighContrast,
+            AccessibilityMode::HighContrast => AccessibilityMode::Standard,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            AccessibilityMode::Standard => "standard",
+            AccessibilityMode::Deuteranopia => "deuteranopia",
+            AccessibilityMode::Protanopia => "protanopia",
+            AccessibilityMode::HighContrast => "high-contrast",
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        [
+            AccessibilityMode::Standard,
+            AccessibilityMode::Deuteranopia,
+            AccessibilityMode::Protanopia,
+            AccessibilityMode::HighContrast,
+        ]
+        .into_iter()
+        .find(|mode| mode.label() == text)
+    }
+
+    /// Safe colors for the red, yellow, green, blue and purple hue bands.
+    fn hue_palette(self) -> Option<[Color; 5]> {
+        let hex = |rgb: u32| Color::from_u32(rgb);
+        match self {
+            AccessibilityMode::Standard => None,
+            AccessibilityMode::Deuteranopia => Some([
+                hex(0xD55E00),
+                hex(0xF0E442),
+                hex(0x56B4E9),
+                hex(0x0072B2),
+                hex(0xCC79A7),
+            ]),
+            AccessibilityMode::Protanopia => Some([
+                hex(0xE69F00),
+                hex(0xF0E442),
+                hex(0x009E73),
+                hex(0x56B4E9),
+                hex(0xCC79A7),
+            ]),
+            AccessibilityMode::HighContrast => Some([
+                Color::new(1.0, 0.55, 0.0),
+                Color::new(1.0, 1.0, 0.0),
+                Color::new(0.0, 1.0, 0.4),
+                Color::new(0.0, 0.9, 1.0),
+                Color::new(1.0, 0.3, 1.0),
+            ]),
+        }
+    }
+
+    fn remap(self, color: Color) -> Color {
+        let Some(palette) = self.hue_palette() else {
+            return color;
+        };
+        let max = color.r.max(color.g).max(color.b);
+        let min = color.r.min(color.g).min(color.b);
+        if max <= 0.0 || (max - min) / max < 0.2 {
+            // Greys: keep them, but lift light greys to white in high contrast.
+            return if self == AccessibilityMode::HighContrast && max > 0.3 {
+                Color::new(1.0, 1.0, 1.0)
+            } else {
+                color
+            };
+        }
+        let delta = max - min;
+        let hue = if max == color.r {
+            60.0 * ((color.g - color.b) / delta).rem_euclid(6.0)
+        } else if max == color.g {
+            60.0 * ((color.b - color.r) / delta + 2.0)
+        } else {
+            60.0 * ((color.r - color.g) / delta + 4.0)
+        };
+        let band = match hue {
+            h if !(20.0..330.0).contains(&h) => 0,
+            h if h < 70.0 => 1,
+            h if h < 170.0 => 2,
+            h if h < 260.0 => 3,
+            _ => 4,
+        };
+        let safe = palette[band];
+        if self == AccessibilityMode::HighContrast {
+            return safe;
+        }
+        let safe_max = safe.r.max(safe.g).max(safe.b);
+        safe * (max.max(0.5) / safe_max)
+    }
+
+    /// Orbit colors are assigned by index instead of by hue, so neighbouring orbits
+    /// never collapse onto the same safe color.
+    fn orbit_color(self, index: usize) -> Option<Color> {
+        let palette = self.hue_palette()?;
+        let order = [1, 3, 0, 2, 4];
+        Some(palette[order[index % order.len()]])
+    }
+
+    /// Dark panels become nearly opaque in high contrast so text stands out.
+    fn panel_alpha(self, color: Color, alpha: f32) -> f32 {
+        let dark = color.r.max(color.g).max(color.b) < 0.1;
+        if self == AccessibilityMode::HighContrast && dark {
+            alpha.max(0.9)
+        } else {
+            alpha
+        }
+    }
+}
+
+/// Viewpoints stored with `Ctrl+1..9`, one per digit, kept across runs in
+/// `BOOKMARKS_PATH` as `[bookmark N]` sections with the fields of a `[shot]`.
+struct Bookmarks {
+    slots: [Option<CameraShot>; 9],
+}
+
+impl Bookmarks {
+    fn load(path: &Path) -> Self {
+        let mut bookmarks = Bookmarks {
+            slots: Default::default(),
+        };
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return bookmarks;
+        };
+        let doc = match parse_scene(&text) {
+            Ok(doc) => doc,
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                return bookmarks;
+            }
+        };
+        for section in doc.sections.iter().filter(|s| s.kind == "bookmark") {
+            let slot = match section.name.parse::<usize>() {
+                Ok(number @ 1..=9) => number - 1,
+                _ => {
+                    eprintln!(
+                        "{}: {}",
+                        path.display(),
+                        section.error("bookmarks are numbered 1 to 9")
+                    );
+                    continue;
+                }
+            };
+            match CameraShot::from_section(section) {
+                Ok(shot) => bookmarks.slots[slot] = Some(Self::named(shot, slot)),
+                Err(err) => eprintln!("{}: {}", path.display(), err),
+            }
+        }
+        bookmarks
+    }
+
+    fn save(&self, path: &Path) -> std::io::Result<()> {
+        let sections = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, shot)| {
+                let shot = shot.as_ref()?;
+                Some(
+                    SceneSection::new("bookmark", &(slot + 1).to_string())
+                        .field("position", shot.position)
+                        .field("yaw", shot.yaw)
+                        .field("pitch", shot.pitch)
+                        .field("fov", shot.fov),
+                )
+            })
+            .collect();
+        std::fs::write(path, SceneDocument { sections }.to_text())
+    }
+
+    fn store(&mut self, slot: usize, camera: &Camera) {
+        let shot = CameraShot {
+            name: String::new(),
+            position: camera.position,
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+            look_at: None,
+            fov: camera.fov,
+            time: None,
+            hold: SHOT_HOLD,
+        };
+        self.slots[slot] = Some(Self::named(shot, slot));
+    }
+
+    fn get(&self, slot: usize) -> Option<&CameraShot> {
+        self.slots.get(slot)?.as_ref()
+    }
+
+    /// Shot names show up as the warp destination on the flight HUD.
+    fn named(shot: CameraShot, slot: usize) -> CameraShot {
+        CameraShot {
+            name: format!("bookmark {}", slot + 1),
+            ..shot
+        }
+    }
+}
+
+/// User preferences kept across runs in `SETTINGS_PATH`, using the scene file syntax.
+struct Settings {
+    accessibility: AccessibilityMode,
+    /// Simulation ticks per second, one of `TICK_RATES`.
+    tick_rate: usize,
+    /// Draw bodies part-way between the last two ticks instead of at the latest one.
+    interpolate: bool,
+    /// Most ticks run in one frame; time beyond that is dropped and the simulation slows.
+    max_catch_up: usize,
+    shading: ShadingQuality,
+    /// Camera shake strength; 0 turns it off.
+    shake: f32,
+    /// Top-down map of the system in the top-left corner.
+    minimap: bool,
+}
+
+impl Settings {
+    fn load(path: &Path) -> Self {
+        let mut settings = Settings {
+            accessibility: AccessibilityMode::Standard,
+            tick_rate: TICK_RATES[0],
+            interpolate: true,
+            max_catch_up: DEFAULT_MAX_CATCH_UP,
+            shading: ShadingQuality::PerPixel,
+            shake: 1.0,
+            minimap: true,
+        };
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return settings;
+        };
+        let doc = match parse_scene(&text) {
+            Ok(doc) => doc,
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                return settings;
+            }
+        };
+        let Some(section) = doc.find("settings", "") else {
+            return settings;
+        };
+        if let Some(value) = section.get("accessibility") {
+            match AccessibilityMode::parse(value) {
+                Some(mode) => settings.accessibility = mode,
+                None => eprintln!("{}: unknown accessibility mode `{}`", path.display(), value),
+            }
+        }
+        if let Some(value) = section.get("tick_rate") {
+            match value.parse().ok().filter(|rate| TICK_RATES.contains(rate)) {
+                Some(rate) => settings.tick_rate = rate,
+                None => eprintln!(
+                    "{}: `tick_rate` must be one of {:?}, got `{}`",
+                    path.display(),
+                    TICK_RATES,
+                    value
+                ),
+            }
+        }
+        if let Some(value) = section.get("interpolate") {
+            match value {
+                "on" => settings.interpolate = true,
+                "off" => settings.interpolate = false,
+                _ => eprintln!(
+                    "{}: `interpolate` must be `on` or `off`, got `{}`",
+                    path.display(),
+                    value
+                ),
+            }
+        }
+        if let Some(value) = section.get("max_catch_up") {
+            match value.parse().ok().filter(|&steps| steps > 0) {
+                Some(steps) => settings.max_catch_up = steps,
+                None => eprintln!(
+                    "{}: `max_catch_up` must be a positive whole number, got `{}`",
+                    path.display(),
+                    value
+                ),
+            }
+        }
+        if let Some(value) = section.get("shading") {
+            match ShadingQuality::parse(value) {
+                Some(shading) => settings.shading = shading,
+                None => eprintln!(
+                    "{}: `shading` must be pixel, adaptive or vertex, got `{}`",
+                    path.display(),
+                    value
+                ),
+            }
+        }
+        if let Some(value) = section.get("shake") {
+            match value.parse::<f32>().ok().filter(|shake| *shake >= 0.0) {
+                Some(shake) => settings.shake = shake,
+                None => eprintln!(
+                    "{}: `shake` must be a number >= 0, got `{}`",
+                    path.display(),
+                    value
+                ),
+            }
+        }
+        if let Some(value) = section.get("minimap") {
+            match value {
+                "on" => settings.minimap = true,
+                "off" => settings.minimap = false,
+                _ => eprintln!(
+                    "{}: `minimap` must be `on` or `off`, got `{}`",
+                    path.display(),
+                    value
+                ),
+            }
+        }
+        settings
+    }
+
+    fn save(&self, path: &Path) -> std::io::Result<()> {
+        let doc = SceneDocument {
+            sections: vec![SceneSection::new("settings", "")
+                .field("accessibility", self.accessibility.label())
+                .field("tick_rate", self.tick_rate)
+                .field("interpolate", self.interpolate)
+                .field("max_catch_up", self.max_catch_up)
+                .field("shading", self.shading.label())
+                .field("shake", self.shake)
+                .field("minimap", self.minimap)],
+        };
+        std::fs::write(path, doc.to_text())
+    }
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Spends frame time on the simulation in whole ticks of `1 / tick_rate` seconds. The
+/// remainder carries over to the next frame; beyond `max_catch_up` ticks in one frame
+/// the extra time is dropped, so a slow frame slows the simulation instead of
+/// making the next one slower still.
+struct SimClock {
+    tick: f32,
+    accumulator: f32,
+    max_catch_up: usize,
+}
+
+impl SimClock {
+    fn new(settings: &Settings) -> Self {
+        Self {
+            tick: 1.0 / settings.tick_rate as f32,
+            accumulator: 0.0,
+            max_catch_up: settings.max_catch_up,
+        }
+    }
+
+    /// Number of ticks to run for `dt` seconds of frame time.
+    fn ticks(&mut self, dt: f32) -> usize {
+        self.accumulator += dt;
+        let ticks = (self.accumulator / self.tick) as usize;
+        if ticks > self.max_catch_up {
+            self.accumulator = 0.0;
+            return self.max_catch_up;
+        }
+        self.accumulator -= ticks as f32 * self.tick;
+        ticks
+    }
+
+    /// How far the frame falls between the last tick and the next one, in `[0, 1)`.
+    fn alpha(&self) -> f32 {
+        (self.accumulator / self.tick).clamp(0.0, 1.0)
+    }
+}
+
+/// Every body's position and transforms, flattened in a fixed order, so a frame can be
+/// drawn part-way between two simulation ticks.
+#[derive(PartialEq)]
+struct BodyPoses {
+    positions: Vec<Vec3>,
+    transforms: Vec<Mat4>,
+}
+
+impl BodyPoses {
+    fn capture(
+        planets: &mut [Planet],
+        stars: &mut [Star],
+        black_holes: &mut [BlackHole],
+        stations: &mut [Station],
+        comets: &mut [Comet],
+    ) -> Self {
+        let (positions, transforms) = body_poses(planets, stars, black_holes, stations, comets);
+        Self {
+            positions: positions.into_iter().map(|p| *p).collect(),
+            transforms: transforms.into_iter().map(|m| *m).collect(),
+        }
+    }
+
+    /// The poses `t` of the way from these to `next`. Transforms are blended entry by
+    /// entry, which is close enough to a true rotation for the angle one tick turns.
+    fn blend(&self, next: &BodyPoses, t: f32) -> Self {
+        Self {
+            positions: self
+                .positions
+                .iter()
+                .zip(&next.positions)
+                .map(|(&a, &b)| Vec3::lerp(a, b, t))
+                .collect(),
+            transforms: self
+                .transforms
+                .iter()
+                .zip(&next.transforms)
+                .map(|(a, b)| Mat4::lerp(a, b, t))
+                .collect(),
+        }
+    }
+
+    fn apply(
+        &self,
+        planets: &mut [Planet],
+        stars: &mut [Star],
+        black_holes: &mut [BlackHole],
+        stations: &mut [Station],
+        comets: &mut [Comet],
+    ) {
+        let (positions, transforms) = body_poses(planets, stars, black_holes, stations, comets);
+        for (position, pose) in positions.into_iter().zip(&self.positions) {
+            *position = *pose;
+        }
+        for (transform, pose) in transforms.into_iter().zip(&self.transforms) {
+            *transform = *pose;
+        }
+    }
+}
+
+/// Borrows the position and transforms of every body that the simulation moves.
+fn body_poses<'a>(
+    planets: &'a mut [Planet],
+    stars: &'a mut [Star],
+    black_holes: &'a mut [BlackHole],
+    stations: &'a mut [Station],
+    comets: &'a mut [Comet],
+) -> (Vec<&'a mut Vec3>, Vec<&'a mut Mat4>) {
+    let mut positions = Vec::new();
+    let mut transforms = Vec::new();
+    for planet in planets {
+        positions.push(&mut planet.position);
+        transforms.push(&mut planet.transform);
+        if let Some(ring) = planet.ring.as_mut() {
+            transforms.push(&mut ring.transform);
+        }
+    }
+    for star in stars {
+        positions.push(&mut star.position);
+        transforms.push(&mut star.transform);
+    }
+    for hole in black_holes {
+        positions.push(&mut hole.position);
+        transforms.push(&mut hole.transform);
+        transforms.push(&mut hole.disk.transform);
+    }
+    for station in stations {
+        positions.push(&mut station.position);
+        transforms.push(&mut station.transform);
+    }
+    for comet in comets {
+        positions.push(&mut comet.position);
+        transforms.push(&mut comet.transform);
+    }
+    (positions, transforms)
+}
+
+/// Steps every moving body forward by `dt` seconds.
+fn advance_simulation(bodies: &mut SimBodies, dt: f32, physics: bool) {
+    for body in bodies.iter_mut() {
+        body.update(dt);
+    }
+    place_scene_graph(bodies.planets, bodies.stations);
+    update_comet_tails(bodies.comets, bodies.stars, dt, physics);
+}
+
+/// Runs the simulation `time` seconds ahead in small fixed steps, so comets integrate
+/// the same way they would have frame by frame.
+fn fast_forward(bodies: &mut SimBodies, time: f32, physics: bool) {
+    // Freshly built bodies only get positions and transforms on their first update.
+    advance_simulation(bodies, 0.0, physics);
+    let mut elapsed = 0.0;
+    while elapsed < time {
+        let step = (time - elapsed).min(SIM_FAST_FORWARD_STEP);
+        advance_simulation(bodies, step, physics);
+        elapsed += step;
+    }
+}
+
+/// Moves each of `bodies` along on its own by `time` seconds, in the steps
+/// `fast_forward` takes. Anything that depends on other bodies, such as placement
+/// under a parent or comet tails, is left to the caller.
+fn fast_forward_bodies<'a>(bodies: impl Iterator<Item = &'a mut dyn CelestialBody>, time: f32) {
+    for body in bodies {
+        body.update(0.0);
+        let mut elapsed = 0.0;
+        while elapsed < time {
+            let step = (time - elapsed).min(SIM_FAST_FORWARD_STEP);
+            body.update(step);
+            elapsed += step;
+        }
+    }
+}
+
+/// Transform hierarchy the planets and stations hang from. A node's world transform
+/// is its parent's times its own local one, so a moon's orbit rides along with its
+/// planet, a ring with the planet's tilted spin and a station with whatever it
+/// circles. Nodes are added parents first, which lets one pass settle the tree.
+#[derive(Default)]
+struct SceneGraph {
+    nodes: Vec<SceneNode>,
+}
+
+struct SceneNode {
+    parent: Option<usize>,
+    local: Mat4,
+    world: Mat4,
+}
+
+impl SceneGraph {
+    fn add(&mut self, parent: Option<usize>, local: Mat4) -> usize {
+        debug_assert!(parent.is_none_or(|parent| parent < self.nodes.len()));
+        self.nodes.push(SceneNode {
+            parent,
+            local,
+            world: local,
+        });
+        self.nodes.len() - 1
+    }
+
+    fn propagate(&mut self) {
+        for idx in 0..self.nodes.len() {
+            if let Some(parent) = self.nodes[idx].parent {
+                self.nodes[idx].world = self.nodes[parent].world * self.nodes[idx].local;
+            }
+        }
+    }
+
+    fn world(&self, node: usize) -> Mat4 {
+        self.nodes[node].world
+    }
+
+    fn origin(&self, node: usize) -> Vec3 {
+        (self.nodes[node].world * Vec4::new(0.0, 0.0, 0.0, 1.0)).xyz()
+    }
+
+    /// Origin of the node's parent, or the world origin for a root.
+    fn parent_origin(&self, node: usize) -> Vec3 {
+        self.nodes[node]
+            .parent
+            .map_or(Vec3::ZERO, |parent| self.origin(parent))
+    }
+}
+
+/// Index of the planet `name` refers to, if any.
+fn planet_named(planets: &[Planet], name: Option<&str>) -> Option<usize> {
+    name.and_then(|name| planets.iter().position(|planet| planet.name == name))
+}
+
+/// Planet indices with every parent ahead of its children. A parent that names no
+/// planet, or a loop of parents, leaves the planet at the root.
+fn planet_order(planets: &[Planet]) -> Vec<(usize, Option<usize>)> {
+    let parents: Vec<Option<usize>> = planets
+        .iter()
+        .map(|planet| planet_named(planets, planet.parent.as_deref()))
+        .collect();
+    let depth = |idx: usize| {
+        let mut depth = 0;
+        let mut current = parents[idx];
+        while let Some(parent) = current {
+            depth += 1;
+            if depth > planets.len() {
+                return None;
+            }
+            current = parents[parent];
+        }
+        Some(depth)
+    };
+    let mut order: Vec<(usize, Option<usize>, usize)> = (0..planets.len())
+        .map(|idx| match depth(idx) {
+            Some(depth) => (idx, parents[idx], depth),
+            None => (idx, None, 0),
+        })
+        .collect();
+    order.sort_by_key(|&(_, _, depth)| depth);
+    order
+        .into_iter()
+        .map(|(idx, parent, _)| (idx, parent))
+        .collect()
+}
+
+/// Orbit and spin node of a body in a `SceneGraph`.
+type BodyNodes = (usize, usize);
+
+/// `SceneGraph` of the planets and stations at their current angles, built from
+/// their parents: orbit nodes carry the position, spin nodes under them the rotation
+/// and tilt that the mesh and ring share. Returns it with the orbit and spin node of
+/// each planet and each station, by index.
+fn body_scene_graph(
+    planets: &[Planet],
+    stations: &[Station],
+) -> (SceneGraph, Vec<BodyNodes>, Vec<BodyNodes>) {
+    let mut graph = SceneGraph::default();
+    let mut planet_nodes = vec![(0, 0); planets.len()];
+    for (idx, parent) in planet_order(planets) {
+        let planet = &planets[idx];
+        let orbit = graph.add(
+            parent.map(|parent| planet_nodes[parent].0),
+            Mat4::translation(planet.current_offset()),
+        );
+        let spin = graph.add(Some(orbit), planet.spin_frame());
+        planet_nodes[idx] = (orbit, spin);
+    }
+    let station_nodes = stations
+        .iter()
+        .map(|station| {
+            let parent = planet_named(planets, station.parent.as_deref());
+            let orbit = graph.add(
+                parent.map(|parent| planet_nodes[parent].0),
+                Mat4::translation(Vec3::new(
+                    station.orbit_angle.cos() * station.orbit_radius,
+                    station.height,
+                    station.orbit_angle.sin() * station.orbit_radius,
+                )),
+            );
+            (
+                orbit,
+                graph.add(Some(orbit), Mat4::rotation_y(station.spin)),
+            )
+        })
+        .collect();
+    graph.propagate();
+    (graph, planet_nodes, station_nodes)
+}
+
+/// Places the planets and stations by walking their `body_scene_graph`. Stars, black
+/// holes and comets never have a parent, so they place themselves in `update`.
+fn place_scene_graph(planets: &mut [Planet], stations: &mut [Station]) {
+    let (graph, planet_nodes, station_nodes) = body_scene_graph(planets, stations);
+    for (planet, &(orbit, spin)) in planets.iter_mut().zip(&planet_nodes) {
+        // The parent's orbit node, when there is one, sits right above this one.
+        planet.orbit_center = graph.parent_origin(orbit);
+        planet.position = graph.origin(orbit);
+        planet.transform = graph.world(spin) * Mat4::scale(Vec3::splat(planet.radius));
+        if let Some(ring) = planet.ring.as_mut() {
+            ring.transform = graph.world(spin);
+        }
+    }
+    hang_stations(&graph, &station_nodes, stations);
+}
+
+/// Places only the stations, leaving the planets they hang from as they are.
+fn place_stations(planets: &[Planet], stations: &mut [Station]) {
+    let (graph, _, station_nodes) = body_scene_graph(planets, stations);
+    hang_stations(&graph, &station_nodes, stations);
+}
+
+fn hang_stations(graph: &SceneGraph, nodes: &[BodyNodes], stations: &mut [Station]) {
+    for (station, &(orbit, spin)) in stations.iter_mut().zip(nodes) {
+        station.position = graph.origin(orbit);
+        station.transform = graph.world(spin) * Mat4::scale(Vec3::splat(station.scale));
+    }
+}
+
+/// Runs the `script` of each planet and station once per simulation tick, after the
+/// bodies have moved. A script sees `time`, `dt`, its body's position `x`/`y`/`z`,
+/// `radius` and `camera_distance` (from the surface), and may set `offset_x`/`_y`/`_z`
+/// to displace the body along with everything orbiting it, `glow` for extra emissive
+/// light and `message` to show a line on screen. The map `state` keeps whatever the
+/// script stores in it between ticks.
+#[cfg(feature = "scripting")]
+struct ScriptHost {
+    engine: rhai::Engine,
+    /// By body name. A script that failed keeps its entry without an AST, so the error
+    /// is shown once; editing the source compiles it again.
+    scripts: HashMap<String, BodyScript>,
+}
+
+#[cfg(feature = "scripting")]
+struct BodyScript {
+    source: String,
+    ast: Option<rhai::AST>,
+    state: rhai::Map,
+}
+
+/// What a script did to its body this tick.
+#[cfg(feature = "scripting")]
+struct ScriptEffect {
+    offset: Vec3,
+    glow: f32,
+    message: Option<String>,
+}
+
+#[cfg(feature = "scripting")]
+impl ScriptHost {
+    fn new() -> Self {
+        let mut engine = rhai::Engine::new();
+        // Scripts run every tick; a runaway loop should fail rather than hang the frame.
+        engine.set_max_operations(SCRIPT_MAX_OPERATIONS);
+        Self {
+            engine,
+            scripts: HashMap::new(),
+        }
+    }
+
+    fn run(
+        &mut self,
+        planets: &mut [Planet],
+        stations: &mut [Station],
+        camera: Vec3,
+        time: f32,
+        dt: f32,
+    ) -> Vec<String> {
+        let mut messages = Vec::new();
+        let mut seen = Vec::new();
+        // How far each planet was displaced, its parents' offsets included, so whatever
+        // orbits it moves along at any depth. Parents come first, so a script sees its
+        // body where its parents' scripts left it.
+        let mut shifts = vec![Vec3::ZERO; planets.len()];
+        for (idx, parent) in planet_order(planets) {
+            let planet = &mut planets[idx];
+            let mut shift = parent.map_or(Vec3::ZERO, |parent| shifts[parent]);
+            planet.orbit_center += shift;
+            if let Some(source) = &planet.script {
+                seen.push(planet.name.clone());
+                let inputs = (planet.position + shift, planet.radius, camera, time, dt);
+                match self.evaluate(&planet.name, source, inputs) {
+                    Ok(Some(effect)) => {
+                        shift += effect.offset;
+                        planet.glow = effect.glow;
+                        messages.extend(effect.message);
+                    }
+                    Ok(None) => {}
+                    Err(err) => messages.push(err),
+                }
+            }
+            if shift != Vec3::ZERO {
+                let translation = Mat4::translation(shift);
+                planet.position += shift;
+                planet.transform = translation * planet.transform;
+                if let Some(ring) = planet.ring.as_mut() {
+                    ring.transform = translation * ring.transform;
+                }
+            }
+            shifts[idx] = shift;
+        }
+        for station in stations.iter_mut() {
+            let mut shift = planet_named(planets, station.parent.as_deref())
+                .map_or(Vec3::ZERO, |parent| shifts[parent]);
+            if let Some(source) = &station.script {
+                seen.push(station.name.clone());
+                let radius = station.collision_radius * station.scale;
+                let inputs = (station.position + shift, radius, camera, time, dt);
+                match self.evaluate(&station.name, source, inputs) {
+                    Ok(Some(effect)) => {
+                        shift += effect.offset;
+                        station.glow = effect.glow;
+                        messages.extend(effect.message);
+                    }
+                    Ok(None) => {}
+                    Err(err) => messages.push(err),
+                }
+            }
+            if shift != Vec3::ZERO {
+                station.position += shift;
+                station.transform = Mat4::translation(shift) * station.transform;
+            }
+        }
+        self.scripts.retain(|name, _| seen.contains(name));
+        messages
+    }
+
+    /// `None` once the script has failed, until its source changes.
+    fn evaluate(
+        &mut self,
+        name: &str,
+        source: &str,
+        (position, radius, camera, time, dt): (Vec3, f32, Vec3, f32, f32),
+    ) -> Result<Option<ScriptEffect>, String> {
+        let engine = &self.engine;
+        let script = self
+            .scripts
+            .entry(name.to_string())
+            .or_insert_with(|| BodyScript {
+                source: String::new(),
+                ast: None,
+                state: rhai::Map::new(),
+            });
+        if script.source != source {
+            script.source = source.to_string();
+            script.state.clear();
+            script.ast = None;
+            script.ast = Some(
+                engine
+                    .compile(source)
+                    .map_err(|err| format!("script {}: {}", name, err))?,
+            );
+        }
+        let Some(ast) = &script.ast else {
+            return Ok(None);
+        };
+        let mut scope = rhai::Scope::new();
+        scope
+            .push("time", time as rhai::FLOAT)
+            .push("dt", dt as rhai::FLOAT)
+            .push("x", position.x as rhai::FLOAT)
+            .push("y", position.y as rhai::FLOAT)
+            .push("z", position.z as rhai::FLOAT)
+            .push("radius", radius as rhai::FLOAT)
+            .push(
+                "camera_distance",
+                ((camera - position).length() - radius).max(0.0) as rhai::FLOAT,
+            )
+            .push("offset_x", 0.0 as rhai::FLOAT)
+            .push("offset_y", 0.0 as rhai::FLOAT)
+            .push("offset_z", 0.0 as rhai::FLOAT)
+            .push("glow", 0.0 as rhai::FLOAT)
+            .push("message", String::new())
+            .push("state", std::mem::take(&mut script.state));
+        let result = engine.run_ast_with_scope(&mut scope, ast);
+        script.state = scope.get_value("state").unwrap_or_default();
+        if let Err(err) = result {
+            script.ast = None;
+            return Err(format!("script {}: {}", name, err));
+        }
+        let float = |key: &str| scope.get_value::<rhai::FLOAT>(key).unwrap_or(0.0) as f32;
+        let message = scope.get_value::<String>("message").unwrap_or_default();
+        Ok(Some(ScriptEffect {
+            offset: Vec3::new(float("offset_x"), float("offset_y"), float("offset_z")),
+            glow: float("glow").max(0.0),
+            message: (!message.is_empty()).then_some(message),
+        }))
+    }
+}
+
+/// Turns `angle` by `rate * dt` and wraps it into 0..TAU; rates may be negative
+/// for retrograde motion.
+fn advance_angle(angle: f32, rate: f32, dt: f32) -> f32 {
+    (angle + rate * dt).rem_euclid(TAU)
+}
+
+/// Solves Kepler's equation `E - e sin E = M` for the eccentric anomaly.
+fn eccentric_anomaly(mean_anomaly: f32, eccentricity: f32) -> f32 {
+    let mut e_anomaly = if eccentricity > 0.8 { PI } else { mean_anomaly };
+    for _ in 0..8 {
+        let f = e_anomaly - eccentricity * e_anomaly.sin() - mean_anomaly;
+        e_anomaly -= f / (1.0 - eccentricity * e_anomaly.cos());
+    }
+    e_anomaly
+}
+
+/// Acceleration on a dust grain from the light of every star, pointing away from
+/// each one and falling off with the inverse square of the distance. `beta` is the
+/// grain's pressure-to-gravity ratio: small grains have a larger beta and drift further.
+fn radiation_acceleration(position: Vec3, beta: f32, stars: &[Star]) -> Vec3 {
+    let mut acceleration = Vec3::ZERO;
+    for star in stars {
+        let away = position - star.position;
+        let distance_sq = away.length_squared().max(star.radius * star.radius);
+        acceleration +=
+            away.normalized() * (RADIATION_PRESSURE * beta * star.light_intensity / distance_sq);
+    }
+    acceleration
+}
+
+/// Ages and spawns tail particles around the comets' current positions.
+///
+/// With `physics` on, tail grains leave the nucleus with the comet's own orbital
+/// velocity and are then pushed out by `radiation_acceleration`, which curves the
+/// tail behind the orbit. Otherwise they are simply launched anti-sunward.
+fn update_comet_tails(comets: &mut [Comet], stars: &[Star], dt: f32, physics: bool) {
+    for comet in comets.iter_mut() {
+        // The tail streams away from the nearest star and grows with the inverse
+        // square of the distance, so it is longest around perihelion.
+        let Some(star) = stars.iter().min_by(|a, b| {
+            let da = (a.position - comet.position).length_squared();
+            let db = (b.position - comet.position).length_squared();
+            da.total_cmp(&db)
+        }) else {
+            continue;
+        };
+        let away = comet.position - star.position;
+        let distance = away.length().max(0.001);
+        let away_dir = away / distance;
+        let activity = (comet.perihelion / distance).powi(2).min(1.0);
+
+        for particle in comet.tail.iter_mut() {
+            particle.age += dt;
+            if physics {
+                particle.velocity +=
+                    radiation_acceleration(particle.position, particle.beta, stars) * dt;
+            }
+            particle.position += particle.velocity * dt;
+        }
+        comet.tail.retain(|particle| particle.age < particle.life);
+
+        comet.spawn_accumulator += COMET_SPAWN_RATE * activity.max(0.1) * dt;
+        while comet.spawn_accumulator >= 1.0 {
+            comet.spawn_accumulator -= 1.0;
+            let jitter = Vec3::new(
+                comet.rng.next_f32() - 0.5,
+                comet.rng.next_f32() - 0.5,
+                comet.rng.next_f32() - 0.5,
+            );
+            let speed = COMET_TAIL_SPEED * activity * (0.6 + comet.rng.next_f32() * 0.4);
+            let spread = jitter * (1.5 * activity + 0.3);
+            let velocity = if physics {
+                comet.velocity + away_dir * (speed * 0.2) + spread
+            } else {
+                away_dir * speed + spread
+            };
+            comet.tail.push(TailParticle {
+                position: comet.position + jitter * comet.nucleus_radius,
+                velocity,
+                age: 0.0,
+                life: COMET_PARTICLE_LIFE * (0.7 + comet.rng.next_f32() * 0.3),
+                beta: 0.3 + comet.rng.next_f32() * 0.7,
+            });
+        }
+    }
+}
+
+/// Frame state a body draws itself with.
+#[derive(Clone, Copy)]
+struct BodyContext<'a> {
+    /// Sphere mesh for round bodies; planets get one tessellated for their size on screen.
+    sphere: &'a Mesh,
+    stars: &'a [Star],
+    tag_filter: TagFilter,
+    ring_style: RingStyle,
+    /// Brightness a parent body passes down to its parts, such as a planet's ring.
+    highlight: f32,
+    wireframe: bool,
+    aurora_color: Color,
+}
+
+/// A kind of body in the star system. The simulation and render loops, collisions
+/// and warps only go through this trait, so a new kind of body joins the scene by
+/// implementing it and being boxed into the custom bodies. A body made of parts
+/// drives them through it too, as a planet does its ring. Placement that depends on
+/// other bodies, such as parents in the scene graph or comet tails streaming from
+/// the stars, stays in the systems that see them all.
+trait CelestialBody {
+    /// Advances the body's own motion by `dt` seconds; 0 just places it.
+    fn update(&mut self, dt: f32);
+    fn render_instances<'a>(
+        &'a self,
+        context: &BodyContext<'a>,
+        instances: &mut Vec<RenderInstance<'a>>,
+    );
+    fn warp_anchor(&self) -> Option<WarpAnchor<'_>> {
+        None
+    }
+    fn collider(&self) -> Option<Collider> {
+        None
+    }
+    /// Sphere holding everything the body draws, so it can be skipped when an
+    /// occluder hides it; without one the body is always drawn.
+    fn bounding_sphere(&self) -> Option<(Vec3, f32)> {
+        None
+    }
+    /// Opaque sphere that hides whatever lies wholly behind it.
+    fn occluder(&self) -> Option<(Vec3, f32)> {
+        None
+    }
+    /// Mesh drawn this frame in place of `BodyContext::sphere`, such as the
+    /// icosphere a planet's scene asks for or a sphere tessellated for its size on
+    /// screen.
+    fn sphere_mesh(&self, _view_projection: &Mat4, _viewport: (f32, f32)) -> Option<Cow<'_, Mesh>> {
+        None
+    }
+}
+
+/// Sphere the camera is pushed out of.
+#[derive(Clone, Copy)]
+struct Collider {
+    center: Vec3,
+    radius: f32,
+}
+
+/// Where a warp to the body ends, the name it is listed under and the sphere it
+/// frames on arrival.
+#[derive(Clone, Copy)]
+struct WarpAnchor<'a> {
+    name: &'a str,
+    anchor: Vec3,
+    center: Vec3,
+    radius: f32,
+}
+
+/// Chains every list of bodies into one iterator of `$body` trait objects, taking
+/// each element with `$iter` and unboxing custom bodies with `$unbox`. This is the
+/// one place that names the built-in kinds; the order is the one warp targets list
+/// them in and the simulation and draw loops visit them.
+macro_rules! chain_bodies {
+    (
+        $iter:ident,
+        $unbox:ident,
+        $body:ty,
+        $stars:expr,
+        $planets:expr,
+        $holes:expr,
+        $stations:expr,
+        $comets:expr,
+        $custom:expr
+    ) => {
+        $stars
+            .$iter()
+            .map(|star| star as $body)
+            .chain($planets.$iter().map(|planet| planet as $body))
+            .chain($holes.$iter().map(|hole| hole as $body))
+            .chain($stations.$iter().map(|station| station as $body))
+            .chain($comets.$iter().map(|comet| comet as $body))
+            .chain($custom.$iter().map(|body| body.$unbox() as $body))
+    };
+}
+
+/// Every body of the scene, built-in and custom.
+fn celestial_bodies<'a>(
+    stars: &'a [Star],
+    planets: &'a [Planet],
+    black_holes: &'a [BlackHole],
+    stations: &'a [Station],
+    comets: &'a [Comet],
+    custom: &'a [Box<dyn CelestialBody>],
+) -> impl Iterator<Item = &'a dyn CelestialBody> {
+    chain_bodies!(
+        iter,
+        as_ref,
+        &dyn CelestialBody,
+        stars,
+        planets,
+        black_holes,
+        stations,
+        comets,
+        custom
+    )
+}
+
+/// The scene's bodies borrowed for the simulation to move. The built-in kinds keep
+/// their own lists for the editors and effects that know them; a body of any other
+/// kind goes in `custom` and is only ever reached through `CelestialBody`.
+struct SimBodies<'a> {
+    stars: &'a mut [Star],
+    planets: &'a mut [Planet],
+    black_holes: &'a mut [BlackHole],
+    stations: &'a mut [Station],
+    comets: &'a mut [Comet],
+    custom: &'a mut [Box<dyn CelestialBody>],
+}
+
+impl SimBodies<'_> {
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut dyn CelestialBody> {
+        chain_bodies!(
+            iter_mut,
+            as_mut,
+            &mut dyn CelestialBody,
+            self.stars,
+            self.planets,
+            self.black_holes,
+            self.stations,
+            self.comets,
+            self.custom
+        )
+    }
+}
+
+/// Pushes `position` out of every collider it entered and returns the longest push.
+fn apply_collisions<'a>(
+    bodies: impl Iterator<Item = &'a dyn CelestialBody>,
+    position: &mut Vec3,
+) -> f32 {
+    let mut deepest: f32 = 0.0;
+    for collider in bodies.filter_map(|body| body.collider()) {
+        let to_camera = *position - collider.center;
+        let dist = to_camera.length();
+        if dist < collider.radius {
+            let push_dir = if dist < 0.001 {
+                Vec3::new(0.0, 1.0, 0.0)
+            } else {
+                to_camera / dist
+            };
+            *position = collider.center + push_dir * collider.radius;
+            deepest = deepest.max(collider.radius - dist);
+        }
+    }
+    deepest
+}
+
+/// One warp target per body with a `WarpAnchor`, in the order of `bodies`. Those
+/// that are one of `planets` carry its index, for the editors that work on planets;
+/// they are matched by identity since a station may share a planet's name.
+fn collect_warp_targets<'a>(
+    bodies: impl Iterator<Item = &'a dyn CelestialBody>,
+    planets: &[Planet],
+) -> Vec<WarpTarget> {
+    bodies
+        .filter_map(|body| {
+            let anchor = body.warp_anchor()?;
+            Some(WarpTarget {
+                name: anchor.name.to_string(),
+                anchor: anchor.anchor,
+                center: anchor.center,
+                radius: anchor.radius,
+                planet: planets
+                    .iter()
+                    .position(|planet| std::ptr::addr_eq(planet, body)),
+            })
+        })
+        .collect()
+}
+
+/// What a click in the view landed on.
+enum Pick {
+    Target(usize),
+    Ship,
+}
+
+/// The nearest of `targets` or the ship under the unit-length ray. Stations and the
+/// ship are hit against their triangles, so a click through a gap in one reaches
+/// whatever is behind; other bodies are hit as their spheres.
+fn pick_body(
+    origin: Vec3,
+    direction: Vec3,
+    targets: &[WarpTarget],
+    stations: &[Station],
+    ship: Option<(&Mesh, &Mat4)>,
+) -> Option<Pick> {
+    let bodies = targets.iter().enumerate().filter_map(|(idx, target)| {
+        let t = match stations.iter().find(|station| station.name == target.name) {
+            Some(station) => station.mesh.ray_hit(&station.transform, origin, direction),
+            None => ray_sphere(origin, direction, target.center, target.radius),
+        };
+        t.map(|t| (t, Pick::Target(idx)))
+    });
+    let ship = ship.and_then(|(mesh, transform)| {
+        mesh.ray_hit(transform, origin, direction)
+            .map(|t| (t, Pick::Ship))
+    });
+    bodies
+        .chain(ship)
+        .min_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, pick)| pick)
+}
+
+/// What an orbit polyline's world points depend on, so a cached projection can
+/// tell when it went stale.
+#[derive(Clone, Copy, PartialEq)]
+enum OrbitShape {
+    Planet {
+        center: Vec3,
+        semi_major: f32,
+        eccentricity: f32,
+        ascending_node: f32,
+        inclination: f32,
+    },
+    Ellipse {
+        semi_major: f32,
+        eccentricity: f32,
+        orientation: Mat4,
+    },
+}
+
+/// Screen-space orbit polylines from earlier frames. While the view-projection is
+/// unchanged (a still or paused camera) each orbit is only reprojected when its
+/// shape changes.
+#[derive(Default)]
+struct OrbitCache {
+    view_projection: Option<Mat4>,
+    orbits: Vec<(Option<OrbitShape>, Vec<Option<Vec2>>)>,
+}
+
+impl OrbitCache {
+    fn begin(&mut self, view_projection: &Mat4) {
+        if self.view_projection != Some(*view_projection) {
+            self.view_projection = Some(*view_projection);
+            for (shape, _) in &mut self.orbits {
+                *shape = None;
+            }
+        }
+    }
+
+    /// Points of orbit `slot`, from the cache or freshly made by `project`, which
+    /// maps a segment index to its screen point (`None` when not projectable).
+    fn points(
+        &mut self,
+        slot: usize,
+        shape: OrbitShape,
+        segments: usize,
+        project: impl Fn(usize) -> Option<Vec2>,
+    ) -> &[Option<Vec2>] {
+        if self.orbits.len() <= slot {
+            self.orbits.resize_with(slot + 1, || (None, Vec::new()));
+        }
+        let (cached, points) = &mut self.orbits[slot];
+        if *cached != Some(shape) || points.len() != segments {
+            *points = (0..segments).map(project).collect();
+            *cached = Some(shape);
+        }
+        points
+    }
+}
+
+/// Connects consecutive projected points, breaking the line at gaps.
+fn draw_polyline(renderer: &mut Renderer, points: &[Option<Vec2>], color: Color) {
+    for pair in points.windows(2) {
+        if let [Some(prev), Some(screen)] = pair {
+            renderer.draw_line(*prev, *screen, color);
+        }
+    }
+}
+
+/// Segment count for an orbit of roughly `radius` traced by `point` (0..1 around
+/// the loop). The orbit's on-screen radius is estimated at the sampled point
+/// nearest the camera, and a chord over `n` segments strays `r (1 - cos(pi / n))`
+/// from the arc, so `n` grows with the square root of that radius.
+fn orbit_segments(
+    radius: f32,
+    camera: &Camera,
+    height: usize,
+    point: impl Fn(f32) -> Vec3,
+) -> usize {
+    let nearest = (0..ORBIT_PROBE_POINTS)
+        .map(|i| (point(i as f32 / ORBIT_PROBE_POINTS as f32) - camera.position).length())
+        .fold(f32::INFINITY, f32::min)
+        .max(NEAR_PLANE);
+    let screen_radius = camera.pixels_per_unit(nearest, height) * radius;
+    let segments = PI * (screen_radius / (2.0 * ORBIT_MAX_SAG)).sqrt();
+    (segments.ceil() as usize).clamp(ORBIT_MIN_SEGMENTS, ORBIT_MAX_SEGMENTS)
+}
+
+fn draw_orbits(
+    renderer: &mut Renderer,
+    planets: &[Planet],
+    camera: &Camera,
+    view_projection: &Mat4,
+    filter: TagFilter,
+) {
+    let mut cache = std::mem::take(&mut renderer.planet_orbits);
+    cache.begin(view_projection);
+    for (idx, planet) in planets.iter().enumerate() {
+        let base = renderer
+            .accessibility
+            .orbit_color(idx)
+            .unwrap_or(planet.orbit_color);
+        let orbit_color = base * filter.highlight(planet.tags);
+        let shape = OrbitShape::Planet {
+            center: planet.orbit_center,
+            semi_major: planet.orbit_radius,
+            eccentricity: planet.eccentricity,
+            ascending_node: planet.ascending_node,
+            inclination: planet.inclination,
+        };
+        let segments = orbit_segments(planet.orbit_radius, camera, renderer.height, |t| {
+            planet.orbit_point(t * TAU)
+        });
+        let points = cache.points(idx, shape, segments + 1, |segment| {
+            let e_anomaly = (segment as f32 / segments as f32) * TAU;
+            renderer.project_point(planet.orbit_point(e_anomaly), view_projection)
+        });
+        draw_polyline(renderer, points, orbit_color);
+    }
+    renderer.planet_orbits = cache;
+}
+
+fn draw_comet_orbits(
+    renderer: &mut Renderer,
+    comets: &[Comet],
+    camera: &Camera,
+    view_projection: &Mat4,
+) {
+    let mut cache = std::mem::take(&mut renderer.comet_orbits);
+    cache.begin(view_projection);
+    for (idx, comet) in comets.iter().enumerate() {
+        let shape = OrbitShape::Ellipse {
+            semi_major: comet.semi_major,
+            eccentricity: comet.eccentricity,
+            orientation: comet.orientation,
+        };
+        let segments = orbit_segments(comet.semi_major, camera, renderer.height, |t| {
+            comet.orbit_point(t * TAU)
+        });
+        let points = cache.points(idx, shape, segments + 1, |segment| {
+            let e_anomaly = (segment as f32 / segments as f32) * TAU;
+            renderer.project_point(comet.orbit_point(e_anomaly), view_projection)
+        });
+        draw_polyline(renderer, points, comet.orbit_color);
+    }
+    renderer.comet_orbits = cache;
+}
+
+/// Splats each ring particle as a small opaque, depth-tested square. Particles take
+/// the light of whichever face is lit, so from that side only their brightness jitter
+/// tells them apart from the ring mesh.
+fn draw_ring_particles(
+    renderer: &mut Renderer,
+    ring: &PlanetRing,
+    camera: &Camera,
+    view_projection: &Mat4,
+    lights: &[Light],
+    highlight: f32,
+) {
+    let normal = (ring.transform * Vec4::new(0.0, 1.0, 0.0, 0.0))
+        .xyz()
+        .normalized();
+    let center = (ring.transform * Vec4::new(0.0, 0.0, 0.0, 1.0)).xyz();
+    let near_distance = ring.outer_radius * 4.0;
+    for particle in &ring.particles {
+        let world = (ring.transform
+            * Vec4::new(particle.offset.x, particle.offset.y, particle.offset.z, 1.0))
+        .xyz();
+        let Some(screen) = renderer.project(world, view_projection) else {
+            continue;
+        };
+        let mut light_sum = 0.2;
+        for light in lights {
+            let to_light = (light.position - center).normalized();
+            light_sum += normal.dot(to_light).abs() * light.intensity;
+        }
+        let color = ring.bands[particle.band].color * (particle.shade * light_sum * highlight);
+        let size = if (world - camera.position).length() < near_distance {
+            particle.size + 1
+        } else {
+            particle.size
+        };
+        renderer.draw_splat(screen, color, size);
+    }
+}
+
+/// Recent world positions of a moving body, oldest first, sampled every
+/// `TRAIL_INTERVAL` seconds and capped at `TRAIL_SAMPLES`.
+#[derive(Default)]
+struct Trail {
+    points: VecDeque<Vec3>,
+    timer: f32,
+}
+
+impl Trail {
+    fn record(&mut self, position: Vec3, dt: f32) {
+        self.timer += dt;
+        if self.timer < TRAIL_INTERVAL && !self.points.is_empty() {
+            return;
+        }
+        self.timer = 0.0;
+        if self.points.len() == TRAIL_SAMPLES {
+            self.points.pop_front();
+        }
+        self.points.push_back(position);
+    }
+}
+
+/// Motion trails for every planet, sampled on simulation time so they stop while
+/// paused, and for the ship, sampled on frame time. They start over whenever the
+/// set of planets changes.
+#[derive(Default)]
+struct Trails {
+    planets: Vec<Trail>,
+    ship: Trail,
+}
+
+impl Trails {
+    fn record(&mut self, planets: &[Planet], ship: Vec3, sim_dt: f32, dt: f32) {
+        if self.planets.len() != planets.len() {
+            self.planets = planets.iter().map(|_| Trail::default()).collect();
+        }
+        for (trail, planet) in self.planets.iter_mut().zip(planets) {
+            if sim_dt > 0.0 {
+                trail.record(planet.position, sim_dt);
+            }
+        }
+        self.ship.record(ship, dt);
+    }
+}
+
+/// Draws each trail as a glowing line from its oldest sample, where it has faded
+/// out, up to the body's current position.
+fn draw_trails(
+    renderer: &mut Renderer,
+    trails: &Trails,
+    world: &WorldView,
+    view_projection: &Mat4,
+) {
+    for (idx, (trail, planet)) in trails.planets.iter().zip(world.planets).enumerate() {
+        let color = renderer
+            .accessibility
+            .orbit_color(idx)
+            .unwrap_or(planet.orbit_color)
+            * world.tag_filter.highlight(planet.tags);
+        draw_trail(renderer, trail, planet.position, color, view_projection);
+    }
+    if let Some(&head) = trails.ship.points.back() {
+        draw_trail(
+            renderer,
+            &trails.ship,
+            head,
+            SHIP_ENGINE_GLOW[0].color,
+            view_projection,
+        );
+    }
+}
+
+fn draw_trail(
+    renderer: &mut Renderer,
+    trail: &Trail,
+    head: Vec3,
+    color: Color,
+    view_projection: &Mat4,
+) {
+    let count = trail.points.len();
+    let mut previous: Option<Vec3> = None;
+    for (i, &position) in trail
+        .points
+        .iter()
+        .chain(std::iter::once(&head))
+        .enumerate()
+    {
+        let point = renderer.project(position, view_projection);
+        if let (Some(prev), Some(point)) = (previous, point) {
+            let fade = i as f32 / count as f32;
+            renderer.draw_glow_line(prev, point, color * (TRAIL_BRIGHTNESS * fade));
+        }
+        previous = point;
+    }
+}
+
+fn draw_comet_tail(renderer: &mut Renderer, comet: &Comet, view_projection: &Mat4) {
+    for particle in &comet.tail {
+        let Some(screen) = renderer.project(particle.position, view_projection) else {
+            continue;
+        };
+        let fade = 1.0 - particle.age / particle.life;
+        renderer.draw_particle(screen, comet.tail_color * (0.35 * fade), 2);
+    }
+}
+
+/// Each loop is a glowing line of plasma with brighter knots strung along it; the
+/// depth test hides the parts behind the star.
+fn draw_prominences(renderer: &mut Renderer, star: &Star, view_projection: &Mat4) {
+    let tint = Color::lerp(star.light_color, PROMINENCE_TINT, 0.6);
+    for prominence in &star.prominences {
+        let color = if prominence.flare {
+            Color::lerp(tint, Color::new(1.0, 1.0, 1.0), 0.5)
+        } else {
+            tint * 0.8
+        };
+        let color = color * prominence.strength();
+        let mut previous: Option<Vec3> = None;
+        for i in 0..=PROMINENCE_SAMPLES {
+            let t = i as f32 / PROMINENCE_SAMPLES as f32;
+            let position = star.position + prominence.point(t) * star.radius;
+            let point = renderer.project(position, view_projection);
+            if let (Some(prev), Some(point)) = (previous, point) {
+                renderer.draw_glow_line(prev, point, color * 0.6);
+            }
+            if let Some(point) = point {
+                let knot = i % 4 == 2;
+                renderer.draw_particle(
+                    point,
+                    color * if knot { 0.5 } else { 0.25 },
+                    2 + knot as i32,
+                );
+            }
+            previous = point;
+        }
+    }
+}
+
+fn spaceship_position_for_camera(camera: &Camera) -> Vec3 {
+    // Push the ship further in front of the camera so it always sits fully visible on screen.
+    camera.position + camera.forward() * 14.0 + Vec3::new(0.0, -2.5, 0.0)
+}
+
+fn spaceship_transform_for_camera(camera: &Camera) -> Mat4 {
+    let forward = camera.forward();
+    let position = spaceship_position_for_camera(camera);
+    let up_reference = Vec3::UP;
+    let right = forward.cross(up_reference).normalized();
+    let corrected_up = right.cross(forward).normalized();
+    Mat4::from_basis(right, corrected_up, forward, position) * Mat4::scale(Vec3::splat(0.8))
+}
+
+fn build_planets(descriptors: &[PlanetDescriptor]) -> Vec<Planet> {
+    descriptors.iter().map(Planet::from_descriptor).collect()
+}
+
+fn build_comets(descriptors: &[CometDescriptor], asteroids: &mut AsteroidCache) -> Vec<Comet> {
+    let mut comets: Vec<Comet> = descriptors
+        .iter()
+        .map(|desc| Comet::from_descriptor(desc, asteroids))
+        .collect();
+    for comet in comets.iter_mut() {
+        comet.update(0.0);
+    }
+    comets
+}
+
+/// Stations for `descriptors`, already hanging from whichever of `planets` they
+/// orbit.
+fn build_stations(
+    descriptors: &[StationDescriptor],
+    planets: &[Planet],
+) -> Result<Vec<Station>, Box<dyn std::error::Error>> {
+    let mut stations = Vec::with_capacity(descriptors.len());
+    for desc in descriptors {
+        stations.push(Station::from_descriptor(desc)?);
+    }
+    place_stations(planets, &mut stations);
+    Ok(stations)
+}
+
+fn build_black_holes(descriptors: &[BlackHoleDescriptor]) -> Vec<BlackHole> {
+    let mut holes: Vec<BlackHole> = descriptors.iter().map(BlackHole::from_descriptor).collect();
+    for hole in holes.iter_mut() {
+        hole.update(0.0);
+    }
+    holes
+}
+
+/// Screen-space gravitational lensing: pixels around the hole sample the frame
+/// further out along the radial direction, bending the background into an
+/// Einstein ring. Anything nearer to the camera than the hole is left untouched.
+fn apply_black_hole_lensing(
+    renderer: &mut Renderer,
+    hole: &BlackHole,
+    camera: &Camera,
+    view_projection: &Mat4,
+) {
+    let Some(center) = renderer.project(hole.position, view_projection) else {
+        return;
+    };
+    let right = camera.forward().cross(Vec3::UP).normalized();
+    let Some(edge) = renderer.project(hole.position + right * hole.radius, view_projection) else {
+        return;
+    };
+    let horizon = (edge.x - center.x).abs();
+    if horizon < 0.5 {
+        return;
+    }
+    renderer.apply_lensing(center, horizon, hole.lensing_strength);
+}
+
+fn build_stars(descriptors: &[StarDescriptor]) -> Vec<Star> {
+    let mut stars: Vec<Star> = descriptors.iter().map(Star::from_descriptor).collect();
+    // Place the stars on their orbits before the first frame so lighting and warps are valid.
+    for star in stars.iter_mut() {
+        star.update(0.0);
+    }
+    stars
+}
+
+/// Optional render passes the governor may drop, listed most expensive first.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OptionalPass {
+    Lensing,
+    Particles,
+    Orbits,
+}
+
+const SHED_ORDER: [OptionalPass; 3] = [
+    OptionalPass::Lensing,
+    OptionalPass::Particles,
+    OptionalPass::Orbits,
+];
+
+impl OptionalPass {
+    fn label(self) -> &'static str {
+        match self {
+            OptionalPass::Lensing => "lensing",
+            OptionalPass::Particles => "particles",
+            OptionalPass::Orbits => "orbits",
+        }
+    }
+}
+
+const GOVERNOR_RUNGS: usize = SHED_ORDER.len() + RESOLUTION_STEPS.len();
+
+/// Watches how long each frame's CPU work takes (excluding the present wait) and
+/// sheds optional passes in `SHED_ORDER` while over budget, then lowers the world's
+/// render resolution through `RESOLUTION_STEPS`. A shed rung comes back once the
+/// time it saved fits in the remaining headroom; that estimate decays so a rung
+/// that only got expensive briefly (e.g. lensing up close) is retried.
+struct FrameGovernor {
+    budget: f32,
+    average: f32,
+    shed: usize,
+    cooldown: f32,
+    average_before_shed: f32,
+    savings: [f32; GOVERNOR_RUNGS],
+}
+
+impl FrameGovernor {
+    fn new(budget: f32) -> Self {
+        Self {
+            budget,
+            average: 0.0,
+            shed: 0,
+            cooldown: GOVERNOR_COOLDOWN,
+            average_before_shed: 0.0,
+            savings: [0.0; GOVERNOR_RUNGS],
+        }
+    }
+
+    fn record(&mut self, work_time: f32, dt: f32) {
+        self.average += (work_time - self.average) * 0.1;
+        if self.cooldown > 0.0 {
+            self.cooldown = (self.cooldown - dt).max(0.0);
+            if self.cooldown == 0.0 && self.average_before_shed > 0.0 {
+                // First settled measurement since the last shed: that is what it saved.
+                self.savings[self.shed - 1] = (self.average_before_shed - self.average).max(0.0);
+                self.average_before_shed = 0.0;
+            }
+            return;
+        }
+        if self.average > self.budget && self.shed < GOVERNOR_RUNGS {
+            self.average_before_shed = self.average;
+            self.shed += 1;
+            self.cooldown = GOVERNOR_COOLDOWN;
+        } else if self.shed > 0 {
+            let saved = &mut self.savings[self.shed - 1];
+            if self.average + *saved < self.budget * 0.85 {
+                self.shed -= 1;
+                self.cooldown = GOVERNOR_COOLDOWN;
+            } else {
+                *saved *= 0.9;
+                self.cooldown = GOVERNOR_COOLDOWN;
+            }
+        }
+    }
+
+    fn enabled(&self, pass: OptionalPass) -> bool {
+        !self.shed_passes().contains(&pass)
+    }
+
+    fn shed_passes(&self) -> &[OptionalPass] {
+        &SHED_ORDER[..self.shed.min(SHED_ORDER.len())]
+    }
+
+    /// Fraction of the window size the world is currently rendered at.
+    fn resolution_scale(&self) -> f32 {
+        self.shed
+            .checked_sub(SHED_ORDER.len() + 1)
+            .map_or(1.0, |step| RESOLUTION_STEPS[step])
+    }
+}
+
+/// Bounded undo/redo of planet edits. Each entry is a full snapshot of the
+/// planet descriptors, so restoring never has to replay individual changes.
+struct EditHistory {
+    undo: Vec<Vec<PlanetDescriptor>>,
+    redo: Vec<Vec<PlanetDescriptor>>,
+}
+
+impl EditHistory {
+    fn new() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, planets: &[Planet]) {
+        self.undo.push(snapshot_planets(planets));
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    fn undo(&mut self, planets: &mut [Planet]) {
+        if let Some(snapshot) = self.undo.pop() {
+            self.redo.push(snapshot_planets(planets));
+            restore_planets(planets, &snapshot);
+        }
+    }
+
+    fn redo(&mut self, planets: &mut [Planet]) {
+        if let Some(snapshot) = self.redo.pop() {
+            self.undo.push(snapshot_planets(planets));
+            restore_planets(planets, &snapshot);
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ThemeField {
+    SkyTop,
+    SkyBottom,
+    StarColor,
+    SunColor,
+    SunLight,
+    LightIntensity,
+    Aurora,
+}
+
+const THEME_FIELDS: [ThemeField; 7] = [
+    ThemeField::SkyTop,
+    ThemeField::SkyBottom,
+    ThemeField::StarColor,
+    ThemeField::Aurora,
+    ThemeField::SunColor,
+    ThemeField::SunLight,
+    ThemeField::LightIntensity,
+];
+
+enum ThemeValue {
+    Color(Color),
+    Scalar(f32),
+}
+
+impl ThemeField {
+    fn label(self) -> &'static str {
+        match self {
+            ThemeField::SkyTop => "sky top",
+            ThemeField::SkyBottom => "sky bottom",
+            ThemeField::StarColor => "stars",
+            ThemeField::SunColor => "sun",
+            ThemeField::SunLight => "sunlight",
+            ThemeField::LightIntensity => "intensity",
+            ThemeField::Aurora => "aurora",
+        }
+    }
+
+    /// Sun fields refer to the theme's first star.
+    fn value(self, theme: &Theme) -> ThemeValue {
+        let sun = &theme.stars[0];
+        match self {
+            ThemeField::SkyTop => ThemeValue::Color(theme.palette.sky_top),
+            ThemeField::SkyBottom => ThemeValue::Color(theme.palette.sky_bottom),
+            ThemeField::StarColor => ThemeValue::Color(theme.palette.star_color),
+            ThemeField::Aurora => ThemeValue::Color(theme.palette.aurora),
+            ThemeField::SunColor => ThemeValue::Color(sun.color),
+            ThemeField::SunLight => ThemeValue::Color(sun.light_color),
+            ThemeField::LightIntensity => ThemeValue::Scalar(sun.light_intensity),
+        }
+    }
+
+    fn color_mut(self, theme: &mut Theme) -> Option<&mut Color> {
+        match self {
+            ThemeField::SkyTop => Some(&mut theme.palette.sky_top),
+            ThemeField::SkyBottom => Some(&mut theme.palette.sky_bottom),
+            ThemeField::StarColor => Some(&mut theme.palette.star_color),
+            ThemeField::Aurora => Some(&mut theme.palette.aurora),
+            ThemeField::SunColor => Some(&mut theme.stars[0].color),
+            ThemeField::SunLight => Some(&mut theme.stars[0].light_color),
+            ThemeField::LightIntensity => None,
+        }
+    }
+}
+
+enum ThemeEditAction {
+    None,
+    Changed,
+    Save,
+}
+
+/// Palette editor: `Tab` picks a field, `[`/`]` a color channel, `-`/`=` adjust it
+/// and `Ctrl+S` writes the theme out with `save_theme`.
+struct ThemeEditor {
+    active: bool,
+    field: usize,
+    channel: usize,
+}
+
+impl ThemeEditor {
+    fn new() -> Self {
+        Self {
+            active: false,
+            field: 0,
+            channel: 0,
+        }
+    }
+
+    fn field(&self) -> ThemeField {
+        THEME_FIELDS[self.field]
+    }
+
+    fn handle_input(
+        &mut self,
+        input: &InputFrame,
+        theme: &mut Theme,
+        stars: &mut [Star],
+        dt: f32,
+    ) -> ThemeEditAction {
+        let ctrl = input.is_key_down(Key::LeftCtrl) || input.is_key_down(Key::RightCtrl);
+        if ctrl && input.is_key_pressed(Key::S, KeyRepeat::No) {
+            return ThemeEditAction::Save;
+        }
+        if input.is_key_pressed(Key::Tab, KeyRepeat::No) {
+            self.field = (self.field + 1) % THEME_FIELDS.len();
+        }
+        if input.is_key_pressed(Key::LeftBracket, KeyRepeat::No) {
+            self.channel = (self.channel + 2) % 3;
+        }
+        if input.is_key_pressed(Key::RightBracket, KeyRepeat::No) {
+            self.channel = (self.channel + 1) % 3;
+        }
+        let mut delta = 0.0;
+        if input.is_key_down(Key::Minus) {
+            delta -= dt;
+        }
+        if input.is_key_down(Key::Equal) {
+            delta += dt;
+        }
+        if delta == 0.0 {
+            return ThemeEditAction::None;
+        }
+        let field = self.field();
+        match field.color_mut(theme) {
+            Some(color) => {
+                let channel = match self.channel {
+                    0 => &mut color.r,
+                    1 => &mut color.g,
+                    _ => &mut color.b,
+                };
+                *channel = (*channel + delta * 0.4).clamp(0.0, 1.0);
+            }
+            None => {
+                let sun = &mut theme.stars[0];
+                sun.light_intensity = (sun.light_intensity + delta).max(0.0);
+            }
+        }
+        if let Some(star) = stars.first_mut() {
+            let sun = &theme.stars[0];
+            star.color = sun.color;
+            star.light_color = sun.light_color;
+            star.light_intensity = sun.light_intensity;
+        }
+        ThemeEditAction::Changed
+    }
+}
+
+/// Saves the theme together with the live planet and prop edits. From then on the
+/// theme's source, and the file watched for reloads, is the copy it was saved to.
+fn save_session(theme: &mut Theme, planets: &[Planet], props: &[Prop], watcher: &mut SceneWatcher) {
+    let mut saved = theme.clone();
+    saved.planets = snapshot_planets(planets);
+    saved.props = props.iter().map(Prop::descriptor).collect();
+    match save_theme(&saved) {
+        Ok(path) => {
+            watcher.path = Some(path.clone());
+            watcher.sync();
+            watcher.report(format!("saved {}", path.display()));
+            theme.source = Some(path);
+        }
+        Err(err) => watcher.report(format!("save failed: {}", err)),
+    }
+}
+
+const GIZMO_AXES: [Vec3; 3] = [
+    Vec3::new(1.0, 0.0, 0.0),
+    Vec3::new(0.0, 1.0, 0.0),
+    Vec3::new(0.0, 0.0, 1.0),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum GizmoTool {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoTool {
+    fn next(self) -> Self {
+        match self {
+            GizmoTool::Translate => GizmoTool::Rotate,
+            GizmoTool::Rotate => GizmoTool::Scale,
+            GizmoTool::Scale => GizmoTool::Translate,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            GizmoTool::Translate => "move",
+            GizmoTool::Rotate => "rotate",
+            GizmoTool::Scale => "scale",
+        }
+    }
+}
+
+/// Gizmo for free-floating props. Dragging an axis handle moves the prop along that
+/// world axis, turns it about the axis or scales it uniformly, depending on the tool;
+/// the drag amount is the mouse motion projected onto the axis as drawn on screen.
+struct PropEditor {
+    active: bool,
+    prop: usize,
+    tool: GizmoTool,
+    /// Axis being dragged and the mouse position from the previous frame.
+    drag: Option<(usize, Vec2)>,
+}
+
+impl PropEditor {
+    fn new() -> Self {
+        Self {
+            active: false,
+            prop: 0,
+            tool: GizmoTool::Translate,
+            drag: None,
+        }
+    }
+
+    /// Returns true when the user asked to save (`Ctrl+S`).
+    fn handle_input(
+        &mut self,
+        input: &InputFrame,
+        camera: &Camera,
+        (width, height): (f32, f32),
+        props: &mut [Prop],
+    ) -> bool {
+        let ctrl = input.is_key_down(Key::LeftCtrl) || input.is_key_down(Key::RightCtrl);
+        if ctrl && input.is_key_pressed(Key::S, KeyRepeat::No) {
+            return true;
+        }
+        if props.is_empty() {
+            return false;
+        }
+        if input.is_key_pressed(Key::Tab, KeyRepeat::No) {
+            self.prop = (self.prop + 1) % props.len();
+            self.drag = None;
+        }
+        self.prop = self.prop.min(props.len() - 1);
+        if input.is_key_pressed(Key::Q, KeyRepeat::No) {
+            self.tool = self.tool.next();
+        }
+
+        if !input.mouse_down() {
+            self.drag = None;
+            return false;
+        }
+        let Some((mx, my)) = input.mouse_pos() else {
+            return false;
+        };
+        let mouse = Vec2::new(mx, my);
+        let view_projection = camera.projection(width / height) * camera.view_matrix();
+        let prop = &mut props[self.prop];
+        let length = prop.gizmo_length();
+        let Some(center) = project_to_screen(prop.position, &view_projection, width, height) else {
+            return false;
+        };
+        let screen_axis = |axis: usize| {
+            project_to_screen(
+                prop.position + GIZMO_AXES[axis] * length,
+                &view_projection,
+                width,
+                height,
+            )
+            .map(|end| Vec2::new(end.x - center.x, end.y - center.y))
+        };
+        let Some((axis, last)) = self.drag else {
+            self.drag = (0..GIZMO_AXES.len())
+                .find(|&axis| {
+                    screen_axis(axis).is_some_and(|a| {
+                        (center.x + a.x - mx).hypot(center.y + a.y - my) <= GIZMO_PICK_RADIUS
+                    })
+                })
+                .map(|axis| (axis, mouse));
+            return false;
+        };
+        self.drag = Some((axis, mouse));
+        let Some(a) = screen_axis(axis) else {
+            return false;
+        };
+        let span = a.x * a.x + a.y * a.y;
+        if span < 1.0 {
+            return false;
+        }
+        // Fraction of the on-screen axis length the mouse moved along it.
+        let t = ((mouse.x - last.x) * a.x + (mouse.y - last.y) * a.y) / span;
+        match self.tool {
+            GizmoTool::Translate => prop.position += GIZMO_AXES[axis] * (t * length),
+            GizmoTool::Rotate => {
+                let angle = t * PI;
+                match axis {
+                    0 => prop.rotation.x += angle,
+                    1 => prop.rotation.y += angle,
+                    _ => prop.rotation.z += angle,
+                }
+            }
+            GizmoTool::Scale => prop.scale = (prop.scale * (1.0 + t)).max(0.05),
+        }
+        prop.update_transform();
+        false
+    }
+}
+
+fn snapshot_planets(planets: &[Planet]) -> Vec<PlanetDescriptor> {
+    planets.iter().map(Planet::descriptor).collect()
+}
+
+fn restore_planets(planets: &mut [Planet], snapshot: &[PlanetDescriptor]) {
+    for (planet, desc) in planets.iter_mut().zip(snapshot) {
+        planet.apply_descriptor(desc);
+    }
+}
+
+/// Runtime tweaking of the selected planet's size and orbit.
+struct PlanetEditor {
+    active: bool,
+    planet: usize,
+    history: EditHistory,
+    drag: Option<GizmoHandle>,
+}
+
+/// Drag handles drawn on the edited planet's orbit. Each sits at a fixed point of
+/// the orbit: the ascending node, the highest point and the point opposite the node.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum GizmoHandle {
+    Node,
+    Tilt,
+    Radius,
+}
+
+const GIZMO_HANDLES: [GizmoHandle; 3] = [GizmoHandle::Node, GizmoHandle::Tilt, GizmoHandle::Radius];
+
+impl GizmoHandle {
+    /// Orbit angle the handle sits at.
+    fn angle(self) -> f32 {
+        match self {
+            GizmoHandle::Node => 0.0,
+            GizmoHandle::Tilt => PI * 1.5,
+            GizmoHandle::Radius => PI,
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            GizmoHandle::Node => Color::new(0.4, 1.0, 0.5),
+            GizmoHandle::Tilt => Color::new(1.0, 0.5, 0.4),
+            GizmoHandle::Radius => Color::new(0.45, 0.7, 1.0),
+        }
+    }
+}
+
+fn snap(value: f32, step: f32) -> f32 {
+    (value / step).round() * step
+}
+
+/// Where a ray meets the plane through the origin with the given normal.
+fn intersect_plane(origin: Vec3, direction: Vec3, normal: Vec3) -> Option<Vec3> {
+    let denom = direction.dot(normal);
+    if denom.abs() < 1e-5 {
+        return None;
+    }
+    let t = -origin.dot(normal) / denom;
+    (t > 0.0).then(|| origin + direction * t)
+}
+
+fn draw_orbit_gizmo(
+    renderer: &mut Renderer,
+    planet: &Planet,
+    view_projection: &Mat4,
+    drag: Option<GizmoHandle>,
+) {
+    let node_axis = (planet.orbit_frame() * Vec4::new(1.0, 0.0, 0.0, 0.0)).xyz();
+    let reach = planet.orbit_radius * 1.15;
+    let center = planet.orbit_center;
+    if let (Some(a), Some(b)) = (
+        renderer.project_point(center + node_axis * reach, view_projection),
+        renderer.project_point(center - node_axis * reach, view_projection),
+    ) {
+        renderer.draw_line(a, b, GizmoHandle::Node.color() * 0.6);
+    }
+    for handle in GIZMO_HANDLES {
+        let Some(screen) =
+            renderer.project_point(planet.orbit_point(handle.angle()), view_projection)
+        else {
+            continue;
+        };
+        let size = if drag == Some(handle) { 9 } else { 7 };
+        renderer.fill_rect(
+            screen.x as i32 - size / 2,
+            screen.y as i32 - size / 2,
+            size,
+            size,
+            handle.color(),
+            1.0,
+        );
+    }
+}
+
+impl PlanetEditor {
+    fn new() -> Self {
+        Self {
+            active: false,
+            planet: 0,
+            history: EditHistory::new(),
+            drag: None,
+        }
+    }
+
+    /// Left-drag on a gizmo handle edits the orbit: the node handle turns the line of
+    /// nodes, the tilt handle sets the inclination and the radius handle resizes the
+    /// orbit. Values snap to `GIZMO_ANGLE_SNAP` / `GIZMO_RADIUS_SNAP`.
+    fn handle_mouse(
+        &mut self,
+        input: &InputFrame,
+        camera: &Camera,
+        (width, height): (f32, f32),
+        planets: &mut [Planet],
+    ) {
+        let Some(planet) = planets.get(self.planet) else {
+            return;
+        };
+        if !input.mouse_down() {
+            self.drag = None;
+            return;
+        }
+        let Some((mx, my)) = input.mouse_pos() else {
+            return;
+        };
+        if self.drag.is_none() {
+            let view_projection = camera.projection(width / height) * camera.view_matrix();
+            let picked = GIZMO_HANDLES.into_iter().find(|handle| {
+                project_to_screen(
+                    planet.orbit_point(handle.angle()),
+                    &view_projection,
+                    width,
+                    height,
+                )
+                .is_some_and(|p| (p.x - mx).hypot(p.y - my) <= GIZMO_PICK_RADIUS)
+            });
+            let Some(handle) = picked else {
+                return;
+            };
+            self.history.record(planets);
+            self.drag = Some(handle);
+        }
+        let (origin, ray) = camera.ray(mx, my, width, height);
+        let planet = &mut planets[self.planet];
+        // The handle planes pass through the orbit's center, which moves for moons.
+        let origin = origin - planet.orbit_center;
+        match self.drag {
+            Some(GizmoHandle::Node) => {
+                if let Some(hit) = intersect_plane(origin, ray, Vec3::UP) {
+                    planet.ascending_node = snap((-hit.z).atan2(hit.x), GIZMO_ANGLE_SNAP);
+                }
+            }
+            Some(GizmoHandle::Tilt) => {
+                let node_axis =
+                    (Mat4::rotation_y(planet.ascending_node) * Vec4::new(1.0, 0.0, 0.0, 0.0)).xyz();
+                let level = (Mat4::rotation_y(planet.ascending_node)
+                    * Vec4::new(0.0, 0.0, -1.0, 0.0))
+                .xyz();
+                if let Some(hit) = intersect_plane(origin, ray, node_axis) {
+                    let tilt = hit.y.atan2(hit.dot(level));
+                    planet.inclination = snap(tilt, GIZMO_ANGLE_SNAP).clamp(-1.4, 1.4);
+                }
+            }
+            Some(GizmoHandle::Radius) => {
+                let normal = (planet.orbit_frame() * Vec4::new(0.0, 1.0, 0.0, 0.0)).xyz();
+                if let Some(hit) = intersect_plane(origin, ray, normal) {
+                    // The handle sits at apoapsis, a(1 + e) from the focus.
+                    let semi_major = hit.length() / (1.0 + planet.eccentricity);
+                    planet.orbit_radius = snap(semi_major, GIZMO_RADIUS_SNAP).max(1.0);
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn handle_input(&mut self, input: &InputFrame, planets: &mut [Planet], dt: f32) {
+        if planets.is_empty() {
+            return;
+        }
+        if input.is_key_pressed(Key::Tab, KeyRepeat::No) {
+            self.planet = (self.planet + 1) % planets.len();
+        }
+        self.planet = self.planet.min(planets.len() - 1);
+
+        let ctrl = input.is_key_down(Key::LeftCtrl) || input.is_key_down(Key::RightCtrl);
+        if ctrl && input.is_key_pressed(Key::Z, KeyRepeat::Yes) {
+            self.history.undo(planets);
+        }
+        if ctrl && input.is_key_pressed(Key::Y, KeyRepeat::Yes) {
+            self.history.redo(planets);
+        }
+
+        // (decrease, increase, units per second) for radius, orbit radius and orbit speed.
+        let edits = [
+            (Key::Minus, Key::Equal, 3.0),
+            (Key::LeftBracket, Key::RightBracket, 12.0),
+            (Key::Comma, Key::Period, 0.15),
+        ];
+        // One snapshot per key press, so holding a key undoes as a single step.
+        if edits.iter().any(|&(down, up, _)| {
+            input.is_key_pressed(down, KeyRepeat::No) || input.is_key_pressed(up, KeyRepeat::No)
+        }) {
+            self.history.record(planets);
+        }
+        let planet = &mut planets[self.planet];
+        for (field, &(down, up, rate)) in edits.iter().enumerate() {
+            let mut delta = 0.0;
+            if input.is_key_down(down) {
+                delta -= rate * dt;
+            }
+            if input.is_key_down(up) {
+                delta += rate * dt;
+            }
+            match field {
+                0 => planet.radius = (planet.radius + delta).max(0.5),
+                1 => planet.orbit_radius = (planet.orbit_radius + delta).max(0.0),
+                _ => planet.orbit_speed += delta,
+            }
+        }
+    }
+}
+
+/// Untyped scene file: `[kind name]` sections holding ordered `key = value` fields.
+/// Blank lines and `#` comments are ignored. Typed loading happens in
+/// `Theme::from_document`; diff and merge work on this level so they don't need
+/// to know every body type.
+#[derive(Clone, PartialEq)]
+struct SceneDocument {
+    sections: Vec<SceneSection>,
+}
+
+#[derive(Clone, PartialEq)]
+struct SceneSection {
+    kind: String,
+    name: String,
+    fields: Vec<(String, String)>,
+    line: usize,
+}
+
+fn parse_scene(text: &str) -> Result<SceneDocument, Box<dyn std::error::Error>> {
+    let mut sections: Vec<SceneSection> = Vec::new();
+    for (idx, raw) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header
+                .strip_suffix(']')
+                .ok_or_else(|| format!("line {}: unterminated section header", line_no))?
+                .trim();
+            let (kind, name) = header
+                .split_once(char::is_whitespace)
+                .map_or((header, ""), |(kind, name)| (kind, name.trim()));
+            if sections.iter().any(|s| s.kind == kind && s.name == name) {
+                return Err(format!("line {}: duplicate section [{}]", line_no, header).into());
+            }
+            sections.push(SceneSection {
+                kind: kind.to_string(),
+                name: name.to_string(),
+                fields: Vec::new(),
+                line: line_no,
+            });
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected `key = value`", line_no))?;
+        let section = sections
+            .last_mut()
+            .ok_or_else(|| format!("line {}: field before any section", line_no))?;
+        let key = key.trim();
+        if section.get(key).is_some() {
+            return Err(format!("line {}: duplicate field `{}`", line_no, key).into());
+        }
+        section
+            .fields
+            .push((key.to_string(), value.trim().to_string()));
+    }
+    Ok(SceneDocument { sections })
+}
+
+impl SceneDocument {
+    fn find(&self, kind: &str, name: &str) -> Option<&SceneSection> {
+        self.sections
+            .iter()
+            .find(|section| section.kind == kind && section.name == name)
+    }
+
+    fn to_text(&self) -> String {
+        let mut out = String::new();
+        for (idx, section) in self.sections.iter().enumerate() {
+            if idx > 0 {
+                out.push('\n');
+            }
+            out.push_str(&format!("[{}]\n", section.label()));
+            for (key, value) in &section.fields {
+                out.push_str(&format!("{} = {}\n", key, value));
+            }
+        }
+        out
+    }
+}
+
+impl SceneSection {
+    fn label(&self) -> String {
+        if self.name.is_empty() {
+            self.kind.clone()
+        } else {
+            format!("{} {}", self.kind, self.name)
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn error(&self, message: &str) -> Box<dyn std::error::Error> {
+        format!("line {}: [{}] {}", self.line, self.label(), message).into()
+    }
+
+    fn text(&self, key: &str) -> Result<&str, Box<dyn std::error::Error>> {
+        self.get(key)
+            .ok_or_else(|| self.error(&format!("missing `{}`", key)))
+    }
+
+    fn floats<const N: usize>(&self, key: &str) -> Result<[f32; N], Box<dyn std::error::Error>> {
+        let raw = self.text(key)?;
+        let parts: Vec<&str> = raw.split_whitespace().collect();
+        let mut values = [0.0; N];
+        if parts.len() != N {
+            return Err(self.error(&format!("`{}` expects {} number(s)", key, N)));
+        }
+        for (value, part) in values.iter_mut().zip(parts) {
+            *value = part
+                .parse()
+                .map_err(|_| self.error(&format!("`{}`: `{}` is not a number", key, part)))?;
+        }
+        Ok(values)
+    }
+
+    fn float(&self, key: &str) -> Result<f32, Box<dyn std::error::Error>> {
+        self.floats::<1>(key).map(|[value]| value)
+    }
+
+    fn float_or(&self, key: &str, default: f32) -> Result<f32, Box<dyn std::error::Error>> {
+        if self.get(key).is_some() {
+            self.float(key)
+        } else {
+            Ok(default)
+        }
+    }
+
+    fn flag_or(&self, key: &str, default: bool) -> Result<bool, Box<dyn std::error::Error>> {
+        match self.get(key) {
+            None => Ok(default),
+            Some("on") => Ok(true),
+            Some("off") => Ok(false),
+            Some(other) => {
+                Err(self.error(&format!("`{}` must be on or off, got `{}`", key, other)))
+            }
+        }
+    }
+
+    fn color(&self, key: &str) -> Result<Color, Box<dyn std::error::Error>> {
+        self.floats::<3>(key).map(|[r, g, b]| Color::new(r, g, b))
+    }
+
+    fn vec3(&self, key: &str) -> Result<Vec3, Box<dyn std::error::Error>> {
+        self.floats::<3>(key).map(|[x, y, z]| Vec3::new(x, y, z))
+    }
+}
+
+/// Human-readable differences from `old` to `new`, one entry per line.
+fn diff_scenes(old: &SceneDocument, new: &SceneDocument) -> Vec<String> {
+    let mut out = Vec::new();
+    for section in &old.sections {
+        if new.find(&section.kind, &section.name).is_none() {
+            out.push(format!("- [{}]", section.label()));
+        }
+    }
+    for section in &new.sections {
+        let Some(previous) = old.find(&section.kind, &section.name) else {
+            out.push(format!("+ [{}]", section.label()));
+            continue;
+        };
+        let mut changes = Vec::new();
+        for (key, value) in &previous.fields {
+            match section.get(key) {
+                None => changes.push(format!("    - {} = {}", key, value)),
+                Some(now) if now != value => {
+                    changes.push(format!("    {}: {} -> {}", key, value, now))
+                }
+                Some(_) => {}
+            }
+        }
+        for (key, value) in &section.fields {
+            if previous.get(key).is_none() {
+                changes.push(format!("    + {} = {}", key, value));
+            }
+        }
+        if !changes.is_empty() {
+            out.push(format!("~ [{}]", section.label()));
+            out.extend(changes);
+        }
+    }
+    out
+}
+
+/// Formats a value the way scene files write it. Floats always keep a decimal
+/// point (`20.0`, not `20`) so saved files diff cleanly against hand-written ones.
+trait SceneValue {
+    fn scene_text(&self) -> String;
+}
+
+impl SceneValue for f32 {
+    fn scene_text(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl SceneValue for u64 {
+    fn scene_text(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl SceneValue for usize {
+    fn scene_text(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl SceneValue for bool {
+    fn scene_text(&self) -> String {
+        if *self { "on" } else { "off" }.to_string()
+    }
+}
+
+impl SceneValue for &str {
+    fn scene_text(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl SceneValue for Color {
+    fn scene_text(&self) -> String {
+        format!("{:?} {:?} {:?}", self.r, self.g, self.b)
+    }
+}
+
+impl SceneValue for Vec3 {
+    fn scene_text(&self) -> String {
+        format!("{:?} {:?} {:?}", self.x, self.y, self.z)
+    }
+}
+
+impl SceneSection {
+    fn new(kind: &str, name: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            name: name.to_string(),
+            fields: Vec::new(),
+            line: 0,
+        }
+    }
+
+    fn field(mut self, key: &str, value: impl SceneValue) -> Self {
+        self.fields.push((key.to_string(), value.scene_text()));
+        self
+    }
+}
+
+impl Theme {
+    /// Inverse of `from_document`: every field is written out, defaults included,
+    /// so a saved file is complete on its own.
+    fn to_document(&self) -> SceneDocument {
+        let palette = &self.palette;
+        let mut theme = SceneSection::new("theme", "")
+            .field("name", self.name.as_str())
+            .field("sky_top", palette.sky_top)
+            .field("sky_bottom", palette.sky_bottom)
+            .field("star_color", palette.star_color)
+            .field("ecliptic", palette.ecliptic)
+            .field("ecliptic_intensity", palette.ecliptic_intensity)
+            .field("ship_color", self.ship_color)
+            .field("star_count", palette.starfield.count)
+            .field("star_size", palette.starfield.size)
+            .field("bright_stars", palette.starfield.bright_fraction)
+            .field(
+                "star_temperature_spread",
+                palette.starfield.temperature_spread,
+            )
+            .field("star_twinkle", palette.starfield.twinkle)
+            .field("galaxy_normal", palette.galaxy.normal)
+            .field("galaxy_width", palette.galaxy.width)
+            .field("galaxy_color", palette.galaxy.color)
+            .field("galaxy_intensity", palette.galaxy.intensity)
+            .field("aurora_color", palette.aurora)
+            .field("style", palette.style.name());
+        if let RenderStyle::Toon { bands, outline } = palette.style {
+            theme = theme
+                .field("toon_bands", bands)
+                .field("toon_outline", outline);
+        }
+        let mut sections = vec![theme];
+        for star in &self.stars {
+            sections.push(
+                SceneSection::new("star", &star.name)
+                    .field("radius", star.radius)
+                    .field("orbit_radius", star.orbit_radius)
+                    .field("orbit_speed", star.orbit_speed)
+                    .field("orbit_phase", star.orbit_phase)
+                    .field("color", star.color)
+                    .field("light_color", star.light_color)
+                    .field("light_intensity", star.light_intensity),
+            );
+        }
+        for planet in &self.planets {
+            let mut section = SceneSection::new("planet", &planet.name)
+                .field("radius", planet.radius)
+                .field("orbit_radius", planet.orbit_radius)
+                .field("orbit_speed", planet.orbit_speed)
+                .field("rotation_speed", planet.rotation_speed)
+                .field("axial_tilt", planet.axial_tilt)
+                .field("inclination", planet.inclination)
+                .field("ascending_node", planet.ascending_node)
+                .field("color", planet.color)
+                .field("orbit_color", planet.orbit_color)
+                .field("reflectivity", planet.reflectivity);
+            if planet.eccentricity != 0.0 {
+                section = section.field("eccentricity", planet.eccentricity);
+            }
+            if planet.orbit_phase != 0.0 {
+                section = section.field("orbit_phase", planet.orbit_phase);
+            }
+            if planet.precession_rate != 0.0 {
+                section = section.field("precession_rate", planet.precession_rate);
+            }
+            if planet.tidally_locked {
+                section = section.field("tidally_locked", true);
+            }
+            if let Some(parent) = &planet.parent {
+                section = section.field("parent", parent.as_str());
+            }
+            if let Some(script) = &planet.script {
+                section = section.field("script", script.as_str());
+            }
+            let tags = planet.tags.names();
+            if !tags.is_empty() {
+                section = section.field("tags", tags.as_str());
+            }
+            if planet.sphere != SphereShape::Adaptive {
+                section = section.field("sphere", planet.sphere);
+            }
+            if let Some(cap) = planet.polar_cap {
+                section = section
+                    .field("polar_cap", cap.extent)
+                    .field("polar_cap_color", cap.color);
+            }
+            if planet.ice {
+                section = section.field("ice", true);
+            }
+            if planet.aurora > 0.0 {
+                section = section.field("aurora", planet.aurora);
+            }
+            if let Some(bands) = planet.cloud_bands {
+                section = section
+                    .field("bands", bands.count)
+                    .field("band_color", bands.color)
+                    .field("band_turbulence", bands.turbulence);
+            }
+            if let Some(rim) = planet.rim {
+                section = section
+                    .field("rim", rim.strength)
+                    .field("rim_color", rim.color);
+            }
+            if let Some(ring) = &planet.ring {
+                section = ring.write(section);
+            }
+            if let Some(audio) = planet.audio {
+                section = audio.write(section);
+            }
+            sections.push(section);
+        }
+        for hole in &self.black_holes {
+            sections.push(
+                SceneSection::new("black_hole", &hole.name)
+                    .field("radius", hole.radius)
+                    .field("orbit_radius", hole.orbit_radius)
+                    .field("orbit_speed", hole.orbit_speed)
+                    .field("orbit_phase", hole.orbit_phase)
+                    .field("disk_inner", hole.disk_inner)
+                    .field("disk_outer", hole.disk_outer)
+                    .field("disk_tilt", hole.disk_tilt)
+                    .field("disk_speed", hole.disk_speed)
+                    .field("disk_color", hole.disk_color)
+                    .field("lensing_strength", hole.lensing_strength),
+            );
+        }
+        for station in &self.stations {
+            let mut section = station.import.write(
+                SceneSection::new("station", &station.name)
+                    .field("mesh", station.mesh_path.as_str()),
+            );
+            section = section
+                .field("scale", station.scale)
+                .field("orbit_radius", station.orbit_radius)
+                .field("orbit_speed", station.orbit_speed)
+                .field("orbit_phase", station.orbit_phase)
+                .field("height", station.height)
+                .field("spin_speed", station.spin_speed)
+                .field("color", station.color)
+                .field("dock_offset", station.dock_offset);
+            if let Some(radius) = station.collision_radius {
+                section = section.field("collision_radius", radius);
+            }
+            if let Some(parent) = &station.parent {
+                section = section.field("parent", parent.as_str());
+            }
+            if let Some(script) = &station.script {
+                section = section.field("script", script.as_str());
+            }
+            sections.push(match station.audio {
+                Some(audio) => audio.write(section),
+                None => section,
+            });
+        }
+        for comet in &self.comets {
+            let section = SceneSection::new("comet", &comet.name)
+                .field("nucleus_radius", comet.nucleus_radius)
+                .field("perihelion", comet.perihelion)
+                .field("aphelion", comet.aphelion)
+                .field("mean_motion", comet.mean_motion)
+                .field("orbit_phase", comet.orbit_phase)
+                .field("inclination", comet.inclination)
+                .field("periapsis_angle", comet.periapsis_angle)
+                .field("color", comet.color)
+                .field("tail_color", comet.tail_color)
+                .field("orbit_color", comet.orbit_color);
+            sections.push(match comet.nucleus_seed {
+                Some(seed) => section.field("nucleus_seed", seed),
+                None => section,
+            });
+        }
+        for prop in &self.props {
+            let section = prop
+                .import
+                .write(SceneSection::new("prop", &prop.name).field("mesh", prop.mesh_path.as_str()))
+                .field("position", prop.position)
+                .field("rotation", prop.rotation)
+                .field("scale", prop.scale)
+                .field("color", prop.color);
+            sections.push(if prop.wireframe {
+                section.field("wireframe", true)
+            } else {
+                section
+            });
+        }
+        sections.extend(self.shots.iter().map(CameraShot::to_section));
+        sections.extend(self.events.iter().map(SceneEvent::to_section));
+        SceneDocument { sections }
+    }
+}
+
+/// Writes the theme to the user copy of its source file (see `user_scene_path`), or
+/// to `<name>.user.scene` in the working directory when it has none. The file a
+/// theme came from is never overwritten: saving regenerates the text, which would
+/// drop its comments and layout.
+fn save_theme(theme: &Theme) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = match &theme.source {
+        Some(source) => user_scene_path(source),
+        None => PathBuf::from(format!(
+            "{}.user.scene",
+            theme.name.to_lowercase().replace(' ', "_")
+        )),
+    };
+    std::fs::write(&path, theme.to_document().to_text())?;
+    Ok(path)
+}
+
+/// `scenes/icy.scene` becomes `scenes/icy.user.scene`; a user copy is its own.
+fn user_scene_path(source: &Path) -> PathBuf {
+    let stem = source
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    if stem.ends_with(".user") {
+        return source.to_path_buf();
+    }
+    source.with_file_name(format!("{}.user.scene", stem))
+}
+
+struct SceneMerge {
+    document: SceneDocument,
+    conflicts: Vec<String>,
+}
+
+/// Three-way merge. A change on only one side wins; the same change on both sides
+/// is taken once; different changes to the same field (or an edit against a
+/// removal) are conflicts, resolved in favour of `ours`.
+fn merge_scenes(base: &SceneDocument, ours: &SceneDocument, theirs: &SceneDocument) -> SceneMerge {
+    let mut sections = Vec::new();
+    let mut conflicts = Vec::new();
+    let theirs_only = theirs
+        .sections
+        .iter()
+        .filter(|section| ours.find(&section.kind, &section.name).is_none());
+    for key in ours.sections.iter().chain(theirs_only) {
+        let b = base.find(&key.kind, &key.name);
+        let o = ours.find(&key.kind, &key.name);
+        let t = theirs.find(&key.kind, &key.name);
+        match (o, t) {
+            (Some(o), Some(t)) => sections.push(merge_sections(b, o, t, &mut conflicts)),
+            (Some(kept), None) | (None, Some(kept)) => match b {
+                None => sections.push(kept.clone()),
+                Some(b) if b.fields == kept.fields => {}
+                Some(_) => {
+                    conflicts.push(format!(
+                        "[{}] edited on one side, removed on the other",
+                        key.label()
+                    ));
+                    sections.push(kept.clone());
+                }
+            },
+            (None, None) => {}
+        }
+    }
+    SceneMerge {
+        document: SceneDocument { sections },
+        conflicts,
+    }
+}
+
+fn merge_sections(
+    base: Option<&SceneSection>,
+    ours: &SceneSection,
+    theirs: &SceneSection,
+    conflicts: &mut Vec<String>,
+) -> SceneSection {
+    let mut merged = ours.clone();
+    merged.fields.clear();
+    let theirs_only = theirs
+        .fields
+        .iter()
+        .filter(|(key, _)| ours.get(key).is_none());
+    for (key, _) in ours.fields.iter().chain(theirs_only) {
+        let b = base.and_then(|section| section.get(key));
+        let o = ours.get(key);
+        let t = theirs.get(key);
+        let value = if o == t || t == b {
+            o
+        } else if o == b {
+            t
+        } else {
+            conflicts.push(format!(
+                "[{}] {}: ours {}, theirs {} (base {})",
+                ours.label(),
+                key,
+                o.unwrap_or("<removed>"),
+                t.unwrap_or("<removed>"),
+                b.unwrap_or("<none>"),
+            ));
+            o
+        };
+        if let Some(value) = value {
+            merged.fields.push((key.clone(), value.to_string()));
+        }
+    }
+    merged
+}
+
+struct Warp {
+    start: Vec3,
+    target: Vec3,
+    planet: Option<usize>,
+    progress: f32,
+    duration: f32,
+    /// Name shown on the flight HUD; `None` for bare coordinates.
+    destination: Option<String>,
+    /// Orientation and FOV to blend between, for warps that also re-aim the camera.
+    view: Option<(CameraView, CameraView)>,
+}
+
+#[derive(Clone, Copy)]
+struct CameraView {
+    yaw: f32,
+    pitch: f32,
+    fov: f32,
+}
+
+impl CameraView {
+    fn of(camera: &Camera) -> Self {
+        Self {
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+            fov: camera.fov,
+        }
+    }
+
+    fn looking(direction: Vec3, fov: f32) -> Self {
+        let direction = direction.normalized();
+        Self {
+            yaw: direction.x.atan2(direction.z),
+            pitch: direction.y.clamp(-1.0, 1.0).asin(),
+            fov,
+        }
+    }
+}
+
+/// Distance at which a sphere of `radius` spans `fill` of the vertical field of view.
+fn framing_distance(radius: f32, fov: f32, fill: f32) -> f32 {
+    radius / (fill * (fov * 0.5).tan()).atan().sin()
+}
+
+/// Flies to the point along the current view line where the body fills
+/// `FRAME_FILL` of the screen height, aimed at its center, at the default FOV.
+fn frame_body(camera: &Camera, center: Vec3, radius: f32) -> Warp {
+    let distance = framing_distance(radius.max(0.5), DEFAULT_FOV, FRAME_FILL);
+    let direction = (center - camera.position).normalized();
+    Warp {
+        view: Some((
+            CameraView::of(camera),
+            CameraView::looking(direction, DEFAULT_FOV),
+        )),
+        ..Warp::to_position(camera.position, center - direction * distance)
+    }
+}
+
+/// Looks down on the whole system from above the ecliptic so every orbit fits on
+/// screen. The FOV widens when the default one would push the far side past the far plane.
+fn frame_system(camera: &Camera, extent: f32) -> Warp {
+    let direction =
+        Vec3::new(camera.yaw.sin(), 0.0, camera.yaw.cos()) * 0.75 + Vec3::new(0.0, -0.66, 0.0);
+    let max_distance = FAR_PLANE * 0.9 - extent;
+    let mut fov = DEFAULT_FOV;
+    let mut distance = framing_distance(extent, fov, 0.95);
+    if distance > max_distance && max_distance > extent {
+        distance = max_distance;
+        fov = (2.0 * ((extent / distance).asin().tan() / 0.95).atan()).min(MAX_FOV);
+    }
+    let direction = direction.normalized();
+    Warp {
+        view: Some((CameraView::of(camera), CameraView::looking(direction, fov))),
+        destination: Some("system".into()),
+        ..Warp::to_position(camera.position, -direction * distance)
+    }
+}
+
+/// Radius around the origin that contains every orbit in the system.
+fn system_extent(
+    stars: &[Star],
+    planets: &[Planet],
+    black_holes: &[BlackHole],
+    stations: &[Station],
+    comets: &[Comet],
+) -> f32 {
+    let stars = stars.iter().map(|star| star.orbit_radius + star.radius);
+    let planets = planets.iter().map(Planet::reach);
+    let holes = black_holes
+        .iter()
+        .map(|hole| hole.orbit_radius + hole.disk_outer);
+    let stations = stations.iter().map(|station| station.position.length());
+    let comets = comets
+        .iter()
+        .map(|comet| comet.semi_major * (1.0 + comet.eccentricity));
+    stars
+        .chain(planets)
+        .chain(holes)
+        .chain(stations)
+        .chain(comets)
+        .fold(1.0, f32::max)
+}
+
+impl Warp {
+    fn new(start: Vec3, target: &WarpTarget) -> Self {
+        Self {
+            planet: target.planet,
+            destination: Some(target.name.clone()),
+            ..Self::to_position(start, target.anchor)
+        }
+    }
+
+    fn to_shot(camera: &Camera, shot: &CameraShot) -> Self {
+        Self {
+            view: Some((CameraView::of(camera), shot.view())),
+            destination: Some(shot.name.clone()),
+            ..Self::to_position(camera.position, shot.position)
+        }
+    }
+
+    fn to_position(start: Vec3, target: Vec3) -> Self {
+        Self {
+            start,
+            target,
+            planet: None,
+            progress: 0.0,
+            duration: WARP_DURATION,
+            destination: None,
+            view: None,
+        }
+    }
+
+    fn fraction(&self) -> f32 {
+        (self.progress / self.duration).clamp(0.0, 1.0)
+    }
+
+    /// Speed of the smoothstep ease relative to its peak mid-flight: 0 at both ends.
+    fn speed(&self) -> f32 {
+        let t = self.fraction();
+        4.0 * t * (1.0 - t)
+    }
+
+    /// How much of the previous frame to keep this frame, so the blur swells
+    /// mid-flight and is gone on arrival.
+    fn motion_blur(&self) -> f32 {
+        WARP_BLUR_PERSISTENCE * self.speed()
+    }
+
+    /// Fov added to the view: eases out to `WARP_FOV_KICK` over the first half and
+    /// holds there, snapping back when the warp ends.
+    fn fov_kick(&self) -> f32 {
+        WARP_FOV_KICK * smoothstep((self.fraction() * 2.0).min(1.0))
+    }
+}
+
+/// Screen shake driven by "trauma" in 0..1, added by collisions and fast close
+/// flybys and drained at `SHAKE_DECAY` per second. The shake grows with trauma
+/// squared so small bumps stay subtle, scaled by the `shake` setting.
+struct CameraShake {
+    trauma: f32,
+    time: f32,
+    intensity: f32,
+}
+
+impl CameraShake {
+    fn new(intensity: f32) -> Self {
+        Self {
+            trauma: 0.0,
+            time: 0.0,
+            intensity,
+        }
+    }
+
+    fn add(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).min(1.0);
+    }
+
+    fn update(&mut self, dt: f32) {
+        self.time += dt;
+        self.trauma = (self.trauma - SHAKE_DECAY * dt).max(0.0);
+    }
+
+    /// `camera` nudged by the current shake: smooth noise on position, yaw and pitch.
+    fn apply(&self, mut camera: Camera) -> Camera {
+        let amount = self.trauma * self.trauma * self.intensity;
+        if amount <= 0.0 {
+            return camera;
+        }
+        let wobble = |channel: f32| {
+            value_noise(Vec3::new(self.time * SHAKE_FREQUENCY, channel * 7.1, 0.0)) * 2.0 - 1.0
+        };
+        camera.position +=
+            Vec3::new(wobble(1.0), wobble(2.0), wobble(3.0)) * (SHAKE_MAX_OFFSET * amount);
+        camera.yaw += wobble(4.0) * SHAKE_MAX_ANGLE * amount;
+        camera.pitch += wobble(5.0) * SHAKE_MAX_ANGLE * amount;
+        camera
+    }
+}
+
+/// Trauma per second from passing a planet at `speed`: nothing below
+/// `SHAKE_FLYBY_SPEED`, rising to `SHAKE_FLYBY_RATE` at the collision margin.
+fn flyby_trauma(position: Vec3, speed: f32, planets: &[Planet]) -> f32 {
+    if speed < SHAKE_FLYBY_SPEED {
+        return 0.0;
+    }
+    planets
+        .iter()
+        .map(|planet| {
+            let altitude = (position - planet.position).length() - planet.radius;
+            (1.0 - altitude / (planet.radius * SHAKE_FLYBY_RANGE)).clamp(0.0, 1.0)
+        })
+        .fold(0.0, f32::max)
+        * SHAKE_FLYBY_RATE
+}
+
+struct WarpTarget {
+    name: String,
+    anchor: Vec3,
+    center: Vec3,
+    radius: f32,
+    planet: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+struct BodyTags(u8);
+
+impl BodyTags {
+    const ROCKY: Self = Self(1 << 0);
+    const GAS: Self = Self(1 << 1);
+    const HABITABLE: Self = Self(1 << 2);
+    // Set at runtime once a warp to the body completes.
+    const VISITED: Self = Self(1 << 3);
+
+    const fn with(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Authored tags as written in scene files. `VISITED` is runtime state and is left out.
+    fn names(self) -> String {
+        let names = [
+            (Self::ROCKY, "rocky"),
+            (Self::GAS, "gas"),
+            (Self::HABITABLE, "habitable"),
+        ];
+        let present: Vec<&str> = names
+            .iter()
+            .filter(|&&(tag, _)| self.contains(tag))
+            .map(|&(_, name)| name)
+            .collect();
+        present.join(" ")
+    }
+
+    /// Space-separated tag names as written in scene files, e.g. `rocky habitable`.
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut tags = Self::default();
+        for word in text.split_whitespace() {
+            tags = tags.with(match word {
+                "rocky" => Self::ROCKY,
+                "gas" => Self::GAS,
+                "habitable" => Self::HABITABLE,
+                "visited" => Self::VISITED,
+                other => return Err(format!("unknown tag `{}`", other)),
+            });
+        }
+        Ok(tags)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TagFilter {
+    All,
+    Rocky,
+    Gas,
+    Habitable,
+    Visited,
+}
+
+impl TagFilter {
+    fn next(self) -> Self {
+        match self {
+            TagFilter::All => TagFilter::Rocky,
+            TagFilter::Rocky => TagFilter::Gas,
+            TagFilter::Gas => TagFilter::Habitable,
+            TagFilter::Habitable => TagFilter::Visited,
+            TagFilter::Visited => TagFilter::All,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            TagFilter::All => "All",
+            TagFilter::Rocky => "Rocky",
+            TagFilter::Gas => "Gas",
+            TagFilter::Habitable => "Habitable",
+            TagFilter::Visited => "Visited",
+        }
+    }
+
+    fn matches(self, tags: BodyTags) -> bool {
+        match self {
+            TagFilter::All => true,
+            TagFilter::Rocky => tags.contains(BodyTags::ROCKY),
+            TagFilter::Gas => tags.contains(BodyTags::GAS),
+            TagFilter::Habitable => tags.contains(BodyTags::HABITABLE),
+            TagFilter::Visited => tags.contains(BodyTags::VISITED),
+        }
+    }
+
+    /// Brightness multiplier for a body: matches get a slight boost, the rest fade out.
+    fn highlight(self, tags: BodyTags) -> f32 {
+        match self {
+            TagFilter::All => 1.0,
+            _ if self.matches(tags) => 1.25,
+            _ => 0.25,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Palette {
+    sky_top: Color,
+    sky_bottom: Color,
+    star_color: Color,
+    ecliptic: Color,
+    /// Peak strength of the ecliptic band added to the sky gradient.
+    ecliptic_intensity: f32,
+    starfield: StarfieldConfig,
+    galaxy: GalaxyConfig,
+    style: RenderStyle,
+    /// Color planets' auroras glow in.
+    aurora: Color,
+}
+
+/// How lit surfaces are drawn, chosen per theme with `style = shaded` or `style = toon`.
+#[derive(Clone, Copy, PartialEq)]
+enum RenderStyle {
+    Shaded,
+    /// Cartoon look: diffuse light snapped to `bands` flat steps and silhouettes
+    /// drawn in `outline`.
+    Toon {
+        bands: usize,
+        outline: Color,
+    },
+}
+
+impl RenderStyle {
+    fn from_section(section: &SceneSection) -> Result<Self, Box<dyn std::error::Error>> {
+        match section.get("style").unwrap_or("shaded") {
+            "shaded" => Ok(RenderStyle::Shaded),
+            "toon" => {
+                let bands = section.float_or("toon_bands", 3.0)?;
+                if bands < 1.0 {
+                    return Err(section.error("toon_bands must be >= 1"));
+                }
+                let outline = match section.get("toon_outline") {
+                    Some(_) => section.color("toon_outline")?,
+                    None => Color::new(0.02, 0.02, 0.04),
+                };
+                Ok(RenderStyle::Toon {
+                    bands: bands as usize,
+                    outline,
+                })
+            }
+            other => Err(section.error(&format!(
+                "unknown style `{}` (expected shaded or toon)",
+                other
+            ))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            RenderStyle::Shaded => "shaded",
+            RenderStyle::Toon { .. } => "toon",
+        }
+    }
+
+    /// Snaps a diffuse light amount to the nearest band; grazing light rounds down to
+    /// the unlit band.
+    fn quantize(self, diffuse: f32) -> f32 {
+        match self {
+            RenderStyle::Shaded => diffuse,
+            RenderStyle::Toon { bands, .. } => (diffuse * bands as f32).round() / bands as f32,
+        }
+    }
+}
+
+/// Milky Way style band along the great circle perpendicular to `normal` (world
+/// space). `width` is the band's angular half-width in radians; intensity 0 hides it.
+#[derive(Clone, Copy, PartialEq)]
+struct GalaxyConfig {
+    normal: Vec3,
+    width: f32,
+    color: Color,
+    intensity: f32,
+}
+
+impl GalaxyConfig {
+    fn from_section(section: &SceneSection) -> Result<Self, Box<dyn std::error::Error>> {
+        let normal = match section.get("galaxy_normal") {
+            Some(_) => section.vec3("galaxy_normal")?,
+            None => Vec3::new(0.35, 0.8, 0.45),
+        };
+        if normal.length_squared() <= f32::EPSILON {
+            return Err(section.error("galaxy_normal must not be zero"));
+        }
+        let color = match section.get("galaxy_color") {
+            Some(_) => section.color("galaxy_color")?,
+            None => Color::new(0.55, 0.55, 0.65),
+        };
+        Ok(Self {
+            normal,
+            width: section.float_or("galaxy_width", 0.22)?.max(0.01),
+            color,
+            intensity: section.float_or("galaxy_intensity", 0.0)?.max(0.0),
+        })
+    }
+
+    /// Band light seen along unit direction `dir`, modulated by fractal noise and a
+    /// darker dust lane along the middle; `None` well away from the band.
+    fn glow(&self, dir: Vec3) -> Option<Color> {
+        let latitude = dir.dot(self.normal.normalized()) / self.width;
+        let band = (-latitude * latitude).exp();
+        if band < 0.01 {
+            return None;
+        }
+        let clouds = fractal_noise(dir * 5.0);
+        let dust = 1.0
+            - 0.6
+                * (-latitude * latitude * 16.0).exp()
+                * fractal_noise(dir * 11.0 + Vec3::splat(7.3));
+        Some(self.color * (self.intensity * band * (0.35 + 0.9 * clouds) * dust))
+    }
+}
+
+/// Background star layout. `size` is the side in pixels of a regular star; bright
+/// stars are one pixel larger. `temperature_spread` blends each star towards a
+/// warm or cool tint, `twinkle` is the depth of the brightness flicker.
+#[derive(Clone, Copy, PartialEq)]
+struct StarfieldConfig {
+    count: usize,
+    size: usize,
+    bright_fraction: f32,
+    temperature_spread: f32,
+    twinkle: f32,
+}
+
+impl Default for StarfieldConfig {
+    fn default() -> Self {
+        Self {
+            count: DEFAULT_STAR_COUNT,
+            size: 1,
+            bright_fraction: 0.04,
+            temperature_spread: 0.35,
+            twinkle: 0.15,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Theme {
+    name: String,
+    /// File the theme was loaded from, watched for hot reload.
+    source: Option<PathBuf>,
+    palette: Palette,
+    stars: Vec<StarDescriptor>,
+    ship_color: Color,
+    planets: Vec<PlanetDescriptor>,
+    black_holes: Vec<BlackHoleDescriptor>,
+    stations: Vec<StationDescriptor>,
+    comets: Vec<CometDescriptor>,
+    props: Vec<PropDescriptor>,
+    shots: Vec<CameraShot>,
+    /// Sorted by time.
+    events: Vec<SceneEvent>,
+}
+
+impl CometDescriptor {
+    fn from_section(section: &SceneSection) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            name: section.name.clone(),
+            nucleus_radius: section.float("nucleus_radius")?,
+            perihelion: section.float("perihelion")?,
+            aphelion: section.float("aphelion")?,
+            mean_motion: section.float("mean_motion")?,
+            orbit_phase: section.float_or("orbit_phase", 0.0)?,
+            inclination: section.float_or("inclination", 0.0)?,
+            periapsis_angle: section.float_or("periapsis_angle", 0.0)?,
+            nucleus_seed: match section.get("nucleus_seed") {
+                Some(value) => Some(value.parse().map_err(|_| {
+                    section.error(&format!(
+                        "`nucleus_seed` must be a whole number, got `{}`",
+                        value
+                    ))
+                })?),
+                None => None,
+            },
+            color: section.color("color")?,
+            tail_color: section.color("tail_color")?,
+            orbit_color: section.color("orbit_color")?,
+        })
+    }
+}
+
+#[derive(Clone)]
+struct CometDescriptor {
+    name: String,
+    nucleus_radius: f32,
+    perihelion: f32,
+    aphelion: f32,
+    // Mean anomaly advance per second; one orbit takes TAU / mean_motion seconds.
+    mean_motion: f32,
+    orbit_phase: f32,
+    inclination: f32,
+    periapsis_angle: f32,
+    /// Seed of the rocky nucleus mesh; a smooth sphere when `None`.
+    nucleus_seed: Option<u64>,
+    color: Color,
+    tail_color: Color,
+    orbit_color: Color,
+}
+
+impl StarfieldConfig {
+    fn from_section(section: &SceneSection) -> Result<Self, Box<dyn std::error::Error>> {
+        let defaults = Self::default();
+        let count = section.float_or("star_count", defaults.count as f32)?;
+        let size = section.float_or("star_size", defaults.size as f32)?;
+        if count < 0.0 || size < 1.0 {
+            return Err(section.error("star_count must be >= 0 and star_size >= 1"));
+        }
+        Ok(Self {
+            count: count as usize,
+            size: size as usize,
+            bright_fraction: section
+                .float_or("bright_stars", defaults.bright_fraction)?
+                .clamp(0.0, 1.0),
+            temperature_spread: section
+                .float_or("star_temperature_spread", defaults.temperature_spread)?
+                .clamp(0.0, 1.0),
+            twinkle: section
+                .float_or("star_twinkle", defaults.twinkle)?
+                .clamp(0.0, 1.0),
+        })
+    }
+}
+
+impl Theme {
+    fn from_document(doc: &SceneDocument) -> Result<Self, Box<dyn std::error::Error>> {
+        let section = doc
+            .sections
+            .iter()
+            .find(|section| section.kind == "theme")
+            .ok_or("scene has no [theme] section")?;
+        let mut theme = Theme {
+            name: section.text("name")?.to_string(),
+            source: None,
+            palette: Palette {
+                sky_top: section.color("sky_top")?,
+                sky_bottom: section.color("sky_bottom")?,
+                star_color: section.color("star_color")?,
+                ecliptic: section.color("ecliptic")?,
+                ecliptic_intensity: section.float_or("ecliptic_intensity", 0.35)?.max(0.0),
+                starfield: StarfieldConfig::from_section(section)?,
+                galaxy: GalaxyConfig::from_section(section)?,
+                style: RenderStyle::from_section(section)?,
+                aurora: match section.get("aurora_color") {
+                    Some(_) => section.color("aurora_color")?,
+                    None => Color::new(0.3, 1.0, 0.55),
+                },
+            },
+            stars: Vec::new(),
+            ship_color: section.color("ship_color")?,
+            planets: Vec::new(),
+            black_holes: Vec::new(),
+            stations: Vec::new(),
+            comets: Vec::new(),
+            props: Vec::new(),
+            shots: Vec::new(),
+            events: Vec::new(),
+        };
+        for section in &doc.sections {
+            match section.kind.as_str() {
+                "theme" => {}
+                "star" => theme.stars.push(StarDescriptor::from_section(section)?),
+                "planet" => theme.planets.push(PlanetDescriptor::from_section(section)?),
+                "black_hole" => theme
+                    .black_holes
+                    .push(BlackHoleDescriptor::from_section(section)?),
+                "station" => theme
+                    .stations
+                    .push(StationDescriptor::from_section(section)?),
+                "comet" => theme.comets.push(CometDescriptor::from_section(section)?),
+                "prop" => theme.props.push(PropDescriptor::from_section(section)?),
+                "shot" => theme.shots.push(CameraShot::from_section(section)?),
+                "event" => theme.events.push(SceneEvent::from_section(section)?),
+                other => return Err(section.error(&format!("unknown section kind `{}`", other))),
+            }
+        }
+        if theme.stars.is_empty() {
+            return Err("scene needs at least one [star] section".into());
+        }
+        theme.check_parents()?;
+        for event in &theme.events {
+            event.check_target(&theme)?;
+        }
+        theme.events.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Ok(theme)
+    }
+
+    /// Every `parent` must name a planet, and following parents from a planet must
+    /// end at the system's center rather than loop.
+    fn check_parents(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let parent_of = |name: &str| {
+            self.planets
+                .iter()
+                .find(|planet| planet.name == name)
+                .map(|planet| planet.parent.as_deref())
+        };
+        let children = self
+            .planets
+            .iter()
+            .map(|planet| ("planet", &planet.name, &planet.parent))
+            .chain(
+                self.stations
+                    .iter()
+                    .map(|station| ("station", &station.name, &station.parent)),
+            );
+        for (kind, name, parent) in children {
+            let mut current = parent.as_deref();
+            let mut steps = 0;
+            while let Some(parent) = current {
+                current = parent_of(parent).ok_or_else(|| {
+                    format!("[{} {}]: no planet named `{}` to orbit", kind, name, parent)
+                })?;
+                steps += 1;
+                if steps > self.planets.len() {
+                    return Err(format!("[{} {}]: its parents orbit each other", kind, name).into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Named camera position from a `[shot Name]` section. The camera is aimed either by
+/// `yaw`/`pitch` (radians) or at a `look_at` point; `time`, when present, also resets
+/// the simulation to that many seconds after the scene starts.
+#[derive(Clone)]
+struct CameraShot {
+    name: String,
+    position: Vec3,
+    yaw: f32,
+    pitch: f32,
+    look_at: Option<Vec3>,
+    fov: f32,
+    time: Option<f32>,
+    hold: f32,
+}
+
+impl CameraShot {
+    fn from_section(section: &SceneSection) -> Result<Self, Box<dyn std::error::Error>> {
+        let look_at = match section.get("look_at") {
+            Some(_) => Some(section.vec3("look_at")?),
+            None => None,
+        };
+        let time = match section.get("time") {
+            Some(_) => Some(section.float("time")?),
+            None => None,
+        };
+        Ok(Self {
+            name: section.name.clone(),
+            position: section.vec3("position")?,
+            yaw: section.float_or("yaw", 0.0)?,
+            pitch: section.float_or("pitch", 0.0)?,
+            look_at,
+            fov: section.float_or("fov", DEFAULT_FOV)?.clamp(0.1, MAX_FOV),
+            time,
+            hold: section.float_or("hold", SHOT_HOLD)?.max(0.0),
+        })
+    }
+
+    fn to_section(&self) -> SceneSection {
+        let mut section = SceneSection::new("shot", &self.name).field("position", self.position);
+        section = match self.look_at {
+            Some(target) => section.field("look_at", target),
+            None => section.field("yaw", self.yaw).field("pitch", self.pitch),
+        };
+        section = section.field("fov", self.fov);
+        if let Some(time) = self.time {
+            section = section.field("time", time);
+        }
+        section.field("hold", self.hold)
+    }
+
+    fn view(&self) -> CameraView {
+        match self.look_at {
+            Some(target) => CameraView::looking(target - self.position, self.fov),
+            None => CameraView {
+                yaw: self.yaw,
+                pitch: self.pitch,
+                fov: self.fov,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum EventAction {
+    /// Only shows the event's message.
+    Message,
+    /// The target star swells and brightens by `scale` and turns whiter.
+    Supernova { scale: f32 },
+    /// The target comet or station is absent until the event.
+    Arrival,
+    /// The target comet or station leaves the scene at the event.
+    Departure,
+}
+
+impl EventAction {
+    fn name(self) -> &'static str {
+        match self {
+            EventAction::Message => "message",
+            EventAction::Supernova { .. } => "supernova",
+            EventAction::Arrival => "arrival",
+            EventAction::Departure => "departure",
+        }
+    }
+}
+
+/// Timed story beat from an `[event Name]` section: at `time` seconds of simulation
+/// the `action` happens to the body named `target` and `message` is shown on screen.
+#[derive(Clone)]
+struct SceneEvent {
+    name: String,
+    time: f32,
+    action: EventAction,
+    target: String,
+    message: Option<String>,
+}
+
+impl SceneEvent {
+    fn from_section(section: &SceneSection) -> Result<Self, Box<dyn std::error::Error>> {
+        let action = match section.text("action")? {
+            "message" => EventAction::Message,
+            "supernova" => EventAction::Supernova {
+                scale: section.float_or("scale", 2.0)?.max(0.1),
+            },
+            "arrival" => EventAction::Arrival,
+            "departure" => EventAction::Departure,
+            other => {
+                return Err(section.error(&format!(
+                    "unknown action `{}` (expected message, supernova, arrival or departure)",
+                    other
+                )))
+            }
+        };
+        let message = section.get("message").map(str::to_string);
+        if action == EventAction::Message && message.is_none() {
+            return Err(section.error("a message event needs `message`"));
+        }
+        Ok(Self {
+            name: section.name.clone(),
+            time: section.float("time")?.max(0.0),
+            action,
+            target: section.get("target").unwrap_or("").to_string(),
+            message,
+        })
+    }
+
+    fn to_section(&self) -> SceneSection {
+        let mut section = SceneSection::new("event", &self.name)
+            .field("time", self.time)
+            .field("action", self.action.name());
+        if let EventAction::Supernova { scale } = self.action {
+            section = section.field("scale", scale);
+        }
+        if !self.target.is_empty() {
+            section = section.field("target", self.target.as_str());
+        }
+        if let Some(message) = &self.message {
+            section = section.field("message", message.as_str());
+        }
+        section
+    }
+
+    /// Fails unless `target` names a body the action applies to.
+    fn check_target(&self, theme: &Theme) -> Result<(), Box<dyn std::error::Error>> {
+        let found = match self.action {
+            EventAction::Message => return Ok(()),
+            EventAction::Supernova { .. } => {
+                theme.stars.iter().any(|star| star.name == self.target)
+            }
+            EventAction::Arrival | EventAction::Departure => {
+                theme.comets.iter().any(|comet| comet.name == self.target)
+                    || theme
+                        .stations
+                        .iter()
+                        .any(|station| station.name == self.target)
+            }
+        };
+        if found {
+            Ok(())
+        } else {
+            Err(format!(
+                "[event {}]: no {} target named `{}`",
+                self.name,
+                match self.action {
+                    EventAction::Supernova { .. } => "star",
+                    _ => "comet or station",
+                },
+                self.target
+            )
+            .into())
+        }
+    }
+
+    fn text(&self) -> String {
+        match (&self.message, self.action) {
+            (Some(message), _) => message.clone(),
+            (None, EventAction::Supernova { .. }) => format!("{} goes supernova", self.target),
+            (None, EventAction::Arrival) => format!("{} arrives", self.target),
+            (None, EventAction::Departure) => format!("{} departs", self.target),
+            (None, EventAction::Message) => String::new(),
+        }
+    }
+
+    fn apply(&self, theme: &Theme, sim_time: f32, bodies: &mut CalendarBodies) -> EventOutcome {
+        let CalendarBodies {
+            planets,
+            stars,
+            stations,
+            comets,
+            asteroids,
+        } = bodies;
+        match self.action {
+            EventAction::Message => {}
+            EventAction::Supernova { scale } => {
+                for star in stars.iter_mut().filter(|star| star.name == self.target) {
+                    star.radius *= scale;
+                    star.light_intensity *= scale;
+                    star.color = Color::lerp(star.color, Color::new(1.0, 1.0, 1.0), 0.5);
+                }
+            }
+            // A body that is already there is left alone: callers build every body of
+            // the theme before `EventCalendar::start` replays the past arrivals. One
+            // that comes in joins its orbit where it would be by now.
+            EventAction::Arrival => {
+                let arriving: Vec<CometDescriptor> = theme
+                    .comets
+                    .iter()
+                    .filter(|comet| comet.name == self.target)
+                    .filter(|_| !comets.iter().any(|comet| comet.name == self.target))
+                    .cloned()
+                    .collect();
+                let mut arrived_comets = build_comets(&arriving, asteroids);
+                let arriving: Vec<StationDescriptor> = theme
+                    .stations
+                    .iter()
+                    .filter(|station| station.name == self.target)
+                    .filter(|_| !stations.iter().any(|station| station.name == self.target))
+                    .cloned()
+                    .collect();
+                let mut arrived_stations = build_stations(&arriving, planets)?;
+                fast_forward_bodies(
+                    arrived_comets
+                        .iter_mut()
+                        .map(|comet| comet as &mut dyn CelestialBody)
+                        .chain(
+                            arrived_stations
+                                .iter_mut()
+                                .map(|station| station as &mut dyn CelestialBody),
+                        ),
+                    sim_time,
+                );
+                place_stations(planets, &mut arrived_stations);
+                comets.extend(arrived_comets);
+                stations.extend(arrived_stations);
+            }
+            EventAction::Departure => self.remove_target(stations, comets),
+        }
+        Ok(())
+    }
+
+    fn remove_target(&self, stations: &mut Vec<Station>, comets: &mut Vec<Comet>) {
+        comets.retain(|comet| comet.name != self.target);
+        stations.retain(|station| station.name != self.target);
+    }
+}
+
+type EventOutcome = Result<(), Box<dyn std::error::Error>>;
+
+/// The bodies calendar events act on, and the nucleus meshes arriving comets share.
+struct CalendarBodies<'a> {
+    planets: &'a [Planet],
+    stars: &'a mut [Star],
+    stations: &'a mut Vec<Station>,
+    comets: &'a mut Vec<Comet>,
+    asteroids: &'a mut AsteroidCache,
+}
+
+/// Walks a theme's events in time order as the simulation clock passes them.
+struct EventCalendar {
+    /// Index of the next event to fire.
+    next: usize,
+}
+
+impl EventCalendar {
+    /// Brings freshly built bodies in line with the calendar at `sim_time`: bodies
+    /// whose arrival is still ahead are removed and past events take effect silently.
+    /// Returns the calendar along with the past events that failed.
+    fn start<'a>(
+        theme: &'a Theme,
+        sim_time: f32,
+        mut bodies: CalendarBodies,
+    ) -> (Self, Vec<(&'a SceneEvent, EventOutcome)>) {
+        for event in &theme.events {
+            if event.action == EventAction::Arrival && event.time > sim_time {
+                event.remove_target(bodies.stations, bodies.comets);
+            }
+        }
+        let mut calendar = Self { next: 0 };
+        let mut failed = calendar.update(theme, sim_time, &mut bodies);
+        failed.retain(|(_, outcome)| outcome.is_err());
+        (calendar, failed)
+    }
+
+    /// Fires every event that has come due by `sim_time`, returning each with the
+    /// outcome of applying it.
+    fn update<'a>(
+        &mut self,
+        theme: &'a Theme,
+        sim_time: f32,
+        bodies: &mut CalendarBodies,
+    ) -> Vec<(&'a SceneEvent, EventOutcome)> {
+        let mut fired = Vec::new();
+        while let Some(event) = theme
+            .events
+            .get(self.next)
+            .filter(|event| event.time <= sim_time)
+        {
+            fired.push((event, event.apply(theme, sim_time, bodies)));
+            self.next += 1;
+        }
+        fired
+    }
+}
+
+/// Free-floating, non-orbiting scene object. `rotation` holds Euler angles in
+/// radians applied as yaw (Y), then pitch (X), then roll (Z).
+#[derive(Clone)]
+struct PropDescriptor {
+    name: String,
+    mesh_path: String,
+    import: MeshImport,
+    position: Vec3,
+    rotation: Vec3,
+    scale: f32,
+    color: Color,
+    wireframe: bool,
+}
+
+impl PropDescriptor {
+    fn from_section(section: &SceneSection) -> Result<Self, Box<dyn std::error::Error>> {
+        let rotation = match section.get("rotation") {
+            Some(_) => section.vec3("rotation")?,
+            None => Vec3::ZERO,
+        };
+        Ok(Self {
+            name: section.name.clone(),
+            mesh_path: section.text("mesh")?.to_string(),
+            import: MeshImport::from_section(section)?,
+            position: section.vec3("position")?,
+            rotation,
+            scale: section.float_or("scale", 1.0)?,
+            color: section.color("color")?,
+            wireframe: section.flag_or("wireframe", false)?,
+        })
+    }
+}
+
+struct Prop {
+    name: String,
+    mesh_path: String,
+    import: MeshImport,
+    mesh: Mesh,
+    position: Vec3,
+    rotation: Vec3,
+    scale: f32,
+    color: Color,
+    wireframe: bool,
+    transform: Mat4,
+}
+
+impl Prop {
+    fn from_descriptor(desc: &PropDescriptor) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut prop = Self {
+            name: desc.name.clone(),
+            mesh_path: desc.mesh_path.clone(),
+            import: desc.import.clone(),
+            mesh: load_mesh(&desc.mesh_path, &desc.import)?,
+            position: desc.position,
+            rotation: desc.rotation,
+            scale: desc.scale,
+            color: desc.color,
+            wireframe: desc.wireframe,
+            transform: Mat4::identity(),
+        };
+        prop.update_transform();
+        Ok(prop)
+    }
+
+    fn update_transform(&mut self) {
+        self.transform = Mat4::translation(self.position)
+            * Mat4::rotation_y(self.rotation.y)
+            * Mat4::rotation_x(self.rotation.x)
+            * Mat4::rotation_z(self.rotation.z)
+            * Mat4::scale(Vec3::splat(self.scale));
+    }
+
+    fn gizmo_length(&self) -> f32 {
+        (self.scale * 3.0).max(2.0)
+    }
+
+    fn descriptor(&self) -> PropDescriptor {
+        PropDescriptor {
+            name: self.name.clone(),
+            mesh_path: self.mesh_path.clone(),
+            import: self.import.clone(),
+            position: self.position,
+            rotation: self.rotation,
+            scale: self.scale,
+            color: self.color,
+            wireframe: self.wireframe,
+        }
+    }
+}
+
+fn build_props(descriptors: &[PropDescriptor]) -> Result<Vec<Prop>, Box<dyn std::error::Error>> {
+    descriptors.iter().map(Prop::from_descriptor).collect()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AudioSignature {
+    Crackle,
+    Hum,
+    Chatter,
+}
+
+impl AudioSignature {
+    fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "crackle" => Ok(AudioSignature::Crackle),
+            "hum" => Ok(AudioSignature::Hum),
+            "chatter" => Ok(AudioSignature::Chatter),
+            other => Err(format!(
+                "unknown audio `{}` (expected crackle, hum or chatter)",
+                other
+            )),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            AudioSignature::Crackle => "crackle",
+            AudioSignature::Hum => "hum",
+            AudioSignature::Chatter => "chatter",
+        }
+    }
+}
+
+/// Ambient sound attached to a body with the `audio`, `audio_gain` and
+/// `audio_range` keys. Only played when built with the `audio` feature.
+#[derive(Clone, Copy)]
+struct AudioEmitter {
+    signature: AudioSignature,
+    gain: f32,
+    /// Distance from the body's surface at which the sound fades out completely.
+    range: f32,
+}
+
+impl AudioEmitter {
+    fn from_section(section: &SceneSection) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let Some(name) = section.get("audio") else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            signature: AudioSignature::parse(name).map_err(|err| section.error(&err))?,
+            gain: section.float_or("audio_gain", 1.0)?.max(0.0),
+            range: section
+                .float_or("audio_range", AUDIO_DEFAULT_RANGE)?
+                .max(1.0),
+        }))
+    }
+
+    fn write(self, section: SceneSection) -> SceneSection {
+        section
+            .field("audio", self.signature.name())
+            .field("audio_gain", self.gain)
+            .field("audio_range", self.range)
+    }
+}
+
+/// One emitter as heard from the camera this frame.
+#[cfg(feature = "audio")]
+#[derive(Clone)]
+struct AudioVoice {
+    /// The emitting body's; the mixer keeps each body's synthesis state under it.
+    name: std::sync::Arc<str>,
+    signature: AudioSignature,
+    gain: f32,
+    /// -1 is fully left, 1 fully right.
+    pan: f32,
+}
+
+/// Voices for every emitting planet and station, keyed by body name. Gain falls off
+/// with the square of the distance to the body's surface and drops while the body is
+/// hidden behind the occluders of `bodies`, by the same test `draw_world` skips
+/// hidden bodies with.
+#[cfg(feature = "audio")]
+fn spatialize_emitters<'a>(
+    camera: &Camera,
+    bodies: impl Iterator<Item = &'a dyn CelestialBody>,
+    planets: &[Planet],
+    stations: &[Station],
+) -> Vec<AudioVoice> {
+    let right = camera.forward().cross(Vec3::UP).normalized();
+    let occluders = ViewDisc::occluders(camera.position, bodies.filter_map(|body| body.occluder()));
+    let planets = planets
+        .iter()
+        .filter_map(|planet| Some((&planet.name, planet.audio?, planet.position, planet.radius)));
+    let stations = stations.iter().filter_map(|station| {
+        let radius = station.collision_radius * station.scale;
+        Some((&station.name, station.audio?, station.position, radius))
+    });
+    planets
+        .chain(stations)
+        .map(|(name, emitter, position, radius)| {
+            let offset = position - camera.position;
+            let distance = (offset.length() - radius).max(0.0);
+            let falloff = (1.0 - distance / emitter.range).clamp(0.0, 1.0);
+            let mut gain = emitter.gain * falloff * falloff;
+            if gain > 0.0 && ViewDisc::sphere_hidden(camera.position, position, radius, &occluders)
+            {
+                gain *= AUDIO_OCCLUDED_GAIN;
+            }
+            AudioVoice {
+                name: std::sync::Arc::from(name.as_str()),
+                signature: emitter.signature,
+                gain,
+                pan: offset.normalized().dot(right).clamp(-1.0, 1.0),
+            }
+        })
+        .collect()
+}
+
+/// Per-voice synthesis state, with the latest gain, pan and signature heard for the
+/// voice. Every signature is generated procedurally, so there are no sample files to
+/// ship.
+#[cfg(feature = "audio")]
+struct VoiceState {
+    signature: AudioSignature,
+    /// Gain the voice ramps toward; 0 once its body is gone.
+    target: f32,
+    gain: f32,
+    pan: f32,
+    phase: f32,
+    envelope: f32,
+    tone: f32,
+    gate: f32,
+    rng: Lcg,
+}
+
+#[cfg(feature = "audio")]
+impl VoiceState {
+    fn new(voice: &AudioVoice) -> Self {
+        Self {
+            signature: voice.signature,
+            target: voice.gain,
+            gain: 0.0,
+            pan: voice.pan,
+            phase: 0.0,
+            envelope: 0.0,
+            tone: 0.0,
+            gate: 0.0,
+            rng: Lcg::from_name(&voice.name),
+        }
+    }
+
+    fn sample(&mut self, sample_rate: f32) -> f32 {
+        let dt = 1.0 / sample_rate;
+        self.phase = (self.phase + dt) % 1000.0;
+        match self.signature {
+            // Sparse clicks of filtered noise, like ice fracturing.
+            AudioSignature::Crackle => {
+                if self.rng.next_f32() < 40.0 * dt {
+                    self.envelope = 0.3 + self.rng.next_f32() * 0.7;
+                }
+                self.envelope *= (-dt * 180.0).exp();
+                (self.rng.next_f32() * 2.0 - 1.0) * self.envelope
+            }
+            // Low drone with a couple of harmonics and a slow beat.
+            AudioSignature::Hum => {
+                let t = self.phase;
+                let beat = 0.7 + 0.3 * (TAU * 0.25 * t).sin();
+                ((TAU * 55.0 * t).sin() * 0.6
+                    + (TAU * 110.5 * t).sin() * 0.3
+                    + (TAU * 165.0 * t).sin() * 0.1)
+                    * beat
+            }
+            // Short radio beeps at random pitches, switched on and off in syllables.
+            AudioSignature::Chatter => {
+                self.gate -= dt;
+                if self.gate <= 0.0 {
+                    self.gate = 0.06 + self.rng.next_f32() * 0.14;
+                    self.tone = if self.rng.next_f32() < 0.6 {
+                        600.0 + self.rng.next_f32() * 800.0
+                    } else {
+                        0.0
+                    };
+                }
+                let target = if self.tone > 0.0 { 1.0 } else { 0.0 };
+                self.envelope += (target - self.envelope) * (dt * 120.0).min(1.0);
+                (TAU * self.tone * self.phase).sin() * self.envelope * 0.5
+            }
+        }
+    }
+}
+
+/// One-shot sound played over the ambient voices, triggered from the event bus.
+#[cfg(feature = "audio")]
+#[derive(Clone, Copy)]
+enum AudioCue {
+    /// Rising sweep with a swell of noise as a warp spools up.
+    WarpOut,
+    /// Two-note chime on arrival.
+    WarpIn,
+}
+
+#[cfg(feature = "audio")]
+impl AudioCue {
+    fn for_event(event: &AppEvent) -> Option<Self> {
+        match event {
+            AppEvent::WarpStarted { .. } => Some(AudioCue::WarpOut),
+            AppEvent::WarpCompleted { .. } => Some(AudioCue::WarpIn),
+            _ => None,
+        }
+    }
+
+    /// Seconds until the cue has faded out.
+    fn duration(self) -> f32 {
+        match self {
+            AudioCue::WarpOut => 0.9,
+            AudioCue::WarpIn => 0.8,
+        }
+    }
+
+    /// The cue's sample `t` seconds after it started.
+    fn sample(self, t: f32, rng: &mut Lcg) -> f32 {
+        match self {
+            AudioCue::WarpOut => {
+                let progress = t / self.duration();
+                let envelope = (t / 0.15).min(1.0) * (1.0 - progress).max(0.0);
+                // Frequency climbing linearly from 80 Hz to 600 Hz, integrated to a phase.
+                let phase = 80.0 * t + 520.0 * t * progress * 0.5;
+                let noise = rng.next_f32() * 2.0 - 1.0;
+                ((TAU * phase).sin() * 0.6 + noise * 0.4 * progress) * envelope
+            }
+            AudioCue::WarpIn => {
+                let note = |start: f32, frequency: f32| {
+                    let t = t - start;
+                    if t < 0.0 {
+                        0.0
+                    } else {
+                        (TAU * frequency * t).sin() * (-t * 7.0).exp()
+                    }
+                };
+                (note(0.0, 660.0) + note(0.12, 990.0)) * 0.4
+            }
+        }
+    }
+}
+
+/// Output stream plus the voices it mixes. The main loop replaces the voices each
+/// frame; the stream callback ramps gains toward them so the sound doesn't click.
+/// Cues queued by `cue_player` start on the callback's next buffer.
+#[cfg(feature = "audio")]
+struct AudioOutput {
+    voices: std::sync::Arc<std::sync::Mutex<Vec<AudioVoice>>>,
+    cues: std::sync::Arc<std::sync::Mutex<Vec<AudioCue>>>,
+    _stream: cpal::Stream,
+}
+
+#[cfg(feature = "audio")]
+impl AudioOutput {
+    fn start() -> Result<Self, Box<dyn std::error::Error>> {
+        use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("no audio output device")?;
+        let supported = device.default_output_config()?;
+        if supported.sample_format() != cpal::SampleFormat::F32 {
+            return Err(
+                format!("unsupported sample format {:?}", supported.sample_format()).into(),
+            );
+        }
+        let config = supported.config();
+        let channels = config.channels as usize;
+        let sample_rate = config.sample_rate.0 as f32;
+        let shared = std::sync::Arc::new(std::sync::Mutex::new(Vec::<AudioVoice>::new()));
+        let latest_voices = std::sync::Arc::clone(&shared);
+        let cues = std::sync::Arc::new(std::sync::Mutex::new(Vec::<AudioCue>::new()));
+        let queued_cues = std::sync::Arc::clone(&cues);
+        // Keyed by body name, so a voice keeps its state while other bodies arrive
+        // and depart, and fades out once its own body is gone.
+        let mut states: HashMap<std::sync::Arc<str>, VoiceState> = HashMap::new();
+        let mut voices: Vec<AudioVoice> = Vec::new();
+        // Cues in progress with the seconds since each started.
+        let mut playing: Vec<(AudioCue, f32)> = Vec::new();
+        let mut cue_rng = Lcg::new(0x5EED);
+        let ramp = 1.0 - (-1.0 / (0.05 * sample_rate)).exp();
+        let stream = device.build_output_stream(
+            &config,
+            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                // Never wait on the main thread here; keep last frame's voices instead.
+                if let Ok(latest) = latest_voices.try_lock() {
+                    voices.clear();
+                    voices.extend_from_slice(&latest);
+                }
+                if let Ok(mut queued) = queued_cues.try_lock() {
+                    playing.extend(queued.drain(..).map(|cue| (cue, 0.0)));
+                }
+                for state in states.values_mut() {
+                    state.target = 0.0;
+                }
+                for voice in &voices {
+                    let state = states
+                        .entry(std::sync::Arc::clone(&voice.name))
+                        .or_insert_with(|| VoiceState::new(voice));
+                    state.signature = voice.signature;
+                    state.target = voice.gain;
+                    state.pan = voice.pan;
+                }
+                for frame in data.chunks_mut(channels) {
+                    let (mut left, mut right) = (0.0, 0.0);
+                    for state in states.values_mut() {
+                        state.gain += (state.target - state.gain) * ramp;
+                        if state.gain < 1e-4 {
+                            continue;
+                        }
+                        let value = state.sample(sample_rate) * state.gain;
+                        let angle = (state.pan + 1.0) * PI / 4.0;
+                        left += value * angle.cos();
+                        right += value * angle.sin();
+                    }
+                    for (cue, elapsed) in playing.iter_mut() {
+                        let value =
+                            cue.sample(*elapsed, &mut cue_rng) * std::f32::consts::FRAC_1_SQRT_2;
+                        left += value;
+                        right += value;
+                        *elapsed += 1.0 / sample_rate;
+                    }
+                    playing.retain(|(cue, elapsed)| *elapsed < cue.duration());
+                    let (left, right) = (left.tanh() * 0.5, right.tanh() * 0.5);
+                    for (channel, sample) in frame.iter_mut().enumerate() {
+                        *sample = match channel {
+                            0 => left,
+                            1 => right,
+                            _ => 0.0,
+                        };
+                    }
+                }
+                states.retain(|name, state| {
+                    state.gain >= 1e-4 || voices.iter().any(|voice| voice.name == *name)
+                });
+            },
+            |err| eprintln!("audio stream error: {}", err),
+            None,
+        )?;
+        stream.play()?;
+        Ok(Self {
+            voices: shared,
+            cues,
+            _stream: stream,
+        })
+    }
+
+    /// An event bus subscriber that plays the warp cues.
+    fn cue_player(&self) -> impl FnMut(&AppEvent, &mut EventTargets) + 'static {
+        let cues = std::sync::Arc::clone(&self.cues);
+        move |event: &AppEvent, _: &mut EventTargets| {
+            if let (Some(cue), Ok(mut queued)) = (AudioCue::for_event(event), cues.lock()) {
+                queued.push(cue);
+            }
+        }
+    }
+
+    fn update(&self, voices: Vec<AudioVoice>) {
+        if let Ok(mut shared) = self.voices.lock() {
+            *shared = voices;
+        }
+    }
+}
+
+#[derive(Clone)]
+struct StationDescriptor {
+    name: String,
+    mesh_path: String,
+    import: MeshImport,
+    scale: f32,
+    orbit_radius: f32,
+    orbit_speed: f32,
+    orbit_phase: f32,
+    height: f32,
+    spin_speed: f32,
+    parent: Option<String>,
+    script: Option<String>,
+    color: Color,
+    // Both in mesh units, before `scale`. Without a collision radius the station
+    // collides with the sphere around its origin that holds the whole mesh.
+    collision_radius: Option<f32>,
+    dock_offset: Vec3,
+    audio: Option<AudioEmitter>,
+}
+
+impl StationDescriptor {
+    fn from_section(section: &SceneSection) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            name: section.name.clone(),
+            mesh_path: section.text("mesh")?.to_string(),
+            import: MeshImport::from_section(section)?,
+            scale: section.float_or("scale", 1.0)?,
+            orbit_radius: section.float("orbit_radius")?,
+            orbit_speed: section.float_or("orbit_speed", 0.0)?,
+            orbit_phase: section.float_or("orbit_phase", 0.0)?,
+            height: section.float_or("height", 0.0)?,
+            spin_speed: section.float_or("spin_speed", 0.0)?,
+            parent: section.get("parent").map(str::to_string),
+            script: section.get("script").map(str::to_string),
+            color: section.color("color")?,
+            collision_radius: match section.get("collision_radius") {
+                Some(_) => Some(section.float("collision_radius")?),
+                None => None,
+            },
+            dock_offset: section.vec3("dock_offset")?,
+            audio: AudioEmitter::from_section(section)?,
+        })
+    }
+}
+
+#[derive(Clone)]
+struct BlackHoleDescriptor {
+    name: String,
+    radius: f32,
+    orbit_radius: f32,
+    orbit_speed: f32,
+    orbit_phase: f32,
+    disk_inner: f32,
+    disk_outer: f32,
+    disk_tilt: f32,
+    disk_speed: f32,
+    disk_color: Color,
+    lensing_strength: f32,
+}
+
+impl BlackHoleDescriptor {
+    fn from_section(section: &SceneSection) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            name: section.name.clone(),
+            radius: section.float("radius")?,
+            orbit_radius: section.float("orbit_radius")?,
+            orbit_speed: section.float_or("orbit_speed", 0.0)?,
+            orbit_phase: section.float_or("orbit_phase", 0.0)?,
+            disk_inner: section.float("disk_inner")?,
+            disk_outer: section.float("disk_outer")?,
+            disk_tilt: section.float_or("disk_tilt", 0.0)?,
+            disk_speed: section.float_or("disk_speed", 0.0)?,
+            disk_color: section.color("disk_color")?,
+            lensing_strength: section.float_or("lensing_strength", 1.0)?,
+        })
+    }
+}
+
+#[derive(Clone)]
+struct StarDescriptor {
+    name: String,
+    radius: f32,
+    orbit_radius: f32,
+    orbit_speed: f32,
+    orbit_phase: f32,
+    color: Color,
+    light_color: Color,
+    light_intensity: f32,
+}
+
+impl StarDescriptor {
+    fn from_section(section: &SceneSection) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            name: section.name.clone(),
+            radius: section.float("radius")?,
+            orbit_radius: section.float_or("orbit_radius", 0.0)?,
+            orbit_speed: section.float_or("orbit_speed", 0.0)?,
+            orbit_phase: section.float_or("orbit_phase", 0.0)?,
+            color: section.color("color")?,
+            light_color: section.color("light_color")?,
+            light_intensity: section.float_or("light_intensity", 1.0)?,
+        })
+    }
+}
+
+#[derive(Clone)]
+struct PlanetDescriptor {
+    name: String,
+    radius: f32,
+    orbit_radius: f32,
+    orbit_speed: f32,
+    rotation_speed: f32,
+    axial_tilt: f32,
+    precession_rate: f32,
+    tidally_locked: bool,
+    inclination: f32,
+    ascending_node: f32,
+    eccentricity: f32,
+    orbit_phase: f32,
+    parent: Option<String>,
+    script: Option<String>,
+    color: Color,
+    orbit_color: Color,
+    ring: Option<RingDescriptor>,
+    polar_cap: Option<PolarCap>,
+    cloud_bands: Option<CloudBands>,
+    rim: Option<RimLight>,
+    reflectivity: f32,
+    ice: bool,
+    aurora: f32,
+    tags: BodyTags,
+    sphere: SphereShape,
+    audio: Option<AudioEmitter>,
+}
+
+impl PlanetDescriptor {
+    fn from_section(section: &SceneSection) -> Result<Self, Box<dyn std::error::Error>> {
+        let ring = RingDescriptor::from_section(section)?;
+        let polar_cap = if section.get("polar_cap").is_some() {
+            Some(PolarCap {
+                extent: section.float("polar_cap")?.clamp(0.0, 1.0),
+                color: match section.get("polar_cap_color") {
+                    Some(_) => section.color("polar_cap_color")?,
+                    None => Color::new(1.0, 1.0, 1.0),
+                },
+            })
+        } else {
+            None
+        };
+        let cloud_bands = if section.get("bands").is_some() {
+            Some(CloudBands {
+                count: section.float("bands")?.max(1.0),
+                color: section.color("band_color")?,
+                turbulence: section.float_or("band_turbulence", 0.5)?.max(0.0),
+            })
+        } else {
+            None
+        };
+        let rim = if section.get("rim").is_some() {
+            Some(RimLight {
+                strength: section.float("rim")?.max(0.0),
+                color: match section.get("rim_color") {
+                    Some(_) => section.color("rim_color")?,
+                    None => Color::new(1.0, 1.0, 1.0),
+                },
+            })
+        } else {
+            None
+        };
+        let tags = match section.get("tags") {
+            Some(text) => BodyTags::parse(text).map_err(|err| section.error(&err))?,
+            None => BodyTags::default(),
+        };
+        let sphere = match section.get("sphere") {
+            Some(text) => SphereShape::parse(text).ok_or_else(|| {
+                section.error(&format!(
+                    "`sphere` must be adaptive, uv or ico [0-{}], got `{}`",
+                    MAX_ICOSPHERE_SUBDIVISIONS, text
+                ))
+            })?,
+            None => SphereShape::Adaptive,
+        };
+        Ok(Self {
+            name: section.name.clone(),
+            radius: section.float("radius")?,
+            orbit_radius: section.float("orbit_radius")?,
+            orbit_speed: section.float("orbit_speed")?,
+            rotation_speed: section.float_or("rotation_speed", 0.0)?,
+            axial_tilt: section.float_or("axial_tilt", 0.0)?,
+            precession_rate: section.float_or("precession_rate", 0.0)?,
+            tidally_locked: section.flag_or("tidally_locked", false)?,
+            inclination: section.float_or("inclination", 0.0)?,
+            ascending_node: section.float_or("ascending_node", 0.0)?,
+            eccentricity: section.float_or("eccentricity", 0.0)?.clamp(0.0, 0.95),
+            orbit_phase: section.float_or("orbit_phase", 0.0)?,
+            parent: section.get("parent").map(str::to_string),
+            script: section.get("script").map(str::to_string),
+            color: section.color("color")?,
+            orbit_color: section.color("orbit_color")?,
+            ring,
+            polar_cap,
+            cloud_bands,
+            rim,
+            reflectivity: section.float_or("reflectivity", 0.0)?.clamp(0.0, 1.0),
+            ice: section.flag_or("ice", false)?,
+            aurora: section.float_or("aurora", 0.0)?.max(0.0),
+            tags,
+            sphere,
+            audio: AudioEmitter::from_section(section)?,
+        })
+    }
+}
+
+/// Which unit sphere a planet is drawn with.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SphereShape {
+    /// Rebuilt every frame, refined where the silhouette needs it.
+    Adaptive,
+    /// Latitude/longitude grid, like stars and comets use.
+    Uv,
+    /// Evenly tessellated, without the UV sphere's crowded poles.
+    Icosphere(usize),
+}
+
+impl SphereShape {
+    fn parse(text: &str) -> Option<Self> {
+        let mut words = text.split_whitespace();
+        let shape = match (words.next()?, words.next()) {
+            ("adaptive", None) => SphereShape::Adaptive,
+            ("uv", None) => SphereShape::Uv,
+            ("ico", None) => SphereShape::Icosphere(DEFAULT_ICOSPHERE_SUBDIVISIONS),
+            ("ico", Some(level)) => SphereShape::Icosphere(
+                level
+                    .parse()
+                    .ok()
+                    .filter(|&level| level <= MAX_ICOSPHERE_SUBDIVISIONS)?,
+            ),
+            _ => return None,
+        };
+        words.next().is_none().then_some(shape)
+    }
+
+    /// The fixed mesh for this shape; `None` for `Adaptive`, which depends on the view.
+    fn mesh(self) -> Option<Mesh> {
+        match self {
+            SphereShape::Adaptive => None,
+            SphereShape::Uv => Some(Mesh::uv_sphere(28, 18)),
+            SphereShape::Icosphere(subdivisions) => Some(Mesh::icosphere(subdivisions)),
+        }
+    }
+}
+
+impl SceneValue for SphereShape {
+    fn scene_text(&self) -> String {
+        match self {
+            SphereShape::Adaptive => "adaptive".to_string(),
+            SphereShape::Uv => "uv".to_string(),
+            SphereShape::Icosphere(subdivisions) => format!("ico {}", subdivisions),
+        }
+    }
+}
+
+/// Ice caps painted over the planet as vertex colors: each covers `extent` of the
+/// radius down from its pole, measured along the spin axis.
+#[derive(Clone, Copy)]
+struct PolarCap {
+    extent: f32,
+    color: Color,
+}
+
+impl PolarCap {
+    /// Surface color at `vertex` of the unit sphere, blending from `surface` to the
+    /// cap over a thin band so the edge doesn't alias.
+    fn tint(&self, vertex: Vec3, surface: Color) -> Color {
+        let edge = 1.0 - self.extent;
+        let t = ((vertex.y.abs() - edge) / POLAR_CAP_BLEND + 0.5).clamp(0.0, 1.0);
+        Color::lerp(surface, self.color, smoothstep(t))
+    }
+}
+
+/// Curtains of light standing on an oval around each pole, brightest at their foot
+/// and on the night side.
+#[derive(Clone)]
+struct Aurora {
+    strength: f32,
+    /// Unit-sphere mesh of both curtains, extruded along the spin axis.
+    crown: Mesh,
+    /// Seconds the curtains have been rippling.
+    time: f32,
+}
+
+impl Aurora {
+    /// `None` for a strength of 0, which turns the aurora off.
+    fn new(strength: f32) -> Option<Self> {
+        (strength > 0.0).then(|| Self {
+            strength,
+            crown: Mesh::aurora_crown(AURORA_SEGMENTS),
+            time: 0.0,
+        })
+    }
+
+    fn instance<'a>(
+        &'a self,
+        planet: &Planet,
+        context: &BodyContext,
+        highlight: f32,
+    ) -> RenderInstance<'a> {
+        // The night side is judged against the nearest star, in the planet's own frame.
+        let sun = context
+            .stars
+            .iter()
+            .min_by(|a, b| {
+                let da = (a.position - planet.position).length_squared();
+                let db = (b.position - planet.position).length_squared();
+                da.total_cmp(&db)
+            })
+            .map_or(Vec3::ZERO, |star| {
+                let to_star = star.position - planet.position;
+                (planet.transform.inverse_affine()
+                    * Vec4::new(to_star.x, to_star.y, to_star.z, 0.0))
+                .xyz()
+                .normalized()
+            });
+        RenderInstance {
+            mesh: &self.crown,
+            transform: planet.transform,
+            material: Material {
+                color: Color::new(0.0, 0.0, 0.0),
+                double_sided: true,
+                additive: true,
+                emissive_map: Some(EmissiveMap::Aurora {
+                    color: context.aurora_color * (self.strength * highlight),
+                    time: self.time,
+                    sun,
+                }),
+                ..Default::default()
+            },
+            wireframe: false,
+        }
+    }
+}
+
+/// Latitudinal cloud bands of a gas giant, `count` of them from pole to pole,
+/// alternating between the surface color and `color`. Their edges are warped by noise
+/// scaled by `turbulence`, which drifts slowly so the bands churn.
+#[derive(Clone, Copy)]
+struct CloudBands {
+    count: f32,
+    color: Color,
+    turbulence: f32,
+}
+
+impl CloudBands {
+    /// Surface color at `point` of the unit sphere, with the noise moved `drift` cells
+    /// along its flow.
+    fn tint(&self, point: Vec3, surface: Color, drift: f32) -> Color {
+        let flow = Vec3::new(drift, 0.0, -drift);
+        let warp = fractal_noise(Vec3::new(point.x * 2.5, point.y * 7.0, point.z * 2.5) + flow);
+        let latitude = point.y + (warp - 0.5) * self.turbulence / self.count * 2.0;
+        let wave = (latitude * self.count * PI).sin() * 0.5 + 0.5;
+        let detail = fractal_noise(point * 11.0 + flow * 2.0);
+        let t = smoothstep((wave * 1.6 - 0.3).clamp(0.0, 1.0)) * (0.75 + 0.25 * detail);
+        Color::lerp(surface, self.color, t)
+    }
+}
+
+/// Rings are a list of bands, innermost first; the space between two bands is a gap.
+#[derive(Clone)]
+struct RingDescriptor {
+    bands: Vec<RingBand>,
+}
+
+/// One annulus of a ring. `opacity` is the share of it that is solid: the mesh
+/// leaves the rest of its pixels open, particles thin out and it casts a fainter
+/// shadow.
+#[derive(Clone, Copy, PartialEq)]
+struct RingBand {
+    inner_radius: f32,
+    outer_radius: f32,
+    color: Color,
+    opacity: f32,
+}
+
+impl RingDescriptor {
+    /// The first band is `ring_inner`, `ring_outer`, `ring_color` and an optional
+    /// `ring_opacity`; further ones add `_2`, `_3`... to each key, and take the
+    /// previous band's color when they leave theirs out.
+    fn from_section(section: &SceneSection) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let mut bands: Vec<RingBand> = Vec::new();
+        loop {
+            let suffix = match bands.len() {
+                0 => String::new(),
+                count => format!("_{}", count + 1),
+            };
+            let key = |name: &str| format!("{}{}", name, suffix);
+            if section.get(&key("ring_inner")).is_none() {
+                break;
+            }
+            let band = RingBand {
+                inner_radius: section.float(&key("ring_inner"))?,
+                outer_radius: section.float(&key("ring_outer"))?,
+                color: match bands.last() {
+                    Some(previous) if section.get(&key("ring_color")).is_none() => previous.color,
+                    _ => section.color(&key("ring_color"))?,
+                },
+                opacity: section.float_or(&key("ring_opacity"), 1.0)?.clamp(0.0, 1.0),
+            };
+            if bands
+                .last()
+                .is_some_and(|previous| band.inner_radius < previous.outer_radius)
+            {
+                return Err(section.error(&format!(
+                    "`{}` must not start inside the band before it",
+                    key("ring_inner")
+                )));
+            }
+            bands.push(band);
+        }
+        Ok((!bands.is_empty()).then_some(Self { bands }))
+    }
+
+    fn write(&self, mut section: SceneSection) -> SceneSection {
+        for (idx, band) in self.bands.iter().enumerate() {
+            let suffix = match idx {
+                0 => String::new(),
+                idx => format!("_{}", idx + 1),
+            };
+            section = section
+                .field(&format!("ring_inner{}", suffix), band.inner_radius)
+                .field(&format!("ring_outer{}", suffix), band.outer_radius)
+                .field(&format!("ring_color{}", suffix), band.color);
+            if band.opacity < 1.0 {
+                section = section.field(&format!("ring_opacity{}", suffix), band.opacity);
+            }
+        }
+        section
+    }
+}
 
 #[derive(Clone)]
 struct Planet {
-    name: &'static str,
+    name: String,
     radius: f32,
     orbit_radius: f32,
     orbit_speed: f32,
     rotation_speed: f32,
     axial_tilt: f32,
+    /// Rate at which the tilted spin axis turns about the vertical, tracing a cone.
+    precession_rate: f32,
+    /// Turns once per orbit, keeping the same side (local -X) towards the body it
+    /// orbits; `rotation_speed` and `precession_rate` are then ignored.
+    tidally_locked: bool,
+    inclination: f32,
+    ascending_node: f32,
+    /// The orbit is an ellipse with `orbit_radius` as its semi-major axis and its
+    /// center of attraction at a focus; periapsis lies on the line of nodes.
+    eccentricity: f32,
+    /// Mean anomaly at time zero.
+    orbit_phase: f32,
+    /// Planet this one orbits as a moon; the star system's center when `None`.
+    parent: Option<String>,
+    /// Mean anomaly, which grows at the constant `orbit_speed`.
+    orbit_angle: f32,
+    rotation: f32,
+    /// Direction the spin axis leans towards, about the vertical.
+    precession: f32,
+    /// Where the parent is this tick, the center of the orbit.
+    orbit_center: Vec3,
+    position: Vec3,
+    transform: Mat4,
+    /// Source of the body's behavior script, run when built with `scripting`.
+    script: Option<String>,
+    /// Extra emissive light the script asked for this tick.
+    glow: f32,
+    color: Color,
+    orbit_color: Color,
+    ring: Option<PlanetRing>,
+    polar_cap: Option<PolarCap>,
+    cloud_bands: Option<CloudBands>,
+    /// How far the cloud bands' turbulence has drifted, in noise cells.
+    band_drift: f32,
+    rim: Option<RimLight>,
+    reflectivity: f32,
+    /// Drawn with the `Material::ice` look.
+    ice: bool,
+    aurora: Option<Aurora>,
+    tags: BodyTags,
+    sphere: SphereShape,
+    /// Mesh built once for a fixed `sphere` shape; `None` when it is adaptive.
+    surface: Option<Mesh>,
+    audio: Option<AudioEmitter>,
+}
+
+impl Planet {
+    /// Radius of a sphere around the planet that also encloses its ring.
+    fn bounding_radius(&self) -> f32 {
+        self.ring
+            .as_ref()
+            .map_or(self.radius, |ring| ring.outer_radius.max(self.radius))
+    }
+
+    fn from_descriptor(desc: &PlanetDescriptor) -> Self {
+        let ring = desc.ring.as_ref().map(PlanetRing::new);
+        Self {
+            name: desc.name.clone(),
+            radius: desc.radius,
+            orbit_radius: desc.orbit_radius,
+            orbit_speed: desc.orbit_speed,
+            rotation_speed: desc.rotation_speed,
+            axial_tilt: desc.axial_tilt,
+            precession_rate: desc.precession_rate,
+            tidally_locked: desc.tidally_locked,
+            inclination: desc.inclination,
+            ascending_node: desc.ascending_node,
+            eccentricity: desc.eccentricity,
+            orbit_phase: desc.orbit_phase,
+            parent: desc.parent.clone(),
+            orbit_angle: desc.orbit_phase,
+            rotation: 0.0,
+            precession: 0.0,
+            orbit_center: Vec3::ZERO,
+            position: Vec3::ZERO,
+            script: desc.script.clone(),
+            glow: 0.0,
+            transform: Mat4::identity(),
+            color: desc.color,
+            orbit_color: desc.orbit_color,
+            ring,
+            polar_cap: desc.polar_cap,
+            cloud_bands: desc.cloud_bands,
+            band_drift: 0.0,
+            rim: desc.rim,
+            reflectivity: desc.reflectivity,
+            ice: desc.ice,
+            aurora: Aurora::new(desc.aurora),
+            tags: desc.tags,
+            sphere: desc.sphere,
+            surface: desc.sphere.mesh(),
+            audio: desc.audio,
+        }
+    }
+
+    /// The orbit plane is the ecliptic tilted by `inclination` about the line of
+    /// nodes, which lies along local X before being turned by `ascending_node`.
+    fn orbit_frame(&self) -> Mat4 {
+        Mat4::rotation_y(self.ascending_node) * Mat4::rotation_x(self.inclination)
+    }
+
+    fn orbit_point(&self, e_anomaly: f32) -> Vec3 {
+        self.orbit_center + self.orbit_offset(e_anomaly)
+    }
+
+    /// Point of the orbit at an eccentric anomaly, relative to the body it orbits.
+    fn orbit_offset(&self, e_anomaly: f32) -> Vec3 {
+        let minor = self.orbit_radius * (1.0 - self.eccentricity * self.eccentricity).sqrt();
+        let local = Vec4::new(
+            self.orbit_radius * (e_anomaly.cos() - self.eccentricity),
+            0.0,
+            minor * e_anomaly.sin(),
+            0.0,
+        );
+        (self.orbit_frame() * local).xyz()
+    }
+
+    /// Where the planet is along its orbit now, relative to the body it orbits.
+    fn current_offset(&self) -> Vec3 {
+        self.orbit_offset(eccentric_anomaly(self.orbit_angle, self.eccentricity))
+    }
+
+    /// Angle from periapsis to the planet's current position, seen from the focus.
+    fn true_anomaly(&self) -> f32 {
+        let e_anomaly = eccentric_anomaly(self.orbit_angle, self.eccentricity);
+        let minor = (1.0 - self.eccentricity * self.eccentricity).sqrt();
+        (minor * e_anomaly.sin()).atan2(e_anomaly.cos() - self.eccentricity)
+    }
+
+    /// Local transform of the spin node: the axis leans by the tilt towards the
+    /// precession angle and the planet spins about it, so the axis only moves as it
+    /// precesses. A locked planet instead turns with its orbit, and its tilt leans
+    /// about the line to the body it orbits.
+    fn spin_frame(&self) -> Mat4 {
+        if self.tidally_locked {
+            self.orbit_frame()
+                * Mat4::rotation_y(-self.true_anomaly())
+                * Mat4::rotation_x(self.axial_tilt)
+        } else {
+            Mat4::rotation_y(self.precession)
+                * Mat4::rotation_x(self.axial_tilt)
+                * Mat4::rotation_y(self.rotation)
+        }
+    }
+
+    /// Farthest the planet gets from the system's center, moons included.
+    fn reach(&self) -> f32 {
+        self.orbit_center.length() + self.orbit_radius * (1.0 + self.eccentricity) + self.radius
+    }
+
+    fn ring_shadow(&self) -> Option<RingShadow> {
+        self.ring.as_ref().map(|ring| RingShadow {
+            center: self.position,
+            normal: (ring.transform * Vec4::new(0.0, 1.0, 0.0, 0.0))
+                .xyz()
+                .normalized(),
+            bands: Rc::clone(&ring.bands),
+            opacity: 0.7,
+        })
+    }
+
+    fn descriptor(&self) -> PlanetDescriptor {
+        PlanetDescriptor {
+            name: self.name.clone(),
+            radius: self.radius,
+            orbit_radius: self.orbit_radius,
+            orbit_speed: self.orbit_speed,
+            rotation_speed: self.rotation_speed,
+            axial_tilt: self.axial_tilt,
+            precession_rate: self.precession_rate,
+            tidally_locked: self.tidally_locked,
+            inclination: self.inclination,
+            ascending_node: self.ascending_node,
+            eccentricity: self.eccentricity,
+            orbit_phase: self.orbit_phase,
+            parent: self.parent.clone(),
+            script: self.script.clone(),
+            color: self.color,
+            orbit_color: self.orbit_color,
+            ring: self.ring.as_ref().map(|ring| RingDescriptor {
+                bands: ring.bands.to_vec(),
+            }),
+            polar_cap: self.polar_cap,
+            cloud_bands: self.cloud_bands,
+            rim: self.rim,
+            reflectivity: self.reflectivity,
+            ice: self.ice,
+            aurora: self.aurora.as_ref().map_or(0.0, |aurora| aurora.strength),
+            tags: self.tags,
+            sphere: self.sphere,
+            audio: self.audio,
+        }
+    }
+
+    /// Replaces the authored parameters while keeping the current orbit and spin phase.
+    fn apply_descriptor(&mut self, desc: &PlanetDescriptor) {
+        let ring_changed = match (&self.ring, &desc.ring) {
+            (Some(ring), Some(ring_desc)) => {
+                ring.bands.len() != ring_desc.bands.len()
+                    || ring.bands.iter().zip(&ring_desc.bands).any(|(band, new)| {
+                        band.inner_radius != new.inner_radius
+                            || band.outer_radius != new.outer_radius
+                            || band.opacity != new.opacity
+                    })
+            }
+            (None, None) => false,
+            _ => true,
+        };
+        if ring_changed {
+            self.ring = desc.ring.as_ref().map(PlanetRing::new);
+        } else if let (Some(ring), Some(ring_desc)) = (self.ring.as_mut(), &desc.ring) {
+            ring.bands = ring_desc.bands.as_slice().into();
+        }
+        self.name = desc.name.clone();
+        self.radius = desc.radius;
+        self.orbit_radius = desc.orbit_radius;
+        self.orbit_speed = desc.orbit_speed;
+        self.rotation_speed = desc.rotation_speed;
+        self.axial_tilt = desc.axial_tilt;
+        self.precession_rate = desc.precession_rate;
+        self.tidally_locked = desc.tidally_locked;
+        self.inclination = desc.inclination;
+        self.ascending_node = desc.ascending_node;
+        self.eccentricity = desc.eccentricity;
+        self.orbit_phase = desc.orbit_phase;
+        self.parent = desc.parent.clone();
+        self.script = desc.script.clone();
+        self.color = desc.color;
+        self.orbit_color = desc.orbit_color;
+        self.polar_cap = desc.polar_cap;
+        self.cloud_bands = desc.cloud_bands;
+        self.rim = desc.rim;
+        self.reflectivity = desc.reflectivity;
+        self.ice = desc.ice;
+        match self.aurora.as_mut() {
+            Some(aurora) if desc.aurora > 0.0 => aurora.strength = desc.aurora,
+            _ => self.aurora = Aurora::new(desc.aurora),
+        }
+        self.tags = desc.tags;
+        if self.sphere != desc.sphere {
+            self.sphere = desc.sphere;
+            self.surface = desc.sphere.mesh();
+        }
+        self.audio = desc.audio;
+    }
+}
+
+impl CelestialBody for Planet {
+    fn update(&mut self, dt: f32) {
+        self.orbit_angle = advance_angle(self.orbit_angle, self.orbit_speed, dt);
+        self.rotation = advance_angle(self.rotation, self.rotation_speed, dt);
+        self.precession = advance_angle(self.precession, self.precession_rate, dt);
+        if self.cloud_bands.is_some() {
+            self.band_drift += CLOUD_BAND_DRIFT * dt;
+        }
+        if let Some(aurora) = self.aurora.as_mut() {
+            aurora.time += dt;
+        }
+        if let Some(ring) = self.ring.as_mut() {
+            ring.update(dt);
+        }
+    }
+
+    fn render_instances<'a>(
+        &'a self,
+        context: &BodyContext<'a>,
+        instances: &mut Vec<RenderInstance<'a>>,
+    ) {
+        let highlight = context.tag_filter.highlight(self.tags);
+        instances.push(RenderInstance {
+            mesh: context.sphere,
+            transform: self.transform,
+            material: Material {
+                // Capped planets carry their surface color in the mesh.
+                color: match self.polar_cap {
+                    Some(_) => Color::new(highlight, highlight, highlight),
+                    None => self.color * highlight,
+                },
+                emissive: (0.05 + self.glow) * highlight,
+                ring_shadow: self.ring_shadow(),
+                heat_glow: heat_glow(equilibrium_temperature(self.position, context.stars)),
+                rim: self.rim.map(|rim| RimLight {
+                    strength: rim.strength * highlight,
+                    ..rim
+                }),
+                reflectivity: self.reflectivity,
+                ice: self.ice,
+                albedo_map: self.cloud_bands.map(|bands| AlbedoMap::Bands {
+                    bands: CloudBands {
+                        color: bands.color * highlight,
+                        ..bands
+                    },
+                    drift: self.band_drift,
+                }),
+                ..Default::default()
+            },
+            wireframe: context.wireframe,
+        });
+        if let Some(aurora) = self.aurora.as_ref().filter(|_| !context.wireframe) {
+            instances.push(aurora.instance(self, context, highlight));
+        }
+        if let Some(ring) = self
+            .ring
+            .as_ref()
+            .filter(|_| context.ring_style == RingStyle::Mesh)
+        {
+            ring.render_instances(
+                &BodyContext {
+                    highlight,
+                    ..*context
+                },
+                instances,
+            );
+        }
+    }
+
+    fn warp_anchor(&self) -> Option<WarpAnchor<'_>> {
+        Some(WarpAnchor {
+            name: &self.name,
+            anchor: self.position + Vec3::new(0.0, self.radius * 0.5, self.radius + 6.0),
+            center: self.position,
+            radius: self.radius,
+        })
+    }
+
+    fn collider(&self) -> Option<Collider> {
+        Some(Collider {
+            center: self.position,
+            radius: self.radius + 3.0,
+        })
+    }
+
+    fn bounding_sphere(&self) -> Option<(Vec3, f32)> {
+        Some((self.position, self.bounding_radius()))
+    }
+
+    fn occluder(&self) -> Option<(Vec3, f32)> {
+        Some((self.position, self.radius))
+    }
+
+    fn sphere_mesh(&self, view_projection: &Mat4, viewport: (f32, f32)) -> Option<Cow<'_, Mesh>> {
+        let mut mesh = match &self.surface {
+            Some(surface) => Cow::Borrowed(surface),
+            None => Cow::Owned(Mesh::adaptive_sphere(
+                &self.transform,
+                view_projection,
+                viewport.0,
+                viewport.1,
+            )),
+        };
+        if let Some(cap) = self.polar_cap {
+            mesh.to_mut().paint(|vertex| cap.tint(vertex, self.color));
+        }
+        Some(mesh)
+    }
+}
+
+/// A ring or accretion disk: one flat annulus mesh per band, out to the last
+/// band's `outer_radius`.
+#[derive(Clone)]
+struct PlanetRing {
+    meshes: Vec<Mesh>,
+    bands: Rc<[RingBand]>,
+    particles: Vec<RingParticle>,
+    transform: Mat4,
+    outer_radius: f32,
+}
+
+/// One grain of the particle ring, in the ring's local frame (ring plane is XZ).
+#[derive(Clone, Copy)]
+struct RingParticle {
+    offset: Vec3,
+    size: i32,
+    shade: f32,
+    /// Index of the band the grain belongs to, which gives its color.
+    band: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RingStyle {
+    Mesh,
+    Particles,
+}
+
+impl RingStyle {
+    fn next(self) -> Self {
+        match self {
+            RingStyle::Mesh => RingStyle::Particles,
+            RingStyle::Particles => RingStyle::Mesh,
+        }
+    }
+}
+
+/// Radial density profile for particle rings: a few overlapping bands plus a
+/// Cassini-like gap, so the grain isn't uniform. `t` runs 0..1 from inner to outer edge.
+fn ring_density(t: f32) -> f32 {
+    let bands = 0.6 + 0.25 * (t * 31.0).sin() + 0.15 * (t * 83.0 + (t * 7.0).sin() * 2.0).sin();
+    let gap = 1.0 - (-((t - 0.62) * 28.0).powi(2)).exp() * 0.9;
+    (bands * gap).clamp(0.0, 1.0)
+}
+
+/// Scatters grains over the whole span of the bands, dropping those that land in a
+/// gap and thinning each band by its opacity.
+fn build_ring_particles(bands: &[RingBand]) -> Vec<RingParticle> {
+    let inner_radius = bands[0].inner_radius;
+    let outer_radius = bands[bands.len() - 1].outer_radius;
+    let mut rng = Lcg::new(inner_radius.to_bits() as u64 ^ ((outer_radius.to_bits() as u64) << 20));
+    let thickness = (outer_radius - inner_radius) * 0.015;
+    let mut particles = Vec::with_capacity(RING_PARTICLE_COUNT);
+    while particles.len() < RING_PARTICLE_COUNT {
+        // Uniform over the annulus area, then thinned by the density profile.
+        let u = rng.next_f32();
+        let radius = (inner_radius * inner_radius
+            + u * (outer_radius * outer_radius - inner_radius * inner_radius))
+            .sqrt();
+        let Some(band) = bands
+            .iter()
+            .position(|band| radius >= band.inner_radius && radius <= band.outer_radius)
+        else {
+            continue;
+        };
+        let t = (radius - inner_radius) / (outer_radius - inner_radius);
+        if rng.next_f32() > ring_density(t) * bands[band].opacity {
+            continue;
+        }
+        let angle = rng.next_f32() * TAU;
+        particles.push(RingParticle {
+            offset: Vec3::new(
+                angle.cos() * radius,
+                (rng.next_f32() - 0.5) * thickness,
+                angle.sin() * radius,
+            ),
+            size: if rng.next_f32() < 0.15 { 2 } else { 1 },
+            shade: 0.6 + rng.next_f32() * 0.5,
+            band,
+        });
+    }
+    particles
+}
+
+impl PlanetRing {
+    fn new(desc: &RingDescriptor) -> Self {
+        Self {
+            particles: build_ring_particles(&desc.bands),
+            ..Self::from_bands(&desc.bands, 72)
+        }
+    }
+
+    /// Ring meshes of `segments` each, without particles.
+    fn from_bands(bands: &[RingBand], segments: usize) -> Self {
+        Self {
+            meshes: bands
+                .iter()
+                .map(|band| Mesh::ring(band.inner_radius, band.outer_radius, segments))
+                .collect(),
+            bands: bands.into(),
+            particles: Vec::new(),
+            transform: Mat4::identity(),
+            outer_radius: bands[bands.len() - 1].outer_radius,
+        }
+    }
+}
+
+/// The ring rides on its planet's spin node, so it has no motion of its own.
+impl CelestialBody for PlanetRing {
+    fn update(&mut self, _dt: f32) {}
+
+    fn render_instances<'a>(
+        &'a self,
+        context: &BodyContext<'a>,
+        instances: &mut Vec<RenderInstance<'a>>,
+    ) {
+        for (mesh, band) in self.meshes.iter().zip(self.bands.iter()) {
+            instances.push(RenderInstance {
+                mesh,
+                transform: self.transform,
+                material: Material {
+                    color: band.color * context.highlight,
+                    emissive: 0.1 * context.highlight,
+                    double_sided: true,
+                    opacity: band.opacity,
+                    ..Default::default()
+                },
+                wireframe: context.wireframe,
+            });
+        }
+    }
+}
+
+struct Star {
+    name: String,
+    radius: f32,
+    orbit_radius: f32,
+    orbit_speed: f32,
     orbit_angle: f32,
     rotation: f32,
+    /// Seconds the surface has been boiling, which drives its granulation.
+    time: f32,
+    position: Vec3,
+    transform: Mat4,
+    color: Color,
+    light_color: Color,
+    light_intensity: f32,
+    prominences: Vec<Prominence>,
+    rng: Lcg,
+}
+
+/// A magnetic loop standing off a star's surface. Its footpoints lie `span` radians
+/// either side of `base` along the great circle towards `tangent`, on a unit star,
+/// and its apex reaches `height` radii above the surface.
+struct Prominence {
+    base: Vec3,
+    tangent: Vec3,
+    span: f32,
+    height: f32,
+    age: f32,
+    life: f32,
+    /// Small, bright and brief, and keeps climbing until it is gone instead of
+    /// settling into an arch.
+    flare: bool,
+}
+
+impl Prominence {
+    fn spawn(rng: &mut Lcg) -> Self {
+        let y = rng.next_f32() * 2.0 - 1.0;
+        let angle = rng.next_f32() * TAU;
+        let around = (1.0 - y * y).sqrt();
+        let base = Vec3::new(angle.cos() * around, y, angle.sin() * around);
+        let helper = if base.y.abs() < 0.9 {
+            Vec3::UP
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let side = base.cross(helper).normalized();
+        let turn = rng.next_f32() * TAU;
+        let tangent = side * turn.cos() + base.cross(side) * turn.sin();
+        let flare = rng.next_f32() < FLARE_CHANCE;
+        let (span, height, life) = if flare {
+            (0.08, 0.2, 1.2)
+        } else {
+            (0.2, 0.3, 5.0)
+        };
+        Self {
+            base,
+            tangent,
+            span: span * (1.0 + rng.next_f32()),
+            height: height * (1.0 + rng.next_f32() * 1.5),
+            age: 0.0,
+            life: life * (1.0 + rng.next_f32()),
+            flare,
+        }
+    }
+
+    /// Brightness from 0 to 1: it flashes up quickly and fades out over the last
+    /// third of its life.
+    fn strength(&self) -> f32 {
+        let onset = (self.age / (self.life * 0.1)).min(1.0);
+        let fade = ((self.life - self.age) / (self.life * 0.35)).clamp(0.0, 1.0);
+        onset * fade
+    }
+
+    /// Point `t` of the way from one footpoint to the other, on a unit star.
+    fn point(&self, t: f32) -> Vec3 {
+        let progress = self.age / self.life;
+        let lift = if self.flare {
+            progress.sqrt() * 1.5
+        } else {
+            smoothstep((progress * 4.0).min(1.0))
+        };
+        let angle = self.span * (2.0 * t - 1.0);
+        let direction = self.base * angle.cos() + self.tangent * angle.sin();
+        direction * (1.0 + self.height * lift * (PI * t).sin())
+    }
+}
+
+impl Star {
+    fn from_descriptor(desc: &StarDescriptor) -> Self {
+        Self {
+            name: desc.name.clone(),
+            radius: desc.radius,
+            orbit_radius: desc.orbit_radius,
+            orbit_speed: desc.orbit_speed,
+            orbit_angle: desc.orbit_phase,
+            rotation: 0.0,
+            time: 0.0,
+            position: Vec3::ZERO,
+            transform: Mat4::identity(),
+            color: desc.color,
+            light_color: desc.light_color,
+            light_intensity: desc.light_intensity,
+            prominences: Vec::new(),
+            rng: Lcg::from_name(&desc.name),
+        }
+    }
+
+    fn light(&self) -> Light {
+        Light {
+            position: self.position,
+            color: self.light_color,
+            intensity: self.light_intensity,
+        }
+    }
+}
+
+impl CelestialBody for Star {
+    fn update(&mut self, dt: f32) {
+        self.orbit_angle = advance_angle(self.orbit_angle, self.orbit_speed, dt);
+        self.rotation = advance_angle(self.rotation, 0.1, dt);
+        self.time += dt;
+        for prominence in self.prominences.iter_mut() {
+            prominence.age += dt;
+        }
+        self.prominences
+            .retain(|prominence| prominence.age < prominence.life);
+        if self.prominences.len() < PROMINENCE_MAX && self.rng.next_f32() < PROMINENCE_RATE * dt {
+            self.prominences.push(Prominence::spawn(&mut self.rng));
+        }
+        self.position = Vec3::new(
+            self.orbit_angle.cos() * self.orbit_radius,
+            0.0,
+            self.orbit_angle.sin() * self.orbit_radius,
+        );
+        self.transform = Mat4::translation(self.position)
+            * Mat4::rotation_y(self.rotation)
+            * Mat4::scale(Vec3::splat(self.radius));
+    }
+
+    fn render_instances<'a>(
+        &'a self,
+        context: &BodyContext<'a>,
+        instances: &mut Vec<RenderInstance<'a>>,
+    ) {
+        instances.push(RenderInstance {
+            mesh: context.sphere,
+            transform: self.transform,
+            material: Material {
+                color: self.color,
+                emissive: 0.75,
+                emissive_map: Some(EmissiveMap::Granulation {
+                    cells: 14.0,
+                    color: self.light_color * 0.35,
+                    time: self.time,
+                }),
+                limb_darkening: Some(STAR_LIMB),
+                ..Default::default()
+            },
+            wireframe: context.wireframe,
+        });
+    }
+
+    fn warp_anchor(&self) -> Option<WarpAnchor<'_>> {
+        Some(WarpAnchor {
+            name: &self.name,
+            anchor: self.position + Vec3::new(0.0, self.radius * 0.4, self.radius + 8.0),
+            center: self.position,
+            radius: self.radius,
+        })
+    }
+
+    fn collider(&self) -> Option<Collider> {
+        Some(Collider {
+            center: self.position,
+            radius: self.radius + 6.0,
+        })
+    }
+
+    fn occluder(&self) -> Option<(Vec3, f32)> {
+        Some((self.position, self.radius))
+    }
+}
+
+struct BlackHole {
+    name: String,
+    radius: f32,
+    orbit_radius: f32,
+    orbit_speed: f32,
+    orbit_angle: f32,
+    position: Vec3,
+    transform: Mat4,
+    disk: PlanetRing,
+    disk_outer: f32,
+    disk_tilt: f32,
+    disk_speed: f32,
+    disk_rotation: f32,
+    lensing_strength: f32,
+}
+
+impl BlackHole {
+    fn from_descriptor(desc: &BlackHoleDescriptor) -> Self {
+        Self {
+            name: desc.name.clone(),
+            radius: desc.radius,
+            orbit_radius: desc.orbit_radius,
+            orbit_speed: desc.orbit_speed,
+            orbit_angle: desc.orbit_phase,
+            position: Vec3::ZERO,
+            transform: Mat4::identity(),
+            disk: PlanetRing::from_bands(
+                &[RingBand {
+                    inner_radius: desc.disk_inner,
+                    outer_radius: desc.disk_outer,
+                    color: desc.disk_color,
+                    opacity: 1.0,
+                }],
+                96,
+            ),
+            disk_outer: desc.disk_outer,
+            disk_tilt: desc.disk_tilt,
+            disk_speed: desc.disk_speed,
+            disk_rotation: 0.0,
+            lensing_strength: desc.lensing_strength,
+        }
+    }
+
+    /// Event horizon exclusion: the camera is kept outside the accretion disk
+    /// rather than just the dark sphere, so it can never reach the horizon.
+    fn safe_radius(&self) -> f32 {
+        self.disk_outer.max(self.radius * 2.5) + 6.0
+    }
+}
+
+impl CelestialBody for BlackHole {
+    fn update(&mut self, dt: f32) {
+        self.orbit_angle = advance_angle(self.orbit_angle, self.orbit_speed, dt);
+        self.disk_rotation = advance_angle(self.disk_rotation, self.disk_speed, dt);
+        self.position = Vec3::new(
+            self.orbit_angle.cos() * self.orbit_radius,
+            0.0,
+            self.orbit_angle.sin() * self.orbit_radius,
+        );
+        self.transform = Mat4::translation(self.position) * Mat4::scale(Vec3::splat(self.radius));
+        self.disk.transform = Mat4::translation(self.position)
+            * Mat4::rotation_x(self.disk_tilt)
+            * Mat4::rotation_y(self.disk_rotation);
+    }
+
+    fn render_instances<'a>(
+        &'a self,
+        context: &BodyContext<'a>,
+        instances: &mut Vec<RenderInstance<'a>>,
+    ) {
+        instances.push(RenderInstance {
+            mesh: context.sphere,
+            transform: self.transform,
+            material: Material {
+                color: Color::new(0.0, 0.0, 0.0),
+                emissive: 0.0,
+                ..Default::default()
+            },
+            wireframe: context.wireframe,
+        });
+        for (mesh, band) in self.disk.meshes.iter().zip(self.disk.bands.iter()) {
+            instances.push(RenderInstance {
+                mesh,
+                transform: self.disk.transform,
+                material: Material {
+                    color: band.color,
+                    emissive: 0.9,
+                    double_sided: true,
+                    ..Default::default()
+                },
+                wireframe: context.wireframe,
+            });
+        }
+    }
+
+    fn warp_anchor(&self) -> Option<WarpAnchor<'_>> {
+        let standoff = self.safe_radius() + 4.0;
+        Some(WarpAnchor {
+            name: &self.name,
+            anchor: self.position + Vec3::new(0.0, standoff * 0.35, standoff),
+            center: self.position,
+            radius: self.disk_outer,
+        })
+    }
+
+    fn collider(&self) -> Option<Collider> {
+        Some(Collider {
+            center: self.position,
+            radius: self.safe_radius(),
+        })
+    }
+}
+
+/// Rocky nucleus meshes by seed, built for the first comet with that seed and
+/// shared with every later one, across theme switches and reloads.
+#[derive(Default)]
+struct AsteroidCache {
+    meshes: HashMap<u64, Rc<Mesh>>,
+}
+
+impl AsteroidCache {
+    fn get(&mut self, seed: u64) -> Rc<Mesh> {
+        Rc::clone(
+            self.meshes
+                .entry(seed)
+                .or_insert_with(|| Rc::new(Mesh::asteroid(seed))),
+        )
+    }
+}
+
+struct Comet {
+    name: String,
+    nucleus_radius: f32,
+    /// Rocky nucleus from `nucleus_seed`; without one the comet is drawn as a sphere.
+    mesh: Option<Rc<Mesh>>,
+    perihelion: f32,
+    semi_major: f32,
+    eccentricity: f32,
+    mean_motion: f32,
+    mean_anomaly: f32,
+    orientation: Mat4,
     position: Vec3,
+    velocity: Vec3,
     transform: Mat4,
     color: Color,
+    tail_color: Color,
     orbit_color: Color,
-    ring: Option<PlanetRing>,
+    tail: Vec<TailParticle>,
+    spawn_accumulator: f32,
+    rng: Lcg,
 }
 
-impl Planet {
-    fn from_descriptor(desc: &PlanetDescriptor) -> Self {
-        let ring = desc.ring.map(|ring_desc| PlanetRing {
-            mesh: Mesh::ring(ring_desc.inner_radius, ring_desc.outer_radius, 72),
-            transform: Mat4::identity(),
-            color: ring_desc.color,
-        });
+struct TailParticle {
+    position: Vec3,
+    velocity: Vec3,
+    age: f32,
+    life: f32,
+    beta: f32,
+}
+
+impl Comet {
+    fn from_descriptor(desc: &CometDescriptor, asteroids: &mut AsteroidCache) -> Self {
+        let semi_major = (desc.perihelion + desc.aphelion) * 0.5;
         Self {
-            name: desc.name,
-            radius: desc.radius,
-            orbit_radius: desc.orbit_radius,
-            orbit_speed: desc.orbit_speed,
-            rotation_speed: desc.rotation_speed,
-            axial_tilt: desc.axial_tilt,
-            orbit_angle: 0.0,
-            rotation: 0.0,
+            name: desc.name.clone(),
+            nucleus_radius: desc.nucleus_radius,
+            mesh: desc.nucleus_seed.map(|seed| asteroids.get(seed)),
+            perihelion: desc.perihelion,
+            semi_major,
+            eccentricity: (desc.aphelion - desc.perihelion) / (desc.aphelion + desc.perihelion),
+            mean_motion: desc.mean_motion,
+            mean_anomaly: desc.orbit_phase,
+            orientation: Mat4::rotation_y(desc.periapsis_angle)
+                * Mat4::rotation_x(desc.inclination),
             position: Vec3::ZERO,
+            velocity: Vec3::ZERO,
             transform: Mat4::identity(),
             color: desc.color,
+            tail_color: desc.tail_color,
             orbit_color: desc.orbit_color,
-            ring,
+            tail: Vec::new(),
+            spawn_accumulator: 0.0,
+            rng: Lcg::from_name(&desc.name),
         }
     }
+
+    /// Radius of a sphere around the comet that holds its whole nucleus; a rocky
+    /// one reaches past `nucleus_radius` where the noise pushes it out.
+    fn bounding_radius(&self) -> f32 {
+        self.mesh.as_ref().map_or(1.0, |mesh| mesh.bounds().reach()) * self.nucleus_radius
+    }
+
+    /// Point on the orbit ellipse for an eccentric anomaly, with the focus at the origin.
+    fn orbit_point(&self, e_anomaly: f32) -> Vec3 {
+        let minor = self.semi_major * (1.0 - self.eccentricity * self.eccentricity).sqrt();
+        let local = Vec3::new(
+            self.semi_major * (e_anomaly.cos() - self.eccentricity),
+            0.0,
+            minor * e_anomaly.sin(),
+        );
+        (self.orientation * Vec4::new(local.x, local.y, local.z, 1.0)).xyz()
+    }
 }
 
-#[derive(Clone)]
-struct PlanetRing {
-    mesh: Mesh,
-    transform: Mat4,
-    color: Color,
+/// Moves the nucleus along its orbit; `update_comet_tails` handles the tail, which
+/// needs the stars.
+impl CelestialBody for Comet {
+    fn update(&mut self, dt: f32) {
+        self.mean_anomaly = advance_angle(self.mean_anomaly, self.mean_motion, dt);
+        let previous = self.position;
+        self.position = self.orbit_point(eccentric_anomaly(self.mean_anomaly, self.eccentricity));
+        if dt > 0.0 {
+            self.velocity = (self.position - previous) / dt;
+        }
+        self.transform =
+            Mat4::translation(self.position) * Mat4::scale(Vec3::splat(self.nucleus_radius));
+    }
+
+    fn render_instances<'a>(
+        &'a self,
+        context: &BodyContext<'a>,
+        instances: &mut Vec<RenderInstance<'a>>,
+    ) {
+        instances.push(RenderInstance {
+            mesh: self.mesh.as_deref().unwrap_or(context.sphere),
+            transform: self.transform,
+            material: Material {
+                color: self.color,
+                emissive: 0.1,
+                heat_glow: heat_glow(equilibrium_temperature(self.position, context.stars)),
+                ..Default::default()
+            },
+            wireframe: context.wireframe,
+        });
+    }
+
+    fn warp_anchor(&self) -> Option<WarpAnchor<'_>> {
+        let standoff = self.bounding_radius() + 8.0;
+        Some(WarpAnchor {
+            name: &self.name,
+            anchor: self.position + Vec3::new(0.0, standoff * 0.4, standoff),
+            center: self.position,
+            radius: self.bounding_radius(),
+        })
+    }
+
+    fn collider(&self) -> Option<Collider> {
+        Some(Collider {
+            center: self.position,
+            radius: self.bounding_radius() + 2.0,
+        })
+    }
+
+    fn bounding_sphere(&self) -> Option<(Vec3, f32)> {
+        Some((self.position, self.bounding_radius()))
+    }
 }
 
-struct Star {
+struct Station {
+    name: String,
+    mesh: Mesh,
+    scale: f32,
+    orbit_radius: f32,
+    orbit_speed: f32,
+    orbit_angle: f32,
+    height: f32,
+    spin_speed: f32,
+    spin: f32,
+    /// Planet the station circles; the star system's center when `None`.
+    parent: Option<String>,
     position: Vec3,
-    radius: f32,
-    rotation: f32,
     transform: Mat4,
+    /// Behavior script and its emissive light, as on `Planet`.
+    #[cfg_attr(not(feature = "scripting"), allow(dead_code))]
+    script: Option<String>,
+    glow: f32,
     color: Color,
+    collision_radius: f32,
+    dock_offset: Vec3,
+    #[cfg_attr(not(feature = "audio"), allow(dead_code))]
+    audio: Option<AudioEmitter>,
+}
+
+impl Station {
+    fn from_descriptor(desc: &StationDescriptor) -> Result<Self, Box<dyn std::error::Error>> {
+        let mesh = load_mesh(&desc.mesh_path, &desc.import)?;
+        Ok(Self {
+            name: desc.name.clone(),
+            collision_radius: desc
+                .collision_radius
+                .unwrap_or_else(|| mesh.bounds().reach()),
+            mesh,
+            scale: desc.scale,
+            orbit_radius: desc.orbit_radius,
+            orbit_speed: desc.orbit_speed,
+            orbit_angle: desc.orbit_phase,
+            height: desc.height,
+            spin_speed: desc.spin_speed,
+            spin: 0.0,
+            parent: desc.parent.clone(),
+            position: Vec3::ZERO,
+            script: desc.script.clone(),
+            glow: 0.0,
+            transform: Mat4::identity(),
+            color: desc.color,
+            dock_offset: desc.dock_offset,
+            audio: desc.audio,
+        })
+    }
+
+    /// World-space airlock position; warping to the station parks the camera here.
+    fn dock_anchor(&self) -> Vec3 {
+        let offset = self.dock_offset;
+        (self.transform * Vec4::new(offset.x, offset.y, offset.z, 1.0)).xyz()
+    }
+}
+
+/// Advances the orbit and spin; `place_scene_graph` then hangs the station from its
+/// parent planet.
+impl CelestialBody for Station {
+    fn update(&mut self, dt: f32) {
+        self.orbit_angle = advance_angle(self.orbit_angle, self.orbit_speed, dt);
+        self.spin = advance_angle(self.spin, self.spin_speed, dt);
+    }
+
+    fn render_instances<'a>(
+        &'a self,
+        context: &BodyContext<'a>,
+        instances: &mut Vec<RenderInstance<'a>>,
+    ) {
+        instances.push(RenderInstance {
+            mesh: &self.mesh,
+            transform: self.transform,
+            material: Material {
+                color: self.color,
+                emissive: 0.15 + self.glow,
+                ..Default::default()
+            },
+            wireframe: context.wireframe,
+        });
+    }
+
+    fn warp_anchor(&self) -> Option<WarpAnchor<'_>> {
+        Some(WarpAnchor {
+            name: &self.name,
+            anchor: self.dock_anchor(),
+            center: self.position,
+            radius: self.collision_radius * self.scale,
+        })
+    }
+
+    fn collider(&self) -> Option<Collider> {
+        Some(Collider {
+            center: self.position,
+            radius: self.collision_radius * self.scale,
+        })
+    }
 }
 
 struct Material {
     color: Color,
     emissive: f32,
+    ring_shadow: Option<RingShadow>,
+    /// Added on the lit side only, scaled by how directly the surface faces a star.
+    heat_glow: Color,
+    /// Draw back faces too, shaded with the normal turned toward the viewer, so flat
+    /// geometry such as rings needs only one set of triangles.
+    double_sided: bool,
+    rim: Option<RimLight>,
+    albedo_map: Option<AlbedoMap>,
+    emissive_map: Option<EmissiveMap>,
+    /// Multiplies the finished color towards this tint as the surface turns edge-on
+    /// (linear in N·V), so a glowing sphere reads as a ball instead of a flat disk.
+    limb_darkening: Option<Color>,
+    /// Share of the lit color replaced by the sky mirrored along the view ray; 0 is matte.
+    reflectivity: f32,
+    /// Frozen surface: a sharp specular highlight, a reflection that grows towards the
+    /// silhouette (Schlick's Fresnel) and a blue subsurface tint along the terminator.
+    ice: bool,
+    /// Share of pixels drawn, picked by `SCREEN_DOOR` so overlapping surfaces need no
+    /// sorting; below 1.0 the surface shows what lies behind it.
+    opacity: f32,
+    /// Adds to what is already drawn instead of covering it and leaves the depth
+    /// buffer alone, for glows that should stay see-through. Drawn after opaque meshes.
+    additive: bool,
+}
+
+/// Color painted over a surface before lighting, sampled at the mesh-space position
+/// wherever the surface is lit: per pixel, or per vertex at that shading quality.
+#[derive(Clone, Copy)]
+enum AlbedoMap {
+    /// Gas-giant cloud bands around a unit sphere's Y axis.
+    Bands { bands: CloudBands, drift: f32 },
+}
+
+impl AlbedoMap {
+    fn paint(&self, base: Color, local: Vec3) -> Color {
+        match self {
+            AlbedoMap::Bands { bands, drift } => bands.tint(local.normalized(), base, *drift),
+        }
+    }
+}
+
+/// Emission painted over a surface, sampled per pixel at the mesh-space position and
+/// added after lighting, so it ignores both the lights and the base color.
+#[derive(Clone, Copy)]
+enum EmissiveMap {
+    /// Bright convection cells over a unit sphere, about `cells` across a radius. They
+    /// ride the mesh as it spins, and `time` cross-fades them into fresh cells.
+    Granulation { cells: f32, color: Color, time: f32 },
+    /// Round glowing patches such as engine nozzles.
+    Spots(&'static [EmissiveSpot]),
+    /// Rippling folds over `Mesh::aurora_crown`, fading with height and towards
+    /// `sun`, the direction of the nearest star in the mesh's frame.
+    Aurora { color: Color, time: f32, sun: Vec3 },
+}
+
+#[derive(Clone, Copy)]
+struct EmissiveSpot {
+    center: Vec3,
+    radius: f32,
+    color: Color,
+}
+
+impl EmissiveMap {
+    fn sample(&self, local: Vec3) -> Color {
+        match self {
+            EmissiveMap::Granulation { cells, color, time } => {
+                let phase = time * GRANULATION_RATE;
+                let (step, blend) = (phase.floor(), smoothstep(phase.fract()));
+                let cells = local * *cells;
+                let old = fractal_noise(cells + GRANULATION_SHIFT * step);
+                let new = fractal_noise(cells + GRANULATION_SHIFT * (step + 1.0));
+                let cell = old + (new - old) * blend;
+                *color * (cell * cell)
+            }
+            EmissiveMap::Spots(spots) => {
+                spots.iter().fold(Color::new(0.0, 0.0, 0.0), |sum, spot| {
+                    let falloff = (1.0 - (local - spot.center).length() / spot.radius).max(0.0);
+                    sum + spot.color * (falloff * falloff)
+                })
+            }
+            EmissiveMap::Aurora { color, time, sun } => {
+                let foot = AURORA_BASE * AURORA_OVAL.cos();
+                let lift = ((local.y.abs() - foot) / AURORA_HEIGHT).clamp(0.0, 1.0);
+                let around = local.z.atan2(local.x);
+                let ripple = fractal_noise(Vec3::new(
+                    around.cos() * 4.0,
+                    around.sin() * 4.0,
+                    local.y.signum() * 5.0 + time * 0.35,
+                ));
+                let folds = (around * 7.0 + ripple * 5.0 + time * 0.4).sin() * 0.5 + 0.5;
+                let fade = (1.0 - lift).powi(2) * (lift * 12.0).min(1.0);
+                let daylight = local.normalized().dot(*sun);
+                let night = 0.15 + 0.85 * smoothstep(((0.2 - daylight) * 2.0).clamp(0.0, 1.0));
+                *color * (ripple * (0.4 + 0.6 * folds) * fade * night * 3.0)
+            }
+        }
+    }
+}
+
+/// Light added where the surface turns away from the viewer, growing with
+/// (1 - |N·V|)³ so it hugs the silhouette. Lit and night sides get it alike.
+#[derive(Clone, Copy)]
+struct RimLight {
+    strength: f32,
+    color: Color,
+}
+
+impl RimLight {
+    fn glow(&self, facing: f32) -> Color {
+        let edge = 1.0 - facing.abs().min(1.0);
+        self.color * (self.strength * edge * edge * edge)
+    }
+}
+
+impl Material {
+    /// Unlit color of a surface point at mesh-space `local` whose vertex color is `tint`.
+    fn base(&self, tint: Color, local: Vec3) -> Color {
+        match &self.albedo_map {
+            Some(map) => map.paint(self.color * tint, local),
+            None => self.color * tint,
+        }
+    }
+
+    /// Lit color of a surface point whose unlit color is `base`.
+    fn shade(
+        &self,
+        base: Color,
+        world: Vec3,
+        normal: Vec3,
+        lights: &[Light],
+        style: RenderStyle,
+        eye: Vec3,
+    ) -> Color {
+        let ambient = 0.2;
+        let mut lighting = ambient;
+        let mut glow = Color::new(0.0, 0.0, 0.0);
+        let mut day_side: f32 = 0.0;
+        let mut ice = Color::new(0.0, 0.0, 0.0);
+        let to_eye = (eye - world).normalized();
+        for light in lights {
+            let to_light = (light.position - world).normalized();
+            let mut diffuse = normal.dot(to_light).max(0.0);
+            if let Some(ring) = &self.ring_shadow {
+                if diffuse > 0.0 {
+                    diffuse *= ring.transmittance(world, light.position);
+                }
+            }
+            lighting += diffuse * light.intensity;
+            day_side = day_side.max(diffuse);
+            glow = glow + light.color * (1.0 / lights.len() as f32);
+            if self.ice {
+                let across = normal.dot(to_light) / ICE_SUBSURFACE_WIDTH;
+                let half = (to_light + to_eye).normalized();
+                let highlight = if diffuse > 0.0 {
+                    normal.dot(half).max(0.0).powi(ICE_SHININESS) * ICE_SPECULAR
+                } else {
+                    0.0
+                };
+                ice = ice
+                    + ICE_SUBSURFACE * ((-across * across).exp() * light.intensity)
+                    + light.color * (style.quantize(highlight) * light.intensity);
+            }
+        }
+        let lighting = ambient + style.quantize(lighting - ambient);
+        let lit = base * lighting + glow * self.emissive + self.heat_glow * day_side + ice;
+        match &self.rim {
+            Some(rim) => lit + rim.glow(normal.dot(to_eye)),
+            None => lit,
+        }
+    }
+
+    /// Share of the lit color replaced by the reflected sky where the surface makes
+    /// `facing` (N·V) with the view.
+    fn reflection(&self, facing: f32) -> f32 {
+        if !self.ice {
+            return self.reflectivity;
+        }
+        let floor = self.reflectivity.max(ICE_REFLECTIVITY);
+        let grazing = (1.0 - facing.abs().min(1.0)).powi(5);
+        floor + (ICE_FRESNEL_MAX.max(floor) - floor) * grazing
+    }
+}
+
+/// Where lighting is evaluated for a triangle.
+#[derive(Clone, Copy)]
+enum Shading<'a> {
+    /// Every pixel is lit from its interpolated normal; `eye` is the camera position,
+    /// used for rim light, reflections and toon outlines.
+    PerPixel { lights: &'a [Light], eye: Vec3 },
+    /// Vertices already hold their lit color in `VertexOut::color`; pixels only
+    /// interpolate it (Gouraud shading) and add reflections.
+    PerVertex { eye: Vec3 },
+}
+
+impl Shading<'_> {
+    fn eye(self) -> Vec3 {
+        match self {
+            Shading::PerPixel { eye, .. } | Shading::PerVertex { eye } => eye,
+        }
+    }
+}
+
+/// Renderer-wide choice between per-pixel and per-vertex lighting.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ShadingQuality {
+    PerPixel,
+    /// Per vertex for instances under `GOURAUD_MAX_RADIUS` pixels, per pixel otherwise.
+    Adaptive,
+    PerVertex,
+}
+
+impl ShadingQuality {
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "pixel" => Some(ShadingQuality::PerPixel),
+            "adaptive" => Some(ShadingQuality::Adaptive),
+            "vertex" => Some(ShadingQuality::PerVertex),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ShadingQuality::PerPixel => "pixel",
+            ShadingQuality::Adaptive => "adaptive",
+            ShadingQuality::PerVertex => "vertex",
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            color: Color::new(1.0, 1.0, 1.0),
+            emissive: 0.0,
+            ring_shadow: None,
+            heat_glow: Color::new(0.0, 0.0, 0.0),
+            double_sided: false,
+            rim: None,
+            albedo_map: None,
+            emissive_map: None,
+            limb_darkening: None,
+            reflectivity: 0.0,
+            ice: false,
+            opacity: 1.0,
+            additive: false,
+        }
+    }
+}
+
+/// World-space ring bands that block light reaching the surface they are attached
+/// to; `opacity` is how much a fully solid band blocks.
+#[derive(Clone)]
+struct RingShadow {
+    center: Vec3,
+    normal: Vec3,
+    bands: Rc<[RingBand]>,
+    opacity: f32,
+}
+
+impl RingShadow {
+    /// Fraction of the light from `light_pos` that reaches `point` (1.0 = unshadowed).
+    fn transmittance(&self, point: Vec3, light_pos: Vec3) -> f32 {
+        let to_light = light_pos - point;
+        let denom = to_light.dot(self.normal);
+        if denom.abs() < 1e-5 {
+            return 1.0;
+        }
+        // Parametric hit with the ring plane; t in (0, 1) lies between surface and light.
+        let t = (self.center - point).dot(self.normal) / denom;
+        if t <= 0.0 || t >= 1.0 {
+            return 1.0;
+        }
+        let hit = point + to_light * t;
+        let r = (hit - self.center).length();
+        self.bands
+            .iter()
+            .find(|band| r >= band.inner_radius && r <= band.outer_radius)
+            .map_or(1.0, |band| 1.0 - self.opacity * band.opacity)
+    }
+}
+
+/// Visits the pixels of the line from `start` to `end` with Bresenham's algorithm,
+/// passing how far along the line each one lies, from 0 to 1. Only the part inside
+/// the `clip` rectangle (min and max corner) is walked, so a segment that mostly
+/// runs off screen costs only the pixels it shows.
+fn trace_line(start: Vec2, end: Vec2, clip: (Vec2, Vec2), mut visit: impl FnMut(i32, i32, f32)) {
+    let Some((t0, t1)) = clip_segment(start, end, clip) else {
+        return;
+    };
+    let at = |t: f32| {
+        (
+            start.x + (end.x - start.x) * t,
+            start.y + (end.y - start.y) * t,
+        )
+    };
+    let (from, to) = (at(t0), at(t1));
+    let mut x0 = from.0 as i32;
+    let mut y0 = from.1 as i32;
+    let x1 = to.0 as i32;
+    let y1 = to.1 as i32;
+    let dx = (x1 - x0).abs();
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let dy = -(y1 - y0).abs();
+    let sy = if y0 < y1 { 1 } else { -1 };
+    let steps = dx.max(-dy).max(1) as f32;
+    let mut err = dx + dy;
+    let mut step = 0;
+    loop {
+        visit(x0, y0, t0 + (t1 - t0) * (step as f32 / steps).min(1.0));
+        if x0 == x1 && y0 == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x0 += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y0 += sy;
+        }
+        step += 1;
+    }
+}
+
+/// The stretch of the segment from `start` to `end` that lies inside the `clip`
+/// rectangle, as the range of the line parameter (0 at `start`, 1 at `end`), using
+/// Liang-Barsky. `None` when the segment misses the rectangle.
+fn clip_segment(start: Vec2, end: Vec2, clip: (Vec2, Vec2)) -> Option<(f32, f32)> {
+    let (min, max) = clip;
+    if !(start.x.is_finite() && start.y.is_finite() && end.x.is_finite() && end.y.is_finite()) {
+        return None;
+    }
+    let (dx, dy) = (end.x - start.x, end.y - start.y);
+    let (mut t0, mut t1) = (0.0_f32, 1.0_f32);
+    for (p, q) in [
+        (-dx, start.x - min.x),
+        (dx, max.x - start.x),
+        (-dy, start.y - min.y),
+        (dy, max.y - start.y),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let r = q / p;
+        if p < 0.0 {
+            t0 = t0.max(r);
+        } else {
+            t1 = t1.min(r);
+        }
+        if t0 > t1 {
+            return None;
+        }
+    }
+    Some((t0, t1))
 }
 
 struct RenderInstance<'a> {
     mesh: &'a Mesh,
     transform: Mat4,
     material: Material,
+    /// Draw triangle edges instead of filling them.
+    wireframe: bool,
 }
 
+/// Point light emitted by a star; every star in the theme contributes one.
 struct Light {
-    direction: Vec3,
+    position: Vec3,
     color: Color,
     intensity: f32,
 }
 
+#[derive(Clone, Copy)]
 struct Camera {
     position: Vec3,
     yaw: f32,
     pitch: f32,
     fov: f32,
+    /// Half the view height in world units for an orthographic projection;
+    /// `None` for perspective.
+    orthographic: Option<f32>,
 }
 
 impl Camera {
@@ -606,10 +10853,40 @@ impl Camera {
             position,
             yaw: 0.5,
             pitch: 0.0,
-            fov: PI / 3.5,
+            fov: DEFAULT_FOV,
+            orthographic: None,
+        }
+    }
+
+    /// Orthographic views keep everything within `FAR_PLANE` on either side of the
+    /// camera, since a diagram has no eye for bodies to fall behind.
+    fn projection(&self, aspect: f32) -> Mat4 {
+        match self.orthographic {
+            Some(half_height) => {
+                Mat4::orthographic(half_height * aspect, half_height, -FAR_PLANE, FAR_PLANE)
+            }
+            None => Mat4::perspective(self.fov, aspect, NEAR_PLANE, FAR_PLANE),
+        }
+    }
+
+    /// Point the view rays converge on, for lighting and culling: the camera itself,
+    /// or far behind it for orthographic views.
+    fn eye(&self) -> Vec3 {
+        match self.orthographic {
+            Some(_) => self.position - self.forward() * ORTHO_EYE_DISTANCE,
+            None => self.position,
         }
     }
 
+    /// Screen pixels per world unit at `distance` in a view `height` pixels tall.
+    fn pixels_per_unit(&self, distance: f32, height: usize) -> f32 {
+        let half_extent = match self.orthographic {
+            Some(half_height) => half_height,
+            None => distance * (self.fov * 0.5).tan(),
+        };
+        height as f32 * 0.5 / half_extent
+    }
+
     fn forward(&self) -> Vec3 {
         let cos_pitch = self.pitch.cos();
         Vec3::new(
@@ -620,61 +10897,306 @@ impl Camera {
         .normalized()
     }
 
+    /// World-space origin and direction of the ray through pixel (`x`, `y`) of a
+    /// `width` x `height` view. Orthographic rays all run along the view direction.
+    fn ray(&self, x: f32, y: f32, width: f32, height: f32) -> (Vec3, Vec3) {
+        let forward = self.forward();
+        let right = forward.cross(Vec3::UP).normalized();
+        let up = right.cross(forward);
+        let ndc_x = x / width * 2.0 - 1.0;
+        let ndc_y = 1.0 - y / height * 2.0;
+        if let Some(half_height) = self.orthographic {
+            let half_width = half_height * width / height;
+            let origin = self.position + right * (ndc_x * half_width) + up * (ndc_y * half_height);
+            return (origin, forward);
+        }
+        let half_height = (self.fov * 0.5).tan();
+        let half_width = half_height * width / height;
+        let direction =
+            (forward + right * (ndc_x * half_width) + up * (ndc_y * half_height)).normalized();
+        (self.position, direction)
+    }
+
     fn view_matrix(&self) -> Mat4 {
         let forward = self.forward();
         Mat4::look_at(self.position, self.position + forward, Vec3::UP)
     }
 }
 
+/// Diagnostic channel shown in place of the shaded world, cycled with `B`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DebugView {
+    Shaded,
+    /// Distance along the depth buffer in grayscale, from white at the nearest pixel
+    /// to dark gray at the farthest, on a log scale so both close ships and distant
+    /// planets get contrast.
+    Depth,
+    /// Surface normal of the front-most fragment, with each axis mapped from -1..1 to 0..1.
+    Normals,
+    /// How many fragments the rasterizer produced for each pixel, hidden ones included.
+    Overdraw,
+}
+
+impl DebugView {
+    fn next(self) -> Self {
+        match self {
+            DebugView::Shaded => DebugView::Depth,
+            DebugView::Depth => DebugView::Normals,
+            DebugView::Normals => DebugView::Overdraw,
+            DebugView::Overdraw => DebugView::Shaded,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            DebugView::Shaded => "shaded",
+            DebugView::Depth => "depth",
+            DebugView::Normals => "normals",
+            DebugView::Overdraw => "overdraw",
+        }
+    }
+}
+
+/// Heat map for `DebugView::Overdraw`: black for untouched pixels, then blue, green,
+/// yellow and red as fragments pile up, saturating at `OVERDRAW_HOT`.
+fn overdraw_color(count: u16) -> Color {
+    if count == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let stops = [
+        Color::new(0.1, 0.2, 0.9),
+        Color::new(0.1, 0.8, 0.3),
+        Color::new(1.0, 0.9, 0.1),
+        Color::new(1.0, 0.15, 0.1),
+    ];
+    let t = ((count - 1) as f32 / (OVERDRAW_HOT - 1) as f32).min(1.0) * (stops.len() - 1) as f32;
+    let low = (t as usize).min(stops.len() - 2);
+    Color::lerp(stops[low], stops[low + 1], t - low as f32)
+}
+
 struct Renderer {
     width: usize,
     height: usize,
     color: Vec<u32>,
     depth: Vec<f32>,
     sky: Sky,
+    environment: EnvironmentMap,
     palette: Palette,
+    /// Applied to overlay drawing only (lines, text, HUD panels), not to shaded meshes.
+    accessibility: AccessibilityMode,
+    planet_orbits: OrbitCache,
+    comet_orbits: OrbitCache,
+    debug_view: DebugView,
+    shading: ShadingQuality,
+    /// Background stars are drawn as radial streaks this strong (0..1) during warps.
+    star_streak: f32,
+    /// Filled only while `debug_view` needs them; empty otherwise.
+    normals: Vec<u32>,
+    overdraw: Vec<u16>,
+    /// The pixels under the region `apply_lensing` is warping, as they were before
+    /// it started; kept between calls so each black hole reuses the allocation.
+    lensing_source: Vec<u32>,
+    stats: RenderStats,
+}
+
+/// What the renderer did with the meshes of the current frame, reset by
+/// `begin_frame`. Culled triangles are those dropped as far-side, clipped or
+/// back-facing; shaded pixels are the ones that passed the depth test.
+#[derive(Clone, Copy, Default)]
+#[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+struct RenderStats {
+    triangles_submitted: usize,
+    triangles_culled: usize,
+    triangles_rasterized: usize,
+    pixels_shaded: usize,
 }
 
 impl Renderer {
-    fn new(width: usize, height: usize, star_count: usize, palette: Palette) -> Self {
+    fn new(width: usize, height: usize, palette: Palette) -> Self {
+        let mut sky = Sky::new(width, height, &palette.starfield);
+        sky.set_background(&palette);
+        let environment = EnvironmentMap::new(&palette, &sky.stars);
         Self {
             width,
             height,
             color: vec![0; width * height],
             depth: vec![f32::INFINITY; width * height],
-            sky: Sky::new(width, height, star_count),
+            sky,
+            environment,
             palette,
+            accessibility: AccessibilityMode::Standard,
+            planet_orbits: OrbitCache::default(),
+            comet_orbits: OrbitCache::default(),
+            debug_view: DebugView::Shaded,
+            shading: ShadingQuality::PerPixel,
+            star_streak: 0.0,
+            normals: Vec::new(),
+            overdraw: Vec::new(),
+            lensing_source: Vec::new(),
+            stats: RenderStats::default(),
+        }
+    }
+
+    /// Reallocates every buffer for a new frame size, keeping the display settings.
+    fn resize(&mut self, width: usize, height: usize) {
+        let mut resized = Renderer::new(width, height, self.palette);
+        resized.accessibility = self.accessibility;
+        resized.debug_view = self.debug_view;
+        resized.shading = self.shading;
+        resized.star_streak = self.star_streak;
+        *self = resized;
+    }
+
+    fn begin_frame(&mut self, time: f32, camera: &Camera) {
+        self.depth.fill(f32::INFINITY);
+        self.sky.paint(
+            &mut self.color,
+            &self.palette,
+            time,
+            camera,
+            self.star_streak,
+        );
+        let pixels = self.width * self.height;
+        self.stats = RenderStats::default();
+        self.normals.clear();
+        self.overdraw.clear();
+        match self.debug_view {
+            DebugView::Normals => self.normals.resize(pixels, 0),
+            DebugView::Overdraw => self.overdraw.resize(pixels, 0),
+            DebugView::Shaded | DebugView::Depth => {}
+        }
+    }
+
+    /// Replaces the frame with the active `debug_view` channel. Called once the world
+    /// is drawn, so the HUD still goes on top.
+    fn present_debug_view(&mut self) {
+        match self.debug_view {
+            DebugView::Shaded => {}
+            DebugView::Depth => {
+                let (near, far) = (NEAR_PLANE, FAR_PLANE);
+                let log_distance = |depth: f32| {
+                    let ndc = depth * 2.0 - 1.0;
+                    (2.0 * far * near / ((far + near) - ndc * (far - near)))
+                        .max(near)
+                        .ln()
+                };
+                let (lowest, highest) = self
+                    .depth
+                    .iter()
+                    .filter(|depth| depth.is_finite())
+                    .map(|&depth| log_distance(depth))
+                    .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), d| {
+                        (lo.min(d), hi.max(d))
+                    });
+                let span = (highest - lowest).max(1e-3);
+                for (color, &depth) in self.color.iter_mut().zip(&self.depth) {
+                    if !depth.is_finite() {
+                        *color = 0;
+                        continue;
+                    }
+                    let gray = 1.0 - (log_distance(depth) - lowest) / span * 0.9;
+                    *color = Color::new(gray, gray, gray).to_u32();
+                }
+            }
+            DebugView::Normals => self.color.copy_from_slice(&self.normals),
+            DebugView::Overdraw => {
+                for (color, &count) in self.color.iter_mut().zip(&self.overdraw) {
+                    *color = overdraw_color(count).to_u32();
+                }
+            }
         }
     }
 
-    fn begin_frame(&mut self) {
-        self.depth.fill(f32::INFINITY);
-        self.sky.paint(&mut self.color, &self.palette);
-    }
-
     fn color_buffer(&self) -> &[u32] {
         &self.color
     }
 
-    fn set_palette(&mut self, palette: Palette) {
-        self.palette = palette;
+    /// Renderer at `scale` times this one's size with the same palette, accessibility
+    /// mode, debug view, shading quality and star streaks. It lives in `slot` and is
+    /// only rebuilt on a size change.
+    fn downscaled<'a>(&self, scale: f32, slot: &'a mut Option<Renderer>) -> &'a mut Renderer {
+        let width = ((self.width as f32 * scale).round() as usize).max(1);
+        let height = ((self.height as f32 * scale).round() as usize).max(1);
+        if slot
+            .as_ref()
+            .is_none_or(|scaled| scaled.width != width || scaled.height != height)
+        {
+            *slot = Some(Renderer::new(width, height, self.palette));
+        }
+        let scaled = slot.as_mut().expect("slot was just filled");
+        scaled.set_palette(self.palette);
+        scaled.accessibility = self.accessibility;
+        scaled.debug_view = self.debug_view;
+        scaled.shading = self.shading;
+        scaled.star_streak = self.star_streak;
+        scaled
     }
 
-    fn draw_ecliptic_band(&mut self) {
-        let band_height = (self.height as f32 * 0.1) as usize;
-        let center = self.height / 2;
-        for y in center - band_height..center + band_height {
-            if y >= self.height {
+    /// Copies `source`'s color into this frame with its top-left corner at (`x`, `y`).
+    fn blit(&mut self, source: &Renderer, x: i32, y: i32) {
+        for row in 0..source.height as i32 {
+            let ty = y + row;
+            if ty < 0 || ty >= self.height as i32 {
                 continue;
             }
-            let t = 1.0 - ((y as f32 - center as f32).abs() / band_height as f32).powi(2);
-            let overlay = self.palette.ecliptic * (0.35 * t);
-            for x in 0..self.width {
-                let idx = y * self.width + x;
-                let base = Color::from_u32(self.color[idx]);
-                self.color[idx] = base.blend_additive(overlay).to_u32();
+            for column in 0..source.width as i32 {
+                let tx = x + column;
+                if tx >= 0 && tx < self.width as i32 {
+                    self.color[ty as usize * self.width + tx as usize] =
+                        source.color[row as usize * source.width + column as usize];
+                }
+            }
+        }
+    }
+
+    /// Nearest-neighbour upscale of `source`'s color and depth over this whole
+    /// frame, so overlays drawn afterwards still depth-test against the world. The
+    /// counters come along, since the source drew this frame's meshes.
+    fn upscale_from(&mut self, source: &Renderer) {
+        self.stats = source.stats;
+        let columns: Vec<usize> = (0..self.width)
+            .map(|x| x * source.width / self.width)
+            .collect();
+        for y in 0..self.height {
+            let row = y * source.height / self.height * source.width;
+            let out = y * self.width;
+            for (x, &column) in columns.iter().enumerate() {
+                self.color[out + x] = source.color[row + column];
+                self.depth[out + x] = source.depth[row + column];
+            }
+        }
+    }
+
+    /// Blends the frame with `trail`, the previous blended frame, keeping `keep` of
+    /// it, and stores the result back into `trail`. A trail of the wrong size (the
+    /// first frame) is just refilled.
+    fn accumulate(&mut self, trail: &mut Vec<u32>, keep: f32) {
+        if trail.len() == self.color.len() {
+            let keep = (keep.clamp(0.0, 1.0) * 256.0) as u32;
+            let fresh = 256 - keep;
+            for (pixel, old) in self.color.iter_mut().zip(trail.iter()) {
+                let mix = |shift: u32| {
+                    let channel = |value: u32| (value >> shift) & 0xff;
+                    ((channel(*pixel) * fresh + channel(*old) * keep) >> 8) << shift
+                };
+                *pixel = mix(16) | mix(8) | mix(0);
             }
         }
+        trail.clear();
+        trail.extend_from_slice(&self.color);
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        if palette == self.palette {
+            return;
+        }
+        self.sky.view = None;
+        if palette.starfield != self.palette.starfield {
+            self.sky = Sky::new(self.width, self.height, &palette.starfield);
+        }
+        self.sky.set_background(&palette);
+        self.environment = EnvironmentMap::new(&palette, &self.sky.stars);
+        self.palette = palette;
     }
 
     fn render(
@@ -682,56 +11204,239 @@ impl Renderer {
         instances: &[RenderInstance],
         view_projection: &Mat4,
         camera: &Camera,
-        light: &Light,
+        lights: &[Light],
     ) {
-        for instance in instances {
-            self.draw_mesh(instance, view_projection, camera, light);
+        // Wireframes and additive glows go last: neither writes depth, so a filled
+        // mesh drawn afterwards would paint over them.
+        let last = |instance: &&RenderInstance| instance.wireframe || instance.material.additive;
+        for instance in instances.iter().filter(|instance| !last(instance)) {
+            self.draw_mesh(instance, view_projection, camera, lights);
+        }
+        for instance in instances.iter().filter(last) {
+            self.draw_mesh(instance, view_projection, camera, lights);
         }
     }
 
     fn project_point(&self, position: Vec3, vp: &Mat4) -> Option<Vec2> {
-        let clip = *vp * Vec4::new(position.x, position.y, position.z, 1.0);
-        if clip.w.abs() < 0.001 {
-            return None;
+        self.project(position, vp).map(|p| Vec2::new(p.x, p.y))
+    }
+
+    /// Screen-space projection that also returns the depth-buffer value in `z`.
+    fn project(&self, position: Vec3, vp: &Mat4) -> Option<Vec3> {
+        project_to_screen(position, vp, self.width as f32, self.height as f32)
+    }
+
+    /// Radially warps the frame around `center` (screen x/y plus depth). Pixels
+    /// inside `horizon` go black; out to `LENSING_REACH * horizon` they sample
+    /// further from the center, with the deflection fading to zero at the edge.
+    fn apply_lensing(&mut self, center: Vec3, horizon: f32, strength: f32) {
+        let reach = horizon * LENSING_REACH;
+        let x0 = (center.x - reach).floor().max(0.0) as usize;
+        let y0 = (center.y - reach).floor().max(0.0) as usize;
+        let x1 = ((center.x + reach).ceil().max(0.0) as usize).min(self.width);
+        let y1 = ((center.y + reach).ceil().max(0.0) as usize).min(self.height);
+        if x0 >= x1 || y0 >= y1 {
+            return;
         }
-        let inv_w = 1.0 / clip.w;
-        let ndc_x = clip.x * inv_w;
-        let ndc_y = clip.y * inv_w;
-        let ndc_z = clip.z * inv_w;
-        if ndc_z > 1.0 || ndc_z < -1.0 {
-            return None;
+        // Only the region is rewritten, so samples that land outside it can be read
+        // straight from the frame and only the region needs copying.
+        let region_width = x1 - x0;
+        self.lensing_source.clear();
+        for y in y0..y1 {
+            let row = y * self.width;
+            self.lensing_source
+                .extend_from_slice(&self.color[row + x0..row + x1]);
+        }
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let dx = x as f32 + 0.5 - center.x;
+                let dy = y as f32 + 0.5 - center.y;
+                let dist = (dx * dx + dy * dy).sqrt();
+                if dist >= reach {
+                    continue;
+                }
+                let idx = y * self.width + x;
+                if self.depth[idx] < center.z {
+                    continue;
+                }
+                if dist <= horizon {
+                    self.color[idx] = 0;
+                    continue;
+                }
+                let falloff = (1.0 - dist / reach).powi(2);
+                let deflection = strength * horizon * horizon / dist * falloff;
+                let scale = (dist + deflection) / dist;
+                let sx = (center.x + dx * scale).clamp(0.0, self.width as f32 - 1.0) as usize;
+                let sy = (center.y + dy * scale).clamp(0.0, self.height as f32 - 1.0) as usize;
+                self.color[idx] = if (x0..x1).contains(&sx) && (y0..y1).contains(&sy) {
+                    self.lensing_source[(sy - y0) * region_width + sx - x0]
+                } else {
+                    self.color[sy * self.width + sx]
+                };
+            }
         }
-        let screen_x = (ndc_x * 0.5 + 0.5) * (self.width as f32 - 1.0);
-        let screen_y = (1.0 - (ndc_y * 0.5 + 0.5)) * (self.height as f32 - 1.0);
-        Some(Vec2::new(screen_x, screen_y))
+    }
+
+    /// The whole frame as a `trace_line` clip rectangle.
+    fn viewport(&self) -> (Vec2, Vec2) {
+        (
+            Vec2::new(0.0, 0.0),
+            Vec2::new(self.width as f32, self.height as f32),
+        )
     }
 
     fn draw_line(&mut self, start: Vec2, end: Vec2, color: Color) {
-        let mut x0 = start.x as i32;
-        let mut y0 = start.y as i32;
-        let x1 = end.x as i32;
-        let y1 = end.y as i32;
-        let dx = (x1 - x0).abs();
-        let sx = if x0 < x1 { 1 } else { -1 };
-        let dy = -(y1 - y0).abs();
-        let sy = if y0 < y1 { 1 } else { -1 };
-        let mut err = dx + dy;
-        loop {
-            if x0 >= 0 && x0 < self.width as i32 && y0 >= 0 && y0 < self.height as i32 {
-                self.color[y0 as usize * self.width + x0 as usize] = color.to_u32();
+        self.draw_line_in(start, end, self.viewport(), color);
+    }
+
+    /// `draw_line` kept inside the `clip` rectangle, thickened pixels included.
+    fn draw_line_in(&mut self, start: Vec2, end: Vec2, clip: (Vec2, Vec2), color: Color) {
+        let packed = self.accessibility.remap(color).to_u32();
+        let thick = self.accessibility != AccessibilityMode::Standard;
+        let max = clip.1;
+        trace_line(start, end, clip, |x, y, _| {
+            self.put_pixel(x, y, packed);
+            if thick {
+                if (x + 1) as f32 <= max.x {
+                    self.put_pixel(x + 1, y, packed);
+                }
+                if (y + 1) as f32 <= max.y {
+                    self.put_pixel(x, y + 1, packed);
+                }
             }
-            if x0 == x1 && y0 == y1 {
-                break;
+        });
+    }
+
+    /// Depth-tested line between two projected points (screen x/y plus depth in
+    /// `z`), offset by `WIREFRAME_DEPTH_OFFSET`; does not write depth.
+    fn draw_depth_line(&mut self, start: Vec3, end: Vec3, packed: u32) {
+        let (width, height) = (self.width as i32, self.height as i32);
+        trace_line(
+            Vec2::new(start.x, start.y),
+            Vec2::new(end.x, end.y),
+            self.viewport(),
+            |x, y, t| {
+                if x < 0 || x >= width || y < 0 || y >= height {
+                    return;
+                }
+                let idx = y as usize * self.width + x as usize;
+                let depth = start.z + (end.z - start.z) * t - WIREFRAME_DEPTH_OFFSET;
+                if depth < self.depth[idx] {
+                    self.color[idx] = packed;
+                }
+            },
+        );
+    }
+
+    /// Depth-tested additive line between two projected points; does not write
+    /// depth.
+    fn draw_glow_line(&mut self, start: Vec3, end: Vec3, color: Color) {
+        let color = self.accessibility.remap(color);
+        let (width, height) = (self.width as i32, self.height as i32);
+        trace_line(
+            Vec2::new(start.x, start.y),
+            Vec2::new(end.x, end.y),
+            self.viewport(),
+            |x, y, t| {
+                if x < 0 || x >= width || y < 0 || y >= height {
+                    return;
+                }
+                let idx = y as usize * self.width + x as usize;
+                if start.z + (end.z - start.z) * t >= self.depth[idx] {
+                    return;
+                }
+                let base = Color::from_u32(self.color[idx]);
+                self.color[idx] = base.blend_additive(color).to_u32();
+            },
+        );
+    }
+
+    fn put_pixel(&mut self, x: i32, y: i32, packed: u32) {
+        if x >= 0 && x < self.width as i32 && y >= 0 && y < self.height as i32 {
+            self.color[y as usize * self.width + x as usize] = packed;
+        }
+    }
+
+    /// Blends a solid rectangle over the frame; `alpha` of 1.0 overwrites it.
+    fn fill_rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: Color, alpha: f32) {
+        let color = self.accessibility.remap(color);
+        let alpha = self.accessibility.panel_alpha(color, alpha);
+        let x0 = x.max(0);
+        let y0 = y.max(0);
+        let x1 = (x + w).min(self.width as i32);
+        let y1 = (y + h).min(self.height as i32);
+        for py in y0..y1 {
+            for px in x0..x1 {
+                let idx = py as usize * self.width + px as usize;
+                let base = Color::from_u32(self.color[idx]);
+                self.color[idx] = Color::lerp(base, color, alpha).to_u32();
+            }
+        }
+    }
+
+    /// Opaque counterpart of `draw_particle`: overwrites color and writes depth.
+    fn draw_splat(&mut self, screen: Vec3, color: Color, size: i32) {
+        let packed = color.to_u32();
+        let x0 = screen.x as i32 - size / 2;
+        let y0 = screen.y as i32 - size / 2;
+        for y in y0.max(0)..(y0 + size).min(self.height as i32) {
+            for x in x0.max(0)..(x0 + size).min(self.width as i32) {
+                let idx = y as usize * self.width + x as usize;
+                if screen.z >= self.depth[idx] {
+                    continue;
+                }
+                self.depth[idx] = screen.z;
+                self.color[idx] = packed;
             }
-            let e2 = 2 * err;
-            if e2 >= dy {
-                err += dy;
-                x0 += sx;
+        }
+    }
+
+    /// Depth-tested additive square of `size` pixels; does not write depth.
+    fn draw_particle(&mut self, screen: Vec3, color: Color, size: i32) {
+        let x0 = screen.x as i32 - size / 2;
+        let y0 = screen.y as i32 - size / 2;
+        for y in y0.max(0)..(y0 + size).min(self.height as i32) {
+            for x in x0.max(0)..(x0 + size).min(self.width as i32) {
+                let idx = y as usize * self.width + x as usize;
+                if screen.z >= self.depth[idx] {
+                    continue;
+                }
+                let base = Color::from_u32(self.color[idx]);
+                self.color[idx] = base.blend_additive(color).to_u32();
             }
-            if e2 <= dx {
-                err += dx;
-                y0 += sy;
+        }
+    }
+
+    fn text_width(&self, text: &str, scale: i32) -> i32 {
+        let count = text.chars().count() as i32;
+        if count == 0 {
+            0
+        } else {
+            (count * GLYPH_ADVANCE - (GLYPH_ADVANCE - GLYPH_WIDTH)) * scale
+        }
+    }
+
+    fn draw_text(&mut self, x: i32, y: i32, text: &str, color: Color, scale: i32) {
+        let packed = self.accessibility.remap(color).to_u32();
+        let mut cursor_x = x;
+        for ch in text.chars() {
+            for (row, bits) in glyph(ch).iter().enumerate() {
+                for col in 0..GLYPH_WIDTH {
+                    if bits & (0b10000 >> col) == 0 {
+                        continue;
+                    }
+                    for sy in 0..scale {
+                        for sx in 0..scale {
+                            self.put_pixel(
+                                cursor_x + col * scale + sx,
+                                y + row as i32 * scale + sy,
+                                packed,
+                            );
+                        }
+                    }
+                }
             }
+            cursor_x += GLYPH_ADVANCE * scale;
         }
     }
 
@@ -740,59 +11445,254 @@ impl Renderer {
         instance: &RenderInstance,
         view_projection: &Mat4,
         camera: &Camera,
-        light: &Light,
+        lights: &[Light],
     ) {
-        let mut transformed = Vec::with_capacity(instance.mesh.vertices.len());
-        for (position, normal) in instance
-            .mesh
-            .vertices
-            .iter()
-            .zip(instance.mesh.normals.iter())
-        {
-            let world_pos = instance.transform * Vec4::new(position.x, position.y, position.z, 1.0);
-            let world = world_pos.xyz();
-            let clip = *view_projection * Vec4::new(world.x, world.y, world.z, 1.0);
-            if clip.w.abs() < 0.001 {
-                transformed.push(None);
-                continue;
+        let (center, radius) = instance.mesh.bounds().world_sphere(&instance.transform);
+        if sphere_outside_frustum(view_projection, center, radius) {
+            self.stats.triangles_submitted += instance.mesh.indices.len();
+            self.stats.triangles_culled += instance.mesh.indices.len();
+            return;
+        }
+        #[cfg(feature = "tracing")]
+        let vertex_span = tracing::trace_span!("vertex").entered();
+        // Spheres drop their far hemisphere up front, and only vertices of surviving
+        // triangles get transformed.
+        let eye = camera.eye();
+        let far_side = instance.mesh.far_hemisphere(&instance.transform, eye);
+        let culled = |indices: &[usize; 3]| {
+            !far_side.is_empty() && indices.iter().all(|&index| far_side[index])
+        };
+        let mut needed = vec![far_side.is_empty(); instance.mesh.vertices.len()];
+        if !far_side.is_empty() {
+            for indices in instance
+                .mesh
+                .indices
+                .iter()
+                .filter(|indices| !culled(indices))
+            {
+                for &index in indices {
+                    needed[index] = true;
+                }
+            }
+        }
+
+        let normal_matrix = instance.transform.normal_matrix();
+        let mut transformed = vec![None; instance.mesh.vertices.len()];
+        let mut batch = [0; VERTEX_LANES];
+        let mut filled = 0;
+        for index in (0..needed.len()).filter(|&index| needed[index]) {
+            batch[filled] = index;
+            filled += 1;
+            if filled == VERTEX_LANES {
+                self.transform_batch(
+                    instance,
+                    view_projection,
+                    &normal_matrix,
+                    &batch,
+                    &mut transformed,
+                );
+                filled = 0;
+            }
+        }
+        self.transform_batch(
+            instance,
+            view_projection,
+            &normal_matrix,
+            &batch[..filled],
+            &mut transformed,
+        );
+
+        let material = &instance.material;
+        let tint = |index: usize| {
+            instance
+                .mesh
+                .colors
+                .get(index)
+                .copied()
+                .unwrap_or(Color::new(1.0, 1.0, 1.0))
+        };
+        let per_vertex = self.lit_per_vertex(instance, camera);
+        let shading = if per_vertex {
+            for (index, vertex) in transformed.iter_mut().enumerate() {
+                if let Some(v) = vertex {
+                    v.color = material.shade(
+                        material.base(tint(index), v.local),
+                        v.world,
+                        v.normal,
+                        lights,
+                        self.palette.style,
+                        eye,
+                    );
+                }
             }
-            let inv_w = 1.0 / clip.w;
-            let ndc_x = clip.x * inv_w;
-            let ndc_y = clip.y * inv_w;
-            let ndc_z = clip.z * inv_w;
-            if ndc_z > 1.0 || ndc_z < -1.0 {
-                transformed.push(None);
+            Shading::PerVertex { eye }
+        } else {
+            Shading::PerPixel { lights, eye }
+        };
+        #[cfg(feature = "tracing")]
+        drop(vertex_span);
+
+        #[cfg(feature = "tracing")]
+        let _raster_span = tracing::trace_span!("raster").entered();
+        let mut drawn = 0;
+        for indices in &instance.mesh.indices {
+            if culled(indices) {
                 continue;
             }
-            let screen_x = (ndc_x * 0.5 + 0.5) * (self.width as f32 - 1.0);
-            let screen_y = (1.0 - (ndc_y * 0.5 + 0.5)) * (self.height as f32 - 1.0);
-            let normal_world = (instance.transform * Vec4::new(normal.x, normal.y, normal.z, 0.0))
-                .xyz()
+            let Some(v0) = transformed[indices[0]] else {
+                continue;
+            };
+            let Some(v1) = transformed[indices[1]] else {
+                continue;
+            };
+            let Some(v2) = transformed[indices[2]] else {
+                continue;
+            };
+            let view_dir = (eye - v0.world).normalized();
+            let normal = (v1.world - v0.world)
+                .cross(v2.world - v0.world)
                 .normalized();
-            transformed.push(Some(VertexOut {
-                screen: Vec3::new(screen_x, screen_y, ndc_z),
-                world,
-                normal: normal_world,
-                inv_w,
-            }));
+            let (v0, v1, v2) = if normal.dot(view_dir) > 0.0 {
+                (v0, v1, v2)
+            } else if material.double_sided {
+                let flip = |v: VertexOut, index: usize| {
+                    let normal = -v.normal;
+                    let color = if per_vertex {
+                        material.shade(
+                            material.color * tint(index),
+                            v.world,
+                            normal,
+                            lights,
+                            self.palette.style,
+                            eye,
+                        )
+                    } else {
+                        v.color
+                    };
+                    VertexOut { normal, color, ..v }
+                };
+                (
+                    flip(v0, indices[0]),
+                    flip(v1, indices[1]),
+                    flip(v2, indices[2]),
+                )
+            } else {
+                continue;
+            };
+            if instance.wireframe {
+                // Lifted toward white so dark meshes such as black holes stay visible.
+                let packed =
+                    Color::lerp(instance.material.color, Color::new(1.0, 1.0, 1.0), 0.25).to_u32();
+                self.draw_depth_line(v0.screen, v1.screen, packed);
+                self.draw_depth_line(v1.screen, v2.screen, packed);
+                self.draw_depth_line(v2.screen, v0.screen, packed);
+            } else {
+                self.rasterize_triangle(&v0, &v1, &v2, material, shading);
+            }
+            drawn += 1;
         }
+        self.stats.triangles_submitted += instance.mesh.indices.len();
+        self.stats.triangles_culled += instance.mesh.indices.len() - drawn;
+        self.stats.triangles_rasterized += drawn;
+    }
 
-        for indices in &instance.mesh.indices {
-            let Some(v0) = transformed[indices[0]] else { continue; };
-            let Some(v1) = transformed[indices[1]] else { continue; };
-            let Some(v2) = transformed[indices[2]] else { continue; };
-            let view_dir = (camera.position - v0.world).normalized();
-            let normal = (v1.world - v0.world).cross(v2.world - v0.world).normalized();
-            if normal.dot(view_dir) <= 0.0 {
+    /// Whether `instance` is lit per vertex under the current `shading` quality.
+    /// The toon style always lights per pixel, since interpolating between vertices
+    /// would blur its bands.
+    fn lit_per_vertex(&self, instance: &RenderInstance, camera: &Camera) -> bool {
+        if self.palette.style != RenderStyle::Shaded {
+            return false;
+        }
+        match self.shading {
+            ShadingQuality::PerPixel => false,
+            ShadingQuality::PerVertex => true,
+            ShadingQuality::Adaptive => {
+                let m = &instance.transform.m;
+                let scale = (0..3)
+                    .map(|col| Vec3::new(m[0][col], m[1][col], m[2][col]).length())
+                    .fold(0.0, f32::max);
+                let extent = if instance.mesh.sphere {
+                    1.0
+                } else {
+                    instance
+                        .mesh
+                        .vertices
+                        .iter()
+                        .map(|vertex| vertex.length())
+                        .fold(0.0, f32::max)
+                };
+                let radius = extent * scale;
+                let center = Vec3::new(m[0][3], m[1][3], m[2][3]);
+                let distance = (center - camera.position).length();
+                if distance <= radius {
+                    return false;
+                }
+                radius * camera.pixels_per_unit(distance, self.height) < GOURAUD_MAX_RADIUS
+            }
+        }
+    }
+
+    /// Transforms up to `VERTEX_LANES` vertices of `instance` at once, keeping one
+    /// array per component so the matrix products, normalization and perspective
+    /// divide run lane-parallel and compile to vector instructions.
+    fn transform_batch(
+        &self,
+        instance: &RenderInstance,
+        view_projection: &Mat4,
+        normal_matrix: &Mat4,
+        batch: &[usize],
+        transformed: &mut [Option<VertexOut>],
+    ) {
+        let mut position = [[0.0; VERTEX_LANES]; 3];
+        let mut normal = [[0.0; VERTEX_LANES]; 3];
+        for (lane, &index) in batch.iter().enumerate() {
+            let (p, n) = (instance.mesh.vertices[index], instance.mesh.normals[index]);
+            [position[0][lane], position[1][lane], position[2][lane]] = [p.x, p.y, p.z];
+            [normal[0][lane], normal[1][lane], normal[2][lane]] = [n.x, n.y, n.z];
+        }
+        let world: [Lanes; 3] = instance.transform.transform_lanes(&position, 1.0);
+        let clip: [Lanes; 4] = view_projection.transform_lanes(&world, 1.0);
+        let mut normal: [Lanes; 3] = normal_matrix.transform_lanes(&normal, 0.0);
+        let mut length = [0.0; VERTEX_LANES];
+        for lane in 0..VERTEX_LANES {
+            length[lane] = (normal[0][lane] * normal[0][lane]
+                + normal[1][lane] * normal[1][lane]
+                + normal[2][lane] * normal[2][lane])
+                .sqrt();
+        }
+        for component in &mut normal {
+            for lane in 0..VERTEX_LANES {
+                // Divide unconditionally and select afterwards so the loop stays branch-free.
+                let scaled = component[lane] / length[lane];
+                component[lane] = if length[lane] > 0.0 { scaled } else { 0.0 };
+            }
+        }
+        let mut inv_w = [0.0; VERTEX_LANES];
+        let mut screen = [[0.0; VERTEX_LANES]; 3];
+        let (width, height) = (self.width as f32 - 1.0, self.height as f32 - 1.0);
+        for lane in 0..VERTEX_LANES {
+            inv_w[lane] = 1.0 / clip[3][lane];
+            screen[0][lane] = (clip[0][lane] * inv_w[lane] * 0.5 + 0.5) * width;
+            screen[1][lane] = (1.0 - (clip[1][lane] * inv_w[lane] * 0.5 + 0.5)) * height;
+            screen[2][lane] = clip[2][lane] * inv_w[lane];
+        }
+        for (lane, &index) in batch.iter().enumerate() {
+            if clip[3][lane].abs() < 0.001 || !(-1.0..=1.0).contains(&screen[2][lane]) {
                 continue;
             }
-            self.rasterize_triangle(
-                &v0,
-                &v1,
-                &v2,
-                &instance.material,
-                light,
-            );
+            transformed[index] = Some(VertexOut {
+                screen: Vec3::new(screen[0][lane], screen[1][lane], screen[2][lane]),
+                world: Vec3::new(world[0][lane], world[1][lane], world[2][lane]),
+                normal: Vec3::new(normal[0][lane], normal[1][lane], normal[2][lane]),
+                color: instance
+                    .mesh
+                    .colors
+                    .get(index)
+                    .copied()
+                    .unwrap_or(Color::new(1.0, 1.0, 1.0)),
+                local: instance.mesh.vertices[index],
+                inv_w: inv_w[lane],
+            });
         }
     }
 
@@ -802,7 +11702,7 @@ impl Renderer {
         v1: &VertexOut,
         v2: &VertexOut,
         material: &Material,
-        light: &Light,
+        shading: Shading,
     ) {
         let min_x = v0.screen.x.min(v1.screen.x).min(v2.screen.x).floor().max(0.0) as i32;
         let max_x = v0.screen.x.max(v1.screen.x).max(v2.screen.x).ceil().min(self.width as f32 - 1.0) as i32;
@@ -811,61 +11711,764 @@ impl Renderer {
         if min_x >= max_x || min_y >= max_y {
             return;
         }
-        let area = edge(&v0.screen, &v1.screen, &v2.screen);
-        if area.abs() < 1e-4 {
+        let fixed = |v: &VertexOut| {
+            let scale = (1 << SUBPIXEL_BITS) as f32;
+            (
+                (v.screen.x * scale).round() as i64,
+                (v.screen.y * scale).round() as i64,
+            )
+        };
+        let (p0, p1, p2) = (fixed(v0), fixed(v1), fixed(v2));
+        let area = (p2.0 - p0.0) * (p1.1 - p0.1) - (p2.1 - p0.1) * (p1.0 - p0.0);
+        if area == 0 {
+            return;
+        }
+        let sign = area.signum();
+        let edges = [
+            EdgeStepper::new(p1, p2, sign),
+            EdgeStepper::new(p2, p0, sign),
+            EdgeStepper::new(p0, p1, sign),
+        ];
+        let inv_area = 1.0 / area.abs() as f32;
+        for tile_y in (min_y..=max_y).step_by(RASTER_TILE) {
+            let last_y = (tile_y + RASTER_TILE as i32 - 1).min(max_y);
+            for tile_x in (min_x..=max_x).step_by(RASTER_TILE) {
+                let last_x = (tile_x + RASTER_TILE as i32 - 1).min(max_x);
+                let mut covered = true;
+                let mut empty = false;
+                for edge in &edges {
+                    let corners = [
+                        edge.at(tile_x, tile_y),
+                        edge.at(last_x, tile_y),
+                        edge.at(tile_x, last_y),
+                        edge.at(last_x, last_y),
+                    ];
+                    let lowest = corners.iter().min().copied().unwrap_or(0) + edge.bias;
+                    let highest = corners.iter().max().copied().unwrap_or(0) + edge.bias;
+                    empty |= highest < 0;
+                    covered &= lowest >= 0;
+                }
+                if empty {
+                    continue;
+                }
+                for y in tile_y..=last_y {
+                    let mut w = edges.each_ref().map(|edge| edge.at(tile_x, y));
+                    for x in tile_x..=last_x {
+                        if covered || (0..3).all(|i| w[i] + edges[i].bias >= 0) {
+                            let weights = w.map(|value| value as f32 * inv_area);
+                            self.shade_pixel(x, y, weights, [v0, v1, v2], material, shading);
+                        }
+                        for i in 0..3 {
+                            w[i] += edges[i].step_x;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Depth-tests and shades one pixel from its barycentric `weights` in screen
+    /// space, interpolating perspective-correctly through each vertex's `inv_w`.
+    fn shade_pixel(
+        &mut self,
+        x: i32,
+        y: i32,
+        [w0, w1, w2]: [f32; 3],
+        [v0, v1, v2]: [&VertexOut; 3],
+        material: &Material,
+        shading: Shading,
+    ) {
+        let w_sum = v0.inv_w * w0 + v1.inv_w * w1 + v2.inv_w * w2;
+        if w_sum <= 0.0 {
+            return;
+        }
+        let ndc_depth = (v0.screen.z * v0.inv_w * w0
+            + v1.screen.z * v1.inv_w * w1
+            + v2.screen.z * v2.inv_w * w2)
+            / w_sum;
+        let depth = ndc_depth * 0.5 + 0.5;
+        if material.opacity < 1.0 && material.opacity <= SCREEN_DOOR[y as usize % 4][x as usize % 4]
+        {
+            return;
+        }
+        let idx = y as usize * self.width + x as usize;
+        if let Some(count) = self.overdraw.get_mut(idx) {
+            *count = count.saturating_add(1);
+        }
+        if depth >= self.depth[idx] {
             return;
         }
-        for y in min_y..=max_y {
-            for x in min_x..=max_x {
-                let px = x as f32 + 0.5;
-                let py = y as f32 + 0.5;
-                let mut w0 = edge(&v1.screen, &v2.screen, &Vec3::new(px, py, 0.0));
-                let mut w1 = edge(&v2.screen, &v0.screen, &Vec3::new(px, py, 0.0));
-                let mut w2 = edge(&v0.screen, &v1.screen, &Vec3::new(px, py, 0.0));
-                if (w0 < 0.0 && w1 < 0.0 && w2 < 0.0) || (w0 > 0.0 && w1 > 0.0 && w2 > 0.0) {
-                    w0 /= area;
-                    w1 /= area;
-                    w2 /= area;
-                    let w_sum = v0.inv_w * w0 + v1.inv_w * w1 + v2.inv_w * w2;
-                    if w_sum <= 0.0 {
-                        continue;
-                    }
-                    let ndc_depth =
-                        (v0.screen.z * v0.inv_w * w0
-                            + v1.screen.z * v1.inv_w * w1
-                            + v2.screen.z * v2.inv_w * w2)
-                            / w_sum;
-                    let depth = ndc_depth * 0.5 + 0.5;
-                    let idx = y as usize * self.width + x as usize;
-                    if depth >= self.depth[idx] {
-                        continue;
+        if !material.additive {
+            self.depth[idx] = depth;
+        }
+        self.stats.pixels_shaded += 1;
+        let weights = [
+            v0.inv_w * w0 / w_sum,
+            v1.inv_w * w1 / w_sum,
+            v2.inv_w * w2 / w_sum,
+        ];
+        let normal = || {
+            (v0.normal * weights[0] + v1.normal * weights[1] + v2.normal * weights[2]).normalized()
+        };
+        if let Some(packed) = self.normals.get_mut(idx).filter(|_| !material.additive) {
+            let normal = normal();
+            *packed = Color::new(
+                normal.x * 0.5 + 0.5,
+                normal.y * 0.5 + 0.5,
+                normal.z * 0.5 + 0.5,
+            )
+            .to_u32();
+        }
+        let shaded = match shading {
+            Shading::PerVertex { .. } => {
+                v0.color * weights[0] + v1.color * weights[1] + v2.color * weights[2]
+            }
+            Shading::PerPixel { lights, eye } => {
+                let world = v0.world * weights[0] + v1.world * weights[1] + v2.world * weights[2];
+                let normal = normal();
+                if let RenderStyle::Toon { outline, .. } = self.palette.style {
+                    // Flat double-sided geometry has no silhouette to outline.
+                    let facing = normal.dot((eye - world).normalized()).abs();
+                    if !material.double_sided && facing < TOON_OUTLINE_THRESHOLD {
+                        self.color[idx] = outline.to_u32();
+                        return;
+                    }
+                }
+                // Most meshes carry no vertex colors, so skip the interpolation when all
+                // three corners agree.
+                let tint = if v0.color == v1.color && v1.color == v2.color {
+                    v0.color
+                } else {
+                    v0.color * weights[0] + v1.color * weights[1] + v2.color * weights[2]
+                };
+                let base = match material.albedo_map {
+                    Some(_) => material.base(
+                        tint,
+                        v0.local * weights[0] + v1.local * weights[1] + v2.local * weights[2],
+                    ),
+                    None => material.color * tint,
+                };
+                material.shade(base, world, normal, lights, self.palette.style, eye)
+            }
+        };
+        let shaded = if material.reflectivity > 0.0 || material.ice {
+            let world = v0.world * weights[0] + v1.world * weights[1] + v2.world * weights[2];
+            let normal = normal();
+            let view = (world - shading.eye()).normalized();
+            let reflected = view - normal * (2.0 * view.dot(normal));
+            Color::lerp(
+                shaded,
+                self.environment.sample(reflected),
+                material.reflection(view.dot(normal)),
+            )
+        } else {
+            shaded
+        };
+        let shaded = match &material.emissive_map {
+            Some(map) => {
+                let local = v0.local * weights[0] + v1.local * weights[1] + v2.local * weights[2];
+                shaded + map.sample(local)
+            }
+            None => shaded,
+        };
+        let shaded = match material.limb_darkening {
+            Some(limb) => {
+                let world = v0.world * weights[0] + v1.world * weights[1] + v2.world * weights[2];
+                let facing = normal().dot((shading.eye() - world).normalized()).abs();
+                shaded * Color::lerp(limb, Color::new(1.0, 1.0, 1.0), facing.min(1.0))
+            }
+            None => shaded,
+        };
+        self.color[idx] = if material.additive {
+            Color::from_u32(self.color[idx])
+                .blend_additive(shaded)
+                .to_u32()
+        } else {
+            shaded.to_u32()
+        };
+    }
+}
+
+/// 5x7 bitmap glyphs, one row per byte with the leftmost pixel in bit 4.
+/// Lowercase letters share the uppercase shapes; unknown characters render as `?`.
+fn glyph(ch: char) -> [u8; 7] {
+    match ch.to_ascii_uppercase() {
+        'A' => [
+            0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001,
+        ],
+        'B' => [
+            0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110,
+        ],
+        'C' => [
+            0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110,
+        ],
+        'D' => [
+            0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110,
+        ],
+        'E' => [
+            0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111,
+        ],
+        'F' => [
+            0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000,
+        ],
+        'G' => [
+            0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111,
+        ],
+        'H' => [
+            0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001,
+        ],
+        'I' => [
+            0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110,
+        ],
+        'J' => [
+            0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100,
+        ],
+        'K' => [
+            0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001,
+        ],
+        'L' => [
+            0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111,
+        ],
+        'M' => [
+            0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001,
+        ],
+        'N' => [
+            0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001,
+        ],
+        'O' => [
+            0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110,
+        ],
+        'P' => [
+            0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000,
+        ],
+        'Q' => [
+            0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101,
+        ],
+        'R' => [
+            0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001,
+        ],
+        'S' => [
+            0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110,
+        ],
+        'T' => [
+            0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100,
+        ],
+        'U' => [
+            0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110,
+        ],
+        'V' => [
+            0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100,
+        ],
+        'W' => [
+            0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010,
+        ],
+        'X' => [
+            0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001,
+        ],
+        'Y' => [
+            0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100,
+        ],
+        'Z' => [
+            0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111,
+        ],
+        '0' => [
+            0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110,
+        ],
+        '1' => [
+            0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110,
+        ],
+        '2' => [
+            0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111,
+        ],
+        '3' => [
+            0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110,
+        ],
+        '4' => [
+            0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010,
+        ],
+        '5' => [
+            0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110,
+        ],
+        '6' => [
+            0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110,
+        ],
+        '7' => [
+            0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000,
+        ],
+        '8' => [
+            0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110,
+        ],
+        '9' => [
+            0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100,
+        ],
+        ' ' => [0; 7],
+        '.' => [0, 0, 0, 0, 0, 0b01100, 0b01100],
+        ',' => [0, 0, 0, 0, 0b01100, 0b00100, 0b01000],
+        ':' => [0, 0b01100, 0b01100, 0, 0b01100, 0b01100, 0],
+        '-' => [0, 0, 0, 0b11111, 0, 0, 0],
+        '+' => [0, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0],
+        '=' => [0, 0, 0b11111, 0, 0b11111, 0, 0],
+        '_' => [0, 0, 0, 0, 0, 0, 0b11111],
+        '/' => [
+            0b00001, 0b00010, 0b00010, 0b00100, 0b01000, 0b01000, 0b10000,
+        ],
+        '%' => [
+            0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011,
+        ],
+        '(' => [
+            0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010,
+        ],
+        ')' => [
+            0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000,
+        ],
+        '[' => [
+            0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110,
+        ],
+        ']' => [
+            0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110,
+        ],
+        '<' => [
+            0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010,
+        ],
+        '>' => [
+            0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000,
+        ],
+        '!' => [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0, 0b00100],
+        '\'' => [0b00100, 0b00100, 0b01000, 0, 0, 0, 0],
+        '*' => [0, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0],
+        '#' => [
+            0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010,
+        ],
+        '|' => [0b00100; 7],
+        '^' => [0b00100, 0b01010, 0b10001, 0, 0, 0, 0],
+        _ => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0, 0b00100],
+    }
+}
+
+/// Screen position (pixels) and depth of a world point, or `None` when it is clipped.
+fn project_to_screen(position: Vec3, vp: &Mat4, width: f32, height: f32) -> Option<Vec3> {
+    let clip = *vp * Vec4::new(position.x, position.y, position.z, 1.0);
+    if clip.w.abs() < 0.001 {
+        return None;
+    }
+    let inv_w = 1.0 / clip.w;
+    let ndc_x = clip.x * inv_w;
+    let ndc_y = clip.y * inv_w;
+    let ndc_z = clip.z * inv_w;
+    if !(-1.0..=1.0).contains(&ndc_z) {
+        return None;
+    }
+    let screen_x = (ndc_x * 0.5 + 0.5) * (width - 1.0);
+    let screen_y = (1.0 - (ndc_y * 0.5 + 0.5)) * (height - 1.0);
+    Some(Vec3::new(screen_x, screen_y, ndc_z * 0.5 + 0.5))
+}
+
+/// One triangle side as an integer edge function over pixel centres, in
+/// `SUBPIXEL_BITS` fixed point and signed so the inside is non-negative. Moving one
+/// pixel adds `step_x` or `step_y`, so scanlines step exactly instead of
+/// re-evaluating. `bias` is the top-left rule: a centre exactly on an edge counts
+/// for only one of the two triangles sharing it.
+struct EdgeStepper {
+    origin: i64,
+    step_x: i64,
+    step_y: i64,
+    bias: i64,
+}
+
+impl EdgeStepper {
+    fn new(a: (i64, i64), b: (i64, i64), sign: i64) -> Self {
+        let half = 1 << (SUBPIXEL_BITS - 1);
+        let (dx, dy) = (sign * (b.1 - a.1), -sign * (b.0 - a.0));
+        let origin = sign * ((half - a.0) * (b.1 - a.1) - (half - a.1) * (b.0 - a.0));
+        let bias = if dx > 0 || (dx == 0 && dy > 0) { 0 } else { -1 };
+        Self {
+            origin,
+            step_x: dx << SUBPIXEL_BITS,
+            step_y: dy << SUBPIXEL_BITS,
+            bias,
+        }
+    }
+
+    /// Value at the centre of pixel (`x`, `y`).
+    fn at(&self, x: i32, y: i32) -> i64 {
+        self.origin + self.step_x * x as i64 + self.step_y * y as i64
+    }
+}
+
+struct SphereRefiner {
+    vertices: Vec<Vec3>,
+    /// Split decision per edge (keyed by its sorted endpoints) and the midpoint used.
+    midpoints: HashMap<(usize, usize), Option<usize>>,
+    indices: Vec<[usize; 3]>,
+    model_view_projection: Mat4,
+    width: f32,
+    height: f32,
+}
+
+impl SphereRefiner {
+    /// Screen position of a unit-sphere point, or `None` behind the near plane.
+    fn screen(&self, point: Vec3) -> Option<Vec2> {
+        let clip = self.model_view_projection * Vec4::new(point.x, point.y, point.z, 1.0);
+        if clip.w < 0.1 {
+            return None;
+        }
+        Some(Vec2::new(
+            (clip.x / clip.w * 0.5 + 0.5) * (self.width - 1.0),
+            (1.0 - (clip.y / clip.w * 0.5 + 0.5)) * (self.height - 1.0),
+        ))
+    }
+
+    fn should_split(&self, a: Vec3, b: Vec3, mid: Vec3) -> bool {
+        let chord = (b - a).length();
+        if chord < TESS_MIN_CHORD {
+            return false;
+        }
+        let (Some(sa), Some(sb), Some(sm)) = (self.screen(a), self.screen(b), self.screen(mid))
+        else {
+            return chord > TESS_NEAR_CHORD;
+        };
+        let xs = [sa.x, sb.x, sm.x];
+        let ys = [sa.y, sb.y, sm.y];
+        let off_screen = xs.iter().all(|&x| x < 0.0)
+            || xs.iter().all(|&x| x > self.width)
+            || ys.iter().all(|&y| y < 0.0)
+            || ys.iter().all(|&y| y > self.height);
+        if off_screen {
+            return false;
+        }
+        let error = (sm.x - (sa.x + sb.x) * 0.5).hypot(sm.y - (sa.y + sb.y) * 0.5);
+        let span = (sb.x - sa.x).hypot(sb.y - sa.y);
+        error > TESS_MAX_ERROR || span > TESS_MAX_EDGE
+    }
+
+    fn midpoint(&mut self, a: usize, b: usize) -> Option<usize> {
+        let key = (a.min(b), a.max(b));
+        if let Some(&split) = self.midpoints.get(&key) {
+            return split;
+        }
+        let (pa, pb) = (self.vertices[a], self.vertices[b]);
+        let mid = (pa + pb).normalized();
+        let split = self.should_split(pa, pb, mid).then(|| {
+            self.vertices.push(mid);
+            self.vertices.len() - 1
+        });
+        self.midpoints.insert(key, split);
+        split
+    }
+
+    fn keep_edge(&mut self, a: usize, b: usize) {
+        self.midpoints.entry((a.min(b), a.max(b))).or_insert(None);
+    }
+
+    fn refine(&mut self, [a, b, c]: [usize; 3]) {
+        let splits = [
+            self.midpoint(a, b),
+            self.midpoint(b, c),
+            self.midpoint(c, a),
+        ];
+        match splits {
+            [None, None, None] => self.indices.push([a, b, c]),
+            [Some(ab), Some(bc), Some(ca)] => {
+                self.refine([a, ab, ca]);
+                self.refine([ab, b, bc]);
+                self.refine([ca, bc, c]);
+                self.refine([ab, bc, ca]);
+            }
+            // One or two split edges: rotate the triangle so the pattern starts at
+            // edge a-b, then fan out from the midpoints. Fan edges are never split
+            // themselves; only the halved outer edges keep refining, which is what
+            // guarantees the recursion ends.
+            [Some(ab), None, None] => {
+                self.keep_edge(ab, c);
+                self.refine([a, ab, c]);
+                self.refine([ab, b, c]);
+            }
+            [None, Some(_), None] => self.refine([b, c, a]),
+            [None, None, Some(_)] => self.refine([c, a, b]),
+            [Some(ab), Some(bc), None] => {
+                self.keep_edge(ab, bc);
+                self.keep_edge(a, bc);
+                self.refine([ab, b, bc]);
+                self.refine([a, ab, bc]);
+                self.refine([a, bc, c]);
+            }
+            [None, Some(_), Some(_)] => self.refine([b, c, a]),
+            [Some(_), None, Some(_)] => self.refine([c, a, b]),
+        }
+    }
+}
+
+/// Triangles of a mesh that an OBJ `o` or `g` statement named.
+#[derive(Clone)]
+struct MeshGroup {
+    name: String,
+    triangles: std::ops::Range<usize>,
+}
+
+#[derive(Clone)]
+struct Mesh {
+    vertices: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    /// Per-vertex tint multiplied with the material color; empty when the mesh has none.
+    colors: Vec<Color>,
+    indices: Vec<[usize; 3]>,
+    /// Named objects and groups from an OBJ file, in file order; empty otherwise.
+    groups: Vec<MeshGroup>,
+    /// Unit sphere around the origin, so each normal doubles as the vertex position
+    /// and `far_hemisphere` can classify vertices without transforming them.
+    sphere: bool,
+    /// Filled by `bounds` and `ray_hit` on first use; whatever moves the vertices
+    /// resets them with `vertices_moved`.
+    bounds: OnceCell<MeshBounds>,
+    bvh: OnceCell<TriangleBvh>,
+}
+
+/// Axis-aligned box around a mesh's vertices and a sphere enclosing them, both in
+/// mesh space.
+#[derive(Clone, Copy)]
+struct MeshBounds {
+    min: Vec3,
+    max: Vec3,
+    /// Middle of the box, also the center of the sphere.
+    center: Vec3,
+    radius: f32,
+}
+
+impl MeshBounds {
+    /// Bounds of `vertices`; a point at the origin when there are none.
+    fn of(vertices: &[Vec3]) -> Self {
+        if vertices.is_empty() {
+            return Self {
+                min: Vec3::ZERO,
+                max: Vec3::ZERO,
+                center: Vec3::ZERO,
+                radius: 0.0,
+            };
+        }
+        let (min, max) = vertices.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), v| {
+                (
+                    Vec3::new(min.x.min(v.x), min.y.min(v.y), min.z.min(v.z)),
+                    Vec3::new(max.x.max(v.x), max.y.max(v.y), max.z.max(v.z)),
+                )
+            },
+        );
+        let center = (min + max) * 0.5;
+        let radius = vertices
+            .iter()
+            .map(|&vertex| (vertex - center).length_squared())
+            .fold(0.0, f32::max)
+            .sqrt();
+        Self {
+            min,
+            max,
+            center,
+            radius,
+        }
+    }
+
+    fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    /// Radius of a sphere around the mesh's origin that holds every vertex, for
+    /// bodies whose position is that origin.
+    fn reach(&self) -> f32 {
+        self.center.length() + self.radius
+    }
+
+    /// The enclosing sphere moved by `transform`, its radius grown by the largest
+    /// of the transform's axis scales.
+    fn world_sphere(&self, transform: &Mat4) -> (Vec3, f32) {
+        let center =
+            (*transform * Vec4::new(self.center.x, self.center.y, self.center.z, 1.0)).xyz();
+        let scale = [(1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, 0.0, 1.0)]
+            .iter()
+            .map(|&(x, y, z)| (*transform * Vec4::new(x, y, z, 0.0)).xyz().length())
+            .fold(0.0, f32::max);
+        (center, self.radius * scale)
+    }
+}
+
+/// Boxes around a mesh's triangles, halved along their longest side down to
+/// `BVH_LEAF_TRIANGLES`, so a ray only tests the triangles in boxes it crosses.
+#[derive(Clone)]
+struct TriangleBvh {
+    nodes: Vec<BvhNode>,
+    /// Triangle indices, ordered so every node owns a contiguous run.
+    triangles: Vec<usize>,
+}
+
+#[derive(Clone)]
+struct BvhNode {
+    min: Vec3,
+    max: Vec3,
+    /// Child nodes; `None` for a leaf, whose triangles are then tested.
+    children: Option<[usize; 2]>,
+    triangles: std::ops::Range<usize>,
+}
+
+impl TriangleBvh {
+    fn build(mesh: &Mesh) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            triangles: (0..mesh.indices.len()).collect(),
+        };
+        if !mesh.indices.is_empty() {
+            bvh.split(mesh, 0..mesh.indices.len());
+        }
+        bvh
+    }
+
+    fn split(&mut self, mesh: &Mesh, range: std::ops::Range<usize>) -> usize {
+        let corners = self.triangles[range.clone()]
+            .iter()
+            .flat_map(|&tri| mesh.indices[tri].map(|index| mesh.vertices[index]));
+        let bounds = MeshBounds::of(&corners.collect::<Vec<_>>());
+        let node = self.nodes.len();
+        self.nodes.push(BvhNode {
+            min: bounds.min,
+            max: bounds.max,
+            children: None,
+            triangles: range.clone(),
+        });
+        if range.len() > BVH_LEAF_TRIANGLES {
+            let size = bounds.size();
+            let axis = |v: Vec3| {
+                if size.x >= size.y && size.x >= size.z {
+                    v.x
+                } else if size.y >= size.z {
+                    v.y
+                } else {
+                    v.z
+                }
+            };
+            let centroid = |tri: usize| {
+                let [a, b, c] = mesh.indices[tri].map(|index| mesh.vertices[index]);
+                axis(a + b + c)
+            };
+            let half = range.len() / 2;
+            self.triangles[range.clone()]
+                .select_nth_unstable_by(half, |&a, &b| centroid(a).total_cmp(&centroid(b)));
+            let mid = range.start + half;
+            let left = self.split(mesh, range.start..mid);
+            let right = self.split(mesh, mid..range.end);
+            self.nodes[node].children = Some([left, right]);
+        }
+        node
+    }
+
+    fn hit(&self, mesh: &Mesh, origin: Vec3, direction: Vec3) -> Option<f32> {
+        let mut nearest: Option<f32> = None;
+        let mut stack = if self.nodes.is_empty() {
+            Vec::new()
+        } else {
+            vec![0]
+        };
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let limit = nearest.unwrap_or(f32::INFINITY);
+            if !ray_crosses_box(origin, direction, node.min, node.max, limit) {
+                continue;
+            }
+            match node.children {
+                Some(children) => stack.extend(children),
+                None => {
+                    for &tri in &self.triangles[node.triangles.clone()] {
+                        let corners = mesh.indices[tri].map(|index| mesh.vertices[index]);
+                        if let Some(t) = ray_triangle(origin, direction, corners) {
+                            if t < limit && nearest.is_none_or(|best| t < best) {
+                                nearest = Some(t);
+                            }
+                        }
                     }
-                    self.depth[idx] = depth;
-                    let normal = ((v0.normal * (v0.inv_w * w0)
-                        + v1.normal * (v1.inv_w * w1)
-                        + v2.normal * (v2.inv_w * w2))
-                        / w_sum)
-                        .normalized();
-                    let diffuse = normal.dot(-light.direction).max(0.0);
-                    let ambient = 0.2;
-                    let shaded = material.color * (ambient + diffuse * light.intensity)
-                        + light.color * material.emissive;
-                    self.color[idx] = shaded.to_u32();
                 }
             }
         }
+        nearest
     }
 }
 
-fn edge(a: &Vec3, b: &Vec3, c: &Vec3) -> f32 {
-    (c.x - a.x) * (b.y - a.y) - (c.y - a.y) * (b.x - a.x)
+/// Slab test: whether the ray enters the box before `limit`.
+fn ray_crosses_box(origin: Vec3, direction: Vec3, min: Vec3, max: Vec3, limit: f32) -> bool {
+    let mut near = 0.0f32;
+    let mut far = limit;
+    for (o, d, lo, hi) in [
+        (origin.x, direction.x, min.x, max.x),
+        (origin.y, direction.y, min.y, max.y),
+        (origin.z, direction.z, min.z, max.z),
+    ] {
+        if d == 0.0 {
+            if o < lo || o > hi {
+                return false;
+            }
+            continue;
+        }
+        let (t0, t1) = ((lo - o) / d, (hi - o) / d);
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+        if near > far {
+            return false;
+        }
+    }
+    true
 }
 
-#[derive(Clone)]
-struct Mesh {
-    vertices: Vec<Vec3>,
-    normals: Vec<Vec3>,
-    indices: Vec<[usize; 3]>,
+/// Möller–Trumbore: distance along the ray to the triangle, in units of
+/// `direction`'s length, from either side.
+fn ray_triangle(origin: Vec3, direction: Vec3, [a, b, c]: [Vec3; 3]) -> Option<f32> {
+    let (edge1, edge2) = (b - a, c - a);
+    let p = direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < 1e-9 {
+        return None;
+    }
+    let inv = 1.0 / determinant;
+    let offset = origin - a;
+    let u = offset.dot(p) * inv;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = offset.cross(edge1);
+    let v = direction.dot(q) * inv;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(q) * inv;
+    (t > 1e-4).then_some(t)
+}
+
+/// Nearest distance along a unit-length ray to the sphere, 0 when starting inside.
+fn ray_sphere(origin: Vec3, direction: Vec3, center: Vec3, radius: f32) -> Option<f32> {
+    let offset = origin - center;
+    let b = offset.dot(direction);
+    let c = offset.length_squared() - radius * radius;
+    if c <= 0.0 {
+        return Some(0.0);
+    }
+    let discriminant = b * b - c;
+    if b > 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    Some(-b - discriminant.sqrt())
+}
+
+/// True when the sphere lies wholly outside one of the six clip planes of
+/// `view_projection`, read off its rows.
+fn sphere_outside_frustum(view_projection: &Mat4, center: Vec3, radius: f32) -> bool {
+    let m = &view_projection.m;
+    let row = |r: usize| [m[r][0], m[r][1], m[r][2], m[r][3]];
+    let w = row(3);
+    (0..3).any(|axis| {
+        let r = row(axis);
+        [1.0, -1.0].iter().any(|&sign| {
+            let plane = [0, 1, 2, 3].map(|i| w[i] + sign * r[i]);
+            let normal = Vec3::new(plane[0], plane[1], plane[2]);
+            let length = normal.length();
+            length > 0.0 && (normal.dot(center) + plane[3]) / length < -radius
+        })
+    })
 }
 
 impl Mesh {
@@ -893,15 +12496,308 @@ impl Mesh {
                 let i1 = i0 + 1;
                 let i2 = i0 + stride;
                 let i3 = i2 + 1;
-                indices.push([i0, i2, i1]);
-                indices.push([i1, i2, i3]);
+                indices.push([i0, i1, i2]);
+                indices.push([i1, i3, i2]);
+            }
+        }
+        Self {
+            vertices,
+            normals,
+            colors: Vec::new(),
+            indices,
+            groups: Vec::new(),
+            sphere: true,
+            bounds: OnceCell::new(),
+            bvh: OnceCell::new(),
+        }
+    }
+
+    /// Unit sphere refined for one view, starting from an icosahedron. The on-screen
+    /// gap between an edge's chord and its arc is largest along the silhouette and
+    /// close to the camera and nearly zero where the surface faces the viewer, so
+    /// triangles concentrate on the limb. Split decisions are made per edge and
+    /// shared by both triangles using it, which keeps the mesh free of cracks.
+    fn adaptive_sphere(transform: &Mat4, view_projection: &Mat4, width: f32, height: f32) -> Self {
+        let (vertices, faces) = icosahedron();
+        let mut refiner = SphereRefiner {
+            vertices,
+            midpoints: HashMap::new(),
+            indices: Vec::new(),
+            model_view_projection: *view_projection * *transform,
+            width,
+            height,
+        };
+        for face in faces {
+            refiner.refine(face);
+        }
+        Self {
+            normals: refiner.vertices.clone(),
+            vertices: refiner.vertices,
+            colors: Vec::new(),
+            indices: refiner.indices,
+            groups: Vec::new(),
+            sphere: true,
+            bounds: OnceCell::new(),
+            bvh: OnceCell::new(),
+        }
+    }
+
+    /// Unit sphere from an icosahedron whose triangles are split in four
+    /// `subdivisions` times, so every triangle is about the same size.
+    fn icosphere(subdivisions: usize) -> Self {
+        let (mut vertices, mut indices) = icosahedron();
+        for _ in 0..subdivisions {
+            let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+            let mut midpoint = |a: usize, b: usize| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    vertices.push((vertices[a] + vertices[b]).normalized());
+                    vertices.len() - 1
+                })
+            };
+            indices = indices
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    [[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]
+                })
+                .collect();
+        }
+        Self {
+            normals: vertices.clone(),
+            vertices,
+            colors: Vec::new(),
+            indices,
+            groups: Vec::new(),
+            sphere: true,
+            bounds: OnceCell::new(),
+            bvh: OnceCell::new(),
+        }
+    }
+
+    /// Lumpy rock around the unit sphere for a comet nucleus: an icosphere pushed in
+    /// and out by `fractal_noise` for the overall shape, then dented by bowl craters
+    /// with raised rims. The seed moves the noise and places the craters, so every
+    /// seed gives a different rock and the same seed always the same one.
+    fn asteroid(seed: u64) -> Self {
+        let mut rng = Lcg::new(seed);
+        let offset = Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()) * 97.0;
+        let craters: Vec<(Vec3, f32, f32)> = (0..ASTEROID_CRATERS)
+            .map(|_| {
+                let height = rng.next_f32() * 2.0 - 1.0;
+                let angle = rng.next_f32() * TAU;
+                let around = (1.0 - height * height).sqrt();
+                let center = Vec3::new(around * angle.cos(), height, around * angle.sin());
+                // Chord radius of the bowl on the unit sphere, and how deep it digs.
+                let size = 0.15 + rng.next_f32() * 0.3;
+                (center, size, size * (0.25 + rng.next_f32() * 0.2))
+            })
+            .collect();
+        let mut mesh = Mesh::icosphere(ASTEROID_SUBDIVISIONS);
+        for vertex in mesh.vertices.iter_mut() {
+            let direction = *vertex;
+            let mut radius = 1.0 + (fractal_noise(direction * 1.4 + offset) - 0.5) * 0.9;
+            for &(center, size, depth) in &craters {
+                let d = (direction - center).length() / size;
+                if d < 1.0 {
+                    radius -= depth * (1.0 - d * d);
+                } else if d < 1.5 {
+                    radius += depth * 0.3 * ((d - 1.0) * 2.0 * PI).sin();
+                }
+            }
+            *vertex = direction * radius;
+        }
+        mesh.normals = smooth_normals(&mesh.vertices, &mesh.indices);
+        mesh.sphere = false;
+        mesh.vertices_moved();
+        mesh
+    }
+
+    /// Ring of `major_radius` around Y with a round tube of `minor_radius`, split into
+    /// `segments` around the ring and `sides` around the tube.
+    fn torus(major_radius: f32, minor_radius: f32, segments: usize, sides: usize) -> Self {
+        let tube = (0..=sides)
+            .map(|i| {
+                let angle = i as f32 / sides as f32 * TAU;
+                let (radial, vertical) = (angle.cos(), -angle.sin());
+                (
+                    [
+                        major_radius + minor_radius * radial,
+                        minor_radius * vertical,
+                    ],
+                    [radial, vertical],
+                )
+            })
+            .collect();
+        Self::revolve(&[tube], segments)
+    }
+
+    /// Closed cylinder along Y, centered on the origin, with flat caps.
+    fn cylinder(radius: f32, height: f32, segments: usize) -> Self {
+        let (top, bottom) = (height * 0.5, -height * 0.5);
+        Self::revolve(
+            &[
+                vec![([0.0, top], [0.0, 1.0]), ([radius, top], [0.0, 1.0])],
+                vec![([radius, top], [1.0, 0.0]), ([radius, bottom], [1.0, 0.0])],
+                vec![
+                    ([radius, bottom], [0.0, -1.0]),
+                    ([0.0, bottom], [0.0, -1.0]),
+                ],
+            ],
+            segments,
+        )
+    }
+
+    /// Cone along Y with its tip at `height / 2` and a flat base below the origin.
+    fn cone(radius: f32, height: f32, segments: usize) -> Self {
+        let (top, bottom) = (height * 0.5, -height * 0.5);
+        // Perpendicular to the slant (`revolve` normalizes it), shared by the tip so
+        // the side shades smoothly.
+        let side = [height, radius];
+        Self::revolve(
+            &[
+                vec![([0.0, top], side), ([radius, bottom], side)],
+                vec![
+                    ([radius, bottom], [0.0, -1.0]),
+                    ([0.0, bottom], [0.0, -1.0]),
+                ],
+            ],
+            segments,
+        )
+    }
+
+    /// Cylinder of `length` along Y closed by hemispheres of `radius`, each split
+    /// into `rings` bands.
+    fn capsule(radius: f32, length: f32, segments: usize, rings: usize) -> Self {
+        let half = length * 0.5;
+        let profile = (0..=rings * 2 + 1)
+            .map(|i| {
+                // The two rings at the equator share an angle and differ in height.
+                let band = if i <= rings { i } else { i - 1 };
+                let angle = band as f32 / (rings * 2) as f32 * PI;
+                let center = if i <= rings { half } else { -half };
+                let (radial, vertical) = (angle.sin(), angle.cos());
+                (
+                    [radius * radial, center + radius * vertical],
+                    [radial, vertical],
+                )
+            })
+            .collect();
+        Self::revolve(&[profile], segments)
+    }
+
+    /// Sweeps each strip of `([radius, height], [radial, vertical] normal)` points
+    /// once around Y in `segments` steps. Strips run from the top of the outline
+    /// down, as the UV sphere's rings do, which winds the triangles outward.
+    fn revolve(strips: &[Vec<([f32; 2], [f32; 2])>], segments: usize) -> Self {
+        let mut vertices = Vec::new();
+        let mut normals = Vec::new();
+        let mut indices = Vec::new();
+        let stride = segments + 1;
+        for strip in strips {
+            let first = vertices.len();
+            for &([radius, height], [radial, vertical]) in strip {
+                for x in 0..=segments {
+                    let phi = x as f32 / segments as f32 * TAU;
+                    let (cos, sin) = (phi.cos(), phi.sin());
+                    vertices.push(Vec3::new(radius * cos, height, radius * sin));
+                    normals.push(Vec3::new(radial * cos, vertical, radial * sin).normalized());
+                }
+            }
+            for y in 0..strip.len().saturating_sub(1) {
+                for x in 0..segments {
+                    let i0 = first + y * stride + x;
+                    let i1 = i0 + 1;
+                    let i2 = i0 + stride;
+                    let i3 = i2 + 1;
+                    indices.push([i0, i1, i2]);
+                    indices.push([i1, i3, i2]);
+                }
             }
         }
         Self {
             vertices,
             normals,
+            colors: Vec::new(),
             indices,
+            groups: Vec::new(),
+            sphere: false,
+            bounds: OnceCell::new(),
+            bvh: OnceCell::new(),
+        }
+    }
+
+    /// Builds the primitive a scene's `mesh` key names instead of a file, such as
+    /// `torus 4 1` or `cone 1 3 24`: the shape's sizes followed by optional
+    /// tessellation counts. `None` when `spec` doesn't start with a primitive name.
+    fn primitive(spec: &str) -> Option<Result<Mesh, String>> {
+        let mut words = spec.split_whitespace();
+        let kind = words.next()?;
+        // Default tessellation counts, each of which the spec may override in turn.
+        let (defaults, usage): (&[usize], _) = match kind {
+            "torus" => (
+                &[32, 16],
+                "torus <major radius> <minor radius> [segments] [sides]",
+            ),
+            "cylinder" => (&[32], "cylinder <radius> <height> [segments]"),
+            "cone" => (&[32], "cone <radius> <height> [segments]"),
+            "capsule" => (&[32, 8], "capsule <radius> <length> [segments] [rings]"),
+            _ => return None,
+        };
+        let words: Vec<&str> = words.collect();
+        if words.len() < 2 || words.len() > 2 + defaults.len() {
+            return Some(Err(format!("expected `{}`", usage)));
         }
+        let mut size = [0.0; 2];
+        for (value, word) in size.iter_mut().zip(&words) {
+            match word.parse::<f32>() {
+                Ok(number) if number > 0.0 => *value = number,
+                _ => return Some(Err(format!("`{}` is not a positive size", word))),
+            }
+        }
+        let mut count = defaults.to_vec();
+        for (value, word) in count.iter_mut().zip(&words[2..]) {
+            match word.parse::<usize>() {
+                Ok(number) if number >= 3 => *value = number,
+                _ => {
+                    return Some(Err(format!(
+                        "`{}` must be a whole number of at least 3",
+                        word
+                    )))
+                }
+            }
+        }
+        let [a, b] = size;
+        Some(Ok(match kind {
+            "torus" => Mesh::torus(a, b, count[0], count[1]),
+            "cylinder" => Mesh::cylinder(a, b, count[0]),
+            "cone" => Mesh::cone(a, b, count[0]),
+            _ => Mesh::capsule(a, b, count[0], count[1]),
+        }))
+    }
+
+    /// Flags the vertices of a sphere mesh that lie on the hemisphere facing away from
+    /// `eye`. The eye is taken into object space through the transform's axes, where a
+    /// vertex faces away exactly when its normal points against the eye direction.
+    /// Returns an empty list for other meshes.
+    fn far_hemisphere(&self, transform: &Mat4, eye: Vec3) -> Vec<bool> {
+        if !self.sphere {
+            return Vec::new();
+        }
+        let offset = eye - (*transform * Vec4::new(0.0, 0.0, 0.0, 1.0)).xyz();
+        let axis = |x: f32, y: f32, z: f32| {
+            let axis = (*transform * Vec4::new(x, y, z, 0.0)).xyz();
+            offset.dot(axis) / axis.length_squared().max(1e-12)
+        };
+        let local_eye = Vec3::new(
+            axis(1.0, 0.0, 0.0),
+            axis(0.0, 1.0, 0.0),
+            axis(0.0, 0.0, 1.0),
+        );
+        let slack = HORIZON_CULL_SLACK * local_eye.length();
+        self.normals
+            .iter()
+            .map(|normal| normal.dot(local_eye) < -slack)
+            .collect()
     }
 
     fn ring(inner_radius: f32, outer_radius: f32, segments: usize) -> Self {
@@ -912,91 +12808,1060 @@ impl Mesh {
             let angle = (i as f32 / segments as f32) * TAU;
             let cos = angle.cos();
             let sin = angle.sin();
-            let outer = Vec3::new(cos * outer_radius, 0.0, sin * outer_radius);
-            let inner = Vec3::new(cos * inner_radius, 0.0, sin * inner_radius);
-            vertices.push(outer);
+            vertices.push(Vec3::new(cos * outer_radius, 0.0, sin * outer_radius));
             normals.push(Vec3::UP);
-            vertices.push(inner);
+            vertices.push(Vec3::new(cos * inner_radius, 0.0, sin * inner_radius));
             normals.push(Vec3::UP);
-            vertices.push(outer);
-            normals.push(-Vec3::UP);
-            vertices.push(inner);
-            normals.push(-Vec3::UP);
         }
-        let stride = 4;
+        // Wound to face up; the underside comes from drawing it with a double-sided material.
         for i in 0..segments {
-            let base = i * stride;
-            let next = base + stride;
-            indices.push([base, next, base + 1]);
-            indices.push([base + 1, next, next + 1]);
-            let base_down = base + 2;
-            let next_down = next + 2;
-            indices.push([base_down, base_down + 1, next_down]);
-            indices.push([base_down + 1, next_down + 1, next_down]);
+            let base = i * 2;
+            let next = base + 2;
+            indices.push([base, base + 1, next]);
+            indices.push([base + 1, next + 1, next]);
+        }
+        Self {
+            vertices,
+            normals,
+            colors: Vec::new(),
+            indices,
+            groups: Vec::new(),
+            sphere: false,
+            bounds: OnceCell::new(),
+            bvh: OnceCell::new(),
+        }
+    }
+
+    /// Two bands standing on circles `AURORA_OVAL` from each pole of the unit sphere,
+    /// raised `AURORA_BASE` off it and reaching `AURORA_HEIGHT` further along Y.
+    fn aurora_crown(segments: usize) -> Self {
+        let mut vertices = Vec::new();
+        let mut normals = Vec::new();
+        let mut indices = Vec::new();
+        let (foot_radius, foot_height) = (
+            AURORA_OVAL.sin() * AURORA_BASE,
+            AURORA_OVAL.cos() * AURORA_BASE,
+        );
+        for pole in [1.0, -1.0] {
+            let first = vertices.len();
+            for i in 0..=segments {
+                let angle = (i as f32 / segments as f32) * TAU;
+                let outward = Vec3::new(angle.cos(), 0.0, angle.sin());
+                let foot = outward * foot_radius + Vec3::new(0.0, foot_height * pole, 0.0);
+                vertices.push(foot);
+                normals.push(outward);
+                vertices.push(foot + Vec3::new(0.0, AURORA_HEIGHT * pole, 0.0));
+                normals.push(outward);
+            }
+            for i in 0..segments {
+                let base = first + i * 2;
+                let next = base + 2;
+                indices.push([base, base + 1, next]);
+                indices.push([base + 1, next + 1, next]);
+            }
         }
         Self {
             vertices,
             normals,
+            colors: Vec::new(),
+            indices,
+            groups: Vec::new(),
+            sphere: false,
+            bounds: OnceCell::new(),
+            bvh: OnceCell::new(),
+        }
+    }
+
+    /// Sets every vertex color from its object-space position.
+    fn paint(&mut self, color: impl Fn(Vec3) -> Color) {
+        self.colors = self.vertices.iter().map(|&vertex| color(vertex)).collect();
+    }
+
+    /// Reads the OBJ at `path`, or the compiled-in copy from `BUILTIN_MESHES` when
+    /// the file can't be read.
+    fn from_obj(path: &Path) -> Result<Self, AppError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse_obj(path, &text),
+            Err(source) => Self::builtin(path).unwrap_or(Err(AppError::MissingFile {
+                path: path.to_path_buf(),
+                source,
+            })),
+        }
+    }
+
+    /// The compiled-in copy of `path`, if `BUILTIN_MESHES` has one.
+    fn builtin(path: &Path) -> Option<Result<Self, AppError>> {
+        BUILTIN_MESHES
+            .iter()
+            .find(|(name, _)| Path::new(name) == path)
+            .map(|(_, text)| Self::parse_obj(path, text))
+    }
+
+    /// Parses OBJ `text`; `path` only goes into errors. Face indices below zero
+    /// count back from the latest vertex. Faces in the same smoothing group (`s 1`)
+    /// share averaged normals at their common vertices, while `s off` faces get
+    /// corners of their own and shade flat. `o` and `g` start named groups of
+    /// triangles that `submesh` picks out.
+    fn parse_obj(path: &Path, text: &str) -> Result<Self, AppError> {
+        let mut positions = Vec::new();
+        let mut colors = Vec::new();
+        // Position behind each output vertex, and the vertex already made for a
+        // position in a smoothing group.
+        let mut sources: Vec<usize> = Vec::new();
+        let mut corners: HashMap<(usize, u32), usize> = HashMap::new();
+        let mut indices: Vec<[usize; 3]> = Vec::new();
+        let mut group_starts: Vec<(String, usize)> = Vec::new();
+        // Faces before any `s` all smooth together; `s 0` itself means off.
+        let mut smoothing = Some(0);
+        for (idx, line) in text.lines().enumerate() {
+            let error = |reason: String| AppError::ObjParse {
+                path: path.to_path_buf(),
+                line: idx + 1,
+                reason,
+            };
+            let mut parts = line.split_whitespace();
+            match parts.next() {
+                Some("v") => {
+                    let values: Vec<f32> = parts
+                        .map(|part| {
+                            part.parse()
+                                .map_err(|_| error(format!("`{}` is not a number", part)))
+                        })
+                        .collect::<Result<_, _>>()?;
+                    let coordinate = |axis: usize| values.get(axis).copied().unwrap_or(0.0);
+                    positions.push(Vec3::new(coordinate(0), coordinate(1), coordinate(2)));
+                    // Optional `v x y z r g b` vertex colors, as written by most exporters.
+                    if let [_, _, _, r, g, b] = values[..] {
+                        colors.resize(positions.len() - 1, Color::new(1.0, 1.0, 1.0));
+                        colors.push(Color::new(r, g, b));
+                    }
+                }
+                Some("f") => {
+                    let mut face = Vec::new();
+                    for chunk in parts {
+                        let field = chunk.split('/').next().unwrap_or(chunk);
+                        let index: i64 = field
+                            .parse()
+                            .map_err(|_| error(format!("`{}` is not a vertex index", field)))?;
+                        let count = positions.len() as i64;
+                        let position = if index < 0 { count + index } else { index - 1 };
+                        if !(0..count).contains(&position) {
+                            return Err(error(format!(
+                                "face index {} is outside the {} vertices so far",
+                                index, count
+                            )));
+                        }
+                        let position = position as usize;
+                        let corner = match smoothing {
+                            Some(group) => *corners.entry((position, group)).or_insert_with(|| {
+                                sources.push(position);
+                                sources.len() - 1
+                            }),
+                            None => {
+                                sources.push(position);
+                                sources.len() - 1
+                            }
+                        };
+                        face.push(corner);
+                    }
+                    for tri in 1..face.len().saturating_sub(1) {
+                        indices.push([face[0], face[tri], face[tri + 1]]);
+                    }
+                }
+                Some("s") => {
+                    smoothing =
+                        match parts.next() {
+                            Some("off") | Some("0") => None,
+                            Some(group) => Some(group.parse().map_err(|_| {
+                                error(format!("`{}` is not a smoothing group", group))
+                            })?),
+                            None => return Err(error("`s` expects a group number or off".into())),
+                        };
+                }
+                Some("o") | Some("g") => {
+                    let name = parts.collect::<Vec<_>>().join(" ");
+                    group_starts.push((name, indices.len()));
+                }
+                // Comments, blank lines, and texture coordinates, normals and
+                // materials, which the renderer has no use for.
+                _ => {}
+            }
+        }
+
+        let vertices: Vec<Vec3> = sources
+            .iter()
+            .map(|&position| positions[position])
+            .collect();
+        let normals = smooth_normals(&vertices, &indices);
+        if !colors.is_empty() {
+            colors.resize(positions.len(), Color::new(1.0, 1.0, 1.0));
+            colors = sources.iter().map(|&position| colors[position]).collect();
+        }
+        let ends = group_starts
+            .iter()
+            .skip(1)
+            .map(|&(_, start)| start)
+            .chain([indices.len()]);
+        let groups = group_starts
+            .iter()
+            .zip(ends)
+            .filter(|((_, start), end)| start < end)
+            .map(|((name, start), end)| MeshGroup {
+                name: name.clone(),
+                triangles: *start..end,
+            })
+            .collect();
+        Ok(Self {
+            vertices,
+            normals,
+            colors,
+            indices,
+            groups,
+            sphere: false,
+            bounds: OnceCell::new(),
+            bvh: OnceCell::new(),
+        })
+    }
+
+    /// Reads a PLY file, ASCII or binary. Vertices need `x`, `y` and `z`; `nx`,
+    /// `ny` and `nz` are used as normals when present (averaged from the faces
+    /// otherwise) and `red`, `green` and `blue` become vertex colors. Faces take
+    /// their `vertex_indices` list; other elements are skipped.
+    fn from_ply(path: &Path) -> Result<Self, AppError> {
+        let bytes = std::fs::read(path).map_err(|source| AppError::MissingFile {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse_ply(&bytes).map_err(|reason| AppError::PlyParse {
+            path: path.to_path_buf(),
+            reason,
+        })
+    }
+
+    fn parse_ply(bytes: &[u8]) -> Result<Self, String> {
+        // The header is read a line at a time so that only a line that is exactly
+        // `end_header` ends it, not a comment or name that happens to contain it.
+        let mut header = Vec::new();
+        let mut rest = bytes;
+        let data = loop {
+            let newline = rest.iter().position(|&byte| byte == b'\n');
+            let (line, next) = match newline {
+                Some(newline) => (&rest[..newline], &rest[newline + 1..]),
+                None => (rest, &rest[rest.len()..]),
+            };
+            let line = std::str::from_utf8(line)
+                .map_err(|_| "header is not text")?
+                .trim();
+            if line == "end_header" {
+                break next;
+            }
+            if newline.is_none() {
+                return Err("no end_header line".to_string());
+            }
+            header.push(line);
+            rest = next;
+        };
+
+        let mut lines = header.into_iter();
+        if lines.next() != Some("ply") {
+            return Err("not a PLY file".to_string());
+        }
+        let mut format = None;
+        let mut elements: Vec<PlyElement> = Vec::new();
+        for line in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let ty = |name: &str| PlyType::parse(name).ok_or(format!("unknown type `{}`", name));
+            match words[..] {
+                ["format", name, _] => format = Some(name.to_string()),
+                ["element", name, count] => elements.push(PlyElement {
+                    name: name.to_string(),
+                    count: count
+                        .parse()
+                        .map_err(|_| format!("bad element count `{}`", count))?,
+                    properties: Vec::new(),
+                }),
+                ["property", "list", count, item, name] => elements
+                    .last_mut()
+                    .ok_or("property before any element")?
+                    .properties
+                    .push(PlyProperty::List(ty(count)?, ty(item)?, name.to_string())),
+                ["property", value, name] => elements
+                    .last_mut()
+                    .ok_or("property before any element")?
+                    .properties
+                    .push(PlyProperty::Scalar(ty(value)?, name.to_string())),
+                _ => {}
+            }
+        }
+        let mut body = match format.as_deref() {
+            Some("ascii") => PlyBody::Ascii(
+                std::str::from_utf8(data)
+                    .map_err(|_| "ASCII data is not text")?
+                    .split_whitespace(),
+            ),
+            Some("binary_little_endian") => PlyBody::Binary {
+                bytes: data,
+                big_endian: false,
+            },
+            Some("binary_big_endian") => PlyBody::Binary {
+                bytes: data,
+                big_endian: true,
+            },
+            Some(other) => return Err(format!("unknown format `{}`", other)),
+            None => return Err("no format line".to_string()),
+        };
+
+        let mut vertices = Vec::new();
+        let mut normals = Vec::new();
+        let mut colors = Vec::new();
+        let mut indices: Vec<[usize; 3]> = Vec::new();
+        for element in &elements {
+            for _ in 0..element.count {
+                let mut scalars: HashMap<&str, (f32, PlyType)> = HashMap::new();
+                let mut face = Vec::new();
+                for property in &element.properties {
+                    match property {
+                        PlyProperty::Scalar(ty, name) => {
+                            scalars.insert(name, (body.read(*ty)? as f32, *ty));
+                        }
+                        PlyProperty::List(count_ty, item_ty, name) => {
+                            let count = body.read(*count_ty)? as usize;
+                            for _ in 0..count {
+                                let value = body.read(*item_ty)?;
+                                if name == "vertex_indices" || name == "vertex_index" {
+                                    face.push(value as usize);
+                                }
+                            }
+                        }
+                    }
+                }
+                let scalar = |name: &str| scalars.get(name).map(|&(value, _)| value);
+                match element.name.as_str() {
+                    "vertex" => {
+                        let (Some(x), Some(y), Some(z)) = (scalar("x"), scalar("y"), scalar("z"))
+                        else {
+                            return Err("vertices need x, y and z".to_string());
+                        };
+                        vertices.push(Vec3::new(x, y, z));
+                        if let (Some(x), Some(y), Some(z)) =
+                            (scalar("nx"), scalar("ny"), scalar("nz"))
+                        {
+                            normals.push(Vec3::new(x, y, z).normalized());
+                        }
+                        let channel = |name: &str| {
+                            scalars
+                                .get(name)
+                                .map(|&(value, ty)| value / ty.color_scale())
+                        };
+                        if let (Some(r), Some(g), Some(b)) =
+                            (channel("red"), channel("green"), channel("blue"))
+                        {
+                            colors.push(Color::new(r, g, b));
+                        }
+                    }
+                    "face" => {
+                        if let Some(&index) = face.iter().find(|&&index| index >= vertices.len()) {
+                            return Err(format!(
+                                "face index {} is outside the {} vertices",
+                                index,
+                                vertices.len()
+                            ));
+                        }
+                        for tri in 1..face.len().saturating_sub(1) {
+                            indices.push([face[0], face[tri], face[tri + 1]]);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if normals.len() != vertices.len() {
+            normals = smooth_normals(&vertices, &indices);
+        }
+        if colors.len() != vertices.len() {
+            colors.clear();
+        }
+        Ok(Self {
+            vertices,
+            normals,
+            colors,
+            indices,
+            groups: Vec::new(),
+            sphere: false,
+            bounds: OnceCell::new(),
+            bvh: OnceCell::new(),
+        })
+    }
+
+    /// The triangles of every group called `name`, with only the vertices they use.
+    fn submesh(&self, name: &str) -> Option<Mesh> {
+        let mut part = Mesh {
+            vertices: Vec::new(),
+            normals: Vec::new(),
+            colors: Vec::new(),
+            indices: Vec::new(),
+            groups: Vec::new(),
+            sphere: false,
+            bounds: OnceCell::new(),
+            bvh: OnceCell::new(),
+        };
+        let mut remap: HashMap<usize, usize> = HashMap::new();
+        let mut found = false;
+        for group in self.groups.iter().filter(|group| group.name == name) {
+            found = true;
+            for tri in &self.indices[group.triangles.clone()] {
+                let tri = tri.map(|index| {
+                    *remap.entry(index).or_insert_with(|| {
+                        part.vertices.push(self.vertices[index]);
+                        part.normals.push(self.normals[index]);
+                        if let Some(&color) = self.colors.get(index) {
+                            part.colors.push(color);
+                        }
+                        part.vertices.len() - 1
+                    })
+                });
+                part.indices.push(tri);
+            }
+        }
+        found.then_some(part)
+    }
+
+    /// Loop subdivision applied `levels` times: every triangle splits in four and
+    /// each vertex is pulled toward its neighbours, rounding off a low-poly model.
+    /// Vertices sharing a position are welded first so flat-shaded faces subdivide
+    /// as one surface; normals come out smooth and groups keep their triangles.
+    fn subdivided(&self, levels: usize) -> Mesh {
+        let mut mesh = self.welded();
+        for _ in 0..levels {
+            mesh = mesh.loop_step();
+        }
+        mesh
+    }
+
+    /// Merges vertices with bit-identical positions, keeping the first one's color.
+    fn welded(&self) -> Mesh {
+        let mut welded = Mesh {
+            vertices: Vec::new(),
+            normals: Vec::new(),
+            colors: Vec::new(),
+            indices: Vec::new(),
+            groups: self.groups.clone(),
+            sphere: false,
+            bounds: OnceCell::new(),
+            bvh: OnceCell::new(),
+        };
+        let mut remap: HashMap<[u32; 3], usize> = HashMap::new();
+        let index_of: Vec<usize> = self
+            .vertices
+            .iter()
+            .enumerate()
+            .map(|(index, vertex)| {
+                let key = [vertex.x.to_bits(), vertex.y.to_bits(), vertex.z.to_bits()];
+                *remap.entry(key).or_insert_with(|| {
+                    welded.vertices.push(*vertex);
+                    if let Some(&color) = self.colors.get(index) {
+                        welded.colors.push(color);
+                    }
+                    welded.vertices.len() - 1
+                })
+            })
+            .collect();
+        welded.indices = self
+            .indices
+            .iter()
+            .map(|tri| tri.map(|index| index_of[index]))
+            .collect();
+        welded.normals = smooth_normals(&welded.vertices, &welded.indices);
+        welded
+    }
+
+    fn loop_step(&self) -> Mesh {
+        let count = self.vertices.len();
+        // Per edge (low index first): its new midpoint vertex and the corners facing it.
+        let mut edges: HashMap<(usize, usize), (usize, Vec<usize>)> = HashMap::new();
+        let mut order = Vec::new();
+        for tri in &self.indices {
+            for corner in 0..3 {
+                let a = tri[corner];
+                let b = tri[(corner + 1) % 3];
+                let key = (a.min(b), a.max(b));
+                edges
+                    .entry(key)
+                    .or_insert_with(|| {
+                        order.push(key);
+                        (count + order.len() - 1, Vec::new())
+                    })
+                    .1
+                    .push(tri[(corner + 2) % 3]);
+            }
+        }
+        let mut ring = vec![Vec::new(); count];
+        let mut rim = vec![Vec::new(); count];
+        for &(a, b) in &order {
+            ring[a].push(b);
+            ring[b].push(a);
+            if edges[&(a, b)].1.len() == 1 {
+                rim[a].push(b);
+                rim[b].push(a);
+            }
+        }
+
+        let p = &self.vertices;
+        let mut vertices = Vec::with_capacity(count + order.len());
+        for (index, &vertex) in p.iter().enumerate() {
+            let sum = ring[index]
+                .iter()
+                .fold(Vec3::ZERO, |sum, &other| sum + p[other]);
+            vertices.push(match (rim[index].as_slice(), ring[index].len()) {
+                (&[b1, b2], _) => vertex * 0.75 + (p[b1] + p[b2]) * 0.125,
+                // Corners of an open edge and non-manifold vertices stay put.
+                ([_, ..], _) | (_, 0) => vertex,
+                (_, n) => {
+                    let beta = if n == 3 {
+                        3.0 / 16.0
+                    } else {
+                        3.0 / (8.0 * n as f32)
+                    };
+                    vertex * (1.0 - n as f32 * beta) + sum * beta
+                }
+            });
+        }
+        let mut colors = self.colors.clone();
+        for &(a, b) in &order {
+            vertices.push(match edges[&(a, b)].1.as_slice() {
+                &[c, d] => (p[a] + p[b]) * 0.375 + (p[c] + p[d]) * 0.125,
+                _ => (p[a] + p[b]) * 0.5,
+            });
+            if !self.colors.is_empty() {
+                colors.push((self.colors[a] + self.colors[b]) * 0.5);
+            }
+        }
+
+        let mid = |a: usize, b: usize| edges[&(a.min(b), a.max(b))].0;
+        let mut indices = Vec::with_capacity(self.indices.len() * 4);
+        for &[a, b, c] in &self.indices {
+            let (ab, bc, ca) = (mid(a, b), mid(b, c), mid(c, a));
+            indices.extend([[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]);
+        }
+        Mesh {
+            normals: smooth_normals(&vertices, &indices),
+            vertices,
+            colors,
             indices,
+            groups: self
+                .groups
+                .iter()
+                .map(|group| MeshGroup {
+                    name: group.name.clone(),
+                    triangles: group.triangles.start * 4..group.triangles.end * 4,
+                })
+                .collect(),
+            sphere: false,
+            bounds: OnceCell::new(),
+            bvh: OnceCell::new(),
+        }
+    }
+
+    /// The mesh with normals rebuilt under `mode`. Each corner averages the faces
+    /// around its position (matched exactly) that `mode` lets it blend with, and
+    /// vertices are split wherever corners sharing one end up with different normals.
+    fn renormalized(&self, mode: NormalMode) -> Mesh {
+        let position_key =
+            |vertex: Vec3| [vertex.x.to_bits(), vertex.y.to_bits(), vertex.z.to_bits()];
+        let face_normals: Vec<Vec3> = self
+            .indices
+            .iter()
+            .map(|tri| {
+                let [a, b, c] = tri.map(|index| self.vertices[index]);
+                (b - a).cross(c - a).normalized()
+            })
+            .collect();
+        let mut around: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+        for (face, tri) in self.indices.iter().enumerate() {
+            for &index in tri {
+                around
+                    .entry(position_key(self.vertices[index]))
+                    .or_default()
+                    .push(face);
+            }
+        }
+        // Least cosine between two faces that still blend; a face always keeps its own.
+        let min_cos = match mode {
+            NormalMode::Flat => f32::INFINITY,
+            NormalMode::Smooth => -1.0,
+            NormalMode::Crease(angle) => angle.to_radians().cos(),
+        };
+        let mut mesh = Mesh {
+            vertices: Vec::new(),
+            normals: Vec::new(),
+            colors: Vec::new(),
+            indices: Vec::with_capacity(self.indices.len()),
+            groups: self.groups.clone(),
+            sphere: false,
+            bounds: OnceCell::new(),
+            bvh: OnceCell::new(),
+        };
+        let mut split: HashMap<(usize, [u32; 3]), usize> = HashMap::new();
+        for (face, tri) in self.indices.iter().enumerate() {
+            let own = face_normals[face];
+            let tri = tri.map(|index| {
+                let normal = around[&position_key(self.vertices[index])]
+                    .iter()
+                    .filter(|&&other| other == face || face_normals[other].dot(own) >= min_cos)
+                    .fold(Vec3::ZERO, |sum, &other| sum + face_normals[other])
+                    .normalized();
+                *split
+                    .entry((index, position_key(normal)))
+                    .or_insert_with(|| {
+                        mesh.vertices.push(self.vertices[index]);
+                        mesh.normals.push(normal);
+                        if let Some(&color) = self.colors.get(index) {
+                            mesh.colors.push(color);
+                        }
+                        mesh.vertices.len() - 1
+                    })
+            });
+            mesh.indices.push(tri);
+        }
+        mesh
+    }
+
+    fn bounds(&self) -> MeshBounds {
+        *self.bounds.get_or_init(|| MeshBounds::of(&self.vertices))
+    }
+
+    /// Moves every vertex by `transform` and turns the normals with it.
+    fn transform(&mut self, transform: &Mat4) {
+        let normal_matrix = transform.normal_matrix();
+        for vertex in self.vertices.iter_mut() {
+            *vertex = (*transform * Vec4::new(vertex.x, vertex.y, vertex.z, 1.0)).xyz();
+        }
+        for normal in self.normals.iter_mut() {
+            *normal = (normal_matrix * Vec4::new(normal.x, normal.y, normal.z, 0.0))
+                .xyz()
+                .normalized();
+        }
+        self.sphere = false;
+        self.vertices_moved();
+    }
+
+    fn vertices_moved(&mut self) {
+        self.bounds = OnceCell::new();
+        self.bvh = OnceCell::new();
+    }
+
+    /// Distance along the ray to the nearest triangle of the mesh drawn with
+    /// `transform`, in units of `direction`'s length. Triangles count from both sides.
+    fn ray_hit(&self, transform: &Mat4, origin: Vec3, direction: Vec3) -> Option<f32> {
+        // An affine map keeps the ray parameter, so `t` in mesh space is `t` in the world.
+        let inverse = transform.inverse_affine();
+        let origin = (inverse * Vec4::new(origin.x, origin.y, origin.z, 1.0)).xyz();
+        let direction = (inverse * Vec4::new(direction.x, direction.y, direction.z, 0.0)).xyz();
+        self.bvh
+            .get_or_init(|| TriangleBvh::build(self))
+            .hit(self, origin, direction)
+    }
+
+    /// Writes the mesh as OBJ object `name`, moved by `transform`, with each vertex
+    /// colored `color` times its own tint. OBJ indices count from the start of the
+    /// file, so `first_vertex` holds how many vertices came before and is advanced
+    /// past these.
+    fn write_obj(
+        &self,
+        out: &mut impl Write,
+        name: &str,
+        transform: &Mat4,
+        color: Color,
+        first_vertex: &mut usize,
+    ) -> std::io::Result<()> {
+        writeln!(out, "o {}", name)?;
+        for (index, vertex) in self.vertices.iter().enumerate() {
+            let p = (*transform * Vec4::new(vertex.x, vertex.y, vertex.z, 1.0)).xyz();
+            let tint = self.colors.get(index).map_or(color, |&own| color * own);
+            writeln!(
+                out,
+                "v {} {} {} {} {} {}",
+                p.x, p.y, p.z, tint.r, tint.g, tint.b
+            )?;
+        }
+        let normal_matrix = transform.normal_matrix();
+        for normal in &self.normals {
+            let n = (normal_matrix * Vec4::new(normal.x, normal.y, normal.z, 0.0))
+                .xyz()
+                .normalized();
+            writeln!(out, "vn {} {} {}", n.x, n.y, n.z)?;
+        }
+        for tri in &self.indices {
+            let [a, b, c] = tri.map(|index| *first_vertex + index + 1);
+            writeln!(out, "f {0}//{0} {1}//{1} {2}//{2}", a, b, c)?;
         }
+        *first_vertex += self.vertices.len();
+        Ok(())
     }
+}
+
+/// The twelve corners of an icosahedron on the unit sphere and its twenty faces,
+/// wound outward like `uv_sphere` so the near side survives backface culling.
+fn icosahedron() -> (Vec<Vec3>, Vec<[usize; 3]>) {
+    let t = (1.0 + 5f32.sqrt()) / 2.0;
+    let corners = [
+        (-1.0, t, 0.0),
+        (1.0, t, 0.0),
+        (-1.0, -t, 0.0),
+        (1.0, -t, 0.0),
+        (0.0, -1.0, t),
+        (0.0, 1.0, t),
+        (0.0, -1.0, -t),
+        (0.0, 1.0, -t),
+        (t, 0.0, -1.0),
+        (t, 0.0, 1.0),
+        (-t, 0.0, -1.0),
+        (-t, 0.0, 1.0),
+    ];
+    let faces = [
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+    let vertices: Vec<Vec3> = corners
+        .iter()
+        .map(|&(x, y, z)| Vec3::new(x, y, z).normalized())
+        .collect();
+    let faces = faces
+        .iter()
+        .map(|&[a, b, c]| {
+            let (pa, pb, pc) = (vertices[a], vertices[b], vertices[c]);
+            if (pb - pa).cross(pc - pa).dot(pa + pb + pc) < 0.0 {
+                [a, c, b]
+            } else {
+                [a, b, c]
+            }
+        })
+        .collect();
+    (vertices, faces)
+}
+
+/// Per-vertex normals averaged from the faces around each vertex.
+fn smooth_normals(vertices: &[Vec3], indices: &[[usize; 3]]) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::ZERO; vertices.len()];
+    for tri in indices {
+        let a = vertices[tri[0]];
+        let b = vertices[tri[1]];
+        let c = vertices[tri[2]];
+        let normal = (b - a).cross(c - a).normalized();
+        normals[tri[0]] += normal;
+        normals[tri[1]] += normal;
+        normals[tri[2]] += normal;
+    }
+    for normal in normals.iter_mut() {
+        if normal.length_squared() > 0.0 {
+            *normal = normal.normalized();
+        }
+    }
+    normals
+}
+
+/// Value type of a PLY property, under either of its header spellings.
+#[derive(Clone, Copy)]
+enum PlyType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => PlyType::I8,
+            "uchar" | "uint8" => PlyType::U8,
+            "short" | "int16" => PlyType::I16,
+            "ushort" | "uint16" => PlyType::U16,
+            "int" | "int32" => PlyType::I32,
+            "uint" | "uint32" => PlyType::U32,
+            "float" | "float32" => PlyType::F32,
+            "double" | "float64" => PlyType::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8,
+        }
+    }
+
+    /// What a color channel of this type reads at full intensity.
+    fn color_scale(self) -> f32 {
+        match self {
+            PlyType::U8 | PlyType::I8 => 255.0,
+            PlyType::U16 | PlyType::I16 => 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+enum PlyProperty {
+    Scalar(PlyType, String),
+    /// A count of type `.0` followed by that many values of type `.1`.
+    List(PlyType, PlyType, String),
+}
+
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+/// The data after a PLY header, read one value at a time.
+enum PlyBody<'a> {
+    Ascii(std::str::SplitWhitespace<'a>),
+    Binary { bytes: &'a [u8], big_endian: bool },
+}
 
-    fn from_obj(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        let mut positions = Vec::new();
-        let mut face_indices: Vec<[usize; 3]> = Vec::new();
-        for line in reader.lines() {
-            let line = line?;
-            if line.starts_with('v') && line.chars().nth(1) == Some(' ') {
-                let mut parts = line.split_whitespace();
-                parts.next();
-                let x: f32 = parts.next().unwrap_or("0").parse()?;
-                let y: f32 = parts.next().unwrap_or("0").parse()?;
-                let z: f32 = parts.next().unwrap_or("0").parse()?;
-                positions.push(Vec3::new(x, y, z));
-            } else if line.starts_with('f') {
-                let mut parts = line.split_whitespace();
-                parts.next();
-                let face: Vec<usize> = parts
-                    .filter_map(|chunk| chunk.split('/').next())
-                    .filter_map(|idx| idx.parse::<usize>().ok().map(|v| v - 1))
-                    .collect();
-                if face.len() >= 3 {
-                    for tri in 1..face.len() - 1 {
-                        face_indices.push([face[0], face[tri], face[tri + 1]]);
-                    }
+impl PlyBody<'_> {
+    fn read(&mut self, ty: PlyType) -> Result<f64, String> {
+        match self {
+            PlyBody::Ascii(words) => {
+                let word = words.next().ok_or("file ends in the middle of the data")?;
+                word.parse()
+                    .map_err(|_| format!("`{}` is not a number", word))
+            }
+            PlyBody::Binary { bytes, big_endian } => {
+                if bytes.len() < ty.size() {
+                    return Err("file ends in the middle of the data".to_string());
                 }
+                let (value, rest) = bytes.split_at(ty.size());
+                *bytes = rest;
+                let mut raw = [0u8; 8];
+                raw[..value.len()].copy_from_slice(value);
+                if *big_endian {
+                    raw[..value.len()].reverse();
+                }
+                Ok(match ty {
+                    PlyType::I8 => raw[0] as i8 as f64,
+                    PlyType::U8 => raw[0] as f64,
+                    PlyType::I16 => i16::from_le_bytes([raw[0], raw[1]]) as f64,
+                    PlyType::U16 => u16::from_le_bytes([raw[0], raw[1]]) as f64,
+                    PlyType::I32 => i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+                    PlyType::U32 => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+                    PlyType::F32 => f32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+                    PlyType::F64 => f64::from_le_bytes(raw),
+                })
             }
         }
-        let mut normals = vec![Vec3::ZERO; positions.len()];
-        for tri in &face_indices {
-            let a = positions[tri[0]];
-            let b = positions[tri[1]];
-            let c = positions[tri[2]];
-            let normal = (b - a).cross(c - a).normalized();
-            normals[tri[0]] += normal;
-            normals[tri[1]] += normal;
-            normals[tri[2]] += normal;
-        }
-        for normal in normals.iter_mut() {
-            if normal.length_squared() > 0.0 {
-                *normal = normal.normalized();
+    }
+}
+
+/// How the mesh file of a prop or station becomes the mesh drawn, from the `object`,
+/// `up`, `center`, `fit`, `subdivide` and `normals` keys next to its `mesh`.
+#[derive(Clone, Default)]
+struct MeshImport {
+    /// Group of the OBJ to show instead of the whole file.
+    object: Option<String>,
+    /// The file was authored with Z up and gets turned to this scene's Y up.
+    z_up: bool,
+    /// Moves the middle of the bounding box to the origin.
+    center: bool,
+    /// Uniform scale that makes the bounding box's longest side this long.
+    fit: Option<f32>,
+    /// Loop subdivision passes, 0 to `MAX_SUBDIVIDE`.
+    subdivide: usize,
+    /// Normals to rebuild; the file's own (or its smoothing groups') when `None`.
+    normals: Option<NormalMode>,
+}
+
+impl MeshImport {
+    fn from_section(section: &SceneSection) -> Result<Self, Box<dyn std::error::Error>> {
+        let subdivide = match section.get("subdivide") {
+            Some(value) => value
+                .parse()
+                .ok()
+                .filter(|&levels| levels <= MAX_SUBDIVIDE)
+                .ok_or_else(|| {
+                    section.error(&format!(
+                        "`subdivide` must be a whole number from 0 to {}, got `{}`",
+                        MAX_SUBDIVIDE, value
+                    ))
+                })?,
+            None => 0,
+        };
+        let normals = match section.get("normals") {
+            Some(value) => Some(NormalMode::parse(value).ok_or_else(|| {
+                section.error(&format!(
+                    "`normals` must be flat, smooth or smooth <degrees>, got `{}`",
+                    value
+                ))
+            })?),
+            None => None,
+        };
+        let z_up = match section.get("up") {
+            None | Some("y") => false,
+            Some("z") => true,
+            Some(other) => {
+                return Err(section.error(&format!("`up` must be y or z, got `{}`", other)))
             }
-        }
+        };
+        let fit = match section.get("fit") {
+            Some(_) => match section.float("fit")? {
+                size if size > 0.0 => Some(size),
+                _ => return Err(section.error("`fit` must be a size above 0")),
+            },
+            None => None,
+        };
         Ok(Self {
-            vertices: positions,
+            object: section.get("object").map(str::to_string),
+            z_up,
+            center: section.flag_or("center", false)?,
+            fit,
+            subdivide,
             normals,
-            indices: face_indices,
         })
     }
+
+    fn write(&self, mut section: SceneSection) -> SceneSection {
+        if let Some(object) = &self.object {
+            section = section.field("object", object.as_str());
+        }
+        if self.z_up {
+            section = section.field("up", "z");
+        }
+        if self.center {
+            section = section.field("center", true);
+        }
+        if let Some(size) = self.fit {
+            section = section.field("fit", size);
+        }
+        if self.subdivide > 0 {
+            section = section.field("subdivide", self.subdivide);
+        }
+        if let Some(normals) = self.normals {
+            section = section.field("normals", normals);
+        }
+        section
+    }
+}
+
+/// How `Mesh::renormalized` shades across edges.
+#[derive(Clone, Copy)]
+enum NormalMode {
+    /// Every triangle lit as a flat face.
+    Flat,
+    /// Normals averaged over all the faces around each position.
+    Smooth,
+    /// Averaged only over faces meeting at less than this many degrees, so sharper
+    /// edges stay hard.
+    Crease(f32),
+}
+
+impl NormalMode {
+    fn parse(text: &str) -> Option<Self> {
+        let mut words = text.split_whitespace();
+        let mode = match (words.next()?, words.next()) {
+            ("flat", None) => NormalMode::Flat,
+            ("smooth", None) => NormalMode::Smooth,
+            ("smooth", Some(angle)) => NormalMode::Crease(
+                angle
+                    .parse()
+                    .ok()
+                    .filter(|angle: &f32| (0.0..=180.0).contains(angle))?,
+            ),
+            _ => return None,
+        };
+        words.next().is_none().then_some(mode)
+    }
+}
+
+impl SceneValue for NormalMode {
+    fn scene_text(&self) -> String {
+        match self {
+            NormalMode::Flat => "flat".to_string(),
+            NormalMode::Smooth => "smooth".to_string(),
+            NormalMode::Crease(angle) => format!("smooth {:?}", angle),
+        }
+    }
+}
+
+/// Loads the OBJ or PLY at `path` (by extension), or builds the primitive it
+/// names (see `Mesh::primitive`), then applies `import` in order: cut down to
+/// its object, turned Y up, subdivided, centered and fitted, renormalized.
+fn load_mesh(path: &str, import: &MeshImport) -> Result<Mesh, Box<dyn std::error::Error>> {
+    let is_ply = Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ply"));
+    let mut mesh = if let Some(primitive) = Mesh::primitive(path) {
+        primitive.map_err(|reason| format!("mesh `{}`: {}", path, reason))?
+    } else if is_ply {
+        Mesh::from_ply(Path::new(path))?
+    } else {
+        Mesh::from_obj(Path::new(path))?
+    };
+    if let Some(name) = &import.object {
+        mesh = mesh
+            .submesh(name)
+            .ok_or_else(|| format!("{}: no object or group named `{}`", path, name))?;
+    }
+    if import.z_up {
+        mesh.transform(&Mat4::rotation_x(-PI / 2.0));
+    }
+    if import.subdivide > 0 {
+        mesh = mesh.subdivided(import.subdivide);
+    }
+    if (import.center || import.fit.is_some()) && !mesh.vertices.is_empty() {
+        let bounds = mesh.bounds();
+        let size = bounds.size();
+        let scale = match import.fit {
+            Some(fit) => fit / size.x.max(size.y).max(size.z).max(1e-6),
+            None => 1.0,
+        };
+        let offset = if import.center {
+            -bounds.center
+        } else {
+            Vec3::ZERO
+        };
+        mesh.transform(&(Mat4::scale(Vec3::splat(scale)) * Mat4::translation(offset)));
+    }
+    if let Some(mode) = import.normals {
+        mesh = mesh.renormalized(mode);
+    }
+    Ok(mesh)
 }
 
+/// One component of `VERTEX_LANES` vertices.
+type Lanes = [f32; VERTEX_LANES];
+
 #[derive(Clone, Copy, Debug)]
 struct VertexOut {
     screen: Vec3,
     world: Vec3,
     normal: Vec3,
+    color: Color,
+    /// Mesh-space position, for emissive maps.
+    local: Vec3,
     inv_w: f32,
 }
 
@@ -1004,50 +13869,409 @@ struct Sky {
     stars: Vec<StarPixel>,
     width: usize,
     height: usize,
+    /// Packed vertical gradient with the ecliptic band, and the palette colors and
+    /// band intensity it was built from.
+    background: Vec<u32>,
+    gradient: Option<(Color, Color, Color, f32)>,
+    /// Gradient plus galaxy band for `view`, and `lit`, the same with the stars
+    /// drawn on top, which is what gets copied into the frame.
+    composed: Vec<u32>,
+    lit: Vec<u32>,
+    /// Camera yaw, pitch and fov that `composed` and `placed` were built for.
+    view: Option<(f32, f32, f32)>,
+    /// Index and top-left pixel of every star on screen for `view`.
+    placed: Vec<(usize, usize, usize)>,
+    /// `lit` holds streaks rather than stars and must be rebuilt from `composed`.
+    streaked: bool,
 }
 
+/// Background star on the unit sphere. `layer` indexes `SKY_LAYER_PARALLAX`.
 struct StarPixel {
-    x: usize,
-    y: usize,
+    direction: Vec3,
+    layer: usize,
+    size: usize,
     intensity: f32,
+    /// -1 is the warmest tint, 1 the coolest.
+    temperature: f32,
+    twinkle_rate: f32,
+    twinkle_phase: f32,
+}
+
+impl StarPixel {
+    /// `star_color` pulled towards warm or cool by the star's temperature.
+    fn tint(&self, star_color: Color) -> Color {
+        if self.temperature < 0.0 {
+            Color::lerp(star_color, Color::new(1.0, 0.72, 0.5), -self.temperature)
+        } else {
+            Color::lerp(star_color, Color::new(0.68, 0.8, 1.0), self.temperature)
+        }
+    }
+}
+
+/// Equirectangular snapshot of the sky by direction (gradient, ecliptic and galaxy
+/// bands, background stars), looked up for reflections. Unlike the sky on screen it
+/// has no parallax or twinkle, and the gradient runs from zenith to nadir.
+struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    texels: Vec<Color>,
+}
+
+impl EnvironmentMap {
+    fn new(palette: &Palette, stars: &[StarPixel]) -> Self {
+        let (width, height) = (ENVIRONMENT_WIDTH, ENVIRONMENT_WIDTH / 2);
+        // About the angular height of the on-screen ecliptic band at the default fov.
+        let band_height = DEFAULT_FOV * 0.1;
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let theta = (y as f32 + 0.5) / height as f32 * PI;
+            for x in 0..width {
+                let phi = (x as f32 + 0.5) / width as f32 * TAU - PI;
+                let dir = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                let mut color = Color::lerp(palette.sky_top, palette.sky_bottom, 0.5 - dir.y * 0.5);
+                let offset = dir.y.abs() / band_height;
+                if offset < 1.0 {
+                    let band = palette.ecliptic_intensity * (1.0 - offset * offset);
+                    color = color.blend_additive(palette.ecliptic * band);
+                }
+                if palette.galaxy.intensity > 0.0 {
+                    if let Some(glow) = palette.galaxy.glow(dir) {
+                        color = color + glow;
+                    }
+                }
+                texels.push(color);
+            }
+        }
+        let mut map = Self {
+            width,
+            height,
+            texels,
+        };
+        // Only the nearest layer's stars, or a curved mirror shows a dense speckle.
+        for star in stars
+            .iter()
+            .filter(|star| star.layer == SKY_LAYER_PARALLAX.len() - 1)
+        {
+            let (x, y) = map.coordinates(star.direction);
+            let index = y as usize * width + x as usize;
+            let light = star.tint(palette.star_color) * star.intensity;
+            map.texels[index] = map.texels[index] + light;
+        }
+        map
+    }
+
+    /// Texel-space position of `dir`, wrapping around the horizon.
+    fn coordinates(&self, dir: Vec3) -> (f32, f32) {
+        let u = (dir.z.atan2(dir.x) + PI) / TAU;
+        let v = dir.y.clamp(-1.0, 1.0).acos() / PI;
+        (
+            (u * self.width as f32).min(self.width as f32 - 1.0),
+            (v * self.height as f32).min(self.height as f32 - 1.0),
+        )
+    }
+
+    /// Sky color along unit direction `dir`, filtered between the four nearest texels.
+    fn sample(&self, dir: Vec3) -> Color {
+        let (u, v) = self.coordinates(dir);
+        let (u, v) = ((u - 0.5).max(0.0), (v - 0.5).max(0.0));
+        let (x, y) = (u as usize, v as usize);
+        let (tx, ty) = (u - x as f32, v - y as f32);
+        let x1 = (x + 1) % self.width;
+        let y1 = (y + 1).min(self.height - 1);
+        let at = |x: usize, y: usize| self.texels[y * self.width + x];
+        Color::lerp(
+            Color::lerp(at(x, y), at(x1, y), tx),
+            Color::lerp(at(x, y1), at(x1, y1), tx),
+            ty,
+        )
+    }
 }
 
 impl Sky {
-    fn new(width: usize, height: usize, count: usize) -> Self {
+    fn new(width: usize, height: usize, config: &StarfieldConfig) -> Self {
+        // `config.count` is how many stars a resting view shows, so scale it up by
+        // the share of the sphere the default frustum covers.
+        let half_y = DEFAULT_FOV * 0.5;
+        let half_x = (half_y.tan() * width as f32 / height as f32).atan();
+        let visible = 4.0 * (half_x.sin() * half_y.sin()).asin() / (4.0 * PI);
+        let total = (config.count as f32 / visible) as usize;
         let mut rng = Lcg::new(42);
-        let mut stars = Vec::with_capacity(count);
-        for _ in 0..count {
-            let x = (rng.next_f32() * width as f32) as usize;
-            let y = (rng.next_f32() * height as f32) as usize;
-            let intensity = 0.5 + rng.next_f32() * 0.5;
-            stars.push(StarPixel { x, y, intensity });
+        let mut stars = Vec::with_capacity(total);
+        for _ in 0..total {
+            let z = rng.next_f32() * 2.0 - 1.0;
+            let phi = rng.next_f32() * TAU;
+            let ring = (1.0 - z * z).sqrt();
+            let direction = Vec3::new(ring * phi.cos(), ring * phi.sin(), z);
+            let pick = rng.next_f32();
+            let layer = if pick < 0.5 {
+                0
+            } else if pick < 0.85 {
+                1
+            } else {
+                2
+            };
+            let bright = rng.next_f32() < config.bright_fraction;
+            let intensity = if bright {
+                0.9 + rng.next_f32() * 0.1
+            } else {
+                0.5 + rng.next_f32() * 0.5
+            };
+            stars.push(StarPixel {
+                direction,
+                layer,
+                size: config.size + bright as usize,
+                intensity,
+                temperature: (rng.next_f32() * 2.0 - 1.0) * config.temperature_spread,
+                twinkle_rate: 1.5 + rng.next_f32() * 3.5,
+                twinkle_phase: rng.next_f32() * TAU,
+            });
         }
         Self {
             stars,
             width,
             height,
+            background: Vec::new(),
+            gradient: None,
+            composed: vec![0; width * height],
+            lit: vec![0; width * height],
+            view: None,
+            placed: Vec::new(),
+            streaked: false,
         }
     }
 
-    fn paint(&self, buffer: &mut [u32], palette: &Palette) {
+    /// Rebuilds the cached background when the gradient or ecliptic band changes.
+    /// Both are fixed on screen, unlike the stars and galaxy band that move with the
+    /// camera. The band covers the middle fifth of the rows and fades quadratically
+    /// towards its edges.
+    fn set_background(&mut self, palette: &Palette) {
+        let key = (
+            palette.sky_top,
+            palette.sky_bottom,
+            palette.ecliptic,
+            palette.ecliptic_intensity,
+        );
+        if self.gradient == Some(key) {
+            return;
+        }
+        self.gradient = Some(key);
+        self.view = None;
+        self.background.clear();
+        let band_height = (self.height as f32 * 0.1).max(1.0);
+        let center = (self.height / 2) as f32;
         for y in 0..self.height {
             let t = y as f32 / (self.height.max(1) as f32);
-            let base = Color::lerp(palette.sky_top, palette.sky_bottom, t);
-            for x in 0..self.width {
-                buffer[y * self.width + x] = base.to_u32();
+            let mut color = Color::lerp(palette.sky_top, palette.sky_bottom, t);
+            let offset = (y as f32 - center).abs() / band_height;
+            if offset < 1.0 {
+                let band = palette.ecliptic_intensity * (1.0 - offset * offset);
+                color = color.blend_additive(palette.ecliptic * band);
             }
+            self.background
+                .extend(std::iter::repeat_n(color.to_u32(), self.width));
         }
-        for star in &self.stars {
-            if star.x >= self.width || star.y >= self.height {
+    }
+
+    /// Adds the galaxy band: each 2x2 pixel block casts a view ray and is lit by how
+    /// close that ray lies to the band's great circle.
+    fn paint_galaxy(
+        &self,
+        buffer: &mut [u32],
+        galaxy: &GalaxyConfig,
+        camera: &Camera,
+        focal_x: f32,
+        focal_y: f32,
+    ) {
+        let forward = camera.forward();
+        let right = forward.cross(Vec3::UP).normalized();
+        let up = right.cross(forward);
+        for by in (0..self.height).step_by(2) {
+            for bx in (0..self.width).step_by(2) {
+                let ndc_x = ((bx as f32 + 1.0) / self.width as f32) * 2.0 - 1.0;
+                let ndc_y = 1.0 - ((by as f32 + 1.0) / self.height as f32) * 2.0;
+                let dir =
+                    (forward + right * (ndc_x / focal_x) + up * (ndc_y / focal_y)).normalized();
+                let Some(glow) = galaxy.glow(dir) else {
+                    continue;
+                };
+                for y in by..(by + 2).min(self.height) {
+                    for x in bx..(bx + 2).min(self.width) {
+                        let idx = y * self.width + x;
+                        buffer[idx] = (Color::from_u32(buffer[idx]) + glow).to_u32();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copies the sky into `buffer`. While the camera keeps its orientation and fov
+    /// the gradient, galaxy band and star positions are reused, and only the star
+    /// pixels are restored and redrawn with their new twinkle. A `streak` above zero
+    /// draws each star with a radial trail, as during a warp.
+    fn paint(
+        &mut self,
+        buffer: &mut [u32],
+        palette: &Palette,
+        time: f32,
+        camera: &Camera,
+        streak: f32,
+    ) {
+        let view = (camera.yaw, camera.pitch, camera.fov);
+        if self.view != Some(view) {
+            self.compose(palette, camera);
+            self.view = Some(view);
+            self.lit.copy_from_slice(&self.composed);
+        } else if streak > 0.0 || self.streaked {
+            self.lit.copy_from_slice(&self.composed);
+        } else if palette.starfield.twinkle > 0.0 {
+            for &(index, sx, sy) in &self.placed {
+                let size = self.stars[index].size;
+                for y in sy..(sy + size).min(self.height) {
+                    let row = y * self.width;
+                    let span = row + sx..row + (sx + size).min(self.width);
+                    self.lit[span.clone()].copy_from_slice(&self.composed[span]);
+                }
+            }
+        } else {
+            buffer.copy_from_slice(&self.lit);
+            return;
+        }
+        self.streaked = streak > 0.0;
+        let twinkle = palette.starfield.twinkle;
+        for &(index, sx, sy) in &self.placed {
+            let star = &self.stars[index];
+            let tint = star.tint(palette.star_color);
+            let flicker =
+                1.0 - twinkle * (0.5 + 0.5 * (time * star.twinkle_rate + star.twinkle_phase).sin());
+            let brightness = star.intensity * flicker * SKY_LAYER_BRIGHTNESS[star.layer];
+            if streak > 0.0 {
+                let size = (self.width, self.height);
+                draw_star_streak(
+                    &mut self.lit,
+                    size,
+                    star.layer,
+                    (sx, sy),
+                    tint * brightness,
+                    streak,
+                );
+            }
+            let packed = (tint * brightness).to_u32();
+            for y in sy..(sy + star.size).min(self.height) {
+                for x in sx..(sx + star.size).min(self.width) {
+                    self.lit[y * self.width + x] = packed;
+                }
+            }
+        }
+        buffer.copy_from_slice(&self.lit);
+    }
+
+    /// Rebuilds `composed` and `placed` for a new camera orientation.
+    fn compose(&mut self, palette: &Palette, camera: &Camera) {
+        self.composed.copy_from_slice(&self.background);
+        let focal_y = 1.0 / (camera.fov * 0.5).tan();
+        let focal_x = focal_y * self.height as f32 / self.width as f32;
+        if palette.galaxy.intensity > 0.0 {
+            let mut composed = std::mem::take(&mut self.composed);
+            self.paint_galaxy(&mut composed, &palette.galaxy, camera, focal_x, focal_y);
+            self.composed = composed;
+        }
+        self.placed.clear();
+        let layer_basis = SKY_LAYER_PARALLAX.map(|parallax| {
+            let layer_camera = Camera {
+                yaw: camera.yaw * parallax,
+                pitch: camera.pitch * parallax,
+                ..Camera::new(Vec3::ZERO)
+            };
+            let forward = layer_camera.forward();
+            let right = forward.cross(Vec3::UP).normalized();
+            (forward, right, right.cross(forward))
+        });
+        for (index, star) in self.stars.iter().enumerate() {
+            let (forward, right, up) = layer_basis[star.layer];
+            let depth = star.direction.dot(forward);
+            if depth <= 0.0 {
                 continue;
             }
-            let idx = star.y * self.width + star.x;
-            let color = palette.star_color * star.intensity;
-            buffer[idx] = color.to_u32();
+            let ndc_x = star.direction.dot(right) / depth * focal_x;
+            let ndc_y = star.direction.dot(up) / depth * focal_y;
+            if ndc_x.abs() > 1.0 || ndc_y.abs() > 1.0 {
+                continue;
+            }
+            let sx = ((ndc_x * 0.5 + 0.5) * self.width as f32) as usize;
+            let sy = ((0.5 - ndc_y * 0.5) * self.height as f32) as usize;
+            self.placed.push((index, sx, sy));
+        }
+    }
+}
+
+/// Trail behind a background star at `head` rushing outward from the centre of a
+/// `width` x `height` sky as the camera surges forward. Its length is the star's
+/// screen velocity: distance from the centre times the layer's parallax, so nearer
+/// layers streak longer.
+fn draw_star_streak(
+    lit: &mut [u32],
+    (width, height): (usize, usize),
+    layer: usize,
+    head: (usize, usize),
+    color: Color,
+    streak: f32,
+) {
+    let nearest = SKY_LAYER_PARALLAX[SKY_LAYER_PARALLAX.len() - 1];
+    let length = streak * WARP_STREAK_LENGTH * SKY_LAYER_PARALLAX[layer] / nearest;
+    let (x0, y0) = (head.0 as f32, head.1 as f32);
+    let dx = (x0 - width as f32 * 0.5) * length;
+    let dy = (y0 - height as f32 * 0.5) * length;
+    let steps = dx.abs().max(dy.abs()).ceil().max(1.0) as usize;
+    for step in 1..=steps {
+        let along = step as f32 / steps as f32;
+        let (x, y) = (x0 - dx * along, y0 - dy * along);
+        if x < 0.0 || y < 0.0 || x >= width as f32 || y >= height as f32 {
+            continue;
         }
+        let idx = y as usize * width + x as usize;
+        lit[idx] = Color::from_u32(lit[idx])
+            .blend_additive(color * (1.0 - along))
+            .to_u32();
     }
 }
 
+fn lattice_hash(x: i32, y: i32, z: i32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ (z as u32).wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5bd1_e995);
+    h ^= h >> 15;
+    h as f32 / u32::MAX as f32
+}
+
+/// Trilinear value noise in 0..1.
+fn value_noise(p: Vec3) -> f32 {
+    let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let (ix, iy, iz) = (fx as i32, fy as i32, fz as i32);
+    let (tx, ty, tz) = (
+        smoothstep(p.x - fx),
+        smoothstep(p.y - fy),
+        smoothstep(p.z - fz),
+    );
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let corner = |dx: i32, dy: i32, dz: i32| lattice_hash(ix + dx, iy + dy, iz + dz);
+    let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), tx);
+    let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), tx);
+    let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), tx);
+    let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), tx);
+    lerp(lerp(x00, x10, ty), lerp(x01, x11, ty), tz)
+}
+
+/// Three octaves of `value_noise`, normalized back to 0..1.
+fn fractal_noise(p: Vec3) -> f32 {
+    (value_noise(p) * 0.57 + value_noise(p * 2.1) * 0.29 + value_noise(p * 4.3) * 0.14).min(1.0)
+}
+
 struct Lcg {
     state: u64,
 }
@@ -1057,6 +14281,14 @@ impl Lcg {
         Self { state: seed }
     }
 
+    /// Seeded from an FNV-1a hash of `name`, so every body gets its own sequence.
+    fn from_name(name: &str) -> Self {
+        let hash = name.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+        Self::new(hash)
+    }
+
     fn next_f32(&mut self) -> f32 {
         self.state = self.state.wrapping_mul(6364136223846793005).wrapping_add(1);
         ((self.state >> 32) as f32) / (u32::MAX as f32)
@@ -1075,7 +14307,7 @@ impl Vec2 {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 struct Vec3 {
     x: f32,
     y: f32,
@@ -1086,7 +14318,7 @@ impl Vec3 {
     const ZERO: Self = Self { x: 0.0, y: 0.0, z: 0.0 };
     const UP: Self = Self { x: 0.0, y: 1.0, z: 0.0 };
 
-    fn new(x: f32, y: f32, z: f32) -> Self {
+    const fn new(x: f32, y: f32, z: f32) -> Self {
         Self { x, y, z }
     }
 
@@ -1191,7 +14423,7 @@ impl Vec4 {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 struct Mat4 {
     m: [[f32; 4]; 4],
 }
@@ -1208,6 +14440,16 @@ impl Mat4 {
         }
     }
 
+    fn lerp(a: &Mat4, b: &Mat4, t: f32) -> Self {
+        let mut m = *a;
+        for (row, b_row) in m.m.iter_mut().zip(&b.m) {
+            for (value, b_value) in row.iter_mut().zip(b_row) {
+                *value += (b_value - *value) * t;
+            }
+        }
+        m
+    }
+
     fn translation(v: Vec3) -> Self {
         let mut m = Self::identity();
         m.m[0][3] = v.x;
@@ -1240,6 +14482,19 @@ impl Mat4 {
         }
     }
 
+    fn rotation_z(angle: f32) -> Self {
+        let c = angle.cos();
+        let s = angle.sin();
+        Self {
+            m: [
+                [c, -s, 0.0, 0.0],
+                [s, c, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
     fn rotation_y(angle: f32) -> Self {
         let c = angle.cos();
         let s = angle.sin();
@@ -1265,6 +14520,19 @@ impl Mat4 {
         }
     }
 
+    /// Parallel projection of the box `half_width` x `half_height` around the view
+    /// axis between `near` and `far`; `w` stays 1, so sizes don't shrink with depth.
+    fn orthographic(half_width: f32, half_height: f32, near: f32, far: f32) -> Self {
+        Self {
+            m: [
+                [1.0 / half_width, 0.0, 0.0, 0.0],
+                [0.0, 1.0 / half_height, 0.0, 0.0],
+                [0.0, 0.0, 2.0 / (near - far), (far + near) / (near - far)],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
     fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Self {
         let forward = (target - eye).normalized();
         let right = forward.cross(up).normalized();
@@ -1279,6 +14547,66 @@ impl Mat4 {
         }
     }
 
+    /// Inverse-transpose of the upper 3x3 part, which keeps normals perpendicular to
+    /// surfaces under non-uniform scale. It is built from cofactors and only divided
+    /// by the determinant's sign, since callers renormalize the result.
+    fn normal_matrix(&self) -> Self {
+        let m = &self.m;
+        let cofactors = [
+            [
+                m[1][1] * m[2][2] - m[1][2] * m[2][1],
+                m[1][2] * m[2][0] - m[1][0] * m[2][2],
+                m[1][0] * m[2][1] - m[1][1] * m[2][0],
+            ],
+            [
+                m[0][2] * m[2][1] - m[0][1] * m[2][2],
+                m[0][0] * m[2][2] - m[0][2] * m[2][0],
+                m[0][1] * m[2][0] - m[0][0] * m[2][1],
+            ],
+            [
+                m[0][1] * m[1][2] - m[0][2] * m[1][1],
+                m[0][2] * m[1][0] - m[0][0] * m[1][2],
+                m[0][0] * m[1][1] - m[0][1] * m[1][0],
+            ],
+        ];
+        let determinant =
+            m[0][0] * cofactors[0][0] + m[0][1] * cofactors[0][1] + m[0][2] * cofactors[0][2];
+        let sign = if determinant < 0.0 { -1.0 } else { 1.0 };
+        let mut normal = Mat4::identity();
+        for (row, cofactor_row) in cofactors.iter().enumerate() {
+            for (col, cofactor) in cofactor_row.iter().enumerate() {
+                normal.m[row][col] = cofactor * sign;
+            }
+        }
+        normal
+    }
+
+    /// Inverse of a matrix with `0 0 0 1` for its last row (rotations, scales and
+    /// translations), from the cofactors of its upper 3x3 part.
+    fn inverse_affine(&self) -> Self {
+        let m = &self.m;
+        let cofactors = self.normal_matrix();
+        let determinant = (0..3)
+            .map(|col| m[0][col] * cofactors.m[0][col])
+            .sum::<f32>()
+            .abs();
+        let mut inverse = Mat4::identity();
+        if determinant <= f32::EPSILON {
+            return inverse;
+        }
+        for row in 0..3 {
+            for col in 0..3 {
+                inverse.m[row][col] = cofactors.m[col][row] / determinant;
+            }
+        }
+        for row in 0..3 {
+            inverse.m[row][3] = -(0..3)
+                .map(|col| inverse.m[row][col] * m[col][3])
+                .sum::<f32>();
+        }
+        inverse
+    }
+
     fn from_basis(right: Vec3, up: Vec3, forward: Vec3, position: Vec3) -> Self {
         Self {
             m: [
@@ -1289,6 +14617,22 @@ impl Mat4 {
             ],
         }
     }
+
+    /// Applies the first `ROWS` rows to `VERTEX_LANES` points (`w` 1) or directions
+    /// (`w` 0) stored one array per component.
+    fn transform_lanes<const ROWS: usize>(&self, input: &[Lanes; 3], w: f32) -> [Lanes; ROWS] {
+        let mut output = [[0.0; VERTEX_LANES]; ROWS];
+        for (row, out) in output.iter_mut().enumerate() {
+            let m = self.m[row];
+            for lane in 0..VERTEX_LANES {
+                out[lane] = m[0] * input[0][lane]
+                    + m[1] * input[1][lane]
+                    + m[2] * input[2][lane]
+                    + m[3] * w;
+            }
+        }
+        output
+    }
 }
 
 impl Mul<Vec4> for Mat4 {
@@ -1307,9 +14651,9 @@ impl Mul for Mat4 {
     type Output = Mat4;
     fn mul(self, rhs: Mat4) -> Mat4 {
         let mut m = [[0.0; 4]; 4];
-        for row in 0..4 {
-            for col in 0..4 {
-                m[row][col] = self.m[row][0] * rhs.m[0][col]
+        for (row, cells) in m.iter_mut().enumerate() {
+            for (col, cell) in cells.iter_mut().enumerate() {
+                *cell = self.m[row][0] * rhs.m[0][col]
                     + self.m[row][1] * rhs.m[1][col]
                     + self.m[row][2] * rhs.m[2][col]
                     + self.m[row][3] * rhs.m[3][col];
@@ -1319,7 +14663,7 @@ impl Mul for Mat4 {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 struct Color {
     r: f32,
     g: f32,
@@ -1335,7 +14679,7 @@ impl Color {
         Self { r, g, b }
     }
 
-    fn to_u32(&self) -> u32 {
+    fn to_u32(self) -> u32 {
         let r = (self.r.clamp(0.0, 1.0) * 255.0) as u32;
         let g = (self.g.clamp(0.0, 1.0) * 255.0) as u32;
         let b = (self.b.clamp(0.0, 1.0) * 255.0) as u32;
@@ -1386,3 +14730,417 @@ impl Mul for Color {
 }
 
 const TAU: f32 = PI * 2.0;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The built-in ember theme with `events` appended to its scene.
+    fn ember_with(events: &str) -> Theme {
+        let (_, text) = BUILTIN_SCENES
+            .iter()
+            .find(|(path, _)| path.ends_with("ember.scene"))
+            .unwrap();
+        Theme::from_document(&parse_scene(&format!("{}\n{}", text, events)).unwrap()).unwrap()
+    }
+
+    fn count_named(theme: &Theme, sim_time: f32, name: &str) -> (usize, usize) {
+        let planets = build_planets(&theme.planets);
+        let mut stars = build_stars(&theme.stars);
+        let mut stations = build_stations(&theme.stations, &planets).unwrap();
+        let mut asteroids = AsteroidCache::default();
+        let mut comets = build_comets(&theme.comets, &mut asteroids);
+        EventCalendar::start(
+            theme,
+            sim_time,
+            CalendarBodies {
+                planets: &planets,
+                stars: &mut stars,
+                stations: &mut stations,
+                comets: &mut comets,
+                asteroids: &mut asteroids,
+            },
+        );
+        (
+            comets.iter().filter(|comet| comet.name == name).count(),
+            stations
+                .iter()
+                .filter(|station| station.name == name)
+                .count(),
+        )
+    }
+
+    #[test]
+    fn calendar_start_after_arrival_keeps_one_body() {
+        let theme = ember_with(
+            "[event Comet In]\ntime = 5.0\naction = arrival\ntarget = Ashfall\n\n\
+             [event Relay In]\ntime = 0.0\naction = arrival\ntarget = Forge Relay\n",
+        );
+        assert_eq!(count_named(&theme, 10.0, "Ashfall"), (1, 0));
+        assert_eq!(count_named(&theme, 10.0, "Forge Relay"), (0, 1));
+        assert_eq!(count_named(&theme, 0.0, "Forge Relay"), (0, 1));
+        assert_eq!(count_named(&theme, 0.0, "Ashfall"), (0, 0));
+    }
+
+    #[test]
+    fn late_arrival_joins_its_orbit_at_the_current_time() {
+        let theme =
+            ember_with("[event Comet In]\ntime = 5.0\naction = arrival\ntarget = Ashfall\n");
+        let planets = build_planets(&theme.planets);
+        let mut stars = build_stars(&theme.stars);
+        let mut stations = build_stations(&theme.stations, &planets).unwrap();
+        let mut asteroids = AsteroidCache::default();
+        let mut comets = build_comets(&theme.comets, &mut asteroids);
+        let (mut calendar, _) = EventCalendar::start(
+            &theme,
+            0.0,
+            CalendarBodies {
+                planets: &planets,
+                stars: &mut stars,
+                stations: &mut stations,
+                comets: &mut comets,
+                asteroids: &mut asteroids,
+            },
+        );
+        calendar.update(
+            &theme,
+            30.0,
+            &mut CalendarBodies {
+                planets: &planets,
+                stars: &mut stars,
+                stations: &mut stations,
+                comets: &mut comets,
+                asteroids: &mut asteroids,
+            },
+        );
+        let arrived = comets.iter().find(|comet| comet.name == "Ashfall").unwrap();
+
+        // The orbit is analytic, so one long step lands where the small ones do.
+        let mut expected = Comet::from_descriptor(
+            theme
+                .comets
+                .iter()
+                .find(|comet| comet.name == "Ashfall")
+                .unwrap(),
+            &mut asteroids,
+        );
+        expected.update(0.0);
+        let start = expected.position;
+        expected.update(30.0);
+        assert!((arrived.position - expected.position).length() < 1e-2);
+        assert!((arrived.position - start).length() > 1.0);
+    }
+
+    /// A body kind the core knows nothing about: drifts along X and blocks the camera.
+    struct Buoy {
+        x: f32,
+    }
+
+    impl CelestialBody for Buoy {
+        fn update(&mut self, dt: f32) {
+            self.x += dt;
+        }
+
+        fn render_instances<'a>(&'a self, _: &BodyContext<'a>, _: &mut Vec<RenderInstance<'a>>) {}
+
+        fn collider(&self) -> Option<Collider> {
+            Some(Collider {
+                center: Vec3::new(self.x, 0.0, 0.0),
+                radius: 0.2,
+            })
+        }
+    }
+
+    #[test]
+    fn custom_body_is_simulated_and_collides() {
+        let mut custom: Vec<Box<dyn CelestialBody>> = vec![Box::new(Buoy { x: 0.0 })];
+        advance_simulation(
+            &mut SimBodies {
+                stars: &mut [],
+                planets: &mut [],
+                black_holes: &mut [],
+                stations: &mut [],
+                comets: &mut [],
+                custom: &mut custom,
+            },
+            0.5,
+            false,
+        );
+        let mut position = Vec3::new(0.55, 0.0, 0.0);
+        let pushed = apply_collisions(
+            celestial_bodies(&[], &[], &[], &[], &[], &custom),
+            &mut position,
+        );
+        assert!(pushed > 0.0);
+        assert!((position.x - 0.7).abs() < 1e-4);
+    }
+
+    #[test]
+    fn built_station_hangs_from_its_planet() {
+        let mut theme = ember_with("");
+        let relay = theme
+            .stations
+            .iter_mut()
+            .find(|station| station.name == "Forge Relay")
+            .unwrap();
+        relay.parent = Some("Titanforge".to_string());
+        let (orbit_radius, height) = (relay.orbit_radius, relay.height);
+        let mut planets = build_planets(&theme.planets);
+        place_scene_graph(&mut planets, &mut []);
+        let stations = build_stations(&theme.stations, &planets).unwrap();
+        let planet = planets
+            .iter()
+            .find(|planet| planet.name == "Titanforge")
+            .unwrap();
+        let station = stations
+            .iter()
+            .find(|station| station.name == "Forge Relay")
+            .unwrap();
+        let distance = (station.position - planet.position).length();
+        assert!((distance - orbit_radius.hypot(height)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn traced_line_stays_inside_the_clip_rectangle() {
+        let clip = (Vec2::new(0.0, 0.0), Vec2::new(64.0, 48.0));
+        let (start, end) = (Vec2::new(-1.0e6, 24.5), Vec2::new(1.0e6, 24.5));
+        let mut visited = Vec::new();
+        trace_line(start, end, clip, |x, y, t| visited.push((x, y, t)));
+        assert!(visited.len() <= 65);
+        assert!(visited
+            .iter()
+            .all(|&(x, y, _)| (0..=64).contains(&x) && y == 24));
+        let (_, _, t) = visited[32];
+        assert!((t - 0.5).abs() < 1e-3);
+        let mut missed = 0;
+        trace_line(end, Vec2::new(2.0e6, 0.0), clip, |_, _, _| missed += 1);
+        assert_eq!(missed, 0);
+    }
+
+    /// A unit quad as PLY in `format`, with a header comment that mentions
+    /// `end_header` and colored vertices.
+    fn quad_ply(format: &str) -> Vec<u8> {
+        let corners = [(0.0_f32, 0.0_f32), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let mut bytes = format!(
+            "ply\nformat {} 1.0\ncomment written before end_header on purpose\n\
+             element vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+             property uchar red\nproperty uchar green\nproperty uchar blue\n\
+             element face 1\nproperty list uchar int vertex_indices\nend_header\n",
+            format
+        )
+        .into_bytes();
+        let big_endian = format == "binary_big_endian";
+        let float = |value: f32| {
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+        let int = |value: i32| {
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+        for (x, y) in corners {
+            if format == "ascii" {
+                bytes.extend(format!("{} {} 0 255 0 51\n", x, y).bytes());
+            } else {
+                for value in [x, y, 0.0] {
+                    bytes.extend(float(value));
+                }
+                bytes.extend([255, 0, 51]);
+            }
+        }
+        if format == "ascii" {
+            bytes.extend(b"4 0 1 2 3\n");
+        } else {
+            bytes.push(4);
+            for index in 0..4 {
+                bytes.extend(int(index));
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn ply_formats_read_the_same_quad() {
+        for format in ["ascii", "binary_little_endian", "binary_big_endian"] {
+            let mesh = Mesh::parse_ply(&quad_ply(format)).unwrap();
+            assert_eq!(mesh.vertices.len(), 4, "{}", format);
+            assert!(
+                (mesh.vertices[2].x - 1.0).abs() < 1e-6 && (mesh.vertices[2].y - 1.0).abs() < 1e-6
+            );
+            assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]], "{}", format);
+            assert_eq!(mesh.colors.len(), 4, "{}", format);
+            assert!((mesh.colors[0].b - 0.2).abs() < 1e-6, "{}", format);
+        }
+    }
+
+    #[test]
+    fn obj_negative_indices_and_smoothing_groups() {
+        let parse = |text: &str| Mesh::parse_obj(Path::new("test.obj"), text).unwrap();
+        let vertices = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+        let smooth = parse(&format!("{}s 1\nf 1 2 3\nf -4 -2 -1\n", vertices));
+        assert_eq!(smooth.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(smooth.vertices.len(), 4);
+        let flat = parse(&format!("{}s off\nf 1 2 3\nf -4 -2 -1\n", vertices));
+        assert_eq!(flat.indices, vec![[0, 1, 2], [3, 4, 5]]);
+        assert_eq!(flat.vertices[4], flat.vertices[2]);
+        assert!(Mesh::parse_obj(Path::new("test.obj"), "v 0 0 0\nf -2 1 1\n").is_err());
+    }
+
+    #[test]
+    fn shape_and_normal_mode_round_trip_through_scene_text() {
+        for text in ["adaptive", "uv", "ico 0", "ico 3"] {
+            assert_eq!(SphereShape::parse(text).unwrap().scene_text(), text);
+        }
+        let default = SphereShape::Icosphere(DEFAULT_ICOSPHERE_SUBDIVISIONS);
+        assert!(SphereShape::parse("ico") == Some(default));
+        let too_fine = format!("ico {}", MAX_ICOSPHERE_SUBDIVISIONS + 1);
+        for text in ["", "cube", "uv 2", too_fine.as_str(), "ico 2 2"] {
+            assert!(SphereShape::parse(text).is_none(), "{}", text);
+        }
+        for text in ["flat", "smooth", "smooth 35.0"] {
+            assert_eq!(NormalMode::parse(text).unwrap().scene_text(), text);
+        }
+        for text in ["", "flat 10", "smooth 200", "smooth sharp", "crease"] {
+            assert!(NormalMode::parse(text).is_none(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn triangles_sharing_edges_shade_each_pixel_once() {
+        let (width, height) = (33, 33);
+        let mut renderer = Renderer::new(width, height, ember_with("").palette);
+        renderer.overdraw = vec![0; width * height];
+        let vertex = |x: f32, y: f32| VertexOut {
+            screen: Vec3::new(x, y, 0.0),
+            world: Vec3::new(x, y, 0.0),
+            normal: Vec3::new(0.0, 0.0, 1.0),
+            color: Color::new(1.0, 1.0, 1.0),
+            local: Vec3::new(x, y, 0.0),
+            inv_w: 1.0,
+        };
+        // A fan of four around the centre of a square, alternating winding, with the
+        // shared edges running through pixel centres where the tie-break decides.
+        let center = vertex(16.5, 16.5);
+        let corners = [
+            vertex(4.5, 4.5),
+            vertex(28.5, 4.5),
+            vertex(28.5, 28.5),
+            vertex(4.5, 28.5),
+        ];
+        let shading = Shading::PerVertex {
+            eye: Vec3::new(0.0, 0.0, 1.0),
+        };
+        for i in 0..4 {
+            let (a, b) = (&corners[i], &corners[(i + 1) % 4]);
+            let (a, b) = if i % 2 == 0 { (a, b) } else { (b, a) };
+            renderer.rasterize_triangle(&center, a, b, &Material::default(), shading);
+        }
+        for y in 0..height {
+            for x in 0..width {
+                let count = renderer.overdraw[y * width + x];
+                if (5..28).contains(&x) && (5..28).contains(&y) {
+                    assert_eq!(count, 1, "pixel ({}, {})", x, y);
+                } else {
+                    assert!(count <= 1, "pixel ({}, {}) shaded {} times", x, y, count);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn saving_a_theme_leaves_its_scene_file_alone() {
+        let dir = std::env::temp_dir().join(format!("scene-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("ember.scene");
+        let (_, text) = BUILTIN_SCENES
+            .iter()
+            .find(|(path, _)| path.ends_with("ember.scene"))
+            .unwrap();
+        std::fs::write(&source, text).unwrap();
+        let mut theme = load_scene_theme(&source).unwrap();
+        theme.palette.sky_top = Color::new(0.25, 0.5, 0.75);
+
+        let saved = save_theme(&theme).unwrap();
+        assert_eq!(saved, dir.join("ember.user.scene"));
+        assert_eq!(std::fs::read_to_string(&source).unwrap(), *text);
+        let reloaded = load_scene_theme(&saved).unwrap();
+        assert!(reloaded.palette.sky_top == theme.palette.sky_top);
+        assert_eq!(user_scene_path(&saved), saved);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn minimap_stays_inside_its_box() {
+        let theme = ember_with("");
+        let mut planets = build_planets(&theme.planets);
+        let mut stations = build_stations(&theme.stations, &planets).unwrap();
+        place_scene_graph(&mut planets, &mut stations);
+        let stars = build_stars(&theme.stars);
+        let comets = build_comets(&theme.comets, &mut AsteroidCache::default());
+        let holes = build_black_holes(&theme.black_holes);
+        let extent = system_extent(&stars, &planets, &holes, &stations, &comets);
+        let mut renderer = Renderer::new(320, 240, theme.palette);
+        renderer.accessibility = AccessibilityMode::HighContrast;
+        renderer.color.fill(0x123456);
+        // Half the real extent, as if a far body were left out: the orbits overflow
+        // the map and have to be clipped.
+        draw_minimap(
+            &mut renderer,
+            &stars,
+            &planets,
+            extent * 0.5,
+            &Camera::new(Vec3::new(1.0e4, 0.0, 0.0)),
+        );
+        let inside = 4..4 + MINIMAP_SIZE as usize;
+        for y in 0..240 {
+            for x in 0..320 {
+                if !(inside.contains(&x) && inside.contains(&y)) {
+                    assert_eq!(renderer.color[y * 320 + x], 0x123456, "({}, {})", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn failed_calendar_event_reaches_the_hud() {
+        let theme = ember_with(
+            "[station Derelict]\nmesh = missing.obj\norbit_radius = 30.0\ncolor = 1 1 1\n\
+             dock_offset = 0 0 0\n\n[event Salvage]\ntime = 0.0\naction = arrival\n\
+             target = Derelict\n",
+        );
+        let mut planets = build_planets(&theme.planets);
+        let mut stars = build_stars(&theme.stars);
+        // Built without the derelict, whose mesh doesn't load, as if it hadn't arrived.
+        let mut stations = Vec::new();
+        let mut asteroids = AsteroidCache::default();
+        let mut comets = Vec::new();
+        let (_, failed) = EventCalendar::start(
+            &theme,
+            1.0,
+            CalendarBodies {
+                planets: &planets,
+                stars: &mut stars,
+                stations: &mut stations,
+                comets: &mut comets,
+                asteroids: &mut asteroids,
+            },
+        );
+        let mut events = EventBus::default();
+        events.subscribe(report_event);
+        emit_calendar(&mut events, failed);
+        let mut watcher = SceneWatcher::new(None);
+        events.dispatch(&mut EventTargets {
+            planets: &mut planets,
+            shake: &mut CameraShake::new(0.0),
+            watcher: &mut watcher,
+        });
+        let (message, _) = watcher.message.unwrap();
+        assert!(message.starts_with("event Salvage failed:"), "{}", message);
+    }
+}